@@ -0,0 +1,84 @@
+use primitives::{hash::CryptoHash, peer::PeerIdSigner, sig::Signature};
+use secp256k1::SecretKey;
+
+/// Message signed to authorize `DELETE /api/notes/{recipient_tag}/{note_id}`:
+/// `sha256("cachecash:delete-note:v1:{recipient_tag}:{note_id}:{timestamp}")`.
+///
+/// `recipient_tag` is the lowercase hex tag without `0x`, `timestamp` is in
+/// Unix seconds.
+pub fn delete_note_message(recipient_tag: &str, note_id: &str, timestamp: u64) -> CryptoHash {
+    CryptoHash::from_vec_hash(
+        format!("cachecash:delete-note:v1:{recipient_tag}:{note_id}:{timestamp}").into_bytes(),
+    )
+}
+
+/// Signs a note deletion with the key owning the address of `recipient_tag`,
+/// the key that first registered the address.
+///
+/// The signature is the 65-byte recoverable `primitives::sig::Signature`
+/// (`r || s || recovery_id`), hex-encoded in the request.
+pub fn sign_delete_note(
+    secret_key: &SecretKey,
+    recipient_tag: &str,
+    note_id: &str,
+    timestamp: u64,
+) -> Signature {
+    sign(
+        secret_key,
+        &delete_note_message(recipient_tag, note_id, timestamp),
+    )
+}
+
+/// Message signed to register or update a username:
+/// `sha256("cachecash:register-username:v1:{username}:{utxo_address}:{encryption_pubkey}:{timestamp}")`.
+///
+/// All values are normalized the way the server stores them: lowercase
+/// username, and lowercase hex without `0x` for the UTXO address and the
+/// x-only encryption pubkey.
+pub fn register_username_message(
+    username: &str,
+    utxo_address: &str,
+    encryption_pubkey: &str,
+    timestamp: u64,
+) -> CryptoHash {
+    CryptoHash::from_vec_hash(
+        format!(
+            "cachecash:register-username:v1:{username}:{utxo_address}:{encryption_pubkey}:{timestamp}"
+        )
+        .into_bytes(),
+    )
+}
+
+/// Signs a username registration.
+///
+/// A new username must be signed by the encryption key being registered; an
+/// existing one by the key that first registered it.
+pub fn sign_register_username(
+    secret_key: &SecretKey,
+    username: &str,
+    utxo_address: &str,
+    encryption_pubkey: &str,
+    timestamp: u64,
+) -> Signature {
+    sign(
+        secret_key,
+        &register_username_message(username, utxo_address, encryption_pubkey, timestamp),
+    )
+}
+
+/// Message signed to release a username with `DELETE /api/address/{username}`:
+/// `sha256("cachecash:release-username:v1:{username}:{timestamp}")`.
+pub fn release_username_message(username: &str, timestamp: u64) -> CryptoHash {
+    CryptoHash::from_vec_hash(
+        format!("cachecash:release-username:v1:{username}:{timestamp}").into_bytes(),
+    )
+}
+
+/// Signs a username release with the key that owns the username.
+pub fn sign_release_username(secret_key: &SecretKey, username: &str, timestamp: u64) -> Signature {
+    sign(secret_key, &release_username_message(username, timestamp))
+}
+
+fn sign(secret_key: &SecretKey, message: &CryptoHash) -> Signature {
+    PeerIdSigner::new(*secret_key).sign(message)
+}
@@ -0,0 +1,528 @@
+use std::{
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use cachecash_client::{
+    note_value, solve_faucet_pow,
+    types::{FaucetPowSolution, FaucetRequest, GetNotesQuery, NotePayload, UploadNoteRequest},
+    CachecashClient, HistoryEntry, HistoryKind, Wallet, WalletDb, WalletKeys, WalletStore,
+};
+use clap::{Parser, Subcommand};
+use element::Element;
+use zk_primitives::{
+    note_encryption::{decrypt_note, derive_recipient_tag, encrypt_note},
+    Note,
+};
+
+const SYNC_PAGE_SIZE: usize = 100;
+
+#[derive(Parser, Debug)]
+#[command(about = "Private CacheCash wallet", version)]
+struct Args {
+    /// Base URL of the server, e.g. http://localhost:9002
+    #[arg(
+        long,
+        global = true,
+        env = "CACHECASH_SERVER_URL",
+        default_value = "http://localhost:9002"
+    )]
+    server_url: String,
+
+    /// Path of the encrypted wallet file
+    #[arg(
+        long,
+        global = true,
+        env = "CACHECASH_WALLET",
+        default_value = "cachecash-wallet.json"
+    )]
+    wallet: PathBuf,
+
+    /// Passphrase protecting the wallet file
+    #[arg(
+        long,
+        global = true,
+        env = "CACHECASH_PASSPHRASE",
+        hide_env_values = true
+    )]
+    passphrase: Option<String>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Create a new wallet file
+    Init {
+        /// Derive keys from a player name, like the web client
+        #[arg(long, conflicts_with = "secret_key")]
+        name: Option<String>,
+        /// Import an existing secp256k1 secret key (hex)
+        #[arg(long)]
+        secret_key: Option<String>,
+        /// Overwrite an existing wallet file
+        #[arg(long)]
+        force: bool,
+    },
+    /// Print the wallet's address, encryption pubkey and recipient tag
+    Address,
+    /// Register a username pointing at this wallet
+    Register { username: String },
+    /// Release a username registered by this wallet
+    Release { username: String },
+    /// Mint test funds into the wallet
+    Faucet {
+        #[arg(long)]
+        amount: Option<u64>,
+    },
+    /// Fetch and decrypt notes sent to this wallet
+    Sync {
+        /// Delete notes from the server once stored locally
+        #[arg(long)]
+        delete: bool,
+    },
+    /// Print the spendable balance
+    Balance,
+    /// Send funds to a registered username
+    Send { username: String, amount: u64 },
+    /// Withdraw funds to an SMT token identity
+    Withdraw {
+        wallet_address: String,
+        amount: u64,
+        #[arg(long, default_value = "oranj")]
+        token_contract: String,
+    },
+    /// Print the wallet history
+    History,
+    /// Print the status of a transaction by tx hash or faucet tracking id
+    Tx { id: String },
+}
+
+struct Session {
+    store: WalletStore,
+    db: WalletDb,
+    keys: WalletKeys,
+    client: CachecashClient,
+}
+
+impl Session {
+    fn open(args: &Args) -> Result<Self> {
+        let store = WalletStore::new(&args.wallet, passphrase(args)?);
+        if !store.exists() {
+            bail!(
+                "wallet file {} does not exist, run `cachecash init` first",
+                store.path().display()
+            );
+        }
+        let db = store.load()?;
+        let keys = WalletKeys::from_hex(&db.secret_key)?;
+        Ok(Self {
+            store,
+            db,
+            keys,
+            client: CachecashClient::new(&args.server_url),
+        })
+    }
+
+    fn wallet(&self) -> Wallet {
+        Wallet::with_notes(
+            self.client.clone(),
+            self.keys.zk_secret_key(),
+            self.db.notes.clone(),
+        )
+    }
+
+    /// Writes the wallet's notes back and persists the database.
+    fn commit(&mut self, wallet: &Wallet) -> Result<()> {
+        self.db.notes = wallet.notes().to_vec();
+        self.store.save(&self.db)
+    }
+
+    fn record(
+        &mut self,
+        kind: HistoryKind,
+        amount: u64,
+        tx_hash: Option<String>,
+        counterparty: Option<String>,
+    ) {
+        self.db.history.push(HistoryEntry {
+            timestamp: now_ms() / 1000,
+            kind,
+            amount,
+            tx_hash,
+            counterparty,
+        });
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+
+    match &args.command {
+        Command::Init {
+            name,
+            secret_key,
+            force,
+        } => init(&args, name.as_deref(), secret_key.as_deref(), *force),
+        Command::Address => {
+            let session = Session::open(&args)?;
+            println!(
+                "utxo_address:      {}",
+                session.keys.utxo_address().to_hex()
+            );
+            println!("encryption_pubkey: {}", session.keys.encryption_pubkey());
+            println!("recipient_tag:     {}", session.keys.recipient_tag());
+            Ok(())
+        }
+        Command::Register { username } => register(Session::open(&args)?, username).await,
+        Command::Release { username } => {
+            let session = Session::open(&args)?;
+            session
+                .client
+                .release_address(username, session.keys.secret_key())
+                .await?;
+            println!("Released {username}");
+            Ok(())
+        }
+        Command::Faucet { amount } => faucet(Session::open(&args)?, *amount).await,
+        Command::Sync { delete } => sync(Session::open(&args)?, *delete).await,
+        Command::Balance => {
+            let session = Session::open(&args)?;
+            println!(
+                "{} ({} notes)",
+                session.wallet().balance(),
+                session.db.notes.len()
+            );
+            Ok(())
+        }
+        Command::Send { username, amount } => send(Session::open(&args)?, username, *amount).await,
+        Command::Withdraw {
+            wallet_address,
+            amount,
+            token_contract,
+        } => {
+            withdraw(
+                Session::open(&args)?,
+                wallet_address,
+                *amount,
+                token_contract,
+            )
+            .await
+        }
+        Command::History => {
+            let session = Session::open(&args)?;
+            for entry in &session.db.history {
+                println!(
+                    "{}  {:<8}  {:>12}  {}  {}",
+                    entry.timestamp,
+                    format!("{:?}", entry.kind).to_lowercase(),
+                    entry.amount,
+                    entry.tx_hash.as_deref().unwrap_or("-"),
+                    entry.counterparty.as_deref().unwrap_or("-"),
+                );
+            }
+            Ok(())
+        }
+        Command::Tx { id } => {
+            let record = CachecashClient::new(&args.server_url).tx_status(id).await?;
+            let status = serde_json::to_value(record.status)?;
+            println!("tx_hash: {}", record.tx_hash.as_deref().unwrap_or("-"));
+            println!("status:  {}", status.as_str().unwrap_or_default());
+            if let Some(reason) = record.failure_reason {
+                println!("reason:  {reason}");
+            }
+            Ok(())
+        }
+    }
+}
+
+fn init(args: &Args, name: Option<&str>, secret_key: Option<&str>, force: bool) -> Result<()> {
+    let store = WalletStore::new(&args.wallet, passphrase(args)?);
+    if store.exists() && !force {
+        bail!(
+            "wallet file {} already exists, pass --force to overwrite",
+            store.path().display()
+        );
+    }
+
+    let keys = match (name, secret_key) {
+        (Some(name), _) => WalletKeys::from_name(name)?,
+        (None, Some(secret_key)) => WalletKeys::from_hex(secret_key)?,
+        (None, None) => WalletKeys::random(),
+    };
+    store.save(&WalletDb {
+        secret_key: keys.secret_key_hex(),
+        ..WalletDb::default()
+    })?;
+
+    println!("Created wallet {}", store.path().display());
+    println!("utxo_address: {}", keys.utxo_address().to_hex());
+    Ok(())
+}
+
+async fn register(session: Session, username: &str) -> Result<()> {
+    let response = session
+        .client
+        .register_address(
+            username,
+            &session.keys.utxo_address().to_hex(),
+            session.keys.secret_key(),
+        )
+        .await?;
+    let action = if response.was_update {
+        "Updated"
+    } else {
+        "Registered"
+    };
+    println!(
+        "{action} {} -> {}",
+        response.username, response.utxo_address
+    );
+    Ok(())
+}
+
+async fn faucet(mut session: Session, amount: Option<u64>) -> Result<()> {
+    let pubkey_hex = session.keys.utxo_address().to_hex();
+    let challenge = session.client.faucet_challenge(&pubkey_hex).await?;
+    println!(
+        "Solving faucet challenge (difficulty {})...",
+        challenge.difficulty
+    );
+    let nonce = tokio::task::spawn_blocking({
+        let challenge = challenge.clone();
+        move || {
+            solve_faucet_pow(
+                &challenge.challenge,
+                &challenge.pubkey_hex,
+                challenge.difficulty,
+            )
+        }
+    })
+    .await?;
+
+    let response = session
+        .client
+        .faucet(&FaucetRequest {
+            pubkey_hex,
+            amount,
+            pow: Some(FaucetPowSolution { challenge, nonce }),
+        })
+        .await?;
+
+    let mut wallet = session.wallet();
+    let minted = note_value(&response.note);
+    if !wallet.add_note(response.note) {
+        bail!("faucet returned a note this wallet cannot spend");
+    }
+    let tracking_id = Some(response.tracking_id).filter(|id| !id.is_empty());
+    session.record(HistoryKind::Faucet, minted, tracking_id, None);
+    session.commit(&wallet)?;
+
+    println!("Minted {minted}, balance {}", wallet.balance());
+    Ok(())
+}
+
+/// Pulls encrypted notes addressed to this wallet.
+///
+/// `since` is exclusive and has second granularity, so each page re-reads the
+/// cursor's second and skips ids that were already processed.
+async fn sync(mut session: Session, delete: bool) -> Result<()> {
+    let recipient_tag = session.keys.recipient_tag();
+    let mut wallet = session.wallet();
+    let mut received = 0usize;
+
+    loop {
+        let query = GetNotesQuery {
+            since: session.db.sync_cursor.checked_sub(1),
+            limit: Some(SYNC_PAGE_SIZE),
+        };
+        let page = session.client.get_notes(&recipient_tag, &query).await?;
+
+        let mut progressed = false;
+        for record in page.notes {
+            if session.db.seen_note_ids.contains_key(&record.id) {
+                continue;
+            }
+            progressed = true;
+            session
+                .db
+                .seen_note_ids
+                .insert(record.id.clone(), record.stored_at);
+            session.db.sync_cursor = session.db.sync_cursor.max(record.stored_at);
+
+            let payload = match decrypt_note(
+                session.keys.secret_key(),
+                &record.encrypted_payload,
+                &record.ephemeral_pubkey,
+            )
+            .map_err(anyhow::Error::from)
+            .and_then(|plaintext| parse_payload(&plaintext))
+            {
+                Ok(payload) => payload,
+                Err(err) => {
+                    eprintln!("Skipping undecryptable note {}: {err:#}", record.id);
+                    continue;
+                }
+            };
+
+            let (mut note, tx_hash, from) = payload;
+            // The web client stores the token in `kind`; commitments use a constant kind of 2
+            note.kind = Element::new(2);
+            let amount = note_value(&note);
+            if wallet.add_note(note) {
+                received += 1;
+                session.record(HistoryKind::Received, amount, tx_hash, from);
+            }
+
+            if delete {
+                if let Err(err) = session
+                    .client
+                    .delete_note(&recipient_tag, &record.id, session.keys.secret_key())
+                    .await
+                {
+                    eprintln!("Failed to delete note {}: {err:#}", record.id);
+                }
+            }
+        }
+
+        if !page.has_more || !progressed {
+            break;
+        }
+    }
+
+    let horizon = session.db.sync_cursor.saturating_sub(1);
+    session
+        .db
+        .seen_note_ids
+        .retain(|_, stored_at| *stored_at >= horizon);
+
+    // Notes restored from a backup may have been spent since
+    let pruned = match wallet.prune_spent().await {
+        Ok(pruned) => pruned,
+        Err(err) => {
+            eprintln!("Failed to check for spent notes: {err:#}");
+            0
+        }
+    };
+    session.commit(&wallet)?;
+
+    println!(
+        "Received {received} notes, dropped {pruned} spent notes, balance {}",
+        wallet.balance()
+    );
+    Ok(())
+}
+
+async fn send(mut session: Session, username: &str, amount: u64) -> Result<()> {
+    let resolved = session.client.resolve_address(username).await?;
+    let recipient: Element = resolved
+        .utxo_address
+        .parse()
+        .map_err(|_| anyhow!("server returned an invalid utxo address for {username}"))?;
+
+    let mut wallet = session.wallet();
+    let receipt = wallet.send(recipient, amount).await?;
+    let tx_hash = receipt.tx_hash.to_string();
+
+    // Input notes are spent from here on: persist before notifying the recipient
+    session.record(
+        HistoryKind::Sent,
+        amount,
+        Some(tx_hash.clone()),
+        Some(username.to_string()),
+    );
+    session.commit(&wallet)?;
+    println!("Sent {amount} to {username} in tx {tx_hash}");
+
+    let payload = NotePayload {
+        note: receipt.transfer_note,
+        tx_hash,
+        amount,
+        from: session.keys.encryption_pubkey(),
+        timestamp: now_ms(),
+    };
+    if let Err(err) = deliver_note(
+        &session,
+        &resolved.utxo_address,
+        &resolved.encryption_pubkey,
+        &payload,
+    )
+    .await
+    {
+        eprintln!("Warning: failed to deliver the note to {username}: {err:#}");
+        eprintln!(
+            "Share it manually so they can spend it: {}",
+            serde_json::to_string(&payload.note)?
+        );
+    }
+    Ok(())
+}
+
+async fn deliver_note(
+    session: &Session,
+    recipient_address: &str,
+    recipient_pubkey: &str,
+    payload: &NotePayload,
+) -> Result<()> {
+    let encrypted = encrypt_note(recipient_pubkey, &serde_json::to_vec(payload)?)?;
+    session
+        .client
+        .upload_note(&UploadNoteRequest {
+            recipient_tag: derive_recipient_tag(recipient_address)?,
+            encrypted_payload: encrypted.encrypted_payload,
+            ephemeral_pubkey: encrypted.ephemeral_pubkey,
+            sender_tag: Some(session.keys.recipient_tag()),
+        })
+        .await?;
+    Ok(())
+}
+
+async fn withdraw(
+    mut session: Session,
+    wallet_address: &str,
+    amount: u64,
+    token_contract: &str,
+) -> Result<()> {
+    let mut wallet = session.wallet();
+    let receipt = wallet
+        .withdraw(wallet_address, amount, token_contract)
+        .await?;
+    let tx_hash = receipt.tx_hash.to_string();
+
+    session.record(
+        HistoryKind::Withdraw,
+        amount,
+        Some(tx_hash.clone()),
+        Some(format!("{wallet_address}@{token_contract}")),
+    );
+    session.commit(&wallet)?;
+
+    println!("Withdrew {amount} to {wallet_address} on {token_contract} in tx {tx_hash}");
+    Ok(())
+}
+
+/// Accepts both `DecryptedNotePayload` and bare notes, like the web client.
+fn parse_payload(plaintext: &[u8]) -> Result<(Note, Option<String>, Option<String>)> {
+    if let Ok(payload) = serde_json::from_slice::<NotePayload>(plaintext) {
+        return Ok((payload.note, Some(payload.tx_hash), Some(payload.from)));
+    }
+    let note = serde_json::from_slice::<Note>(plaintext).context("decoding note payload")?;
+    Ok((note, None, None))
+}
+
+fn passphrase(args: &Args) -> Result<String> {
+    match &args.passphrase {
+        Some(passphrase) if !passphrase.is_empty() => Ok(passphrase.clone()),
+        _ => bail!("a passphrase is required: pass --passphrase or set CACHECASH_PASSPHRASE"),
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
@@ -0,0 +1,584 @@
+use std::{
+    fmt,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Context, Result};
+use element::Element;
+use reqwest::{Client, RequestBuilder, StatusCode};
+use secp256k1::{PublicKey, SecretKey, SECP256K1};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use zk_primitives::note_encryption::x_only;
+
+use crate::{
+    auth::{sign_delete_note, sign_register_username, sign_release_username},
+    types::{
+        BlobHashResponse, CreateBlobRequest, DepositRequest, ErrorCode, FaucetChallenge,
+        FaucetChallengeRequest, FaucetRequest, FaucetResponse, FinalizeTransferRequest,
+        FinalizeTransferResponse, GetNotesQuery, GetNotesResponse, NoteStatusRequest,
+        NoteStatusResponse, RegisterAddressRequest, RegisterAddressResponse,
+        ResolveAddressResponse, ServerConfigResponse, SmtWitnessResponse, SmtWitnessesRequest,
+        SmtWitnessesResponse, TxRecord, UploadNoteRequest, UploadNoteResponse,
+    },
+};
+
+/// HTTP client for the CacheCash server REST API.
+///
+/// Every method maps to a single endpoint and uses the same request/response
+/// types as the server handlers.
+#[derive(Clone, Debug)]
+pub struct CachecashClient {
+    http: Client,
+    base_url: String,
+}
+
+/// JSON error body returned by the server. Servers predating error codes
+/// only send `error`.
+#[derive(Debug, Deserialize)]
+struct ErrorBody {
+    error: String,
+    #[serde(default)]
+    code: Option<ErrorCode>,
+}
+
+/// Error response of the server, returned inside the [`anyhow::Error`] of
+/// failed requests so callers can downcast it and match on `code`.
+#[derive(Debug, Clone)]
+pub struct ServerError {
+    /// `METHOD /path` of the failed request
+    pub endpoint: String,
+    pub status: StatusCode,
+    pub code: Option<ErrorCode>,
+    pub message: String,
+}
+
+impl fmt::Display for ServerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} failed ({}): {}",
+            self.endpoint, self.status, self.message
+        )
+    }
+}
+
+impl std::error::Error for ServerError {}
+
+impl CachecashClient {
+    /// Creates a client for the server at `base_url` (e.g. `http://localhost:9002`).
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self::with_http_client(base_url, Client::new())
+    }
+
+    /// Creates a client reusing an existing `reqwest::Client`.
+    pub fn with_http_client(base_url: impl Into<String>, http: Client) -> Self {
+        let base_url = base_url.into();
+        let base_url = if base_url.contains("://") {
+            base_url
+        } else {
+            format!("http://{base_url}")
+        };
+        Self {
+            http,
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    // ---- Server Config ----
+
+    pub async fn config(&self) -> Result<ServerConfigResponse> {
+        self.send_json(self.http.get(self.url("/api/config"))).await
+    }
+
+    // ---- Faucet / Deposit ----
+
+    pub async fn faucet_challenge(&self, pubkey_hex: &str) -> Result<FaucetChallenge> {
+        let request = FaucetChallengeRequest {
+            pubkey_hex: pubkey_hex.to_string(),
+        };
+        self.post_json("/api/faucet/challenge", &request).await
+    }
+
+    pub async fn faucet(&self, request: &FaucetRequest) -> Result<FaucetResponse> {
+        self.post_json("/api/faucet", request).await
+    }
+
+    pub async fn deposit(&self, request: &DepositRequest) -> Result<FaucetResponse> {
+        self.post_json("/api/deposit", request).await
+    }
+
+    // ---- Transfers ----
+
+    /// Computes the tx_hash of the blob transaction without submitting it.
+    pub async fn hash_blob(&self, request: &CreateBlobRequest) -> Result<BlobHashResponse> {
+        self.post_json("/api/blob/hash", request).await
+    }
+
+    /// Submits the blob transaction and both proofs in one call.
+    pub async fn finalize_transfer(
+        &self,
+        request: &FinalizeTransferRequest,
+    ) -> Result<FinalizeTransferResponse> {
+        self.post_json("/api/transfer/finalize", request).await
+    }
+
+    /// Fetches SMT witnesses for two input commitments from the hyli-utxo-state indexer,
+    /// against `notes_root` or the current root.
+    pub async fn smt_witness(
+        &self,
+        utxo_state_contract_name: &str,
+        commitment0: Element,
+        commitment1: Element,
+        notes_root: Option<&str>,
+    ) -> Result<SmtWitnessResponse> {
+        let mut path = format!(
+            "/v1/indexer/contract/{}/smt-witness?commitment0={}&commitment1={}",
+            utxo_state_contract_name,
+            commitment0.to_hex(),
+            commitment1.to_hex()
+        );
+        if let Some(notes_root) = notes_root {
+            path.push_str(&format!("&notes_root={notes_root}"));
+        }
+        self.send_json(self.http.get(self.url(&path))).await
+    }
+
+    /// Fetches compact SMT witnesses for any number of commitments, all
+    /// against `notes_root` or the current root. See
+    /// [`crate::types::CompactSmtWitness::expand`].
+    pub async fn smt_witnesses(
+        &self,
+        utxo_state_contract_name: &str,
+        commitments: &[Element],
+        notes_root: Option<&str>,
+    ) -> Result<SmtWitnessesResponse> {
+        let request = SmtWitnessesRequest {
+            commitments: commitments.iter().map(|c| c.to_hex()).collect(),
+            notes_root: notes_root.map(str::to_string),
+        };
+        let path = format!("/v1/indexer/contract/{utxo_state_contract_name}/smt-witnesses");
+        self.post_json(&path, &request).await
+    }
+
+    /// Looks up whether `commitments` are in the notes tree and whether
+    /// `nullifiers` are spent, against a single hyli-utxo-state root.
+    pub async fn note_status(
+        &self,
+        utxo_state_contract_name: &str,
+        commitments: &[Element],
+        nullifiers: &[Element],
+    ) -> Result<NoteStatusResponse> {
+        let request = NoteStatusRequest {
+            commitments: commitments.iter().map(|c| c.to_hex()).collect(),
+            nullifiers: nullifiers.iter().map(|n| n.to_hex()).collect(),
+        };
+        let path = format!("/v1/indexer/contract/{utxo_state_contract_name}/note-status");
+        self.post_json(&path, &request).await
+    }
+
+    /// Fetches the lifecycle of a transaction by tx hash or faucet tracking id.
+    pub async fn tx_status(&self, id: &str) -> Result<TxRecord> {
+        let path = format!("/api/tx/{id}");
+        self.send_json(self.http.get(self.url(&path))).await
+    }
+
+    // ---- Encrypted Notes ----
+
+    pub async fn upload_note(&self, request: &UploadNoteRequest) -> Result<UploadNoteResponse> {
+        self.post_json("/api/notes", request).await
+    }
+
+    pub async fn get_notes(
+        &self,
+        recipient_tag: &str,
+        query: &GetNotesQuery,
+    ) -> Result<GetNotesResponse> {
+        let mut params = Vec::new();
+        if let Some(since) = query.since {
+            params.push(format!("since={since}"));
+        }
+        if let Some(limit) = query.limit {
+            params.push(format!("limit={limit}"));
+        }
+        let mut path = format!("/api/notes/{recipient_tag}");
+        if !params.is_empty() {
+            path.push('?');
+            path.push_str(&params.join("&"));
+        }
+        self.send_json(self.http.get(self.url(&path))).await
+    }
+
+    /// Deletes a note, signing the request with the key owning the recipient's
+    /// address.
+    pub async fn delete_note(
+        &self,
+        recipient_tag: &str,
+        note_id: &str,
+        secret_key: &SecretKey,
+    ) -> Result<()> {
+        let timestamp = unix_timestamp()?;
+        let signature = sign_delete_note(secret_key, recipient_tag, note_id, timestamp);
+        let path = format!(
+            "/api/notes/{recipient_tag}/{note_id}?timestamp={timestamp}&signature={}",
+            hex::encode(signature.inner())
+        );
+        self.send(self.http.delete(self.url(&path))).await?;
+        Ok(())
+    }
+
+    // ---- Address Registry ----
+
+    /// Registers (or updates) `username`, signing the request with `secret_key`.
+    ///
+    /// The encryption pubkey registered is the x-coordinate of `secret_key`'s
+    /// public key; updating an existing username requires the key that first
+    /// registered it.
+    pub async fn register_address(
+        &self,
+        username: &str,
+        utxo_address: &str,
+        secret_key: &SecretKey,
+    ) -> Result<RegisterAddressResponse> {
+        let username = username.to_lowercase();
+        let utxo_address = utxo_address
+            .strip_prefix("0x")
+            .unwrap_or(utxo_address)
+            .to_lowercase();
+        let encryption_pubkey =
+            hex::encode(x_only(&PublicKey::from_secret_key(SECP256K1, secret_key)));
+        let timestamp = unix_timestamp()?;
+        let signature = sign_register_username(
+            secret_key,
+            &username,
+            &utxo_address,
+            &encryption_pubkey,
+            timestamp,
+        );
+        let request = RegisterAddressRequest {
+            username,
+            utxo_address,
+            encryption_pubkey,
+            timestamp: Some(timestamp),
+            signature: Some(hex::encode(signature.inner())),
+        };
+        self.post_json("/api/address/register", &request).await
+    }
+
+    /// Releases `username`, signing the request with the owner's `secret_key`.
+    pub async fn release_address(&self, username: &str, secret_key: &SecretKey) -> Result<()> {
+        let username = username.to_lowercase();
+        let timestamp = unix_timestamp()?;
+        let signature = sign_release_username(secret_key, &username, timestamp);
+        let path = format!(
+            "/api/address/{username}?timestamp={timestamp}&signature={}",
+            hex::encode(signature.inner())
+        );
+        self.send(self.http.delete(self.url(&path))).await?;
+        Ok(())
+    }
+
+    pub async fn resolve_address(&self, username: &str) -> Result<ResolveAddressResponse> {
+        let path = format!("/api/address/resolve/{username}");
+        self.send_json(self.http.get(self.url(&path))).await
+    }
+
+    // ---- Helpers ----
+
+    async fn post_json<B: Serialize, T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &B,
+    ) -> Result<T> {
+        self.send_json(self.http.post(self.url(path)).json(body))
+            .await
+    }
+
+    async fn send_json<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T> {
+        let response = self.send(request).await?;
+        response
+            .json()
+            .await
+            .context("decoding server response body")
+    }
+
+    async fn send(&self, request: RequestBuilder) -> Result<reqwest::Response> {
+        let request = request.build().context("building request")?;
+        let endpoint = format!("{} {}", request.method(), request.url().path());
+        let response = self
+            .http
+            .execute(request)
+            .await
+            .with_context(|| format!("sending request to {endpoint}"))?;
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+
+        let text = response
+            .text()
+            .await
+            .unwrap_or_else(|_| "<failed to read error body>".to_string());
+        let (message, code) = match serde_json::from_str::<ErrorBody>(&text) {
+            Ok(body) => (body.error, body.code),
+            Err(_) => (text, None),
+        };
+        Err(ServerError {
+            endpoint,
+            status,
+            code,
+            message,
+        }
+        .into())
+    }
+}
+
+fn unix_timestamp() -> Result<u64> {
+    Ok(SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .context("system clock is before the Unix epoch")?
+        .as_secs())
+}
+
+/// Parses a 32-byte hex string (with or without `0x`) into an [`Element`].
+pub(crate) fn parse_element_hex(value: &str) -> Result<Element> {
+    let normalized = value.strip_prefix("0x").unwrap_or(value);
+    let bytes = hex::decode(normalized).map_err(|e| anyhow!("invalid hex '{value}': {e}"))?;
+    let bytes: [u8; 32] = bytes
+        .try_into()
+        .map_err(|b: Vec<u8>| anyhow!("expected 32 bytes, got {}", b.len()))?;
+    Ok(Element::from_be_bytes(bytes))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use axum::{
+        extract::Query,
+        routing::{get, post},
+        Json, Router,
+    };
+    use serde_json::json;
+    use zk_primitives::Note;
+
+    use super::*;
+    use crate::types::{EncryptedNoteRecord, InputNoteData};
+
+    /// Serves `router` on a free local port, in place of the server.
+    pub(crate) async fn serve(router: Router) -> CachecashClient {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+        CachecashClient::new(address.to_string())
+    }
+
+    fn note(value: u64) -> Note {
+        Note {
+            kind: Element::new(2),
+            contract: Element::new(1),
+            address: Element::new(3),
+            psi: Element::new(value + 100),
+            value: Element::new(value),
+        }
+    }
+
+    /// Serializes `value` and reads it back as the type the server handlers
+    /// take, which must serialize the same.
+    fn round_trip<T: Serialize + DeserializeOwned>(value: &T) -> serde_json::Value {
+        let json = serde_json::to_value(value).unwrap();
+        let parsed: T = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&parsed).unwrap(), json);
+        json
+    }
+
+    #[test]
+    fn requests_round_trip_through_the_server_types() {
+        let finalize = FinalizeTransferRequest {
+            blob_data: vec![1; 128],
+            smt_blob_data: None,
+            output_notes: [note(5), Note::padding_note()],
+            token_transfer: None,
+            proof: "cHJvb2Y=".to_string(),
+            public_inputs: vec![format!("0x{}", Element::new(9).to_hex())],
+            smt_proof: None,
+            smt_public_inputs: None,
+            input_notes: Some([
+                InputNoteData {
+                    note: note(7),
+                    nullifier_key: Element::new(11).to_hex(),
+                },
+                InputNoteData {
+                    note: Note::padding_note(),
+                    nullifier_key: Element::ZERO.to_hex(),
+                },
+            ]),
+            siblings_0: Some(vec![Element::ZERO.to_hex(); 4]),
+            siblings_1: Some(vec![Element::ZERO.to_hex(); 4]),
+            notes_root: Some(Element::new(13).to_hex()),
+        };
+        let json = round_trip(&finalize);
+        assert_eq!(
+            json["input_notes"][0]["nullifier_key"],
+            Element::new(11).to_hex()
+        );
+
+        // Requests of clients predating the server-side SMT proof
+        let legacy: FinalizeTransferRequest = serde_json::from_value(json!({
+            "blob_data": finalize.blob_data,
+            "smt_blob_data": vec![2; 96],
+            "output_notes": finalize.output_notes,
+            "proof": finalize.proof,
+            "public_inputs": finalize.public_inputs,
+            "smt_proof": "cHJvb2Y=",
+            "smt_public_inputs": [],
+        }))
+        .unwrap();
+        assert!(legacy.input_notes.is_none());
+        assert!(legacy.notes_root.is_none());
+
+        let unsigned = RegisterAddressRequest {
+            username: "alice".to_string(),
+            utxo_address: "ab".repeat(32),
+            encryption_pubkey: "11".repeat(32),
+            timestamp: None,
+            signature: None,
+        };
+        let json = round_trip(&unsigned);
+        assert!(json.get("timestamp").is_none());
+        assert!(json.get("signature").is_none());
+
+        round_trip(&NoteStatusRequest {
+            commitments: vec![Element::new(1).to_hex()],
+            nullifiers: Vec::new(),
+        });
+    }
+
+    #[tokio::test]
+    async fn note_status_sends_hex_elements() {
+        let client = serve(Router::new().route(
+            "/v1/indexer/contract/{contract}/note-status",
+            post(|Json(request): Json<NoteStatusRequest>| async move {
+                Json(NoteStatusResponse {
+                    notes_root: Element::new(1).to_hex(),
+                    nullified_root: Element::new(2).to_hex(),
+                    commitments: request
+                        .commitments
+                        .iter()
+                        .map(|c| *c == Element::new(5).to_hex())
+                        .collect(),
+                    nullifiers: request
+                        .nullifiers
+                        .iter()
+                        .map(|n| *n == Element::new(7).to_hex())
+                        .collect(),
+                })
+            }),
+        ))
+        .await;
+
+        let status = client
+            .note_status(
+                "hyli-utxo-state",
+                &[Element::new(5), Element::new(6)],
+                &[Element::new(7)],
+            )
+            .await
+            .unwrap();
+        assert_eq!(status.commitments, vec![true, false]);
+        assert_eq!(status.nullifiers, vec![true]);
+        assert_eq!(status.notes_root, Element::new(1).to_hex());
+    }
+
+    #[tokio::test]
+    async fn get_notes_sends_the_query() {
+        let client = serve(Router::new().route(
+            "/api/notes/{recipient_tag}",
+            get(|Query(query): Query<GetNotesQuery>| async move {
+                Json(GetNotesResponse {
+                    notes: vec![EncryptedNoteRecord {
+                        id: format!("{:?}-{:?}", query.since, query.limit),
+                        encrypted_payload: "cGF5bG9hZA==".to_string(),
+                        ephemeral_pubkey: "22".repeat(33),
+                        sender_tag: None,
+                        stored_at: 10,
+                    }],
+                    has_more: true,
+                })
+            }),
+        ))
+        .await;
+
+        let notes = client
+            .get_notes(
+                "aa",
+                &GetNotesQuery {
+                    since: Some(10),
+                    limit: Some(2),
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(notes.notes[0].id, "Some(10)-Some(2)");
+        assert!(notes.has_more);
+
+        let notes = client
+            .get_notes("aa", &GetNotesQuery::default())
+            .await
+            .unwrap();
+        assert_eq!(notes.notes[0].id, "None-None");
+    }
+
+    #[tokio::test]
+    async fn server_errors_carry_their_code() {
+        let client = serve(
+            Router::new()
+                .route(
+                    "/api/config",
+                    get(|| async {
+                        (
+                            StatusCode::TOO_MANY_REQUESTS,
+                            Json(json!({ "error": "slow down", "code": "QUOTA_EXCEEDED" })),
+                        )
+                    }),
+                )
+                .route(
+                    "/api/tx/{id}",
+                    get(|| async { (StatusCode::NOT_FOUND, "no such transaction") }),
+                )
+                .route(
+                    "/api/address/resolve/{username}",
+                    get(|| async {
+                        (
+                            StatusCode::BAD_REQUEST,
+                            Json(json!({ "error": "bad", "code": "SOMETHING_NEW" })),
+                        )
+                    }),
+                ),
+        )
+        .await;
+
+        let server_error = |err: anyhow::Error| err.downcast::<ServerError>().unwrap();
+
+        let err = server_error(client.config().await.unwrap_err());
+        assert_eq!(err.endpoint, "GET /api/config");
+        assert_eq!(err.status, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(err.code, Some(ErrorCode::QuotaExceeded));
+        assert_eq!(err.message, "slow down");
+
+        // Servers predating error codes answer with plain text
+        let err = server_error(client.tx_status("ab").await.unwrap_err());
+        assert_eq!(err.status, StatusCode::NOT_FOUND);
+        assert_eq!(err.code, None);
+        assert_eq!(err.message, "no such transaction");
+
+        let err = server_error(client.resolve_address("alice").await.unwrap_err());
+        assert_eq!(err.code, Some(ErrorCode::Unknown));
+    }
+}
@@ -0,0 +1,91 @@
+use anyhow::{bail, Context, Result};
+use element::Element;
+use secp256k1::{PublicKey, SecretKey, SECP256K1};
+use sha2::{Digest, Sha256};
+use zk_primitives::{
+    get_address_for_private_key,
+    note_encryption::{derive_recipient_tag, x_only},
+};
+
+/// Keys of a CacheCash identity, derived the same way as `KeyService.ts`.
+///
+/// - `secret_key`: secp256k1 key used for note encryption (ECDH)
+/// - `zk_secret_key`: `hash_merge([low_128, high_128])` of the secp256k1 key, spends notes
+/// - `utxo_address`: `hash_merge([zk_secret_key, 0])`, appears in notes
+#[derive(Clone)]
+pub struct WalletKeys {
+    secret_key: SecretKey,
+    zk_secret_key: Element,
+}
+
+impl WalletKeys {
+    pub fn new(secret_key: SecretKey) -> Self {
+        let bytes = secret_key.secret_bytes();
+        let mut high = [0u8; 32];
+        let mut low = [0u8; 32];
+        high[16..].copy_from_slice(&bytes[..16]);
+        low[16..].copy_from_slice(&bytes[16..]);
+        let zk_secret_key =
+            hash::hash_merge([Element::from_be_bytes(low), Element::from_be_bytes(high)]);
+        Self {
+            secret_key,
+            zk_secret_key,
+        }
+    }
+
+    pub fn random() -> Self {
+        Self::new(SecretKey::new(&mut rand::thread_rng()))
+    }
+
+    /// Deterministic keys from a player name, matching the web client's `deriveKeyPairFromName`.
+    ///
+    /// Only ASCII names are accepted since the web client NFKC-normalizes before hashing.
+    pub fn from_name(name: &str) -> Result<Self> {
+        let normalized = name.trim().to_lowercase();
+        if normalized.is_empty() {
+            bail!("cannot derive keys from an empty name");
+        }
+        if !normalized.is_ascii() {
+            bail!("only ASCII names can be used to derive keys");
+        }
+        let digest = Sha256::digest(normalized.as_bytes());
+        let secret_key = SecretKey::from_slice(&digest).context("name hashes to an invalid key")?;
+        Ok(Self::new(secret_key))
+    }
+
+    pub fn from_hex(secret_key_hex: &str) -> Result<Self> {
+        let normalized = secret_key_hex.strip_prefix("0x").unwrap_or(secret_key_hex);
+        let bytes = hex::decode(normalized).context("decoding secret key hex")?;
+        let secret_key = SecretKey::from_slice(&bytes).context("invalid secp256k1 secret key")?;
+        Ok(Self::new(secret_key))
+    }
+
+    pub fn secret_key(&self) -> &SecretKey {
+        &self.secret_key
+    }
+
+    pub fn secret_key_hex(&self) -> String {
+        hex::encode(self.secret_key.secret_bytes())
+    }
+
+    pub fn zk_secret_key(&self) -> Element {
+        self.zk_secret_key
+    }
+
+    pub fn utxo_address(&self) -> Element {
+        get_address_for_private_key(self.zk_secret_key)
+    }
+
+    /// x-coordinate of the secp256k1 public key (64 hex chars), as registered for ECDH.
+    pub fn encryption_pubkey(&self) -> String {
+        hex::encode(x_only(&PublicKey::from_secret_key(
+            SECP256K1,
+            &self.secret_key,
+        )))
+    }
+
+    pub fn recipient_tag(&self) -> String {
+        derive_recipient_tag(&self.utxo_address().to_hex())
+            .expect("utxo address is always 32 bytes")
+    }
+}
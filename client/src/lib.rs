@@ -0,0 +1,24 @@
+//! Rust client for the CacheCash server.
+//!
+//! [`CachecashClient`] wraps the REST endpoints one-to-one, while [`Wallet`]
+//! builds, proves and finalizes private transfers on top of it. The `cachecash`
+//! binary is a command-line wallet keeping its notes in a passphrase-encrypted
+//! [`WalletStore`].
+
+mod auth;
+mod client;
+mod keys;
+mod pow;
+mod store;
+pub mod types;
+mod wallet;
+
+pub use auth::{
+    delete_note_message, register_username_message, release_username_message, sign_delete_note,
+    sign_register_username, sign_release_username,
+};
+pub use client::{CachecashClient, ServerError};
+pub use keys::WalletKeys;
+pub use pow::{faucet_pow_digest, leading_zero_bits, solve_faucet_pow};
+pub use store::{HistoryEntry, HistoryKind, WalletDb, WalletStore};
+pub use wallet::{note_value, SendReceipt, Wallet, WithdrawReceipt};
@@ -0,0 +1,36 @@
+use sha2::{Digest, Sha256};
+
+/// Digest checked by the faucet proof of work:
+/// `sha256("cachecash:faucet-pow:v1:{challenge}:{pubkey_hex}:{nonce}")`.
+///
+/// `pubkey_hex` is the lowercase hex UTXO address without `0x`, as returned in
+/// the challenge.
+pub fn faucet_pow_digest(challenge: &str, pubkey_hex: &str, nonce: u64) -> [u8; 32] {
+    Sha256::digest(format!(
+        "cachecash:faucet-pow:v1:{challenge}:{pubkey_hex}:{nonce}"
+    ))
+    .into()
+}
+
+/// Number of leading zero bits of `digest`.
+pub fn leading_zero_bits(digest: &[u8]) -> u32 {
+    let mut bits = 0;
+    for byte in digest {
+        bits += byte.leading_zeros();
+        if *byte != 0 {
+            break;
+        }
+    }
+    bits
+}
+
+/// Finds the smallest nonce whose digest has at least `difficulty` leading
+/// zero bits. Expect about `2^difficulty` hashes.
+pub fn solve_faucet_pow(challenge: &str, pubkey_hex: &str, difficulty: u8) -> u64 {
+    (0..)
+        .find(|&nonce| {
+            leading_zero_bits(&faucet_pow_digest(challenge, pubkey_hex, nonce))
+                >= u32::from(difficulty)
+        })
+        .expect("nonce space exhausted")
+}
@@ -0,0 +1,165 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use aes_gcm::{
+    aead::{Aead, KeyInit},
+    Aes256Gcm, Nonce,
+};
+use anyhow::{anyhow, bail, Context, Result};
+use base64::prelude::*;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use zk_primitives::Note;
+
+const STORE_VERSION: u8 = 1;
+const PBKDF2_ITERATIONS: u32 = 600_000;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+/// Plaintext content of the local wallet database.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct WalletDb {
+    /// secp256k1 secret key (64 hex chars)
+    pub secret_key: String,
+    /// Unspent notes owned by the wallet
+    #[serde(default)]
+    pub notes: Vec<Note>,
+    /// Chronological record of balance changes
+    #[serde(default)]
+    pub history: Vec<HistoryEntry>,
+    /// `stored_at` of the newest encrypted note fetched from the server
+    #[serde(default)]
+    pub sync_cursor: u64,
+    /// Encrypted note ids already processed, with their `stored_at`.
+    /// Entries older than the cursor are pruned on every sync.
+    #[serde(default)]
+    pub seen_note_ids: BTreeMap<String, u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// Unix timestamp (seconds)
+    pub timestamp: u64,
+    pub kind: HistoryKind,
+    pub amount: u64,
+    #[serde(default)]
+    pub tx_hash: Option<String>,
+    /// Username, address or token identity on the other side
+    #[serde(default)]
+    pub counterparty: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryKind {
+    Faucet,
+    Received,
+    Sent,
+    Withdraw,
+}
+
+/// On-disk envelope: the JSON-encoded [`WalletDb`] sealed with AES-256-GCM
+/// under a PBKDF2-HMAC-SHA256 key derived from the passphrase.
+#[derive(Serialize, Deserialize)]
+struct SealedWalletDb {
+    version: u8,
+    iterations: u32,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// Passphrase-encrypted wallet file.
+pub struct WalletStore {
+    path: PathBuf,
+    passphrase: String,
+}
+
+impl WalletStore {
+    pub fn new(path: impl Into<PathBuf>, passphrase: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            passphrase: passphrase.into(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn exists(&self) -> bool {
+        self.path.exists()
+    }
+
+    pub fn load(&self) -> Result<WalletDb> {
+        let raw = fs::read(&self.path)
+            .with_context(|| format!("reading wallet file {}", self.path.display()))?;
+        let sealed: SealedWalletDb =
+            serde_json::from_slice(&raw).context("decoding wallet file envelope")?;
+        if sealed.version != STORE_VERSION {
+            bail!("unsupported wallet file version {}", sealed.version);
+        }
+
+        let salt = BASE64_STANDARD
+            .decode(&sealed.salt)
+            .context("decoding salt")?;
+        let nonce = BASE64_STANDARD
+            .decode(&sealed.nonce)
+            .context("decoding nonce")?;
+        if nonce.len() != NONCE_LEN {
+            bail!("invalid nonce length {}", nonce.len());
+        }
+        let ciphertext = BASE64_STANDARD
+            .decode(&sealed.ciphertext)
+            .context("decoding ciphertext")?;
+
+        let cipher = self.cipher(&salt, sealed.iterations);
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+            .map_err(|_| anyhow!("wrong passphrase or corrupted wallet file"))?;
+        serde_json::from_slice(&plaintext).context("decoding wallet database")
+    }
+
+    /// Seals and writes `db` atomically (temp file + rename), with a fresh salt and nonce.
+    pub fn save(&self, db: &WalletDb) -> Result<()> {
+        let mut salt = [0u8; SALT_LEN];
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut salt);
+        rand::thread_rng().fill_bytes(&mut nonce);
+
+        let plaintext = serde_json::to_vec(db).context("encoding wallet database")?;
+        let ciphertext = self
+            .cipher(&salt, PBKDF2_ITERATIONS)
+            .encrypt(Nonce::from_slice(&nonce), plaintext.as_slice())
+            .map_err(|_| anyhow!("encrypting wallet database"))?;
+
+        let sealed = SealedWalletDb {
+            version: STORE_VERSION,
+            iterations: PBKDF2_ITERATIONS,
+            salt: BASE64_STANDARD.encode(salt),
+            nonce: BASE64_STANDARD.encode(nonce),
+            ciphertext: BASE64_STANDARD.encode(ciphertext),
+        };
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("creating directory {}", parent.display()))?;
+        }
+        let temp_path = self.path.with_extension("json.tmp");
+        fs::write(&temp_path, serde_json::to_vec_pretty(&sealed)?)
+            .with_context(|| format!("writing {}", temp_path.display()))?;
+        fs::rename(&temp_path, &self.path)
+            .with_context(|| format!("renaming wallet file to {}", self.path.display()))?;
+        Ok(())
+    }
+
+    fn cipher(&self, salt: &[u8], iterations: u32) -> Aes256Gcm {
+        let mut key = [0u8; 32];
+        pbkdf2::pbkdf2_hmac::<Sha256>(self.passphrase.as_bytes(), salt, iterations, &mut key);
+        Aes256Gcm::new(&key.into())
+    }
+}
@@ -0,0 +1,702 @@
+use sdk::TxHash;
+use serde::{Deserialize, Serialize};
+use zk_primitives::Note;
+
+// ---- Server Config API Types ----
+
+/// Response for the /api/config endpoint.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ServerConfigResponse {
+    /// The UTXO contract name (e.g. "hyli_utxo")
+    pub contract_name: String,
+    /// The UTXO state contract name (e.g. "hyli-utxo-state")
+    pub utxo_state_contract_name: String,
+    /// The SMT inclusion proof contract name (e.g. "hyli_smt_incl_proof")
+    pub smt_incl_proof_contract_name: String,
+}
+
+// ---- Address Registry API Types ----
+
+/// Request to register a username -> UTXO address mapping.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RegisterAddressRequest {
+    /// The username (e.g., "matteo" - without @wallet suffix)
+    pub username: String,
+    /// The UTXO address (64-char hex, derived from poseidon2([secret_key, 0]))
+    pub utxo_address: String,
+    /// The secp256k1 public key for ECDH encryption (64-char hex, x-coordinate)
+    pub encryption_pubkey: String,
+    /// Unix timestamp (seconds) included in the signed message.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<u64>,
+    /// Hex-encoded signature over [`crate::register_username_message`], by the
+    /// registered encryption key for a new username or by the owner's key for
+    /// an update.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+/// Response after successfully registering an address.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RegisterAddressResponse {
+    /// The normalized username (lowercase)
+    pub username: String,
+    /// The registered UTXO address
+    pub utxo_address: String,
+    /// The secp256k1 public key for ECDH encryption
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub encryption_pubkey: String,
+    /// Unix timestamp when registered
+    pub registered_at: u64,
+    /// Whether this was an update to an existing registration
+    pub was_update: bool,
+}
+
+/// Response when resolving a username to an address.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResolveAddressResponse {
+    /// The username
+    pub username: String,
+    /// The UTXO address
+    pub utxo_address: String,
+    /// The secp256k1 public key for ECDH encryption (may be empty for legacy registrations)
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub encryption_pubkey: String,
+    /// Unix timestamp when registered
+    pub registered_at: u64,
+}
+
+/// Query parameters authorizing a username release.
+///
+/// `signature` is a hex-encoded 65-byte recoverable signature over
+/// [`crate::release_username_message`] by the key owning the username.
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct ReleaseAddressQuery {
+    /// Unix timestamp (seconds) included in the signed message.
+    #[serde(default)]
+    pub timestamp: Option<u64>,
+    /// Hex-encoded signature (`r || s || recovery_id`).
+    #[serde(default)]
+    pub signature: Option<String>,
+}
+
+// ---- Encrypted Notes API Types ----
+
+/// Request to upload an encrypted note.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UploadNoteRequest {
+    /// Recipient tag (hex-encoded, derived from recipient's public key).
+    pub recipient_tag: String,
+    /// Base64-encoded encrypted payload.
+    pub encrypted_payload: String,
+    /// Hex-encoded ephemeral public key for ECDH decryption.
+    pub ephemeral_pubkey: String,
+    /// Optional sender tag for grouping/filtering.
+    #[serde(default)]
+    pub sender_tag: Option<String>,
+}
+
+/// Response after successfully uploading a note.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UploadNoteResponse {
+    /// Unique identifier for the stored note.
+    pub id: String,
+    /// Unix timestamp when the note was stored.
+    pub stored_at: u64,
+}
+
+/// Query parameters for fetching notes.
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct GetNotesQuery {
+    /// Only return notes stored after this Unix timestamp.
+    #[serde(default)]
+    pub since: Option<u64>,
+    /// Maximum number of notes to return.
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+/// Query parameters authorizing a note deletion.
+///
+/// `signature` is a hex-encoded 65-byte recoverable signature over
+/// [`crate::delete_note_message`] by the key owning the recipient's address,
+/// the key that first registered it.
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct DeleteNoteQuery {
+    /// Unix timestamp (seconds) included in the signed message.
+    #[serde(default)]
+    pub timestamp: Option<u64>,
+    /// Hex-encoded signature (`r || s || recovery_id`).
+    #[serde(default)]
+    pub signature: Option<String>,
+}
+
+/// A single encrypted note record in API responses.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EncryptedNoteRecord {
+    /// Unique identifier.
+    pub id: String,
+    /// Base64-encoded encrypted payload.
+    pub encrypted_payload: String,
+    /// Hex-encoded ephemeral public key.
+    pub ephemeral_pubkey: String,
+    /// Optional sender tag.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sender_tag: Option<String>,
+    /// Unix timestamp when stored.
+    pub stored_at: u64,
+}
+
+/// Response containing fetched notes.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GetNotesResponse {
+    /// The encrypted notes.
+    pub notes: Vec<EncryptedNoteRecord>,
+    /// Whether there are more notes available beyond the limit.
+    pub has_more: bool,
+}
+
+/// Query parameters for the `/api/notes/stream` Server-Sent Events endpoint.
+///
+/// The stream sends a `note` event per stored note, with an opaque cursor
+/// as the SSE event id, and a periodic `heartbeat` event carrying the current
+/// cursor. Reconnecting with `Last-Event-ID` (or `cursor`) replays the notes
+/// stored since that cursor; without a cursor only new notes are sent.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NoteStreamQuery {
+    /// Comma-separated recipient tags to subscribe to
+    pub tags: String,
+    /// Event id of the last note received
+    #[serde(default)]
+    pub cursor: Option<String>,
+}
+
+/// Payload of a `note` event on `/api/notes/stream`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NoteStreamEvent {
+    pub recipient_tag: String,
+    pub note: EncryptedNoteRecord,
+}
+
+/// Payload of a `heartbeat` event on `/api/notes/stream` and `/api/state/stream`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StreamHeartbeat {
+    /// Cursor to resume from, also sent as the SSE event id
+    pub cursor: String,
+    /// Server Unix timestamp
+    pub timestamp: u64,
+}
+
+/// Query parameters for the `/api/state/stream` Server-Sent Events endpoint.
+///
+/// The stream sends a `transition` event per settled hyli-utxo-state
+/// transaction, with its sequence number as the SSE event id, and a periodic
+/// `heartbeat` event. Reconnecting with `Last-Event-ID` (or `cursor`) replays
+/// the retained transitions since that sequence; when they are no longer
+/// retained a `reset` event is sent first and witnesses should be refreshed.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StateStreamQuery {
+    /// Sequence number of the last transition received
+    #[serde(default)]
+    pub cursor: Option<u64>,
+}
+
+/// Payload of a `transition` event on `/api/state/stream`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateTransitionEvent {
+    /// Position of the transition in the stream, starting at 1
+    pub sequence: u64,
+    /// Hash of the settled transaction (hex)
+    pub tx_hash: String,
+    /// Notes root after the transition (hex)
+    pub notes_root: String,
+    /// Note commitments added to the tree (hex)
+    pub created_commitments: Vec<String>,
+    /// Nullifiers spent by the transaction (hex)
+    pub nullifiers: Vec<String>,
+    /// Unix timestamp at which the server saw the settlement
+    pub settled_at: u64,
+}
+
+/// Plaintext of an encrypted note, as written by the web client (`DecryptedNotePayload`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotePayload {
+    /// The note sent to the recipient.
+    pub note: Note,
+    /// Hash of the transfer transaction.
+    pub tx_hash: String,
+    /// Amount transferred.
+    pub amount: u64,
+    /// Sender's encryption pubkey (x-coordinate hex).
+    pub from: String,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+}
+
+// ---- Transfer API Types ----
+
+/// Input note data for transfer requests (full note + nullifier key).
+///
+/// Carries no spend authority: the nullifier key only derives the note's
+/// nullifier, it cannot authorize spending the note.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InputNoteData {
+    /// The note being spent
+    pub note: Note,
+    /// Nullifier key of the note owner (64-char hex)
+    pub nullifier_key: String,
+}
+
+/// Response after successful transfer
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TransferResponse {
+    /// Transaction hash
+    pub tx_hash: TxHash,
+    /// Change note if any
+    pub change_note: Option<Note>,
+}
+
+/// Request to transfer with a pre-generated proof (client-side proving)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProvedTransferRequest {
+    /// Base64-encoded proof bytes (raw proof without public inputs)
+    pub proof: String,
+    /// Public inputs as hex strings (733 field elements)
+    pub public_inputs: Vec<String>,
+    /// 128-byte blob data
+    pub blob_data: Vec<u8>,
+    /// Output notes: [recipient_note, change_note]
+    pub output_notes: [Note; 2],
+}
+
+// ---- Two-Step Transfer API Types ----
+
+/// Request to create a blob transaction (step 1 of two-step transfer)
+///
+/// `smt_blob_data` can be provided directly, or the server computes it from
+/// `input_notes` + `notes_root`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateBlobRequest {
+    /// 128-byte blob data: [input_commit_0, input_commit_1, nullifier_0, nullifier_1]
+    pub blob_data: Vec<u8>,
+    /// 96-byte SMT blob data: [nullifier_0 (32B)][nullifier_1 (32B)][notes_root (32B)].
+    /// Optional when `input_notes` + `notes_root` are provided.
+    #[serde(default)]
+    pub smt_blob_data: Option<Vec<u8>>,
+    /// Output notes: [recipient_note, change_note]
+    pub output_notes: [Note; 2],
+    /// Optional token transfer blob to include in the same transaction.
+    #[serde(default)]
+    pub token_transfer: Option<TokenTransferRequest>,
+    /// Input notes for computing smt_blob_data server-side (required when smt_blob_data is absent)
+    #[serde(default)]
+    pub input_notes: Option<[InputNoteData; 2]>,
+    /// Hex-encoded notes root (required when smt_blob_data is absent; returned by /smt-witness)
+    #[serde(default)]
+    pub notes_root: Option<String>,
+}
+
+/// Response after creating a blob transaction
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateBlobResponse {
+    /// Transaction hash from the blockchain
+    pub tx_hash: String,
+    /// The blobs that were included in the transaction (for client to use in proof)
+    pub blobs: Vec<BlobInfo>,
+}
+
+/// Information about a blob in the transaction
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BlobInfo {
+    /// Contract name this blob targets
+    pub contract_name: String,
+    /// Blob data as hex string
+    pub data: String,
+}
+
+/// Response for /api/blob/hash — returns the tx_hash without submitting to the chain.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BlobHashResponse {
+    /// Deterministic transaction hash (SHA3-256 of identity + blob hashes)
+    pub tx_hash: TxHash,
+}
+
+/// Request to finalize a transfer atomically: submit blob tx + both proofs in one call.
+///
+/// Two modes:
+/// 1. **Client-provided proofs**: supply `smt_blob_data`, `smt_proof`, and `smt_public_inputs`.
+/// 2. **Server-side SMT proof generation**: omit `smt_proof` and instead provide `input_notes`,
+///    `siblings_0`, `siblings_1`, and `notes_root`. The server computes nullifiers, builds
+///    `smt_blob_data`, and generates the proof.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FinalizeTransferRequest {
+    /// 128-byte blob data: [input_commit_0, input_commit_1, nullifier_0, nullifier_1]
+    pub blob_data: Vec<u8>,
+    /// 96-byte SMT blob data: [nullifier_0 (32B)][nullifier_1 (32B)][notes_root (32B)].
+    /// Required when `smt_proof` is provided; computed by the server otherwise.
+    #[serde(default)]
+    pub smt_blob_data: Option<Vec<u8>>,
+    /// Output notes: [recipient_note, change_note]
+    pub output_notes: [Note; 2],
+    /// Optional token transfer blob to include in the same transaction.
+    #[serde(default)]
+    pub token_transfer: Option<TokenTransferRequest>,
+    /// Base64-encoded proof bytes for hyli_utxo
+    pub proof: String,
+    /// Public inputs as hex strings for hyli_utxo
+    pub public_inputs: Vec<String>,
+    /// Base64-encoded proof bytes for hyli_smt_incl_proof (optional — server generates if absent)
+    #[serde(default)]
+    pub smt_proof: Option<String>,
+    /// Public inputs as hex strings for hyli_smt_incl_proof (required when smt_proof is provided)
+    #[serde(default)]
+    pub smt_public_inputs: Option<Vec<String>>,
+    /// Input notes for server-side SMT proof generation (required when smt_proof is absent)
+    #[serde(default)]
+    pub input_notes: Option<[InputNoteData; 2]>,
+    /// SMT siblings for input_notes[0] — hex-encoded field elements (required when smt_proof is absent)
+    #[serde(default)]
+    pub siblings_0: Option<Vec<String>>,
+    /// SMT siblings for input_notes[1] — hex-encoded field elements (required when smt_proof is absent)
+    #[serde(default)]
+    pub siblings_1: Option<Vec<String>>,
+    /// Hex-encoded notes root from the SMT (required when smt_proof is absent; returned by /smt-witness)
+    #[serde(default)]
+    pub notes_root: Option<String>,
+}
+
+/// Response after finalizing a transfer
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FinalizeTransferResponse {
+    /// Transaction hash from the blockchain
+    pub tx_hash: TxHash,
+}
+
+/// Request to submit a proof for an existing blob transaction (step 2 of two-step transfer)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SubmitProofRequest {
+    /// Transaction hash from CreateBlobResponse
+    pub tx_hash: TxHash,
+    /// Base64-encoded proof bytes for hyli_utxo
+    pub proof: String,
+    /// Public inputs as hex strings for hyli_utxo
+    pub public_inputs: Vec<String>,
+    /// Base64-encoded proof bytes for hyli_smt_incl_proof
+    pub smt_proof: String,
+    /// Public inputs as hex strings for hyli_smt_incl_proof
+    pub smt_public_inputs: Vec<String>,
+}
+
+// ---- SMT Witness API Types ----
+
+/// Query parameters for the hyli-utxo-state `/smt-witness` endpoint.
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::IntoParams))]
+pub struct SmtWitnessQuery {
+    /// Hex-encoded commitment of the first input note
+    pub commitment0: String,
+    /// Hex-encoded commitment of the second input note (defaults to zero)
+    #[serde(default)]
+    pub commitment1: Option<String>,
+    /// Hex-encoded notes root to build the witnesses against, one of the
+    /// roots the contract still accepts (defaults to the current root)
+    #[serde(default)]
+    pub notes_root: Option<String>,
+}
+
+/// SMT witnesses for up to two commitments, taken against the requested or
+/// current notes root.
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct SmtWitnessResponse {
+    /// Hex-encoded notes root the siblings were built against
+    pub notes_root: String,
+    /// 256 "0x"-prefixed sibling field elements for `commitment0`
+    pub siblings_0: Vec<String>,
+    /// 256 "0x"-prefixed sibling field elements for `commitment1`
+    pub siblings_1: Vec<String>,
+}
+
+/// Request body of the hyli-utxo-state `POST /smt-witnesses` endpoint.
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct SmtWitnessesRequest {
+    /// Hex-encoded commitments to build witnesses for
+    pub commitments: Vec<String>,
+    /// Hex-encoded notes root to build the witnesses against, one of the
+    /// roots the contract still accepts (defaults to the current root)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes_root: Option<String>,
+}
+
+/// SMT witnesses for many commitments, all taken against one notes root.
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct SmtWitnessesResponse {
+    /// Hex-encoded notes root the siblings were built against
+    pub notes_root: String,
+    /// One witness per requested commitment, in request order
+    pub witnesses: Vec<CompactSmtWitness>,
+}
+
+/// The 256 siblings of a commitment with the zero (default) ones left out.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct CompactSmtWitness {
+    /// Hex-encoded commitment the witness is for
+    pub commitment: String,
+    /// Hex-encoded 32-byte bitmap; bit `h % 8` (least significant first) of
+    /// byte `h / 8` is set when the sibling at height `h` is not zero
+    pub bitmap: String,
+    /// "0x"-prefixed non-zero siblings, by increasing height
+    pub siblings: Vec<String>,
+}
+
+impl CompactSmtWitness {
+    /// Compacts the 256 big-endian siblings of `commitment`.
+    pub fn from_siblings(commitment: String, siblings: &[[u8; 32]; 256]) -> Self {
+        let mut bitmap = [0u8; 32];
+        let mut non_zero = Vec::new();
+        for (height, sibling) in siblings.iter().enumerate() {
+            if *sibling != [0u8; 32] {
+                bitmap[height / 8] |= 1 << (height % 8);
+                non_zero.push(format!("0x{}", hex::encode(sibling)));
+            }
+        }
+        Self {
+            commitment,
+            bitmap: hex::encode(bitmap),
+            siblings: non_zero,
+        }
+    }
+
+    /// Expands the witness back to the 256 "0x"-prefixed siblings of
+    /// [`SmtWitnessResponse`].
+    pub fn expand(&self) -> Result<Vec<String>, String> {
+        let bitmap = hex::decode(&self.bitmap).map_err(|e| format!("invalid bitmap hex: {e}"))?;
+        if bitmap.len() != 32 {
+            return Err(format!("bitmap must be 32 bytes, got {}", bitmap.len()));
+        }
+        let set_bits: usize = bitmap.iter().map(|byte| byte.count_ones() as usize).sum();
+        if set_bits != self.siblings.len() {
+            return Err(format!(
+                "bitmap has {set_bits} bits set but {} siblings were sent",
+                self.siblings.len()
+            ));
+        }
+
+        let zero = format!("0x{}", hex::encode([0u8; 32]));
+        let mut non_zero = self.siblings.iter();
+        Ok((0..256)
+            .map(|height| {
+                if bitmap[height / 8] & (1 << (height % 8)) != 0 {
+                    non_zero.next().cloned().unwrap_or_default()
+                } else {
+                    zero.clone()
+                }
+            })
+            .collect())
+    }
+}
+
+/// Request body of the hyli-utxo-state `/note-status` endpoint.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct NoteStatusRequest {
+    /// Hex-encoded note commitments to look up in the notes tree
+    #[serde(default)]
+    pub commitments: Vec<String>,
+    /// Hex-encoded nullifiers to look up in the nullified tree
+    #[serde(default)]
+    pub nullifiers: Vec<String>,
+}
+
+/// Membership of the requested commitments and nullifiers, all read from the
+/// same state.
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct NoteStatusResponse {
+    /// Hex-encoded notes root the commitments were checked against
+    pub notes_root: String,
+    /// Hex-encoded nullified tree root the nullifiers were checked against
+    pub nullified_root: String,
+    /// Whether each commitment is in the notes tree, in request order
+    pub commitments: Vec<bool>,
+    /// Whether each nullifier is spent, in request order
+    pub nullifiers: Vec<bool>,
+}
+
+// ---- Existing Types ----
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FaucetRequest {
+    pub pubkey_hex: String,
+    #[serde(default)]
+    pub amount: Option<u64>,
+    /// Solved challenge from `/api/faucet/challenge`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pow: Option<FaucetPowSolution>,
+}
+
+/// Request for a faucet proof-of-work challenge.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FaucetChallengeRequest {
+    /// UTXO address the faucet will mint to (32-byte hex).
+    pub pubkey_hex: String,
+}
+
+/// Server-signed hashcash challenge bound to a faucet recipient.
+///
+/// Solved by a nonce for which [`crate::faucet_pow_digest`] has at least
+/// `difficulty` leading zero bits.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FaucetChallenge {
+    /// Random challenge id (hex).
+    pub challenge: String,
+    /// Normalized UTXO address the challenge is bound to.
+    pub pubkey_hex: String,
+    /// Required leading zero bits.
+    pub difficulty: u8,
+    /// Unix timestamp after which the challenge is rejected.
+    pub expires_at: u64,
+    /// Server HMAC over the fields above (hex).
+    pub signature: String,
+}
+
+/// A challenge together with the nonce solving it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FaucetPowSolution {
+    pub challenge: FaucetChallenge,
+    pub nonce: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DepositRequest {
+    pub pubkey_hex: String,
+    #[serde(default)]
+    pub amount: Option<u64>,
+    #[serde(default)]
+    pub token_contract: Option<String>,
+    #[serde(default)]
+    pub wallet_account: Option<String>,
+    #[serde(default)]
+    pub secp256k1_blob: Option<Vec<u8>>,
+    #[serde(default)]
+    pub wallet_blob: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TokenTransferRequest {
+    pub token_contract: String,
+    pub sender: String,
+    pub recipient: String,
+    pub amount: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FaucetResponse {
+    pub note: Note,
+    /// Id to poll on `/api/tx/{hash}` while the mint is queued; the record
+    /// carries the tx hash once the blob transaction is submitted.
+    #[serde(default)]
+    pub tracking_id: String,
+}
+
+// ---- Transaction Tracking Types ----
+
+/// What a tracked blob transaction does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TxKind {
+    Faucet,
+    Deposit,
+    Transfer,
+}
+
+/// Lifecycle of a blob transaction submitted by the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TxStatus {
+    /// Queued or submitted, waiting for proofs
+    Pending,
+    /// The hyli_utxo proof was submitted, waiting for settlement
+    ProofSubmitted,
+    Settled,
+    Failed,
+    TimedOut,
+}
+
+impl TxStatus {
+    /// Whether the transaction can no longer change state.
+    pub fn is_final(self) -> bool {
+        matches!(
+            self,
+            TxStatus::Settled | TxStatus::Failed | TxStatus::TimedOut
+        )
+    }
+}
+
+/// Response of `/api/tx/{hash}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TxRecord {
+    /// Hex-encoded blob tx hash, unset while the transaction is queued
+    #[serde(default)]
+    pub tx_hash: Option<String>,
+    pub kind: TxKind,
+    pub status: TxStatus,
+    /// Why the transaction failed or timed out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure_reason: Option<String>,
+    /// Unix timestamp at which the server queued the transaction
+    pub submitted_at: u64,
+    /// Unix timestamp of the last status change
+    pub updated_at: u64,
+}
+
+// ---- Error Types ----
+
+/// Machine-readable reason of an error response, so clients can react
+/// without parsing the message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    /// The request is malformed or inconsistent
+    BadRequest,
+    /// A proof public input has the wrong count or width, or is not a
+    /// canonical field element
+    InvalidPublicInput,
+    /// A proof is malformed or does not verify
+    InvalidProof,
+    /// A proof is valid but for another transaction, identity or blob
+    ProofMismatch,
+    /// The request signature is missing or malformed
+    Unauthorized,
+    /// The request signature does not grant access to the resource
+    Forbidden,
+    NotFound,
+    PayloadTooLarge,
+    /// The faucet proof of work is missing or invalid
+    InvalidPow,
+    /// A faucet cooldown, quota or budget is exhausted
+    QuotaExceeded,
+    /// The prover cannot take more work, retry after `Retry-After`
+    ProverBusy,
+    /// An input note is being spent by a transaction that has not settled
+    NullifierInFlight,
+    /// An input note is already spent
+    NullifierSpent,
+    /// The notes root is not one of the last roots the contract accepts,
+    /// the witnesses and proof must be rebuilt against a recent one
+    RootTooOld,
+    Internal,
+    /// A code this client does not know about
+    #[serde(other)]
+    Unknown,
+}
+
+/// JSON body of every error response.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErrorResponse {
+    pub error: String,
+    pub code: ErrorCode,
+}
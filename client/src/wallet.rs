@@ -0,0 +1,534 @@
+use anyhow::{anyhow, bail, Context, Result};
+use barretenberg::Prove;
+use element::Element;
+use sdk::TxHash;
+use zk_primitives::{
+    get_address_for_private_key, HyliUtxo, InputNote, Note, Utxo, UtxoKind, HYLI_BLOB_LENGTH_BYTES,
+};
+
+use crate::{
+    client::{parse_element_hex, CachecashClient},
+    types::{
+        CreateBlobRequest, FinalizeTransferRequest, InputNoteData, ServerConfigResponse,
+        TokenTransferRequest,
+    },
+};
+
+/// Number of blobs in a plain transfer: [hyli-utxo-state, hyli_utxo, hyli_smt_incl_proof].
+const TRANSFER_BLOB_COUNT: u32 = 3;
+/// Index of the hyli_utxo blob within the transaction.
+const HYLI_UTXO_BLOB_INDEX: u32 = 1;
+/// Nullifiers looked up per `/note-status` request.
+const NOTE_STATUS_BATCH_SIZE: usize = 512;
+
+/// Outcome of a successful [`Wallet::send`].
+#[derive(Debug, Clone)]
+pub struct SendReceipt {
+    /// Hash of the submitted blob transaction
+    pub tx_hash: TxHash,
+    /// Note created for the recipient
+    pub transfer_note: Note,
+    /// Change note returned to the sender, if any
+    pub change_note: Option<Note>,
+}
+
+/// Outcome of a successful [`Wallet::withdraw`].
+#[derive(Debug, Clone)]
+pub struct WithdrawReceipt {
+    /// Hash of the submitted blob transaction
+    pub tx_hash: TxHash,
+    /// Change note returned to the sender, if any
+    pub change_note: Option<Note>,
+}
+
+/// Spending key plus the set of notes it owns, bound to a server.
+///
+/// The wallet keeps its notes in memory only; callers are responsible for
+/// persisting [`Wallet::notes`] between runs.
+pub struct Wallet {
+    client: CachecashClient,
+    secret_key: Element,
+    notes: Vec<Note>,
+}
+
+impl Wallet {
+    pub fn new(client: CachecashClient, secret_key: Element) -> Self {
+        Self {
+            client,
+            secret_key,
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn with_notes(client: CachecashClient, secret_key: Element, notes: Vec<Note>) -> Self {
+        let mut wallet = Self::new(client, secret_key);
+        for note in notes {
+            wallet.add_note(note);
+        }
+        wallet
+    }
+
+    pub fn client(&self) -> &CachecashClient {
+        &self.client
+    }
+
+    /// UTXO address owning this wallet's notes: `hash_merge([secret_key, 0])`.
+    pub fn address(&self) -> Element {
+        get_address_for_private_key(self.secret_key)
+    }
+
+    pub fn notes(&self) -> &[Note] {
+        &self.notes
+    }
+
+    /// Adds a spendable note. Notes owned by another address or already known are ignored.
+    pub fn add_note(&mut self, note: Note) -> bool {
+        if note.address != self.address()
+            || note_value(&note) == 0
+            || self.notes.iter().any(|n| n.psi == note.psi)
+        {
+            return false;
+        }
+        self.notes.push(note);
+        true
+    }
+
+    pub fn balance(&self) -> u64 {
+        self.notes.iter().map(note_value).sum()
+    }
+
+    /// Drops the notes whose nullifier is recorded on chain, e.g. after
+    /// restoring a backup taken before they were spent. Returns the number of
+    /// notes dropped.
+    pub async fn prune_spent(&mut self) -> Result<usize> {
+        if self.notes.is_empty() {
+            return Ok(0);
+        }
+        let config = self.client.config().await?;
+        let nullifiers: Vec<Element> = self
+            .notes
+            .iter()
+            .map(|note| InputNote::new(note.clone(), self.secret_key).nullifier())
+            .collect();
+
+        let mut spent = Vec::with_capacity(nullifiers.len());
+        for batch in nullifiers.chunks(NOTE_STATUS_BATCH_SIZE) {
+            let status = self
+                .client
+                .note_status(&config.utxo_state_contract_name, &[], batch)
+                .await?;
+            if status.nullifiers.len() != batch.len() {
+                bail!(
+                    "server returned {} nullifier statuses for {} nullifiers",
+                    status.nullifiers.len(),
+                    batch.len()
+                );
+            }
+            spent.extend(status.nullifiers);
+        }
+
+        let before = self.notes.len();
+        let mut spent = spent.into_iter();
+        self.notes.retain(|_| !spent.next().unwrap_or(false));
+        Ok(before - self.notes.len())
+    }
+
+    /// Sends `amount` to the UTXO address `recipient`.
+    ///
+    /// Selects at most two input notes, proves `HyliUtxo` locally and lets the
+    /// server generate the SMT inclusion proof when finalizing.
+    pub async fn send(&mut self, recipient: Element, amount: u64) -> Result<SendReceipt> {
+        let config = self.client.config().await?;
+        let (inputs, change) = self.select_inputs(amount)?;
+        let contract = inputs[0].note.contract;
+
+        let transfer_note = new_note(contract, recipient, amount);
+        let change_note = (change > 0).then(|| new_note(contract, self.address(), change));
+        let output_notes = [
+            transfer_note.clone(),
+            change_note.clone().unwrap_or_else(Note::padding_note),
+        ];
+
+        let utxo = Utxo::new_send(inputs, output_notes);
+        let tx_hash = self.submit(&config, utxo, None).await?;
+
+        Ok(SendReceipt {
+            tx_hash,
+            transfer_note,
+            change_note,
+        })
+    }
+
+    /// Burns `amount` from the private pool and credits it to `wallet_address`
+    /// on the SMT token contract `token_contract`.
+    pub async fn withdraw(
+        &mut self,
+        wallet_address: &str,
+        amount: u64,
+        token_contract: &str,
+    ) -> Result<WithdrawReceipt> {
+        let burn_address = encode_wallet_address(wallet_address)?;
+        let config = self.client.config().await?;
+        let (inputs, change) = self.select_inputs(amount)?;
+        let contract = inputs[0].note.contract;
+
+        let change_note = (change > 0).then(|| new_note(contract, self.address(), change));
+        let output_notes = [
+            Note::padding_note(),
+            change_note.clone().unwrap_or_else(Note::padding_note),
+        ];
+
+        let utxo = Utxo::new(UtxoKind::Burn, inputs, output_notes, Some(burn_address));
+        let token_transfer = TokenTransferRequest {
+            token_contract: token_contract.to_string(),
+            sender: config.utxo_state_contract_name.clone(),
+            recipient: wallet_address.trim().to_string(),
+            amount,
+        };
+        let tx_hash = self.submit(&config, utxo, Some(token_transfer)).await?;
+
+        Ok(WithdrawReceipt {
+            tx_hash,
+            change_note,
+        })
+    }
+
+    /// Proves and finalizes `utxo`, returning the blob tx hash.
+    ///
+    /// Spent input notes are removed from the wallet and the change note (if any)
+    /// is added once the server accepts the transaction.
+    async fn submit(
+        &mut self,
+        config: &ServerConfigResponse,
+        utxo: Utxo,
+        token_transfer: Option<TokenTransferRequest>,
+    ) -> Result<TxHash> {
+        let identity = format!("transfer@{}", config.contract_name);
+        let tx_blob_count = TRANSFER_BLOB_COUNT + u32::from(token_transfer.is_some());
+
+        let input_commitments = [
+            utxo.input_notes[0].note.commitment(),
+            utxo.input_notes[1].note.commitment(),
+        ];
+        let witness = self
+            .client
+            .smt_witness(
+                &config.utxo_state_contract_name,
+                input_commitments[0],
+                input_commitments[1],
+                None,
+            )
+            .await?;
+        let notes_root = parse_element_hex(&witness.notes_root).context("parsing notes_root")?;
+
+        let mut hyli_utxo = HyliUtxo {
+            version: 2,
+            initial_state: [0u8; 4],
+            next_state: [0u8; 4],
+            identity_len: u8::try_from(identity.len())
+                .map_err(|_| anyhow!("identity '{identity}' exceeds Noir payload limit"))?,
+            identity,
+            tx_hash: String::new(),
+            index: HYLI_UTXO_BLOB_INDEX,
+            blob_number: 1,
+            blob_index: HYLI_UTXO_BLOB_INDEX,
+            blob_contract_name_len: u8::try_from(config.contract_name.len())
+                .map_err(|_| anyhow!("contract name exceeds Noir payload limit"))?,
+            blob_contract_name: config.contract_name.clone(),
+            blob_capacity: HYLI_BLOB_LENGTH_BYTES as u32,
+            blob_len: HYLI_BLOB_LENGTH_BYTES as u32,
+            blob: [0u8; HYLI_BLOB_LENGTH_BYTES],
+            tx_blob_count,
+            success: true,
+            utxo: utxo.clone(),
+        };
+        hyli_utxo.blob = hyli_utxo.expected_blob();
+
+        // [nullifier_0 (32B)][nullifier_1 (32B)][notes_root (32B)]
+        let mut smt_blob_data = hyli_utxo.blob[64..128].to_vec();
+        smt_blob_data.extend_from_slice(&notes_root.to_be_bytes());
+
+        let blob_request = CreateBlobRequest {
+            blob_data: hyli_utxo.blob.to_vec(),
+            smt_blob_data: Some(smt_blob_data),
+            output_notes: utxo.output_notes.clone(),
+            token_transfer: token_transfer.clone(),
+            input_notes: None,
+            notes_root: None,
+        };
+        let tx_hash = self.client.hash_blob(&blob_request).await?.tx_hash;
+        hyli_utxo.tx_hash = hex::encode(&tx_hash.0);
+
+        // Proof generation is CPU-intensive; run in a blocking thread
+        let proof = tokio::task::spawn_blocking(move || {
+            hyli_utxo
+                .prove()
+                .map_err(|err| anyhow!("generating hyli_utxo proof: {err}"))
+        })
+        .await
+        .context("proof task panicked")??;
+
+        let input_notes = utxo.input_notes.each_ref().map(|input| InputNoteData {
+            note: input.note.clone(),
+            nullifier_key: input.nullifier_key().to_hex(),
+        });
+        let request = FinalizeTransferRequest {
+            blob_data: blob_request.blob_data,
+            smt_blob_data: None,
+            output_notes: utxo.output_notes.clone(),
+            token_transfer,
+            proof: base64_encode(&proof.proof.0),
+            public_inputs: proof
+                .public_inputs
+                .iter()
+                .map(|e| format!("0x{}", e.to_hex()))
+                .collect(),
+            smt_proof: None,
+            smt_public_inputs: None,
+            input_notes: Some(input_notes),
+            siblings_0: Some(witness.siblings_0),
+            siblings_1: Some(witness.siblings_1),
+            notes_root: Some(witness.notes_root),
+        };
+        let finalized = self.client.finalize_transfer(&request).await?;
+        if finalized.tx_hash != tx_hash {
+            bail!(
+                "server finalized tx {} but proofs were built for {}",
+                finalized.tx_hash,
+                tx_hash
+            );
+        }
+
+        tracing::info!(%tx_hash, "Finalized transfer");
+
+        let spent: Vec<Element> = utxo.input_notes.iter().map(|n| n.note.psi).collect();
+        self.notes.retain(|note| !spent.contains(&note.psi));
+        self.add_note(utxo.output_notes[1].clone());
+
+        Ok(tx_hash)
+    }
+
+    /// Picks one or two notes covering `amount`, preferring a single note.
+    fn select_inputs(&self, amount: u64) -> Result<([InputNote; 2], u64)> {
+        if amount == 0 {
+            bail!("amount must be greater than zero");
+        }
+
+        let mut sorted: Vec<&Note> = self.notes.iter().collect();
+        sorted.sort_by_key(|note| note_value(note));
+
+        let input = |note: &Note| InputNote::new(note.clone(), self.secret_key);
+
+        if let Some(note) = sorted.iter().find(|note| note_value(note) >= amount) {
+            return Ok((
+                [input(note), InputNote::padding_note()],
+                note_value(note) - amount,
+            ));
+        }
+
+        for (i, a) in sorted.iter().enumerate() {
+            for b in &sorted[i + 1..] {
+                let total = note_value(a) + note_value(b);
+                if total >= amount {
+                    return Ok(([input(a), input(b)], total - amount));
+                }
+            }
+        }
+
+        bail!(
+            "insufficient balance in at most two notes: have {} across {} notes, need {}",
+            self.balance(),
+            self.notes.len(),
+            amount
+        )
+    }
+}
+
+/// Value of a note as `u64` (notes are minted from `u64` amounts).
+pub fn note_value(note: &Note) -> u64 {
+    note.value.to_u256().as_u64()
+}
+
+/// Builds a fresh note with a random psi.
+///
+/// `kind` is always 2: the circuits hash a constant kind into the commitment and
+/// carry the token in `contract`.
+fn new_note(contract: Element, address: Element, amount: u64) -> Note {
+    Note {
+        kind: Element::new(2),
+        contract,
+        address,
+        psi: Element::secure_random(rand::thread_rng()),
+        value: Element::new(amount),
+    }
+}
+
+/// Encodes a token-contract identity into the burn address field: its UTF-8
+/// bytes, left-padded with zeros to 32 bytes.
+fn encode_wallet_address(wallet_address: &str) -> Result<Element> {
+    let bytes = wallet_address.trim().as_bytes();
+    if bytes.is_empty() {
+        bail!("wallet address must not be empty");
+    }
+    if bytes.len() > 31 {
+        bail!("wallet address is too long to encode in the withdraw proof");
+    }
+    let mut field = [0u8; 32];
+    field[32 - bytes.len()..].copy_from_slice(bytes);
+    Ok(Element::from_be_bytes(field))
+}
+
+fn base64_encode(bytes: &[u8]) -> String {
+    use base64::prelude::*;
+    BASE64_STANDARD.encode(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashSet,
+        sync::{Arc, Mutex},
+    };
+
+    use axum::{
+        routing::{get, post},
+        Json, Router,
+    };
+
+    use super::*;
+    use crate::{
+        client::tests::serve,
+        types::{NoteStatusRequest, NoteStatusResponse},
+    };
+
+    const TOKEN: Element = Element::ZERO;
+
+    /// A wallet owning notes of `values`, bound to a server that is never
+    /// reached.
+    fn wallet_with(values: &[u64]) -> Wallet {
+        let mut wallet = Wallet::new(CachecashClient::new("127.0.0.1:9"), Element::new(7));
+        for value in values {
+            assert!(wallet.add_note(new_note(TOKEN, wallet.address(), *value)));
+        }
+        wallet
+    }
+
+    fn values(inputs: &[InputNote; 2]) -> [u64; 2] {
+        inputs.each_ref().map(|input| note_value(&input.note))
+    }
+
+    #[test]
+    fn send_spends_the_smallest_note_covering_the_amount() {
+        let wallet = wallet_with(&[5, 20, 12]);
+
+        let (inputs, change) = wallet.select_inputs(10).unwrap();
+        assert_eq!(values(&inputs), [12, 0]);
+        assert_eq!(inputs[1].note, Note::padding_note());
+        assert_eq!(change, 2);
+
+        let (inputs, change) = wallet.select_inputs(20).unwrap();
+        assert_eq!(values(&inputs), [20, 0]);
+        assert_eq!(change, 0);
+    }
+
+    #[test]
+    fn send_combines_two_notes_when_none_covers_the_amount() {
+        let wallet = wallet_with(&[5, 7, 3]);
+
+        let (inputs, change) = wallet.select_inputs(11).unwrap();
+        assert_eq!(values(&inputs), [5, 7]);
+        assert_eq!(change, 1);
+        for input in &inputs {
+            assert_eq!(input.note.address, wallet.address());
+        }
+    }
+
+    #[test]
+    fn send_rejects_amounts_beyond_two_notes() {
+        let wallet = wallet_with(&[3, 3, 3]);
+
+        assert!(wallet.select_inputs(7).is_err());
+        assert!(wallet.select_inputs(0).is_err());
+        assert!(wallet_with(&[]).select_inputs(1).is_err());
+        assert_eq!(wallet.balance(), 9);
+    }
+
+    #[test]
+    fn add_note_ignores_foreign_empty_and_known_notes() {
+        let mut wallet = wallet_with(&[5]);
+        let known = wallet.notes()[0].clone();
+
+        assert!(!wallet.add_note(known));
+        assert!(!wallet.add_note(new_note(TOKEN, Element::new(1), 5)));
+        assert!(!wallet.add_note(new_note(TOKEN, wallet.address(), 0)));
+        assert_eq!(wallet.notes().len(), 1);
+    }
+
+    /// A server whose `/note-status` reports `spent` as spent, recording the
+    /// number of nullifiers of each request in `batches`.
+    fn note_status_server(spent: HashSet<String>, batches: Arc<Mutex<Vec<usize>>>) -> Router {
+        Router::new()
+            .route(
+                "/api/config",
+                get(|| async {
+                    Json(ServerConfigResponse {
+                        contract_name: "hyli_utxo".to_string(),
+                        utxo_state_contract_name: "hyli-utxo-state".to_string(),
+                        smt_incl_proof_contract_name: "hyli_smt_incl_proof".to_string(),
+                    })
+                }),
+            )
+            .route(
+                "/v1/indexer/contract/hyli-utxo-state/note-status",
+                post(move |Json(request): Json<NoteStatusRequest>| async move {
+                    batches.lock().unwrap().push(request.nullifiers.len());
+                    Json(NoteStatusResponse {
+                        notes_root: Element::ZERO.to_hex(),
+                        nullified_root: Element::ZERO.to_hex(),
+                        commitments: Vec::new(),
+                        nullifiers: request
+                            .nullifiers
+                            .iter()
+                            .map(|nullifier| spent.contains(nullifier))
+                            .collect(),
+                    })
+                }),
+            )
+    }
+
+    #[tokio::test]
+    async fn prune_spent_drops_the_spent_notes() {
+        let values: Vec<u64> = (1..=600).collect();
+        let local = wallet_with(&values);
+        let spent: HashSet<String> = local
+            .notes()
+            .iter()
+            .filter(|note| note_value(note) % 100 == 0)
+            .map(|note| {
+                InputNote::new(note.clone(), local.secret_key)
+                    .nullifier()
+                    .to_hex()
+            })
+            .collect();
+        let batches = Arc::new(Mutex::new(Vec::new()));
+        let client = serve(note_status_server(spent, batches.clone())).await;
+        let mut wallet = Wallet::with_notes(client, local.secret_key, local.notes);
+
+        assert_eq!(wallet.prune_spent().await.unwrap(), 6);
+        assert_eq!(wallet.notes().len(), 594);
+        assert!(wallet
+            .notes()
+            .iter()
+            .all(|note| note_value(note) % 100 != 0));
+        assert_eq!(*batches.lock().unwrap(), vec![NOTE_STATUS_BATCH_SIZE, 88]);
+
+        // Nothing left to drop
+        assert_eq!(wallet.prune_spent().await.unwrap(), 0);
+        assert_eq!(wallet.notes().len(), 594);
+    }
+
+    #[tokio::test]
+    async fn prune_spent_without_notes_does_not_reach_the_server() {
+        assert_eq!(wallet_with(&[]).prune_spent().await.unwrap(), 0);
+    }
+}
@@ -1,4 +1,4 @@
-use std::collections::VecDeque;
+use std::{collections::VecDeque, io};
 
 use acvm::FieldElement;
 use borsh::{BorshDeserialize, BorshSerialize};
@@ -7,14 +7,19 @@ use sdk::{
     caller::ExecutionContext, merkle_utils::BorshableMerkleProof, utils::parse_calldata, Calldata,
     ContractName, RunResult, StateCommitment, StructuredBlobData,
 };
-use sparse_merkle_tree::H256;
+use sparse_merkle_tree::{
+    default_store::DefaultStore,
+    traits::{Hasher, StoreReadOps},
+    H256,
+};
 
 use crate::zk::{
-    smt::{self as smt, BorshableH256, WitnessLeaf, SMT},
+    smt::{self as smt, BorshableH256, Poseidon2Hasher, SmtStore, WitnessLeaf, SMT},
     Proof, ZkVmWitnessVec,
 };
 
-const MAX_ROOTS: usize = 1000;
+/// Number of recent notes roots an SMT inclusion proof may be built against.
+pub const MAX_ROOTS: usize = 1000;
 
 #[derive(Debug, BorshSerialize, BorshDeserialize, Clone)]
 pub struct ContractConfig {
@@ -23,11 +28,28 @@ pub struct ContractConfig {
     pub smt_contract_name: ContractName,
 }
 
-#[derive(Debug, Default, BorshSerialize, BorshDeserialize)]
-pub struct HyliUtxoState {
-    notes_tree: SMT<BorshableH256>,
-    nullified_tree: SMT<BorshableH256>,
-    roots: VecDeque<[u8; 8]>,
+#[derive(Debug, Default)]
+pub struct HyliUtxoState<S = DefaultStore<H256>> {
+    notes_tree: SMT<BorshableH256, S>,
+    nullified_tree: SMT<BorshableH256, S>,
+    /// The last [`MAX_ROOTS`] notes roots, latest first.
+    roots: VecDeque<BorshableH256>,
+    /// Root history accumulator before the oldest root of `roots`.
+    roots_base: BorshableH256,
+    /// Root history accumulator of every notes root so far.
+    roots_acc: BorshableH256,
+}
+
+impl<S: SmtStore> Clone for HyliUtxoState<S> {
+    fn clone(&self) -> Self {
+        Self {
+            notes_tree: self.notes_tree.clone(),
+            nullified_tree: self.nullified_tree.clone(),
+            roots: self.roots.clone(),
+            roots_base: self.roots_base,
+            roots_acc: self.roots_acc,
+        }
+    }
 }
 
 #[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
@@ -35,7 +57,14 @@ pub struct HyliUtxoZkVmState {
     pub created_notes: ZkVmWitnessVec<WitnessLeaf>,
     pub nullified_notes: ZkVmWitnessVec<WitnessLeaf>,
     pub config: ContractConfig,
-    pub roots: [[u8; 8]; MAX_ROOTS],
+    /// Root history accumulator before the first root of `roots`.
+    pub roots_base: BorshableH256,
+    /// Notes roots the transaction's SMT inclusion proof may be built against
+    /// besides the current one: the latest roots, oldest first, from the one
+    /// the proof references. Folded into `roots_base` they give the root
+    /// history accumulator of the state commitment, so the host cannot pass
+    /// roots the contract never had.
+    pub roots: Vec<BorshableH256>,
 }
 
 #[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
@@ -69,10 +98,28 @@ impl HyliUtxoZkVmBatch {
     }
 }
 
+/// Nullifier derivation of the notes in the nullified tree:
+/// `poseidon2([commitment, nullifier_key])`. Notes spent under the earlier
+/// `poseidon2([psi, secret_key])` scheme would get a fresh nullifier, so the
+/// scheme is part of the state commitment and a state built under another
+/// one cannot be continued.
+pub const NULLIFIER_SCHEME: u8 = 2;
+
 #[derive(BorshSerialize)]
 struct CommitmentSnapshot {
+    nullifier_scheme: u8,
     notes_root: BorshableH256,
     nullified_notes_root: BorshableH256,
+    roots_acc: BorshableH256,
+}
+
+/// Folds `root` into the root history accumulator `acc`, as
+/// `poseidon2([acc, root])`. The accumulator of the empty history is zero.
+pub fn accumulate_root(acc: BorshableH256, root: BorshableH256) -> BorshableH256 {
+    let mut hasher = Poseidon2Hasher::default();
+    hasher.write_h256(&acc);
+    hasher.write_h256(&root);
+    BorshableH256(hasher.finish())
 }
 
 /// The action for the Hyli UTXO state is empty since all necessary information is passed through the Noir blobs in the calldata.
@@ -85,37 +132,13 @@ pub type HyliUtxoBlob = [BorshableH256; 4];
 pub type SeparatedHyliUtxoBlob = ([BorshableH256; 2], [BorshableH256; 2]);
 
 impl HyliUtxoState {
-    pub fn update_roots(&mut self) {
-        let new_root = self.notes_tree.root();
-        let new_root: [u8; 8] = new_root.as_slice()[..8]
-            .try_into()
-            .expect("slice with incorrect length");
-        self.roots.push_front(new_root);
-
-        if self.roots.len() > MAX_ROOTS {
-            self.roots.pop_back();
-        }
-    }
-
-    pub fn record_created(&mut self, commitments: &[BorshableH256]) -> Result<(), String> {
-        for commitment in commitments {
-            if commitment.0 == H256::zero() {
-                continue;
-            }
-
-            if self.notes_tree.contains(commitment) {
-                return Err("created note already exists in notes tree".to_string());
-            }
-
-            self.notes_tree
-                .update_leaf(*commitment, *commitment)
-                .map_err(|e| format!("failed to insert note into SMT: {e}"))?;
-        }
-        Ok(())
+    /// Empty state whose trees are kept in memory.
+    pub fn new() -> Self {
+        Self::default()
     }
 
     /// The padding nullifier is poseidon2([0, 0], 2) - this is a well-known constant
-    /// that results from using a padding note (psi=0, secret_key=0).
+    /// that results from using a padding note (commitment=0, nullifier_key=0).
     /// We must skip this value to allow multiple transactions with padding notes.
     pub const PADDING_NULLIFIER: [u8; 32] = [
         0x0b, 0x63, 0xa5, 0x37, 0x87, 0x02, 0x1a, 0x4a, 0x96, 0x2a, 0x45, 0x2c, 0x29, 0x21, 0xb3,
@@ -140,6 +163,79 @@ impl HyliUtxoState {
             .copied()
             .collect()
     }
+}
+
+impl<S: SmtStore> HyliUtxoState<S> {
+    /// Empty state whose trees keep their nodes in the given stores.
+    pub fn with_stores(notes: S, nullified: S) -> Self {
+        let empty_root = BorshableH256::from(H256::zero());
+        Self {
+            notes_tree: SMT::from_store(empty_root, notes),
+            nullified_tree: SMT::from_store(empty_root, nullified),
+            roots: VecDeque::new(),
+            roots_base: BorshableH256::default(),
+            roots_acc: BorshableH256::default(),
+        }
+    }
+
+    /// Moves both trees to other stores, e.g. persistent ones, keeping their
+    /// roots and the root history. `f` is given each tree root with its store.
+    pub fn map_stores<S2: SmtStore, E>(
+        self,
+        mut f: impl FnMut(BorshableH256, S) -> Result<S2, E>,
+    ) -> Result<HyliUtxoState<S2>, E> {
+        let notes_root = self.notes_tree.root();
+        let nullified_root = self.nullified_tree.root();
+        Ok(HyliUtxoState {
+            notes_tree: SMT::from_store(notes_root, f(notes_root, self.notes_tree.into_store())?),
+            nullified_tree: SMT::from_store(
+                nullified_root,
+                f(nullified_root, self.nullified_tree.into_store())?,
+            ),
+            roots: self.roots,
+            roots_base: self.roots_base,
+            roots_acc: self.roots_acc,
+        })
+    }
+
+    /// The root and store of both trees, e.g. to flush the stores.
+    pub fn stores_mut(&mut self) -> [(BorshableH256, &mut S); 2] {
+        let notes_root = self.notes_tree.root();
+        let nullified_root = self.nullified_tree.root();
+        [
+            (notes_root, self.notes_tree.store_mut()),
+            (nullified_root, self.nullified_tree.store_mut()),
+        ]
+    }
+
+    pub fn update_roots(&mut self) {
+        let root = self.notes_tree.root();
+        self.roots_acc = accumulate_root(self.roots_acc, root);
+        self.roots.push_front(root);
+
+        if self.roots.len() > MAX_ROOTS {
+            if let Some(oldest) = self.roots.pop_back() {
+                self.roots_base = accumulate_root(self.roots_base, oldest);
+            }
+        }
+    }
+
+    pub fn record_created(&mut self, commitments: &[BorshableH256]) -> Result<(), String> {
+        for commitment in commitments {
+            if commitment.0 == H256::zero() {
+                continue;
+            }
+
+            if self.notes_tree.contains(commitment) {
+                return Err("created note already exists in notes tree".to_string());
+            }
+
+            self.notes_tree
+                .update_leaf(*commitment, *commitment)
+                .map_err(|e| format!("failed to insert note into SMT: {e}"))?;
+        }
+        Ok(())
+    }
 
     pub fn record_nullified(&mut self, commitments: &[BorshableH256]) -> Result<(), String> {
         for (i, commitment) in commitments.iter().enumerate() {
@@ -150,7 +246,7 @@ impl HyliUtxoState {
             // Skip the padding nullifier - it's poseidon2([0, 0], 2) and is used
             // by all transactions that have only 1 real input note.
             let commitment_bytes: [u8; 32] = commitment.0.into();
-            if commitment_bytes == Self::PADDING_NULLIFIER {
+            if commitment_bytes == HyliUtxoState::PADDING_NULLIFIER {
                 continue;
             }
 
@@ -176,30 +272,54 @@ impl HyliUtxoState {
         config: ContractConfig,
         created_note_keys: &[BorshableH256],
         nullified_keys: &[BorshableH256],
+        referenced_root: Option<BorshableH256>,
     ) -> Result<HyliUtxoZkVmState, String> {
-        let filtered_created = Self::filter_keys(created_note_keys, false);
-        let filtered_nullified = Self::filter_keys(nullified_keys, true);
+        let filtered_created = HyliUtxoState::filter_keys(created_note_keys, false);
+        let filtered_nullified = HyliUtxoState::filter_keys(nullified_keys, true);
         let created_notes = Self::build_witness(&self.notes_tree, &filtered_created)?;
         let nullified = Self::build_witness(&self.nullified_tree, &filtered_nullified)?;
-        let mut roots_vec: Vec<[u8; 8]> = self.roots.iter().cloned().collect();
-        roots_vec.resize(MAX_ROOTS, [0u8; 8]);
-        let roots: [[u8; 8]; MAX_ROOTS] = roots_vec.try_into().map_err(|_| {
-            format!(
-                "failed to convert roots to array: expected length {}",
-                MAX_ROOTS,
-            )
-        })?;
+        let (roots_base, roots) = self.root_history_from(referenced_root);
 
         Ok(HyliUtxoZkVmState {
             created_notes,
             nullified_notes: nullified,
             config,
+            roots_base,
             roots,
         })
     }
 
+    /// The roots from `referenced` to the latest, oldest first, with the
+    /// accumulator before them. None are needed when `referenced` is the
+    /// current root, and none can be given when it left the history.
+    fn root_history_from(
+        &self,
+        referenced: Option<BorshableH256>,
+    ) -> (BorshableH256, Vec<BorshableH256>) {
+        let position = referenced
+            .filter(|root| *root != self.notes_tree.root())
+            .and_then(|root| self.roots.iter().position(|kept| *kept == root));
+        let Some(position) = position else {
+            return (self.roots_acc, Vec::new());
+        };
+        let base = self
+            .roots
+            .iter()
+            .skip(position + 1)
+            .rev()
+            .fold(self.roots_base, |acc, root| accumulate_root(acc, *root));
+        let roots = self
+            .roots
+            .iter()
+            .take(position + 1)
+            .rev()
+            .copied()
+            .collect();
+        (base, roots)
+    }
+
     fn build_witness(
-        tree: &SMT<BorshableH256>,
+        tree: &SMT<BorshableH256, S>,
         keys: &[BorshableH256],
     ) -> Result<ZkVmWitnessVec<WitnessLeaf>, String> {
         if keys.is_empty() {
@@ -224,9 +344,8 @@ impl HyliUtxoState {
         for key in keys {
             let value = tree
                 .store()
-                .leaves_map()
-                .get(&key.as_h256())
-                .copied()
+                .get_leaf(&key.as_h256())
+                .map_err(|e| format!("failed to read leaf from SMT: {e}"))?
                 .map(BorshableH256::from)
                 .unwrap_or_else(|| BorshableH256::from(H256::zero()));
             witness.values.push(WitnessLeaf::new(*key, value));
@@ -239,6 +358,35 @@ impl HyliUtxoState {
         self.notes_tree.root()
     }
 
+    /// Whether an SMT inclusion proof built against `root` is accepted.
+    pub fn accepts_root(&self, root: &BorshableH256) -> bool {
+        *root == self.notes_tree.root() || self.roots.contains(root)
+    }
+
+    pub fn notes_tree(&self) -> &SMT<BorshableH256, S> {
+        &self.notes_tree
+    }
+
+    pub fn nullified_root(&self) -> BorshableH256 {
+        self.nullified_tree.root()
+    }
+
+    /// Whether `commitment` was added to the notes tree.
+    pub fn contains_note(&self, commitment: &BorshableH256) -> bool {
+        self.notes_tree.contains(commitment)
+    }
+
+    /// Whether `nullifier` was recorded in the nullified tree, i.e. its note
+    /// is spent.
+    pub fn is_nullified(&self, nullifier: &BorshableH256) -> bool {
+        self.nullified_tree.contains(nullifier)
+    }
+
+    /// Siblings of `commitment` in the notes tree, as the Noir circuit expects them.
+    pub fn build_smt_witness(&self, commitment: BorshableH256) -> [FieldElement; 256] {
+        smt::build_siblings(&self.notes_tree, commitment)
+    }
+
     pub fn build_smt_witnesses(
         &self,
         commitment0: BorshableH256,
@@ -252,8 +400,10 @@ impl HyliUtxoState {
 
     pub fn commitment(&self) -> StateCommitment {
         let snapshot = CommitmentSnapshot {
+            nullifier_scheme: NULLIFIER_SCHEME,
             notes_root: self.notes_tree.root(),
             nullified_notes_root: self.nullified_tree.root(),
+            roots_acc: self.roots_acc,
         };
 
         StateCommitment(
@@ -262,6 +412,30 @@ impl HyliUtxoState {
     }
 }
 
+impl<S: SmtStore> BorshSerialize for HyliUtxoState<S> {
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.notes_tree.serialize(writer)?;
+        self.nullified_tree.serialize(writer)?;
+        self.roots.serialize(writer)?;
+        self.roots_base.serialize(writer)?;
+        self.roots_acc.serialize(writer)
+    }
+}
+
+impl BorshDeserialize for HyliUtxoState {
+    fn deserialize_reader<R: io::Read>(reader: &mut R) -> io::Result<Self> {
+        let notes_tree = SMT::<BorshableH256>::deserialize_reader(reader)?;
+        let nullified_tree = SMT::<BorshableH256>::deserialize_reader(reader)?;
+        Ok(Self {
+            notes_tree,
+            nullified_tree,
+            roots: VecDeque::deserialize_reader(reader)?,
+            roots_base: BorshableH256::deserialize_reader(reader)?,
+            roots_acc: BorshableH256::deserialize_reader(reader)?,
+        })
+    }
+}
+
 impl sdk::FullStateRevert for HyliUtxoZkVmState {}
 
 impl HyliUtxoZkVmState {
@@ -270,10 +444,19 @@ impl HyliUtxoZkVmState {
             config,
             created_notes: Default::default(),
             nullified_notes: Default::default(),
-            roots: [[0u8; 8]; MAX_ROOTS],
+            roots_base: BorshableH256::default(),
+            roots: Vec::new(),
         }
     }
 
+    /// The root history accumulator `roots` leads to. The state commitment
+    /// holds it, binding the given history to the on-chain state.
+    fn roots_acc(&self) -> BorshableH256 {
+        self.roots
+            .iter()
+            .fold(self.roots_base, |acc, root| accumulate_root(acc, *root))
+    }
+
     fn parse_smt_incl_blob_payload(blob: &sdk::Blob) -> Result<Vec<u8>, String> {
         let structured: StructuredBlobData<Vec<u8>> =
             blob.data.clone().try_into().map_err(|_| {
@@ -311,7 +494,14 @@ impl HyliUtxoZkVmState {
         let (smt_nullifier0, smt_nullifier1, smt_blob_notes_root) =
             parse_hyli_smt_incl_blob(&smt_blob_payload)?;
 
-        if !self.roots.contains(smt_blob_notes_root) {
+        if self.roots.len() > MAX_ROOTS {
+            return Err(format!(
+                "root history of {} roots exceeds the {MAX_ROOTS} accepted",
+                self.roots.len()
+            ));
+        }
+        let notes_root = self.created_notes.compute_root()?;
+        if smt_blob_notes_root != notes_root && !self.roots.contains(&smt_blob_notes_root) {
             return Err("smt inclusion proof blob does not match notes root".to_string());
         }
 
@@ -416,6 +606,10 @@ impl sdk::ZkContract for HyliUtxoZkVmState {
 
         self.apply_action(calldata)?;
 
+        // As `update_roots` does on the host
+        let notes_root = self.created_notes.compute_root()?;
+        self.roots.push(notes_root);
+
         Ok((Vec::new(), ctx, Vec::new()))
     }
 
@@ -430,8 +624,10 @@ impl sdk::ZkContract for HyliUtxoZkVmState {
             .expect("compute nullified root from witness");
 
         let snapshot = CommitmentSnapshot {
+            nullifier_scheme: NULLIFIER_SCHEME,
             notes_root,
             nullified_notes_root: nullified_root,
+            roots_acc: self.roots_acc(),
         };
 
         StateCommitment(
@@ -494,7 +690,7 @@ pub fn parse_hyli_utxo_blob(bytes: &[u8]) -> Result<SeparatedHyliUtxoBlob, Strin
 
 pub fn parse_hyli_smt_incl_blob(
     bytes: &[u8],
-) -> Result<(BorshableH256, BorshableH256, &[u8; 8]), String> {
+) -> Result<(BorshableH256, BorshableH256, BorshableH256), String> {
     const EXPECTED_SIZE: usize = 96;
     if bytes.len() != EXPECTED_SIZE {
         return Err(format!(
@@ -512,12 +708,12 @@ pub fn parse_hyli_smt_incl_blob(
             .map_err(|_| "Failed to read nullifier1 from smt blob".to_string())?,
     );
 
-    // Only extract fingerprint for notes root (first 8 bytes) since that's the only one we need to verify in the zk contract.
-    let notes_root_fingerprint = bytes[64..(64 + 8)]
-        .try_into()
-        .map_err(|_| "Failed to read notes root fingerprint from smt blob".to_string())?;
+    let notes_root = BorshableH256::from(
+        <[u8; 32]>::try_from(&bytes[64..96])
+            .map_err(|_| "Failed to read notes root from smt blob".to_string())?,
+    );
 
-    Ok((nullifier0, nullifier1, notes_root_fingerprint))
+    Ok((nullifier0, nullifier1, notes_root))
 }
 
 #[cfg(test)]
@@ -619,8 +815,8 @@ mod tests {
 
     #[test]
     fn check_noir_blobs_accepts_withdraw_topology() {
-        let mut state = state_with_root(7);
-        state.roots[0] = [7u8; 8];
+        let mut state = state_with_root(8);
+        state.roots = vec![BorshableH256::from([7u8; 32])];
 
         let calldata = sdk::Calldata {
             tx_hash: TxHash(vec![0u8; 32]),
@@ -645,10 +841,157 @@ mod tests {
             .expect("withdraw topology should be accepted");
     }
 
+    #[test]
+    fn check_noir_blobs_rejects_root_sharing_only_a_prefix() {
+        let mut state = state_with_root(8);
+        let mut prefix_only = [0u8; 32];
+        prefix_only[..8].fill(7);
+        state.roots = vec![BorshableH256::from(prefix_only)];
+
+        let calldata = sdk::Calldata {
+            tx_hash: TxHash(vec![0u8; 32]),
+            identity: "alice".into(),
+            blobs: vec![
+                make_state_blob(vec![BlobIndex(2)]),
+                make_utxo_blob(9),
+                make_smt_blob(7, 9),
+            ]
+            .into(),
+            tx_blob_count: 3,
+            index: BlobIndex(0),
+            tx_ctx: None,
+            private_input: Vec::new(),
+        };
+        let (_, mut ctx) =
+            parse_calldata::<HyliUtxoStateAction>(&calldata).expect("parse state calldata");
+
+        let err = state
+            .check_noir_blobs(&calldata, &mut ctx)
+            .expect_err("a root matching only its first 8 bytes must be rejected");
+        assert!(err.contains("does not match notes root"));
+    }
+
+    #[test]
+    fn passes_the_roots_since_the_referenced_one_to_the_zkvm() {
+        let config = state_with_root(0).config;
+        let mut state = HyliUtxoState::new();
+        let mut roots = Vec::new();
+        for byte in 1u8..=3 {
+            state
+                .record_created(&[BorshableH256::from([byte; 32])])
+                .unwrap();
+            state.update_roots();
+            roots.push(state.notes_root());
+        }
+
+        let zkvm = state
+            .to_zkvm_state(config.clone(), &[], &[], Some(roots[0]))
+            .unwrap();
+        assert_eq!(zkvm.roots, roots);
+        assert_eq!(zkvm.roots_acc(), state.roots_acc);
+
+        let zkvm = state
+            .to_zkvm_state(config.clone(), &[], &[], Some(roots[1]))
+            .unwrap();
+        assert_eq!(zkvm.roots, roots[1..]);
+        assert_eq!(zkvm.roots_acc(), state.roots_acc);
+
+        // The current root is checked against the notes witness instead
+        let zkvm = state
+            .to_zkvm_state(config.clone(), &[], &[], Some(roots[2]))
+            .unwrap();
+        assert!(zkvm.roots.is_empty());
+        assert_eq!(zkvm.roots_acc(), state.roots_acc);
+
+        let unknown = BorshableH256::from([9u8; 32]);
+        let zkvm = state
+            .to_zkvm_state(config, &[], &[], Some(unknown))
+            .unwrap();
+        assert!(zkvm.roots.is_empty());
+    }
+
+    #[test]
+    fn commitment_binds_the_root_history() {
+        let mut state = HyliUtxoState::new();
+        state
+            .record_created(&[BorshableH256::from([1u8; 32])])
+            .unwrap();
+        let before = state.commitment();
+        state.update_roots();
+        assert_ne!(state.commitment(), before);
+
+        // A history with a root the state never had commits differently
+        let config = state_with_root(0).config;
+        let mut zkvm = state
+            .to_zkvm_state(config, &[], &[], Some(state.notes_root()))
+            .unwrap();
+        let honest = <HyliUtxoZkVmState as sdk::ZkContract>::commit(&zkvm);
+        assert_eq!(honest, state.commitment());
+        zkvm.roots.push(BorshableH256::from([7u8; 32]));
+        assert_ne!(
+            <HyliUtxoZkVmState as sdk::ZkContract>::commit(&zkvm),
+            honest
+        );
+    }
+
+    #[test]
+    fn evicted_roots_stay_in_the_accumulator() {
+        let mut state = HyliUtxoState::new();
+        state
+            .record_created(&[BorshableH256::from([1u8; 32])])
+            .unwrap();
+        state.update_roots();
+        let evicted = state.notes_root();
+        state
+            .record_created(&[BorshableH256::from([2u8; 32])])
+            .unwrap();
+        for _ in 0..MAX_ROOTS {
+            state.update_roots();
+        }
+
+        assert!(!state.accepts_root(&evicted));
+        let expected = state
+            .roots
+            .iter()
+            .rev()
+            .fold(state.roots_base, |acc, root| accumulate_root(acc, *root));
+        assert_eq!(state.roots_acc, expected);
+        assert_eq!(
+            state.roots_base,
+            accumulate_root(BorshableH256::default(), evicted)
+        );
+    }
+
+    #[test]
+    fn check_noir_blobs_accepts_the_current_root() {
+        let state = state_with_root(7);
+
+        let calldata = sdk::Calldata {
+            tx_hash: TxHash(vec![0u8; 32]),
+            identity: "alice".into(),
+            blobs: vec![
+                make_state_blob(vec![BlobIndex(2)]),
+                make_utxo_blob(9),
+                make_smt_blob(7, 9),
+            ]
+            .into(),
+            tx_blob_count: 3,
+            index: BlobIndex(0),
+            tx_ctx: None,
+            private_input: Vec::new(),
+        };
+        let (_, mut ctx) =
+            parse_calldata::<HyliUtxoStateAction>(&calldata).expect("parse state calldata");
+
+        state
+            .check_noir_blobs(&calldata, &mut ctx)
+            .expect("a proof against the current root should be accepted");
+    }
+
     #[test]
     fn check_noir_blobs_rejects_missing_withdraw_token_callee() {
         let mut state = state_with_root(7);
-        state.roots[0] = [7u8; 8];
+        state.roots = vec![BorshableH256::from([7u8; 32])];
 
         let calldata = sdk::Calldata {
             tx_hash: TxHash(vec![0u8; 32]),
@@ -669,4 +1012,21 @@ mod tests {
             .expect_err("withdraw topology should fail during calldata parsing");
         assert!(err.contains("Blob callees do not match actual callees"));
     }
+
+    #[test]
+    fn reports_note_and_nullifier_membership() {
+        let mut state = HyliUtxoState::new();
+        let note = BorshableH256::from([1u8; 32]);
+        let nullifier = BorshableH256::from([2u8; 32]);
+        assert!(!state.contains_note(&note));
+        assert!(!state.is_nullified(&nullifier));
+
+        state.record_created(&[note]).unwrap();
+        state.record_nullified(&[nullifier]).unwrap();
+        assert!(state.contains_note(&note));
+        assert!(state.is_nullified(&nullifier));
+        // The trees are distinct
+        assert!(!state.contains_note(&nullifier));
+        assert!(!state.is_nullified(&note));
+    }
 }
@@ -2,7 +2,11 @@ use std::{io, marker::PhantomData};
 
 use acvm::{AcirField, FieldElement};
 use borsh::{BorshDeserialize, BorshSerialize};
-use sparse_merkle_tree::{default_store::DefaultStore, traits::Value, SparseMerkleTree, H256};
+use sparse_merkle_tree::{
+    default_store::DefaultStore,
+    traits::{StoreReadOps, StoreWriteOps, Value},
+    SparseMerkleTree, H256,
+};
 
 #[derive(Debug)]
 pub struct Poseidon2Hasher {
@@ -175,15 +179,40 @@ impl<'a> From<&'a H256> for &'a BorshableH256 {
     }
 }
 
+/// Node storage an [`SMT`] can be backed by. The SP1 guest and tests use the
+/// in-memory [`DefaultStore`]; hosts may keep large trees in a persistent
+/// store instead.
+pub trait SmtStore: StoreReadOps<H256> + StoreWriteOps<H256> + Clone {
+    /// Whether a leaf is stored under `key`.
+    fn contains_leaf(&self, key: &H256) -> bool;
+
+    /// Every stored `(key, value)` leaf.
+    fn leaves(&self) -> Vec<(H256, H256)>;
+}
+
+impl SmtStore for DefaultStore<H256> {
+    fn contains_leaf(&self, key: &H256) -> bool {
+        self.leaves_map().contains_key(key)
+    }
+
+    fn leaves(&self) -> Vec<(H256, H256)> {
+        self.leaves_map()
+            .iter()
+            .map(|(key, value)| (*key, *value))
+            .collect()
+    }
+}
+
 #[derive(Debug, Default)]
-pub struct SMT<T: Value + Clone>(
-    SparseMerkleTree<Poseidon2Hasher, H256, DefaultStore<H256>>,
+pub struct SMT<T: Value + Clone, S = DefaultStore<H256>>(
+    SparseMerkleTree<Poseidon2Hasher, H256, S>,
     PhantomData<T>,
 );
 
-impl<T> SMT<T>
+impl<T, S> SMT<T, S>
 where
     T: Value + Clone,
+    S: SmtStore + Default,
 {
     pub fn zero() -> Self {
         SMT(
@@ -191,11 +220,22 @@ where
             PhantomData,
         )
     }
+}
 
-    pub fn from_store(root: BorshableH256, store: DefaultStore<H256>) -> Self {
+impl<T, S> SMT<T, S>
+where
+    T: Value + Clone,
+    S: SmtStore,
+{
+    pub fn from_store(root: BorshableH256, store: S) -> Self {
         SMT(SparseMerkleTree::new(root.into(), store), PhantomData)
     }
 
+    /// Gives back the store holding the tree nodes.
+    pub fn into_store(self) -> S {
+        self.0.take_store()
+    }
+
     pub fn update_all_from_ref<'a, I>(
         &mut self,
         leaves: I,
@@ -230,19 +270,23 @@ where
     }
 
     pub fn contains(&self, key: &BorshableH256) -> bool {
-        let store = self.0.store();
-        let h = key.as_h256();
-        store.leaves_map().contains_key(&h)
+        self.0.store().contains_leaf(&key.as_h256())
     }
 
     pub fn root(&self) -> BorshableH256 {
         BorshableH256(*self.0.root())
     }
 
-    pub fn store(&self) -> &DefaultStore<H256> {
+    pub fn store(&self) -> &S {
         self.0.store()
     }
 
+    /// The store holding the tree nodes, e.g. to flush it. Nodes changed
+    /// through it are not reflected in the root.
+    pub fn store_mut(&mut self) -> &mut S {
+        self.0.store_mut()
+    }
+
     pub fn merkle_proof<'a, I, V>(
         &self,
         keys: I,
@@ -256,15 +300,21 @@ where
     }
 }
 
-impl BorshSerialize for SMT<BorshableH256> {
+impl<T: Value + Clone, S: SmtStore> Clone for SMT<T, S> {
+    fn clone(&self) -> Self {
+        SMT::from_store(self.root(), self.store().clone())
+    }
+}
+
+impl<S: SmtStore> BorshSerialize for SMT<BorshableH256, S> {
     fn serialize<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
         self.root().serialize(writer)?;
-        let leaves = self.store().leaves_map();
+        let leaves = self.store().leaves();
         let len = leaves.len() as u32;
         len.serialize(writer)?;
-        for (key, value) in leaves.iter() {
-            BorshableH256(*key).serialize(writer)?;
-            BorshableH256(*value).serialize(writer)?;
+        for (key, value) in leaves {
+            BorshableH256(key).serialize(writer)?;
+            BorshableH256(value).serialize(writer)?;
         }
         Ok(())
     }
@@ -283,7 +333,10 @@ pub fn h256_to_field(h: &H256) -> FieldElement {
 /// Build the flat 256-entry siblings array that the Noir circuit expects.
 /// Each entry is the FieldElement representation of the sibling hash at that height,
 /// or FieldElement::zero() if that level has no sibling.
-pub fn build_siblings(tree: &SMT<BorshableH256>, commitment: BorshableH256) -> [FieldElement; 256] {
+pub fn build_siblings<S: SmtStore>(
+    tree: &SMT<BorshableH256, S>,
+    commitment: BorshableH256,
+) -> [FieldElement; 256] {
     let proof = tree.merkle_proof(std::iter::once(&commitment)).unwrap();
     let leaves_bitmap = proof.leaves_bitmap();
     let merkle_path = proof.merkle_path();
@@ -330,7 +383,7 @@ pub mod smt_fixture {
 
     /// Print a Prover.toml for hyli_smt_incl_proof to stdout.
     /// Uses the new circuit format: blob has [nullifier0, nullifier1, notes_root],
-    /// and input_notes provides note fields + secret key for commitment/nullifier computation.
+    /// and input_notes provides note fields + nullifier key for commitment/nullifier computation.
     pub fn print_prover_toml() {
         // Known note fields (Field elements as BE hex)
         let kind = FieldElement::from(1u128); // non-zero = real note
@@ -352,10 +405,14 @@ pub mod smt_fixture {
         ])
         .unwrap();
 
-        // Nullifier = poseidon2([psi, secret_key])
-        let nullifier = bn254_blackbox_solver::poseidon_hash(&[psi, secret_key]).unwrap();
+        // Nullifier key = poseidon2([secret_key, 1]), the prover never sees secret_key
+        let nullifier_key =
+            bn254_blackbox_solver::poseidon_hash(&[secret_key, FieldElement::one()]).unwrap();
+
+        // Nullifier = poseidon2([commitment, nullifier_key])
+        let nullifier = bn254_blackbox_solver::poseidon_hash(&[commitment, nullifier_key]).unwrap();
 
-        // Padding nullifier = poseidon2([0, 0])
+        // Padding nullifier = poseidon2([0, 0]) (zero commitment, zero nullifier key)
         let padding_nullifier =
             bn254_blackbox_solver::poseidon_hash(&[FieldElement::zero(), FieldElement::zero()])
                 .unwrap();
@@ -438,7 +495,7 @@ pub mod smt_fixture {
         println!();
         // Input note 0: real note
         println!("[[input_notes]]");
-        println!("secret_key = {}", field_hex(&secret_key));
+        println!("nullifier_key = {}", field_hex(&nullifier_key));
         println!("[input_notes.note]");
         println!("kind = {}", field_hex(&kind));
         println!("value = {}", field_hex(&value));
@@ -448,7 +505,7 @@ pub mod smt_fixture {
         // Input note 1: padding note
         println!("[[input_notes]]");
         println!(
-            "secret_key = \"0x0000000000000000000000000000000000000000000000000000000000000000\""
+            "nullifier_key = \"0x0000000000000000000000000000000000000000000000000000000000000000\""
         );
         println!("[input_notes.note]");
         println!("kind = \"0x0000000000000000000000000000000000000000000000000000000000000000\"");
@@ -0,0 +1,118 @@
+// Regenerates vectors.json with Node's WebCrypto, following the same steps as
+// front/src/services/CryptoService.ts but with a fixed ephemeral key and nonce.
+//
+//   node fixtures/note_encryption/generate.mjs
+import crypto from "node:crypto";
+import { writeFileSync } from "node:fs";
+
+const subtle = crypto.webcrypto.subtle;
+const textEncoder = new TextEncoder();
+const SALT_LABEL = textEncoder.encode("hyli-notes-v1");
+const HKDF_INFO = textEncoder.encode("hyli-notes:aes-gcm:key:v1");
+
+const base64Url = (bytes) =>
+  Buffer.from(bytes).toString("base64").replace(/\+/g, "-").replace(/\//g, "_").replace(/=+$/g, "");
+
+function compressedPubkey(secretKeyHex) {
+  const ecdh = crypto.createECDH("secp256k1");
+  ecdh.setPrivateKey(Buffer.from(secretKeyHex, "hex"));
+  return ecdh.getPublicKey("hex", "compressed");
+}
+
+async function encryptNote(recipientXHex, plaintext, ephemeralSecretKeyHex, nonce) {
+  const ephemeral = crypto.createECDH("secp256k1");
+  ephemeral.setPrivateKey(Buffer.from(ephemeralSecretKeyHex, "hex"));
+  const sharedSecret = ephemeral.computeSecret(Buffer.from("02" + recipientXHex, "hex"));
+  const ephemeralCompressedHex = ephemeral.getPublicKey("hex", "compressed");
+
+  const salt = crypto
+    .createHash("sha256")
+    .update(Buffer.concat([SALT_LABEL, Buffer.from(recipientXHex, "hex"), Buffer.from(ephemeralCompressedHex, "hex")]))
+    .digest();
+  const hkdfKey = await subtle.importKey("raw", sharedSecret, "HKDF", false, ["deriveBits"]);
+  const bits = await subtle.deriveBits({ name: "HKDF", hash: "SHA-256", salt, info: HKDF_INFO }, hkdfKey, 256);
+  const aesKey = await subtle.importKey("raw", bits, "AES-GCM", false, ["encrypt"]);
+
+  const encrypted = new Uint8Array(
+    await subtle.encrypt({ name: "AES-GCM", iv: nonce, tagLength: 128 }, aesKey, textEncoder.encode(plaintext))
+  );
+  const envelope = {
+    v: 1,
+    nonce: base64Url(nonce),
+    ciphertext: base64Url(encrypted.slice(0, encrypted.length - 16)),
+    tag: base64Url(encrypted.slice(encrypted.length - 16)),
+  };
+  return {
+    encryptedPayload: base64Url(textEncoder.encode(JSON.stringify(envelope))),
+    ephemeralPubkey: ephemeralCompressedHex,
+  };
+}
+
+const recipientTag = (address) =>
+  crypto.createHash("sha256").update(address.replace(/^0x/i, "").toLowerCase() + ":recipient_tag").digest("hex");
+
+const notePayload = {
+  note: {
+    kind: "0000000000000000000000000000000000000000000000000000000000000002",
+    contract: "000000000000000000000000000000000000000000000000000000000000abcd",
+    address: "1f2e3d4c5b6a79881f2e3d4c5b6a79881f2e3d4c5b6a79881f2e3d4c5b6a7988",
+    psi: "0badc0ffee0badc0ffee0badc0ffee0badc0ffee0badc0ffee0badc0ffee0bad",
+    value: "0000000000000000000000000000000000000000000000000000000000000064",
+  },
+  tx_hash: "9c1f5a0b7e3d2c4b6a8f9e0d1c2b3a4f5e6d7c8b9a0f1e2d3c4b5a6f7e8d9c0b",
+  amount: 100,
+  from: "4f355bdcb7cc0af728ef3cceb9615d90684bb5b2ca5f859ab0f0b704075871aa",
+  timestamp: 1760000000000,
+};
+
+const cases = [
+  {
+    name: "simple",
+    recipient_secret_key: "11".repeat(32),
+    ephemeral_secret_key: "22".repeat(32),
+    nonce: "07".repeat(12),
+    plaintext: JSON.stringify({ hello: "world" }),
+  },
+  {
+    name: "note_payload",
+    recipient_secret_key: "33".repeat(32),
+    ephemeral_secret_key: "5a".repeat(32),
+    nonce: "000102030405060708090a0b",
+    plaintext: JSON.stringify(notePayload),
+  },
+  {
+    // Generator point recipient, odd-y ephemeral key (n - 1)
+    name: "empty_array",
+    recipient_secret_key: "0000000000000000000000000000000000000000000000000000000000000001",
+    ephemeral_secret_key: "fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364140",
+    nonce: "ffffffffffffffffffffffff",
+    plaintext: "[]",
+  },
+];
+
+const vectors = { encryption: [], recipient_tag: [] };
+for (const vector of cases) {
+  const recipientPubkey = compressedPubkey(vector.recipient_secret_key).slice(2);
+  const encrypted = await encryptNote(
+    recipientPubkey,
+    vector.plaintext,
+    vector.ephemeral_secret_key,
+    Buffer.from(vector.nonce, "hex")
+  );
+  vectors.encryption.push({
+    ...vector,
+    recipient_pubkey: recipientPubkey,
+    encrypted_payload: encrypted.encryptedPayload,
+    ephemeral_pubkey: encrypted.ephemeralPubkey,
+  });
+}
+for (const address of [
+  "ab".repeat(32),
+  "0x" + "AB".repeat(32),
+  "0000000000000000000000000000000000000000000000000000000000000001",
+  "1f2e3d4c5b6a79881f2e3d4c5b6a79881f2e3d4c5b6a79881f2e3d4c5b6a7988",
+]) {
+  vectors.recipient_tag.push({ utxo_address: address, recipient_tag: recipientTag(address) });
+}
+
+writeFileSync(new URL("./vectors.json", import.meta.url), JSON.stringify(vectors, null, 2) + "\n");
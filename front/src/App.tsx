@@ -170,7 +170,7 @@ function AppContent() {
 
         const registerIdentity = (identity: FullIdentity) => {
             addressService
-                .register(wallet.username, identity.utxoAddress, identity.publicKey)
+                .register(wallet.username, identity.utxoAddress, identity.publicKey, identity.privateKey)
                 .catch((error) => console.warn("Address registration failed:", error));
         };
 
@@ -4,7 +4,7 @@ import { encryptedNoteService, DecryptedNoteRecord, DecryptedNotePayload } from
 import { addStoredNote } from "../services/noteStorage";
 import { StoredNote, PrivateNote } from "../types/note";
 
-const POLLING_INTERVAL_MS = 30000; // 30 seconds
+const POLLING_INTERVAL_MS = 30000; // 30 seconds, fallback for the notes stream
 const LAST_FETCH_KEY_PREFIX = "encryptedNotes:lastFetch:";
 
 interface UseEncryptedNotesOptions {
@@ -41,6 +41,7 @@ function saveLastFetch(utxoAddress: string, timestamp: number): void {
 
 /**
  * Hook for polling encrypted notes from the server.
+ * Fetches as soon as the server streams a new note, and polls as a fallback.
  * Uses the identity's UTXO address for tag derivation and private key for decryption.
  */
 export function useEncryptedNotes(
@@ -64,6 +65,7 @@ export function useEncryptedNotes(
 
     const isMounted  = useRef(true);
     const pollingRef = useRef<ReturnType<typeof setTimeout> | null>(null);
+    const fetchRef   = useRef<() => Promise<void>>(async () => {});
 
     // Update lastFetch when identity changes
     useEffect(() => {
@@ -135,6 +137,8 @@ export function useEncryptedNotes(
         }
     }, [identity, playerName, lastFetch, onNotesReceived, onError]);
 
+    fetchRef.current = fetchNotes;
+
     // Set up polling
     useEffect(() => {
         isMounted.current = true;
@@ -156,8 +160,15 @@ export function useEncryptedNotes(
 
         scheduleNextPoll();
 
+        const stream = typeof EventSource === "undefined"
+            ? null
+            : encryptedNoteService.openNoteStream(identity, () => {
+                fetchRef.current();
+            });
+
         return () => {
             isMounted.current = false;
+            stream?.close();
             if (pollingRef.current) {
                 clearTimeout(pollingRef.current);
                 pollingRef.current = null;
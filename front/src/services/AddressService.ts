@@ -1,4 +1,5 @@
 import { getServerBaseUrl } from "./ConfigService";
+import { signUsernameRegistration } from "./CryptoService";
 
 interface ResolveAddressResponse {
     username: string;
@@ -31,14 +32,37 @@ class AddressService {
         return `${base}${path}`;
     }
 
-    async register(username: string, utxoAddress: string, encryptionPubkey: string): Promise<void> {
+    /**
+     * Registers `username` for this identity, signed with its encryption key
+     * (the server only lets the original owner update a username).
+     */
+    async register(
+        username: string,
+        utxoAddress: string,
+        encryptionPubkey: string,
+        privateKey: string
+    ): Promise<void> {
+        const normalizedUsername = username.toLowerCase();
+        const normalizedAddress = utxoAddress.replace(/^0x/i, "").toLowerCase();
+        const normalizedPubkey = normalizeEncryptionPubkey(encryptionPubkey);
+        const timestamp = Math.floor(Date.now() / 1000);
+        const signature = signUsernameRegistration(
+            privateKey,
+            normalizedUsername,
+            normalizedAddress,
+            normalizedPubkey,
+            timestamp
+        );
+
         const response = await fetch(this.buildUrl("/api/address/register"), {
             method: "POST",
             headers: { "Content-Type": "application/json" },
             body: JSON.stringify({
-                username,
-                utxo_address: utxoAddress,
-                encryption_pubkey: normalizeEncryptionPubkey(encryptionPubkey),
+                username: normalizedUsername,
+                utxo_address: normalizedAddress,
+                encryption_pubkey: normalizedPubkey,
+                timestamp,
+                signature,
             }),
         });
 
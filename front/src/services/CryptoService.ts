@@ -1,5 +1,6 @@
 import { ec as EC } from "elliptic";
 import SHA256 from "crypto-js/sha256";
+import SHA3 from "crypto-js/sha3";
 import Hex from "crypto-js/enc-hex";
 
 const curve = new EC("secp256k1");
@@ -34,6 +35,56 @@ export function deriveRecipientTag(utxoAddress: string): string {
   return SHA256(tagInput).toString(Hex);
 }
 
+/**
+ * Signs an authenticated server request with the identity's secp256k1 key.
+ *
+ * Matches the server's `primitives::sig::Signature` scheme: the message hash
+ * sha256(message) is prefixed with "Payy" and its length, hashed with
+ * keccak256 and signed. Returns the hex-encoded `r || s || recoveryId`
+ * (65 bytes).
+ */
+function signRequest(privateKeyHex: string, message: string): string {
+  const messageHex = SHA256(message).toString(Hex);
+  const prefix = "0000000000000004" + "50617979"; // u64 BE length of "Payy", then "Payy"
+  const digestHex = SHA3(Hex.parse(prefix + messageHex), { outputLength: 256 }).toString(Hex);
+
+  const key = curve.keyFromPrivate(privateKeyHex.replace(/^0x/i, ""), "hex");
+  const signature = key.sign(digestHex, { canonical: true });
+  const recoveryId = (signature.recoveryParam ?? 0).toString(16).padStart(2, "0");
+  return signature.r.toString(16, 64) + signature.s.toString(16, 64) + recoveryId;
+}
+
+/**
+ * Signs a request to delete a stored note, proving ownership of the key
+ * that first registered the recipient's address.
+ */
+export function signNoteDeletion(
+  privateKeyHex: string,
+  recipientTag: string,
+  noteId: string,
+  timestamp: number
+): string {
+  return signRequest(privateKeyHex, `cachecash:delete-note:v1:${recipientTag}:${noteId}:${timestamp}`);
+}
+
+/**
+ * Signs a username registration. New usernames must be signed by the
+ * encryption key being registered, updates by the key that first registered
+ * the name. Values must be normalized (lowercase, hex without 0x).
+ */
+export function signUsernameRegistration(
+  privateKeyHex: string,
+  username: string,
+  utxoAddress: string,
+  encryptionPubkey: string,
+  timestamp: number
+): string {
+  return signRequest(
+    privateKeyHex,
+    `cachecash:register-username:v1:${username}:${utxoAddress}:${encryptionPubkey}:${timestamp}`
+  );
+}
+
 /**
  * Encrypts a note for a recipient using:
  * - secp256k1 ECDH (ephemeral sender key)
@@ -1,7 +1,7 @@
 import { FullIdentity } from "./KeyService";
 import { getServerBaseUrl } from "./ConfigService";
 import { PrivateNote } from "../types/note";
-import { encryptNote, decryptNote, deriveRecipientTag, EncryptedNote } from "./CryptoService";
+import { encryptNote, decryptNote, deriveRecipientTag, signNoteDeletion, EncryptedNote } from "./CryptoService";
 
 export interface UploadNoteResponse {
     id: string;
@@ -170,6 +170,29 @@ class EncryptedNoteService {
         return { notes, hasMore: response.has_more };
     }
 
+    /**
+     * Opens a Server-Sent Events stream of notes stored for the given identity.
+     * The browser resumes from the last note id after reconnects.
+     */
+    openNoteStream(
+        identity: FullIdentity,
+        onNote: (note: EncryptedNoteRecord) => void
+    ): EventSource {
+        const recipientTag = deriveRecipientTag(identity.utxoAddress);
+        const source = new EventSource(this.buildUrl(`/api/notes/stream?tags=${recipientTag}`));
+        source.addEventListener("note", (event) => {
+            const { note: n } = JSON.parse((event as MessageEvent<string>).data);
+            onNote({
+                id:               n.id,
+                encryptedPayload: n.encrypted_payload,
+                ephemeralPubkey:  n.ephemeral_pubkey,
+                senderTag:        n.sender_tag,
+                storedAt:         n.stored_at,
+            });
+        });
+        return source;
+    }
+
     /**
      * Fetches and decrypts notes for the given identity.
      * Decryption uses the identity's secp256k1 private key (ECDH).
@@ -211,7 +234,10 @@ class EncryptedNoteService {
      */
     async deleteNote(identity: FullIdentity, noteId: string): Promise<void> {
         const recipientTag = deriveRecipientTag(identity.utxoAddress);
-        await this.request(`/api/notes/${recipientTag}/${noteId}`, {
+        const timestamp = Math.floor(Date.now() / 1000);
+        const signature = signNoteDeletion(identity.privateKey, recipientTag, noteId, timestamp);
+        const query = new URLSearchParams({ timestamp: String(timestamp), signature });
+        await this.request(`/api/notes/${recipientTag}/${noteId}?${query}`, {
             method: "DELETE",
         });
     }
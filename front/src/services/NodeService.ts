@@ -1,5 +1,7 @@
 import { PrivateNote } from "../types/note";
 import { getServerBaseUrl } from "./ConfigService";
+import SHA256 from "crypto-js/sha256";
+import Hex from "crypto-js/enc-hex";
 
 type MaybeFaucetNote = {
     kind?: string;
@@ -19,16 +21,65 @@ interface FaucetResponse {
     contract_name?: string;
     amount?: number;
     tx_hash?: string;
+    tracking_id?: string;
     transaction?: unknown;
     note?: MaybeFaucetNote | null;
     [key: string]: unknown;
 }
 
+export type TxStatus = "pending" | "proof-submitted" | "settled" | "failed" | "timed-out";
+
+export interface TxRecord {
+    tx_hash: string | null;
+    kind: "faucet" | "deposit" | "transfer";
+    status: TxStatus;
+    failure_reason?: string;
+    submitted_at: number;
+    updated_at: number;
+}
+
 export interface CreateBlobResponse {
     tx_hash: string;
     blobs: Array<{ contract_name: string; data: string }>;
 }
 
+interface FaucetChallenge {
+    challenge: string;
+    pubkey_hex: string;
+    difficulty: number;
+    expires_at: number;
+    signature: string;
+}
+
+function leadingZeroBits(digestHex: string): number {
+    let bits = 0;
+    for (const char of digestHex) {
+        const nibble = parseInt(char, 16);
+        if (nibble === 0) {
+            bits += 4;
+            continue;
+        }
+        return bits + Math.clz32(nibble) - 28;
+    }
+    return bits;
+}
+
+/**
+ * Finds a nonce such that sha256("cachecash:faucet-pow:v1:{challenge}:{pubkey}:{nonce}")
+ * has `difficulty` leading zero bits, yielding to the event loop between batches.
+ */
+async function solveFaucetChallenge(challenge: FaucetChallenge): Promise<number> {
+    const prefix = `cachecash:faucet-pow:v1:${challenge.challenge}:${challenge.pubkey_hex}:`;
+    for (let nonce = 0; ; nonce++) {
+        if (leadingZeroBits(SHA256(prefix + nonce).toString(Hex)) >= challenge.difficulty) {
+            return nonce;
+        }
+        if (nonce % 4096 === 4095) {
+            await new Promise((resolve) => setTimeout(resolve, 0));
+        }
+    }
+}
+
 export interface SmtWitnessResponse {
     notes_root: string;       // 64-char hex
     siblings_0: string[];     // 256 "0x..." hex field elements
@@ -90,8 +141,18 @@ class NodeService {
             throw new Error("UTXO address must be a 32-byte hex string");
         }
 
+        const challenge = await this.request<FaucetChallenge>("/api/faucet/challenge", {
+            method: "POST",
+            body: JSON.stringify({ pubkey_hex: normalized }),
+        });
+        if (!challenge) {
+            throw new Error("Unexpected faucet challenge response");
+        }
+        const nonce = await solveFaucetChallenge(challenge);
+
         const payload: Record<string, unknown> = {
             pubkey_hex: normalized,
+            pow: { challenge, nonce },
         };
         if (typeof amount === "number") {
             payload.amount = amount;
@@ -300,6 +361,19 @@ class NodeService {
         return response.json() as Promise<SmtWitnessResponse>;
     }
 
+    /**
+     * GET /api/tx/{hash}
+     * Returns the lifecycle of a transaction, by tx hash or faucet tracking id.
+     */
+    async getTxStatus(id: string): Promise<TxRecord> {
+        const normalized = id.trim().replace(/^0x/i, "");
+        const data = await this.request<TxRecord>(`/api/tx/${normalized}`);
+        if (!data) {
+            throw new Error("Unexpected empty response from /api/tx");
+        }
+        return data;
+    }
+
     /**
      * POST /api/proof/submit
      * Submits both proofs (hyli_utxo + hyli_smt_incl_proof) for the transaction.
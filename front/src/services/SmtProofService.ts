@@ -90,7 +90,7 @@ class SmtProofService {
         txHash: string;
         blobCount: number; // 3
         inputNotes: [PrivateNote, PrivateNote]; // private: used to compute commitments for SMT lookup
-        secretKeys: [string, string]; // private: used to compute nullifiers
+        nullifierKeys: [string, string]; // private: used to compute nullifiers, cannot spend
         siblings0: string[]; // 256 "0x..." hex field elements
         siblings1: string[]; // 256 "0x..." hex field elements
     }): Promise<{ proof: string; publicInputs: string[] }> {
@@ -139,8 +139,8 @@ class SmtProofService {
                     program_outputs:     [0, 0, 0, 0, 0],
                 },
                 input_notes: [
-                    { note: toCircuitNote(params.inputNotes[0]), secret_key: "0x" + params.secretKeys[0] },
-                    { note: toCircuitNote(params.inputNotes[1]), secret_key: "0x" + params.secretKeys[1] },
+                    { note: toCircuitNote(params.inputNotes[0]), nullifier_key: "0x" + params.nullifierKeys[0] },
+                    { note: toCircuitNote(params.inputNotes[1]), nullifier_key: "0x" + params.nullifierKeys[1] },
                 ],
                 siblings_0: params.siblings0,
                 siblings_1: params.siblings1,
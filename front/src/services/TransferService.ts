@@ -129,9 +129,24 @@ async function computeCommitment(note: PrivateNote): Promise<string> {
     ]);
 }
 
-/** Nullifier: poseidon2([psi, secretKey], 2) */
-async function computeNullifier(psi: string, secretKey: string): Promise<string> {
-    return poseidon2Service.hash([psi, secretKey]);
+/**
+ * Nullifier key: poseidon2([secretKey, 1], 2)
+ * Derives nullifiers without spend authority. Returns zero for the padding key.
+ */
+async function computeNullifierKey(secretKey: string): Promise<string> {
+    if (normalizeHex64(secretKey) === "0".repeat(64)) {
+        return "0".repeat(64);
+    }
+    return poseidon2Service.hash([secretKey, "0".repeat(63) + "1"]);
+}
+
+/** Nullifier: poseidon2([commitment, nullifierKey], 2) */
+async function computeNullifier(input: InputNoteData): Promise<string> {
+    const [commitment, nullifierKey] = await Promise.all([
+        computeCommitment(input.note),
+        computeNullifierKey(input.secretKey),
+    ]);
+    return poseidon2Service.hash([commitment, nullifierKey]);
 }
 
 /**
@@ -210,8 +225,8 @@ class TransferService {
         const [outputCommit0, outputCommit1, nullifier0, nullifier1] = await Promise.all([
             computeCommitment(outputNotes[0]),
             computeCommitment(outputNotes[1]),
-            computeNullifier(inputNotes[0].note.psi, inputNotes[0].secretKey),
-            computeNullifier(inputNotes[1].note.psi, inputNotes[1].secretKey),
+            computeNullifier(inputNotes[0]),
+            computeNullifier(inputNotes[1]),
         ]);
 
         const blob = new Uint8Array(128);
@@ -318,7 +333,9 @@ class TransferService {
                 txHash,
                 blobCount: 3,
                 inputNotes: selection.selectedInputs.map((n) => n.note) as [PrivateNote, PrivateNote],
-                secretKeys: selection.selectedInputs.map((n) => n.secretKey) as [string, string],
+                nullifierKeys: await Promise.all(
+                    selection.selectedInputs.map((n) => computeNullifierKey(n.secretKey)),
+                ) as [string, string],
                 siblings0: smtWitness.siblings_0,
                 siblings1: smtWitness.siblings_1,
             });
@@ -488,7 +505,9 @@ class TransferService {
                 txHash,
                 blobCount: 4,
                 inputNotes: selection.selectedInputs.map((n) => n.note) as [PrivateNote, PrivateNote],
-                secretKeys: selection.selectedInputs.map((n) => n.secretKey) as [string, string],
+                nullifierKeys: await Promise.all(
+                    selection.selectedInputs.map((n) => computeNullifierKey(n.secretKey)),
+                ) as [string, string],
                 siblings0: smtWitness.siblings_0,
                 siblings1: smtWitness.siblings_1,
             });
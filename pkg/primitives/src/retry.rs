@@ -1,5 +1,5 @@
 use std::time::Duration;
-use tokio::time::sleep;
+use tokio::time::{Instant, sleep};
 
 pub fn retry<F, T, E, Fut>(operation: F) -> Retry<F, T, E, Fut>
 where
@@ -9,6 +9,7 @@ where
     Retry {
         operation,
         retry_delay: Duration::from_millis(1000),
+        max_retry_delay: None,
         error_handler: None,
     }
 }
@@ -31,9 +32,17 @@ where
 {
     operation: F,
     retry_delay: Duration,
+    max_retry_delay: Option<Duration>,
     error_handler: Option<ErrorHandler<E>>,
 }
 
+fn next_delay(delay: Duration, max_retry_delay: Option<Duration>) -> Duration {
+    match max_retry_delay {
+        Some(max_delay) => delay.saturating_mul(2).min(max_delay),
+        None => delay,
+    }
+}
+
 // Type alias to reduce type complexity warning
 type ErrorHandler<E> = Box<dyn Fn(&E) + Send>;
 
@@ -55,8 +64,15 @@ where
         self
     }
 
+    /// Doubles the delay after each failed attempt, up to `max_delay`.
+    pub fn backoff(mut self, max_delay: Duration) -> Self {
+        self.max_retry_delay = Some(max_delay);
+        self
+    }
+
     pub async fn exec_forever(self) -> T {
         let mut operation = self.operation;
+        let mut delay = self.retry_delay;
         loop {
             match operation().await {
                 Ok(value) => return value,
@@ -64,7 +80,30 @@ where
                     if let Some(ref handler) = self.error_handler {
                         handler(&err);
                     }
-                    sleep(self.retry_delay).await;
+                    sleep(delay).await;
+                    delay = next_delay(delay, self.max_retry_delay);
+                }
+            }
+        }
+    }
+
+    /// Retries until an attempt succeeds, or returns the last error once the
+    /// next attempt would start after `deadline`. Always attempts once.
+    pub async fn exec_until(self, deadline: Instant) -> Result<T, E> {
+        let mut operation = self.operation;
+        let mut delay = self.retry_delay;
+        loop {
+            match operation().await {
+                Ok(value) => return Ok(value),
+                Err(err) => {
+                    if let Some(ref handler) = self.error_handler {
+                        handler(&err);
+                    }
+                    if Instant::now() + delay > deadline {
+                        return Err(err);
+                    }
+                    sleep(delay).await;
+                    delay = next_delay(delay, self.max_retry_delay);
                 }
             }
         }
@@ -72,6 +111,7 @@ where
 
     pub async fn exec(self, attempts: usize) -> Result<T, E> {
         let mut operation = self.operation;
+        let mut delay = self.retry_delay;
         let mut last_error: Option<E> = None;
 
         for _ in 0..attempts {
@@ -82,7 +122,8 @@ where
                         handler(&err);
                     }
                     last_error = Some(err);
-                    sleep(self.retry_delay).await;
+                    sleep(delay).await;
+                    delay = next_delay(delay, self.max_retry_delay);
                 }
             }
         }
@@ -90,3 +131,77 @@ where
         Err(last_error.unwrap())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let max = Some(Duration::from_millis(500));
+        let mut delay = Duration::from_millis(100);
+        let mut delays = vec![];
+        for _ in 0..5 {
+            delay = next_delay(delay, max);
+            delays.push(delay.as_millis());
+        }
+        assert_eq!(delays, [200, 400, 500, 500, 500]);
+
+        // Without backoff the delay stays constant
+        assert_eq!(
+            next_delay(Duration::from_millis(100), None),
+            Duration::from_millis(100)
+        );
+    }
+
+    #[tokio::test]
+    async fn exec_until_stops_when_the_next_attempt_misses_the_deadline() {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let counted = Arc::clone(&attempts);
+        let deadline = Instant::now() + Duration::from_millis(50);
+        let result: Result<(), usize> = retry(|| {
+            let attempts = Arc::clone(&counted);
+            async move { Err(attempts.fetch_add(1, Ordering::SeqCst) + 1) }
+        })
+        .retry_delay(Duration::from_millis(20))
+        .backoff(Duration::from_secs(1))
+        .exec_until(deadline)
+        .await;
+
+        // Attempts at 0ms and 20ms; the next one would start at 60ms
+        assert_eq!(result, Err(2));
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn exec_until_attempts_once_past_the_deadline() {
+        let attempts = AtomicUsize::new(0);
+        let result: Result<(), ()> = retry(|| {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            async { Err(()) }
+        })
+        .exec_until(Instant::now())
+        .await;
+
+        assert_eq!(result, Err(()));
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn exec_until_returns_the_first_success() {
+        let attempts = AtomicUsize::new(0);
+        let result: Result<usize, ()> = retry(|| {
+            let attempt = attempts.fetch_add(1, Ordering::SeqCst);
+            async move { if attempt < 2 { Err(()) } else { Ok(attempt) } }
+        })
+        .retry_delay(Duration::from_millis(1))
+        .exec_until(Instant::now() + Duration::from_secs(5))
+        .await;
+
+        assert_eq!(result, Ok(2));
+    }
+}
@@ -14,3 +14,30 @@ use element::Element;
 pub fn get_address_for_private_key(private_key: Element) -> Element {
     hash::hash_merge([private_key, Element::ZERO])
 }
+
+/// Get the nullifier key from a private key
+///
+/// The nullifier key is enough to compute a note's nullifier but cannot spend it,
+/// so it can be handed to an inclusion prover. The zero key used by padding notes
+/// maps to zero, keeping the padding nullifier at `hash_merge([0, 0])`.
+#[must_use]
+#[inline]
+pub fn get_nullifier_key_for_private_key(private_key: Element) -> Element {
+    if private_key == Element::ZERO {
+        Element::ZERO
+    } else {
+        hash::hash_merge([private_key, Element::ONE])
+    }
+}
+
+/// Get the nullifier for a note commitment
+///
+/// # Arguments
+///
+/// * `commitment` - The commitment of the note being spent.
+/// * `nullifier_key` - The nullifier key of the note's owner.
+#[must_use]
+#[inline]
+pub fn get_nullifier(commitment: Element, nullifier_key: Element) -> Element {
+    hash::hash_merge([commitment, nullifier_key])
+}
@@ -1,4 +1,4 @@
-use crate::{InputNote, ToBytes, UtxoProofBytes};
+use crate::{InclusionNote, ToBytes, UtxoProofBytes};
 use borsh::{BorshDeserialize, BorshSerialize};
 use element::{Base, Element};
 use serde::{Deserialize, Serialize};
@@ -52,8 +52,9 @@ pub struct HyliSmtIncl {
     pub tx_blob_count: u32,
     /// Execution success flag reported by the host.
     pub success: bool,
-    /// Input notes (note data + secret key) whose commitments are proven to be in the SMT.
-    pub input_notes: [InputNote; 2],
+    /// Input notes (note data + nullifier key) whose commitments are proven to be in the SMT.
+    /// The spending key is never part of this witness.
+    pub input_notes: [InclusionNote; 2],
     /// SMT siblings for input_notes[0] commitment (256 Field elements).
     pub siblings_0: Box<[Base; 256]>,
     /// SMT siblings for input_notes[1] commitment (256 Field elements).
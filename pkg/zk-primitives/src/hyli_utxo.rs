@@ -1,7 +1,6 @@
-use crate::{ToBytes, Utxo, UtxoProofBytes};
+use crate::{InputNote, ToBytes, Utxo, UtxoProofBytes};
 use borsh::{BorshDeserialize, BorshSerialize};
 use element::Element;
-use hash::hash_merge;
 use serde::{Deserialize, Serialize};
 
 /// Number of public input fields emitted by the Hyli UTXO proof.
@@ -94,11 +93,7 @@ impl HyliUtxo {
     /// Returns the private commitments inserted into the nullifier tree for each input note.
     #[must_use]
     pub fn nullifier_commitments(&self) -> [Element; 2] {
-        let mut commitments = [Element::ZERO; 2];
-        for (index, note) in self.utxo.input_notes.iter().enumerate() {
-            commitments[index] = hash_merge([note.note.psi, note.secret_key]);
-        }
-        commitments
+        self.utxo.input_notes.each_ref().map(InputNote::nullifier)
     }
 
     /// Computes the expected blob payload derived from the underlying commitments.
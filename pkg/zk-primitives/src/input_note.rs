@@ -1,4 +1,7 @@
-use crate::{NoteURLPayload, decode_activity_url_payload, note::Note};
+use crate::{
+    NoteURLPayload, decode_activity_url_payload, get_nullifier, get_nullifier_key_for_private_key,
+    note::Note,
+};
 use element::Element;
 use serde::{Deserialize, Serialize};
 
@@ -51,4 +54,76 @@ impl InputNote {
         let payload: NoteURLPayload = self.into();
         payload.encode_activity_url_payload()
     }
+
+    /// Nullifier key of the note owner, see [`get_nullifier_key_for_private_key`]
+    #[must_use]
+    pub fn nullifier_key(&self) -> Element {
+        get_nullifier_key_for_private_key(self.secret_key)
+    }
+
+    /// Nullifier revealed when this note is spent
+    #[must_use]
+    pub fn nullifier(&self) -> Element {
+        get_nullifier(self.note.commitment(), self.nullifier_key())
+    }
+}
+
+/// InclusionNote is an input note stripped of its spending key. It carries just
+/// enough to prove the note is in the notes tree and derive its nullifier, so it
+/// can be shared with a prover that must not be able to spend the note.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct InclusionNote {
+    /// The note being spent
+    pub note: Note,
+    /// Nullifier key of the note owner
+    pub nullifier_key: Element,
+}
+
+impl InclusionNote {
+    /// Create a new inclusion note
+    #[must_use]
+    pub fn new(note: Note, nullifier_key: Element) -> Self {
+        Self {
+            note,
+            nullifier_key,
+        }
+    }
+
+    /// Nullifier revealed when this note is spent
+    #[must_use]
+    pub fn nullifier(&self) -> Element {
+        get_nullifier(self.note.commitment(), self.nullifier_key)
+    }
+}
+
+impl From<&InputNote> for InclusionNote {
+    fn from(input_note: &InputNote) -> Self {
+        Self {
+            note: input_note.note.clone(),
+            nullifier_key: input_note.nullifier_key(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inclusion_note_derives_the_spent_nullifier() {
+        let input_note =
+            InputNote::new_from_ephemeral_private_key(Element::new(101), Element::new(1));
+        let inclusion_note = InclusionNote::from(&input_note);
+
+        assert_ne!(inclusion_note.nullifier_key, input_note.secret_key);
+        assert_eq!(inclusion_note.nullifier(), input_note.nullifier());
+    }
+
+    #[test]
+    fn padding_nullifier_is_unchanged() {
+        assert_eq!(
+            InputNote::padding_note().nullifier(),
+            hash::hash_merge([Element::ZERO, Element::ZERO])
+        );
+    }
 }
@@ -19,6 +19,7 @@ mod input_note;
 mod merkle_path;
 mod migrate;
 mod note;
+pub mod note_encryption;
 mod note_url;
 mod points;
 mod signature;
@@ -0,0 +1,430 @@
+//! Encrypted note envelope shared with the web client (`CryptoService.ts`).
+//!
+//! - secp256k1 ECDH between an ephemeral sender key and the recipient's
+//!   encryption key; the shared secret is the x-coordinate of the shared point
+//! - HKDF-SHA256 with salt `sha256("hyli-notes-v1" || recipient_x || ephemeral_compressed)`
+//!   and info `hyli-notes:aes-gcm:key:v1`
+//! - AES-256-GCM with a 12-byte nonce, a 16-byte tag and no associated data
+//!
+//! The ciphertext is wrapped in a JSON envelope
+//! (`{"v":1,"nonce":..,"ciphertext":..,"tag":..}`, base64url fields) which is
+//! itself base64url-encoded. Test vectors shared with the TypeScript client
+//! live in `fixtures/note_encryption/vectors.json`.
+
+use aes_gcm::{
+    Aes256Gcm, Nonce,
+    aead::{Aead, KeyInit},
+};
+use base64::prelude::*;
+use hkdf::Hkdf;
+use rand::RngCore;
+use secp256k1::{PublicKey, SECP256K1, SecretKey, ecdh::shared_secret_point};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+const SALT_LABEL: &[u8] = b"hyli-notes-v1";
+const HKDF_INFO: &[u8] = b"hyli-notes:aes-gcm:key:v1";
+const ENVELOPE_VERSION: u8 = 1;
+const TAG_LEN: usize = 16;
+
+/// Length of the AES-GCM nonce in bytes
+pub const NONCE_LEN: usize = 12;
+
+/// Errors returned while encrypting or decrypting a note
+#[derive(Debug, thiserror::Error)]
+pub enum NoteEncryptionError {
+    /// A key, address or encoded field could not be parsed
+    #[error("invalid {0}: {1}")]
+    InvalidInput(&'static str, String),
+    /// The envelope version is not supported
+    #[error("unsupported encrypted note version: {0}")]
+    UnsupportedVersion(u8),
+    /// The envelope is not valid JSON or has invalid field lengths
+    #[error("malformed envelope: {0}")]
+    MalformedEnvelope(String),
+    /// AES-GCM authentication failed (wrong key or tampered payload)
+    #[error("decryption failed")]
+    Decryption,
+}
+
+/// An encrypted note, as uploaded to `/api/notes`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncryptedNote {
+    /// Base64url (unpadded) JSON envelope
+    pub encrypted_payload: String,
+    /// Compressed ephemeral secp256k1 public key (66 hex chars)
+    pub ephemeral_pubkey: String,
+}
+
+/// JSON envelope wrapped in `encrypted_payload`. Field order matters for
+/// byte-compatibility with `JSON.stringify`.
+#[derive(Serialize, Deserialize)]
+struct NoteEnvelopeV1 {
+    v: u8,
+    nonce: String,
+    ciphertext: String,
+    tag: String,
+}
+
+/// Derives the tag used to index a recipient's notes on the server:
+/// `hex(sha256("<lowercase utxo address hex>:recipient_tag"))`.
+///
+/// Matches `deriveRecipientTag` in the web client; a `0x` prefix is accepted.
+pub fn derive_recipient_tag(utxo_address_hex: &str) -> Result<String, NoteEncryptionError> {
+    let normalized = strip_0x(utxo_address_hex).to_lowercase();
+    if normalized.len() != 64 || hex::decode(&normalized).is_err() {
+        return Err(NoteEncryptionError::InvalidInput(
+            "utxo address",
+            "must be a 64-character hex string (32 bytes)".to_string(),
+        ));
+    }
+    let digest = Sha256::digest(format!("{normalized}:recipient_tag").as_bytes());
+    Ok(hex::encode(digest))
+}
+
+/// Encrypts `plaintext` for the recipient's encryption public key, given as the
+/// x-coordinate hex (64 chars) registered in the address registry.
+///
+/// The web client JSON-encodes the note before encrypting it, so `plaintext`
+/// should be the JSON bytes of the payload.
+pub fn encrypt_note(
+    recipient_pubkey_hex: &str,
+    plaintext: &[u8],
+) -> Result<EncryptedNote, NoteEncryptionError> {
+    let ephemeral = SecretKey::new(&mut rand::thread_rng());
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::thread_rng().fill_bytes(&mut nonce);
+    encrypt_note_with(recipient_pubkey_hex, plaintext, &ephemeral, nonce)
+}
+
+/// Deterministic variant of [`encrypt_note`] with a caller-provided ephemeral
+/// key and nonce.
+///
+/// Only meant for test vectors: reusing an ephemeral key or nonce breaks the
+/// confidentiality of every note encrypted with it.
+pub fn encrypt_note_with(
+    recipient_pubkey_hex: &str,
+    plaintext: &[u8],
+    ephemeral: &SecretKey,
+    nonce: [u8; NONCE_LEN],
+) -> Result<EncryptedNote, NoteEncryptionError> {
+    let recipient_x = decode_x_only(recipient_pubkey_hex, "recipient pubkey")?;
+    let recipient = x_only_to_pubkey(&recipient_x, "recipient pubkey")?;
+
+    let shared_secret = ecdh_x(&recipient, ephemeral);
+    let ephemeral_compressed = PublicKey::from_secret_key(SECP256K1, ephemeral).serialize();
+    let cipher = derive_cipher(&shared_secret, &recipient_x, &ephemeral_compressed);
+
+    let mut ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), plaintext)
+        .map_err(|_| NoteEncryptionError::MalformedEnvelope("encryption failed".to_string()))?;
+    let tag = ciphertext.split_off(ciphertext.len() - TAG_LEN);
+
+    let envelope = NoteEnvelopeV1 {
+        v: ENVELOPE_VERSION,
+        nonce: BASE64_URL_SAFE_NO_PAD.encode(nonce),
+        ciphertext: BASE64_URL_SAFE_NO_PAD.encode(&ciphertext),
+        tag: BASE64_URL_SAFE_NO_PAD.encode(&tag),
+    };
+    let envelope_bytes = serde_json::to_vec(&envelope)
+        .map_err(|e| NoteEncryptionError::MalformedEnvelope(e.to_string()))?;
+
+    Ok(EncryptedNote {
+        encrypted_payload: BASE64_URL_SAFE_NO_PAD.encode(envelope_bytes),
+        ephemeral_pubkey: hex::encode(ephemeral_compressed),
+    })
+}
+
+/// Decrypts a payload produced by [`encrypt_note`] or the web client's `encryptNote`.
+///
+/// `ephemeral_pubkey_hex` may be compressed (66), uncompressed (130) or
+/// x-coordinate only (64 hex chars, lifted to the even-y point).
+pub fn decrypt_note(
+    secret_key: &SecretKey,
+    encrypted_payload: &str,
+    ephemeral_pubkey_hex: &str,
+) -> Result<Vec<u8>, NoteEncryptionError> {
+    let ephemeral = parse_ephemeral_pubkey(ephemeral_pubkey_hex)?;
+    let recipient_x = x_only(&PublicKey::from_secret_key(SECP256K1, secret_key));
+
+    let shared_secret = ecdh_x(&ephemeral, secret_key);
+    let cipher = derive_cipher(&shared_secret, &recipient_x, &ephemeral.serialize());
+
+    let envelope_bytes = base64url_decode(encrypted_payload, "encrypted payload")?;
+    let envelope: NoteEnvelopeV1 = serde_json::from_slice(&envelope_bytes)
+        .map_err(|e| NoteEncryptionError::MalformedEnvelope(e.to_string()))?;
+    if envelope.v != ENVELOPE_VERSION {
+        return Err(NoteEncryptionError::UnsupportedVersion(envelope.v));
+    }
+
+    let nonce = base64url_decode(&envelope.nonce, "nonce")?;
+    let mut combined = base64url_decode(&envelope.ciphertext, "ciphertext")?;
+    let tag = base64url_decode(&envelope.tag, "tag")?;
+    if nonce.len() != NONCE_LEN {
+        return Err(NoteEncryptionError::MalformedEnvelope(format!(
+            "invalid nonce length: {}",
+            nonce.len()
+        )));
+    }
+    if tag.len() != TAG_LEN {
+        return Err(NoteEncryptionError::MalformedEnvelope(format!(
+            "invalid tag length: {}",
+            tag.len()
+        )));
+    }
+    combined.extend_from_slice(&tag);
+
+    cipher
+        .decrypt(Nonce::from_slice(&nonce), combined.as_slice())
+        .map_err(|_| NoteEncryptionError::Decryption)
+}
+
+/// The x-coordinate of a public key, the form used for `encryption_pubkey`
+/// registrations
+#[must_use]
+pub fn x_only(pubkey: &PublicKey) -> [u8; 32] {
+    let mut x = [0u8; 32];
+    x.copy_from_slice(&pubkey.serialize()[1..]);
+    x
+}
+
+fn ecdh_x(pubkey: &PublicKey, secret_key: &SecretKey) -> [u8; 32] {
+    let point = shared_secret_point(pubkey, secret_key);
+    let mut x = [0u8; 32];
+    x.copy_from_slice(&point[..32]);
+    x
+}
+
+fn derive_cipher(shared_secret: &[u8; 32], recipient_x: &[u8; 32], ephemeral: &[u8]) -> Aes256Gcm {
+    let mut salt_hasher = Sha256::new();
+    salt_hasher.update(SALT_LABEL);
+    salt_hasher.update(recipient_x);
+    salt_hasher.update(ephemeral);
+    let salt = salt_hasher.finalize();
+
+    let mut key = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(&salt), shared_secret)
+        .expand(HKDF_INFO, &mut key)
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+    Aes256Gcm::new(&key.into())
+}
+
+fn parse_ephemeral_pubkey(value: &str) -> Result<PublicKey, NoteEncryptionError> {
+    let normalized = strip_0x(value).to_lowercase();
+    match normalized.len() {
+        66 | 130 => {
+            let bytes = hex::decode(&normalized).map_err(|e| {
+                NoteEncryptionError::InvalidInput("ephemeral pubkey", e.to_string())
+            })?;
+            PublicKey::from_slice(&bytes)
+                .map_err(|e| NoteEncryptionError::InvalidInput("ephemeral pubkey", e.to_string()))
+        }
+        64 => x_only_to_pubkey(
+            &decode_x_only(&normalized, "ephemeral pubkey")?,
+            "ephemeral pubkey",
+        ),
+        _ => Err(NoteEncryptionError::InvalidInput(
+            "ephemeral pubkey",
+            "must be 66-char compressed, 130-char uncompressed, or 64-char x-coordinate hex"
+                .to_string(),
+        )),
+    }
+}
+
+fn decode_x_only(value: &str, field: &'static str) -> Result<[u8; 32], NoteEncryptionError> {
+    let bytes = hex::decode(strip_0x(value))
+        .map_err(|e| NoteEncryptionError::InvalidInput(field, e.to_string()))?;
+    bytes.try_into().map_err(|_| {
+        NoteEncryptionError::InvalidInput(field, "must be a 64-character hex string".to_string())
+    })
+}
+
+/// Lifts an x-coordinate to a point, preferring the even-y encoding like the
+/// web client. Either choice yields the same ECDH x-coordinate.
+fn x_only_to_pubkey(x: &[u8; 32], field: &'static str) -> Result<PublicKey, NoteEncryptionError> {
+    let mut compressed = [0u8; 33];
+    compressed[1..].copy_from_slice(x);
+    compressed[0] = 0x02;
+    PublicKey::from_slice(&compressed).or_else(|_| {
+        compressed[0] = 0x03;
+        PublicKey::from_slice(&compressed)
+            .map_err(|e| NoteEncryptionError::InvalidInput(field, e.to_string()))
+    })
+}
+
+fn base64url_decode(value: &str, field: &'static str) -> Result<Vec<u8>, NoteEncryptionError> {
+    BASE64_URL_SAFE_NO_PAD
+        .decode(value.trim_end_matches('='))
+        .map_err(|e| NoteEncryptionError::InvalidInput(field, e.to_string()))
+}
+
+fn strip_0x(value: &str) -> &str {
+    value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+        .unwrap_or(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VECTORS: &str = include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../../fixtures/note_encryption/vectors.json"
+    ));
+
+    #[derive(Deserialize)]
+    struct Vectors {
+        encryption: Vec<EncryptionVector>,
+        recipient_tag: Vec<RecipientTagVector>,
+    }
+
+    #[derive(Deserialize)]
+    struct EncryptionVector {
+        name: String,
+        recipient_secret_key: String,
+        recipient_pubkey: String,
+        ephemeral_secret_key: String,
+        nonce: String,
+        plaintext: String,
+        encrypted_payload: String,
+        ephemeral_pubkey: String,
+    }
+
+    #[derive(Deserialize)]
+    struct RecipientTagVector {
+        utxo_address: String,
+        recipient_tag: String,
+    }
+
+    fn vectors() -> Vectors {
+        serde_json::from_str(VECTORS).unwrap()
+    }
+
+    fn secret_key(hex_str: &str) -> SecretKey {
+        SecretKey::from_slice(&hex::decode(hex_str).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn encrypt_matches_vectors() {
+        for vector in vectors().encryption {
+            let recipient = secret_key(&vector.recipient_secret_key);
+            assert_eq!(
+                hex::encode(x_only(&PublicKey::from_secret_key(SECP256K1, &recipient))),
+                vector.recipient_pubkey,
+                "{}",
+                vector.name
+            );
+
+            let nonce: [u8; NONCE_LEN] = hex::decode(&vector.nonce).unwrap().try_into().unwrap();
+            let encrypted = encrypt_note_with(
+                &vector.recipient_pubkey,
+                vector.plaintext.as_bytes(),
+                &secret_key(&vector.ephemeral_secret_key),
+                nonce,
+            )
+            .unwrap();
+
+            assert_eq!(
+                encrypted.encrypted_payload, vector.encrypted_payload,
+                "{}",
+                vector.name
+            );
+            assert_eq!(
+                encrypted.ephemeral_pubkey, vector.ephemeral_pubkey,
+                "{}",
+                vector.name
+            );
+        }
+    }
+
+    #[test]
+    fn decrypt_vectors_with_every_ephemeral_encoding() {
+        for vector in vectors().encryption {
+            let recipient = secret_key(&vector.recipient_secret_key);
+            let ephemeral =
+                PublicKey::from_secret_key(SECP256K1, &secret_key(&vector.ephemeral_secret_key));
+            let mut encodings = vec![
+                vector.ephemeral_pubkey.clone(),
+                format!("0x{}", vector.ephemeral_pubkey),
+                hex::encode(ephemeral.serialize_uncompressed()),
+            ];
+            // An x-only key is lifted to even y, and the salt commits to that
+            // encoding (same as the web client)
+            if ephemeral.serialize()[0] == 0x02 {
+                encodings.push(hex::encode(x_only(&ephemeral)));
+            }
+
+            for encoding in encodings {
+                let plaintext =
+                    decrypt_note(&recipient, &vector.encrypted_payload, &encoding).unwrap();
+                assert_eq!(plaintext, vector.plaintext.as_bytes(), "{}", vector.name);
+            }
+        }
+    }
+
+    #[test]
+    fn recipient_tag_matches_vectors() {
+        for vector in vectors().recipient_tag {
+            assert_eq!(
+                derive_recipient_tag(&vector.utxo_address).unwrap(),
+                vector.recipient_tag
+            );
+        }
+        assert!(derive_recipient_tag("abcd").is_err());
+        assert!(derive_recipient_tag(&"zz".repeat(32)).is_err());
+    }
+
+    #[test]
+    fn round_trip_with_random_keys() {
+        let recipient = SecretKey::new(&mut rand::thread_rng());
+        let recipient_x = hex::encode(x_only(&PublicKey::from_secret_key(SECP256K1, &recipient)));
+
+        let encrypted = encrypt_note(&recipient_x, b"{\"value\":42}").unwrap();
+        let plaintext = decrypt_note(
+            &recipient,
+            &encrypted.encrypted_payload,
+            &encrypted.ephemeral_pubkey,
+        )
+        .unwrap();
+        assert_eq!(plaintext, b"{\"value\":42}");
+
+        let other = SecretKey::new(&mut rand::thread_rng());
+        assert!(matches!(
+            decrypt_note(
+                &other,
+                &encrypted.encrypted_payload,
+                &encrypted.ephemeral_pubkey
+            ),
+            Err(NoteEncryptionError::Decryption)
+        ));
+    }
+
+    #[test]
+    fn rejects_tampered_and_unknown_envelopes() {
+        let vector = vectors().encryption.remove(0);
+        let recipient = secret_key(&vector.recipient_secret_key);
+
+        let envelope_bytes = BASE64_URL_SAFE_NO_PAD
+            .decode(&vector.encrypted_payload)
+            .unwrap();
+        let mut envelope: NoteEnvelopeV1 = serde_json::from_slice(&envelope_bytes).unwrap();
+
+        let mut tag = BASE64_URL_SAFE_NO_PAD.decode(&envelope.tag).unwrap();
+        tag[0] ^= 1;
+        envelope.tag = BASE64_URL_SAFE_NO_PAD.encode(&tag);
+        let tampered = BASE64_URL_SAFE_NO_PAD.encode(serde_json::to_vec(&envelope).unwrap());
+        assert!(matches!(
+            decrypt_note(&recipient, &tampered, &vector.ephemeral_pubkey),
+            Err(NoteEncryptionError::Decryption)
+        ));
+
+        envelope.v = 2;
+        let unknown = BASE64_URL_SAFE_NO_PAD.encode(serde_json::to_vec(&envelope).unwrap());
+        assert!(matches!(
+            decrypt_note(&recipient, &unknown, &vector.ephemeral_pubkey),
+            Err(NoteEncryptionError::UnsupportedVersion(2))
+        ));
+    }
+}
@@ -0,0 +1,2 @@
+DROP TABLE address_registrations;
+DROP TABLE encrypted_notes;
@@ -0,0 +1,26 @@
+CREATE TABLE encrypted_notes (
+    id TEXT PRIMARY KEY,
+    -- Insertion order across servers, the stream cursor
+    seq BIGSERIAL NOT NULL UNIQUE,
+    recipient_tag TEXT NOT NULL,
+    encrypted_payload TEXT NOT NULL,
+    ephemeral_pubkey TEXT NOT NULL,
+    sender_tag TEXT,
+    stored_at BIGINT NOT NULL
+);
+
+CREATE INDEX encrypted_notes_recipient_tag_seq_idx ON encrypted_notes (recipient_tag, seq);
+CREATE INDEX encrypted_notes_recipient_tag_stored_at_idx ON encrypted_notes (recipient_tag, stored_at);
+CREATE INDEX encrypted_notes_stored_at_idx ON encrypted_notes (stored_at);
+
+CREATE TABLE address_registrations (
+    username TEXT PRIMARY KEY,
+    utxo_address TEXT NOT NULL,
+    -- Derived from utxo_address, NULL if it is not a valid address
+    recipient_tag TEXT,
+    encryption_pubkey TEXT NOT NULL,
+    registered_at BIGINT NOT NULL,
+    ownership_proof JSONB
+);
+
+CREATE INDEX address_registrations_recipient_tag_idx ON address_registrations (recipient_tag);
@@ -0,0 +1,7 @@
+-- Signed requests accepted by a server, kept until their signature expires
+CREATE TABLE used_signed_requests (
+    message TEXT PRIMARY KEY,
+    expires_at BIGINT NOT NULL
+);
+
+CREATE INDEX used_signed_requests_expires_at_idx ON used_signed_requests (expires_at);
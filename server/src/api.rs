@@ -1,46 +1,68 @@
-use std::sync::Arc;
+use std::{net::SocketAddr, sync::Arc, time::Duration};
 
 use crate::{
     app::{
         build_note, FaucetDepositCommand, FaucetMintCommand, TransferCommand,
         TransferWithProofCommand, FAUCET_MINT_AMOUNT,
     },
+    faucet_pow::{FaucetPow, PowError},
+    faucet_quota::{FaucetQuota, QuotaError},
+    hyli_utxo_state_client::UtxoStateStore,
     init::{HYLI_SMT_INCL_PROOF_VK, HYLI_UTXO_NOIR_VK},
     metrics::FaucetMetrics,
-    note_store::{AddressRegistry, NoteStore},
+    note_store::{blocking, current_timestamp, AddressRegistry, NoteStore, RegistrationError},
+    note_stream::note_events,
+    nullifier_guard::{blob_nullifiers, NullifierError, NullifierGuard},
+    proof_verification::{check_client_proof, decode_public_inputs, ExpectedTx, ProofCheckError},
+    proving_scheduler::{Circuit, ProofPriority, ProverBusy, ProvingScheduler},
+    request_auth::{RequestAuthError, RequestAuthorizer},
     smt_incl_prover::HyliSmtInclNoirProver,
+    state_stream::{transition_events, StateTransitionFeed},
+    tx_tracker::TxTracker,
     types::{
-        BlobHashResponse, BlobInfo, CreateBlobRequest, CreateBlobResponse, DepositRequest,
-        EncryptedNoteRecord, FaucetRequest, FaucetResponse, FinalizeTransferRequest,
-        FinalizeTransferResponse, GetNotesQuery, GetNotesResponse, InputNoteData,
-        RegisterAddressRequest, RegisterAddressResponse, ResolveAddressResponse,
-        ServerConfigResponse, SubmitProofRequest, TokenTransferRequest, TransferResponse,
-        UploadNoteRequest, UploadNoteResponse,
+        BlobHashResponse, BlobInfo, CreateBlobRequest, CreateBlobResponse, DeleteNoteQuery,
+        DepositRequest, EncryptedNoteRecord, ErrorCode, ErrorResponse, FaucetChallenge,
+        FaucetChallengeRequest, FaucetRequest, FaucetResponse, FinalizeTransferRequest,
+        FinalizeTransferResponse, GetNotesQuery, GetNotesResponse, InputNoteData, NoteStreamQuery,
+        RegisterAddressRequest, RegisterAddressResponse, ReleaseAddressQuery,
+        ResolveAddressResponse, ServerConfigResponse, StateStreamQuery, SubmitProofRequest,
+        TokenTransferRequest, TransferResponse, TxKind, TxRecord, UploadNoteRequest,
+        UploadNoteResponse,
     },
 };
 use anyhow::Result;
 use axum::{
-    extract::{Path, Query, State},
-    http::{Method, StatusCode},
-    response::{IntoResponse, Response},
+    extract::{ConnectInfo, Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    response::{
+        sse::{Event, Sse},
+        IntoResponse, Response,
+    },
     routing::{delete, get, post},
-    Json, Router,
+    Extension, Json, Router,
 };
 use client_sdk::rest_client::{NodeApiClient, NodeApiHttpClient};
+use futures::Stream;
 use hyli_modules::{
     bus::{BusClientSender, SharedMessageBus},
     module_bus_client, module_handle_messages,
     modules::{BuildApiContextInner, Module},
 };
 use hyli_smt_token::SmtTokenAction;
-use hyli_utxo_state::state::HYLI_UTXO_STATE_ACTION;
+use hyli_utxo_state::{
+    state::{parse_hyli_smt_incl_blob, HYLI_UTXO_STATE_ACTION, MAX_ROOTS},
+    zk::BorshableH256,
+};
 use sdk::{
     Blob, BlobData, BlobIndex, BlobTransaction, ContractAction, ContractName, Hashed, Identity,
     ProgramId, ProofData, ProofTransaction, StructuredBlobData, TxHash, Verifier,
 };
 use serde_json::json;
 use tower_http::cors::{Any, CorsLayer};
-use zk_primitives::{InputNote, ToBytes, HYLI_SMT_INCL_BLOB_LENGTH_BYTES};
+use zk_primitives::{HyliSmtIncl, InclusionNote, ToBytes, HYLI_SMT_INCL_BLOB_LENGTH_BYTES};
+
+/// Maximum number of recipient tags a single `/api/notes/stream` can follow.
+const MAX_NOTE_STREAM_TAGS: usize = 16;
 
 pub struct ApiModule {
     bus: ApiModuleBusClient,
@@ -51,9 +73,20 @@ pub struct ApiModuleCtx {
     pub default_amount: u64,
     pub contract_name: ContractName,
     pub metrics: FaucetMetrics,
+    pub faucet_quota: Arc<FaucetQuota>,
+    pub faucet_pow: Arc<FaucetPow>,
+    pub tx_tracker: Arc<TxTracker>,
+    pub nullifier_guard: Arc<NullifierGuard>,
+    pub proving_scheduler: Arc<ProvingScheduler>,
+    pub state_feed: Arc<StateTransitionFeed>,
     pub note_store: Arc<NoteStore>,
     pub address_registry: Arc<AddressRegistry>,
+    /// Indexed hyli-utxo-state, to refuse notes roots the contract no
+    /// longer accepts
+    pub utxo_state: UtxoStateStore,
     pub max_note_payload_size: usize,
+    pub request_authorizer: Arc<RequestAuthorizer>,
+    pub stream_heartbeat_secs: u64,
     pub client: NodeApiHttpClient,
     pub utxo_contract_name: String,
     pub utxo_state_contract_name: String,
@@ -65,9 +98,18 @@ struct RouterCtx {
     default_amount: u64,
     bus: ApiModuleBusClient,
     metrics: FaucetMetrics,
+    faucet_quota: Arc<FaucetQuota>,
+    faucet_pow: Arc<FaucetPow>,
+    tx_tracker: Arc<TxTracker>,
+    nullifier_guard: Arc<NullifierGuard>,
+    proving_scheduler: Arc<ProvingScheduler>,
+    state_feed: Arc<StateTransitionFeed>,
     note_store: Arc<NoteStore>,
     address_registry: Arc<AddressRegistry>,
+    utxo_state: UtxoStateStore,
+    request_authorizer: Arc<RequestAuthorizer>,
     max_note_payload_size: usize,
+    stream_heartbeat: Duration,
     client: NodeApiHttpClient,
     utxo_contract_name: String,
     utxo_state_contract_name: String,
@@ -94,9 +136,18 @@ impl Module for ApiModule {
             default_amount: ctx.default_amount,
             bus: module_bus.clone(),
             metrics: ctx.metrics.clone(),
+            faucet_quota: ctx.faucet_quota.clone(),
+            faucet_pow: ctx.faucet_pow.clone(),
+            tx_tracker: ctx.tx_tracker.clone(),
+            nullifier_guard: ctx.nullifier_guard.clone(),
+            proving_scheduler: ctx.proving_scheduler.clone(),
+            state_feed: ctx.state_feed.clone(),
             note_store: ctx.note_store.clone(),
             address_registry: ctx.address_registry.clone(),
+            utxo_state: ctx.utxo_state.clone(),
+            request_authorizer: ctx.request_authorizer.clone(),
             max_note_payload_size: ctx.max_note_payload_size,
+            stream_heartbeat: Duration::from_secs(ctx.stream_heartbeat_secs.max(1)),
             client: ctx.client.clone(),
             utxo_contract_name: ctx.utxo_contract_name.clone(),
             utxo_state_contract_name: ctx.utxo_state_contract_name.clone(),
@@ -112,6 +163,7 @@ impl Module for ApiModule {
             .route("/_health", get(health))
             .route("/api/config", get(get_config))
             .route("/api/faucet", post(faucet))
+            .route("/api/faucet/challenge", post(faucet_challenge))
             .route("/api/deposit", post(deposit))
             // Two-step transfer endpoints (client-side proving with real tx_hash)
             .route("/api/blob/create", post(create_blob))
@@ -119,13 +171,17 @@ impl Module for ApiModule {
             // Atomic transfer: compute tx_hash before proving, then submit all at once
             .route("/api/blob/hash", post(hash_blob))
             .route("/api/transfer/finalize", post(finalize_transfer))
+            .route("/api/tx/{hash}", get(tx_status))
+            .route("/api/state/stream", get(stream_state))
             // Encrypted notes endpoints
             .route("/api/notes", post(upload_note))
+            .route("/api/notes/stream", get(stream_notes))
             .route("/api/notes/{recipient_tag}", get(get_notes))
             .route("/api/notes/{recipient_tag}/{note_id}", delete(delete_note))
             // Address registry endpoints
             .route("/api/address/register", post(register_address))
             .route("/api/address/resolve/{username}", get(resolve_address))
+            .route("/api/address/{username}", delete(release_address))
             .with_state(router_ctx)
             .layer(cors);
 
@@ -159,12 +215,18 @@ async fn get_config(State(state): State<RouterCtx>) -> Json<ServerConfigResponse
 
 async fn faucet(
     State(state): State<RouterCtx>,
+    headers: HeaderMap,
+    connect_info: Option<Extension<ConnectInfo<SocketAddr>>>,
     Json(request): Json<FaucetRequest>,
 ) -> Result<Json<FaucetResponse>, ApiError> {
     let RouterCtx {
         default_amount,
         mut bus,
         metrics,
+        faucet_quota,
+        faucet_pow,
+        tx_tracker,
+        proving_scheduler,
         ..
     } = state;
 
@@ -199,28 +261,83 @@ async fn faucet(
         return Err(ApiError::bad_request("pubkey_hex must decode to 32 bytes"));
     }
 
+    // Turn mints away before they consume a challenge or quota
+    check_proving_capacity(&proving_scheduler, ProofPriority::Faucet).map_err(|err| {
+        metrics.record_failure("prover_busy");
+        ApiError::from(err)
+    })?;
+
+    let recipient_hex = hex::encode(&pubkey_bytes);
+    let now = current_timestamp();
+    faucet_pow
+        .redeem(&recipient_hex, request.pow.as_ref(), now)
+        .map_err(|err| {
+            metrics.record_failure(err.reason());
+            ApiError::from(err)
+        })?;
+
+    let client_ip = faucet_quota
+        .client_ip(
+            &headers,
+            connect_info.map(|Extension(ConnectInfo(addr))| addr),
+        )
+        .map_err(|err| {
+            metrics.record_failure(err.reason());
+            ApiError::from(err)
+        })?;
+    faucet_quota
+        .try_consume(&recipient_hex, client_ip, amount, now)
+        .map_err(|err| {
+            metrics.record_failure(err.reason());
+            ApiError::from(err)
+        })?;
+
     let mut address_bytes = [0u8; 32];
     address_bytes.copy_from_slice(&pubkey_bytes);
     let recipient_address = element::Element::from_be_bytes(address_bytes);
 
     let note = build_note(recipient_address, amount);
+    let tracking_id = hex::encode(note.commitment().to_be_bytes());
+    tx_tracker.record_queued(&tracking_id, TxKind::Faucet, now);
 
     bus.send(FaucetMintCommand {
         recipient_pubkey: pubkey_bytes,
         amount,
         note: note.clone(),
+        tracking_id: tracking_id.clone(),
     })
     .map_err(|err| {
+        faucet_quota.refund(&recipient_hex, client_ip, amount);
+        tx_tracker.record_submission_failed(&tracking_id, TxKind::Faucet, err.to_string(), now);
         metrics.record_failure("bus_send_failed");
         ApiError::internal(err.to_string())
     })?;
 
-    let response = FaucetResponse { note };
+    let response = FaucetResponse { note, tracking_id };
     metrics.record_success(amount);
 
     Ok(Json(response))
 }
 
+async fn faucet_challenge(
+    State(state): State<RouterCtx>,
+    Json(request): Json<FaucetChallengeRequest>,
+) -> Result<Json<FaucetChallenge>, ApiError> {
+    let pubkey_hex = request.pubkey_hex.trim();
+    let normalized_pubkey = pubkey_hex.strip_prefix("0x").unwrap_or(pubkey_hex);
+    let pubkey_bytes = hex::decode(normalized_pubkey)
+        .map_err(|err| ApiError::bad_request(format!("invalid pubkey_hex: {err}")))?;
+    if pubkey_bytes.len() != 32 {
+        return Err(ApiError::bad_request("pubkey_hex must decode to 32 bytes"));
+    }
+
+    Ok(Json(
+        state
+            .faucet_pow
+            .issue(&hex::encode(pubkey_bytes), current_timestamp()),
+    ))
+}
+
 async fn deposit(
     State(state): State<RouterCtx>,
     Json(request): Json<DepositRequest>,
@@ -229,6 +346,8 @@ async fn deposit(
         default_amount,
         mut bus,
         metrics,
+        tx_tracker,
+        proving_scheduler,
         ..
     } = state;
 
@@ -273,11 +392,19 @@ async fn deposit(
         return Err(ApiError::bad_request("token_contract must not be empty"));
     }
 
+    check_proving_capacity(&proving_scheduler, ProofPriority::User).map_err(|err| {
+        metrics.record_failure("prover_busy");
+        ApiError::from(err)
+    })?;
+
     let mut address_bytes = [0u8; 32];
     address_bytes.copy_from_slice(&pubkey_bytes);
     let recipient_address = element::Element::from_be_bytes(address_bytes);
 
     let note = build_note(recipient_address, amount);
+    let tracking_id = hex::encode(note.commitment().to_be_bytes());
+    let now = current_timestamp();
+    tx_tracker.record_queued(&tracking_id, TxKind::Deposit, now);
 
     bus.send(FaucetDepositCommand {
         recipient_pubkey: pubkey_bytes,
@@ -287,13 +414,15 @@ async fn deposit(
         wallet_account: request.wallet_account,
         secp256k1_blob: request.secp256k1_blob,
         wallet_blob: request.wallet_blob,
+        tracking_id: tracking_id.clone(),
     })
     .map_err(|err| {
+        tx_tracker.record_submission_failed(&tracking_id, TxKind::Deposit, err.to_string(), now);
         metrics.record_failure("bus_send_failed");
         ApiError::internal(err.to_string())
     })?;
 
-    let response = FaucetResponse { note };
+    let response = FaucetResponse { note, tracking_id };
     metrics.record_success(amount);
 
     Ok(Json(response))
@@ -313,14 +442,34 @@ async fn create_blob(
     }
 
     let built = build_blob_transaction(&state, &request)?;
+    check_notes_root(&state, &built.notes_root).await?;
+    let tx_hash_hex = hex::encode(&built.transaction.hashed().0);
+    let reservation = state
+        .nullifier_guard
+        .reserve(
+            &tx_hash_hex,
+            &blob_nullifiers(&request.blob_data),
+            current_timestamp(),
+        )
+        .await?;
 
+    let blobs = built.transaction.blobs.clone();
     let tx_hash = state
         .client
         .send_tx_blob(built.transaction)
         .await
         .map_err(|e| ApiError::internal(format!("failed to send blob tx: {}", e)))?;
+    // The proofs are submitted by a later request
+    reservation.keep();
 
     tracing::info!(%tx_hash, "Submitted blob transaction (create_blob)");
+    let now = current_timestamp();
+    state
+        .tx_tracker
+        .record_submitted(&hex::encode(&tx_hash.0), TxKind::Transfer, None, now);
+    state
+        .tx_tracker
+        .record_blobs(&hex::encode(&tx_hash.0), blobs, now);
 
     let blobs = vec![
         BlobInfo {
@@ -353,21 +502,50 @@ async fn submit_proof(
         return Err(ApiError::bad_request("tx_hash must not be empty"));
     }
 
-    // ---- hyli_utxo proof ----
-    let proof_bytes = base64_decode(&request.proof)
-        .map_err(|e| ApiError::bad_request(format!("invalid base64 proof: {}", e)))?;
-
-    let public_inputs_bytes: Vec<u8> = request
-        .public_inputs
-        .iter()
-        .flat_map(|hex_str| {
-            let normalized = hex_str.strip_prefix("0x").unwrap_or(hex_str);
-            hex::decode(normalized).unwrap_or_else(|_| vec![0u8; 32])
-        })
-        .collect();
-
-    let mut proof_with_inputs = public_inputs_bytes;
-    proof_with_inputs.extend_from_slice(&proof_bytes);
+    let proof_with_inputs = client_proof_with_inputs(
+        Circuit::HyliUtxo,
+        "proof",
+        &request.proof,
+        &request.public_inputs,
+    )?;
+    let smt_proof_with_inputs = client_proof_with_inputs(
+        Circuit::SmtIncl,
+        "smt_proof",
+        &request.smt_proof,
+        &request.smt_public_inputs,
+    )?;
+
+    // The proofs must be for the blobs /api/blob/create sent
+    let tx_hash_hex = hex::encode(&request.tx_hash.0);
+    let blobs = state.tx_tracker.blobs(&tx_hash_hex).ok_or_else(|| {
+        ApiError::not_found(format!(
+            "no pending transfer {} was created by /api/blob/create",
+            request.tx_hash
+        ))
+    })?;
+    let identity = Identity(format!("transfer@{}", state.utxo_contract_name));
+    check_client_proof(
+        Circuit::HyliUtxo,
+        &proof_with_inputs,
+        &ExpectedTx {
+            tx_hash: &request.tx_hash,
+            identity: &identity,
+            contract_name: &state.utxo_contract_name,
+            blobs: &blobs,
+        },
+    )
+    .await?;
+    check_client_proof(
+        Circuit::SmtIncl,
+        &smt_proof_with_inputs,
+        &ExpectedTx {
+            tx_hash: &request.tx_hash,
+            identity: &identity,
+            contract_name: &state.smt_incl_proof_contract_name,
+            blobs: &blobs,
+        },
+    )
+    .await?;
 
     let utxo_proof_tx = ProofTransaction {
         contract_name: ContractName(state.utxo_contract_name.clone()),
@@ -380,23 +558,7 @@ async fn submit_proof(
         .client
         .send_tx_proof(utxo_proof_tx)
         .await
-        .map_err(|e| ApiError::internal(format!("failed to send utxo proof tx: {}", e)))?;
-
-    // ---- hyli_smt_incl_proof proof ----
-    let smt_proof_bytes = base64_decode(&request.smt_proof)
-        .map_err(|e| ApiError::bad_request(format!("invalid base64 smt_proof: {}", e)))?;
-
-    let smt_public_inputs_bytes: Vec<u8> = request
-        .smt_public_inputs
-        .iter()
-        .flat_map(|hex_str| {
-            let normalized = hex_str.strip_prefix("0x").unwrap_or(hex_str);
-            hex::decode(normalized).unwrap_or_else(|_| vec![0u8; 32])
-        })
-        .collect();
-
-    let mut smt_proof_with_inputs = smt_public_inputs_bytes;
-    smt_proof_with_inputs.extend_from_slice(&smt_proof_bytes);
+        .map_err(|e| proof_submission_failed(&state, &tx_hash_hex, "utxo", e))?;
 
     let smt_proof_tx = ProofTransaction {
         contract_name: ContractName(state.smt_incl_proof_contract_name.clone()),
@@ -409,9 +571,12 @@ async fn submit_proof(
         .client
         .send_tx_proof(smt_proof_tx)
         .await
-        .map_err(|e| ApiError::internal(format!("failed to send smt proof tx: {}", e)))?;
+        .map_err(|e| proof_submission_failed(&state, &tx_hash_hex, "smt", e))?;
 
     tracing::info!(tx_hash = %request.tx_hash, "Submitted proof transactions (step 2 of two-step transfer)");
+    state
+        .tx_tracker
+        .record_proof_submitted(&tx_hash_hex, current_timestamp());
 
     Ok(Json(TransferResponse {
         tx_hash: request.tx_hash,
@@ -423,6 +588,8 @@ async fn submit_proof(
 
 struct BuiltBlob {
     transaction: BlobTransaction,
+    /// Notes root the SMT inclusion proof is built against
+    notes_root: BorshableH256,
     state_blob_hex: String,
     hyli_utxo_hex: String,
     smt_hex: String,
@@ -450,11 +617,6 @@ fn build_blob_transaction(
     state: &RouterCtx,
     request: &CreateBlobRequest,
 ) -> Result<BuiltBlob, ApiError> {
-    let mut nullifier_0 = [0u8; 32];
-    let mut nullifier_1 = [0u8; 32];
-    nullifier_0.copy_from_slice(&request.blob_data[64..96]);
-    nullifier_1.copy_from_slice(&request.blob_data[96..128]);
-
     // Resolve smt_blob_data: client-provided or computed from input_notes + notes_root
     let raw_smt_blob_data = match &request.smt_blob_data {
         Some(data) => {
@@ -481,6 +643,9 @@ fn build_blob_transaction(
         }
     };
 
+    let (_, _, notes_root) =
+        parse_hyli_smt_incl_blob(&raw_smt_blob_data).map_err(ApiError::bad_request)?;
+
     let contract_name = state.utxo_contract_name.clone();
     let identity = Identity(format!("transfer@{}", contract_name));
     let hyli_utxo_data = BlobData(request.blob_data.clone());
@@ -532,12 +697,33 @@ fn build_blob_transaction(
 
     Ok(BuiltBlob {
         transaction,
+        notes_root,
         state_blob_hex: hex::encode(&state_blob_data.0),
         hyli_utxo_hex: hex::encode(&hyli_utxo_data.0),
         smt_hex: hex::encode(&smt_blob_data.0),
     })
 }
 
+/// Refuses a notes root the contract no longer accepts, as the transaction
+/// would fail once settled. Not checked until the indexer has the state.
+async fn check_notes_root(state: &RouterCtx, notes_root: &BorshableH256) -> Result<(), ApiError> {
+    let store = state.utxo_state.read().await;
+    let Some(executor) = store.as_ref() else {
+        return Ok(());
+    };
+    if executor.utxo_state().accepts_root(notes_root) {
+        return Ok(());
+    }
+    Err(ApiError::new(
+        StatusCode::CONFLICT,
+        ErrorCode::RootTooOld,
+        format!(
+            "notes root {} is not one of the last {MAX_ROOTS} roots, rebuild the witnesses against a recent one",
+            hex::encode(notes_root.as_ref())
+        ),
+    ))
+}
+
 /// Compute the tx_hash for blob data without submitting to the chain.
 /// Client uses this to generate proofs with the real tx_hash, then calls /api/transfer/finalize.
 async fn hash_blob(
@@ -550,8 +736,14 @@ async fn hash_blob(
             request.blob_data.len()
         )));
     }
+    // Spent notes are reported before the wallet spends time proving
+    state
+        .nullifier_guard
+        .check(&blob_nullifiers(&request.blob_data), current_timestamp())
+        .await?;
 
     let built = build_blob_transaction(&state, &request)?;
+    check_notes_root(&state, &built.notes_root).await?;
     let tx_hash = built.transaction.hashed();
 
     Ok(Json(BlobHashResponse { tx_hash }))
@@ -590,6 +782,17 @@ async fn finalize_transfer(
             blob_data.len()
         )));
     }
+    let nullifiers = blob_nullifiers(&blob_data);
+    state
+        .nullifier_guard
+        .check(&nullifiers, current_timestamp())
+        .await?;
+    if smt_proof.is_none() {
+        // Refuse before the blob is submitted, it would time out unproven
+        state
+            .proving_scheduler
+            .check_capacity(Circuit::SmtIncl, ProofPriority::User)?;
+    }
 
     let blob_request = CreateBlobRequest {
         blob_data,
@@ -600,10 +803,12 @@ async fn finalize_transfer(
         notes_root: notes_root.clone(),
     };
     let built = build_blob_transaction(&state, &blob_request)?;
+    check_notes_root(&state, &built.notes_root).await?;
     let tx_hash = built.transaction.hashed();
 
-    // Extract SMT blob data before the transaction is moved (BlobTransaction is not Clone)
-    let smt_blob_info = if smt_proof.is_none() {
+    // The witness of a server-generated SMT proof is built before anything is
+    // sent, so malformed notes or siblings leave nothing on chain
+    let smt_witness = if smt_proof.is_none() {
         let (smt_blob_index, smt_blob) = built
             .transaction
             .blobs
@@ -625,39 +830,105 @@ async fn finalize_transfer(
         let mut blob_payload = [0u8; HYLI_SMT_INCL_BLOB_LENGTH_BYTES];
         blob_payload.copy_from_slice(&smt_blob.data.0);
 
-        Some((
+        Some(smt_proof_witness(
+            &state.smt_incl_proof_contract_name,
+            &tx_hash,
             blob_payload,
             built.transaction.identity.clone(),
             built.transaction.blobs.len() as u32,
             smt_blob_index as u32,
-        ))
+            input_notes,
+            siblings_0,
+            siblings_1,
+        )?)
     } else {
         None
     };
 
-    // Submit blob transaction
+    let proof_with_inputs =
+        client_proof_with_inputs(Circuit::HyliUtxo, "proof", &proof, &public_inputs)?;
+
+    // ---- client-provided hyli_smt_incl_proof proof ----
+    let client_smt_proof = match smt_proof {
+        Some(client_proof) => {
+            let smt_pubs = smt_public_inputs.ok_or_else(|| {
+                ApiError::bad_request(
+                    "smt_public_inputs is required when smt_proof is provided".to_string(),
+                )
+            })?;
+            Some(client_proof_with_inputs(
+                Circuit::SmtIncl,
+                "smt_proof",
+                &client_proof,
+                &smt_pubs,
+            )?)
+        }
+        None => None,
+    };
+
+    // Reject proofs the node would not settle before the blob is sent, it
+    // would otherwise time out unproven
+    check_client_proof(
+        Circuit::HyliUtxo,
+        &proof_with_inputs,
+        &ExpectedTx {
+            tx_hash: &tx_hash,
+            identity: &built.transaction.identity,
+            contract_name: &state.utxo_contract_name,
+            blobs: &built.transaction.blobs,
+        },
+    )
+    .await?;
+    if let Some(smt_proof_with_inputs) = &client_smt_proof {
+        check_client_proof(
+            Circuit::SmtIncl,
+            smt_proof_with_inputs,
+            &ExpectedTx {
+                tx_hash: &tx_hash,
+                identity: &built.transaction.identity,
+                contract_name: &state.smt_incl_proof_contract_name,
+                blobs: &built.transaction.blobs,
+            },
+        )
+        .await?;
+    }
+
+    // Submit blob transaction, unless a concurrent request spent the same
+    // notes while the proofs were checked
+    let tx_hash_hex = hex::encode(&tx_hash.0);
+    let reservation = state
+        .nullifier_guard
+        .reserve(&tx_hash_hex, &nullifiers, current_timestamp())
+        .await?;
+
+    // A server-generated SMT proof is proven before the blob is sent, which
+    // would otherwise stay on chain unproven if proving failed
+    let smt_proof_with_inputs = match client_smt_proof {
+        Some(client_proof) => client_proof,
+        None => {
+            let witness = smt_witness.expect("smt_witness must be Some when smt_proof is None");
+            prove_smt_incl(&state, &tx_hash, witness).await?
+        }
+    };
+
     state
         .client
         .send_tx_blob(built.transaction)
         .await
-        .map_err(|e| ApiError::internal(format!("failed to send blob tx: {}", e)))?;
+        .map_err(|e| {
+            state.tx_tracker.record_submission_failed(
+                &tx_hash_hex,
+                TxKind::Transfer,
+                format!("submitting blob transaction: {e:#}"),
+                current_timestamp(),
+            );
+            ApiError::internal(format!("failed to send blob tx: {}", e))
+        })?;
 
     tracing::info!(%tx_hash, "Submitted blob transaction (finalize_transfer)");
-
-    // ---- hyli_utxo proof ----
-    let proof_bytes = base64_decode(&proof)
-        .map_err(|e| ApiError::bad_request(format!("invalid base64 proof: {}", e)))?;
-
-    let public_inputs_bytes: Vec<u8> = public_inputs
-        .iter()
-        .flat_map(|hex_str| {
-            let normalized = hex_str.strip_prefix("0x").unwrap_or(hex_str);
-            hex::decode(normalized).unwrap_or_else(|_| vec![0u8; 32])
-        })
-        .collect();
-
-    let mut proof_with_inputs = public_inputs_bytes;
-    proof_with_inputs.extend_from_slice(&proof_bytes);
+    state
+        .tx_tracker
+        .record_submitted(&tx_hash_hex, TxKind::Transfer, None, current_timestamp());
 
     state
         .client
@@ -668,48 +939,7 @@ async fn finalize_transfer(
             proof: ProofData(proof_with_inputs),
         })
         .await
-        .map_err(|e| ApiError::internal(format!("failed to send utxo proof tx: {}", e)))?;
-
-    // ---- hyli_smt_incl_proof proof ----
-    let smt_proof_with_inputs = match smt_proof {
-        Some(client_proof) => {
-            // Client-provided proof
-            let smt_proof_bytes = base64_decode(&client_proof)
-                .map_err(|e| ApiError::bad_request(format!("invalid base64 smt_proof: {}", e)))?;
-            let smt_pubs = smt_public_inputs.ok_or_else(|| {
-                ApiError::bad_request(
-                    "smt_public_inputs is required when smt_proof is provided".to_string(),
-                )
-            })?;
-            let smt_public_inputs_bytes: Vec<u8> = smt_pubs
-                .iter()
-                .flat_map(|hex_str| {
-                    let normalized = hex_str.strip_prefix("0x").unwrap_or(hex_str);
-                    hex::decode(normalized).unwrap_or_else(|_| vec![0u8; 32])
-                })
-                .collect();
-            let mut combined = smt_public_inputs_bytes;
-            combined.extend_from_slice(&smt_proof_bytes);
-            combined
-        }
-        None => {
-            // Server-side proof generation
-            let (blob_payload, identity, tx_blob_count, smt_blob_index) =
-                smt_blob_info.expect("smt_blob_info must be Some when smt_proof is None");
-            generate_smt_proof(
-                &state,
-                &tx_hash,
-                blob_payload,
-                identity,
-                tx_blob_count,
-                smt_blob_index,
-                input_notes,
-                siblings_0,
-                siblings_1,
-            )
-            .await?
-        }
-    };
+        .map_err(|e| proof_submission_failed(&state, &tx_hash_hex, "utxo", e))?;
 
     state
         .client
@@ -720,30 +950,71 @@ async fn finalize_transfer(
             proof: ProofData(smt_proof_with_inputs),
         })
         .await
-        .map_err(|e| ApiError::internal(format!("failed to send smt proof tx: {}", e)))?;
+        .map_err(|e| proof_submission_failed(&state, &tx_hash_hex, "smt", e))?;
+    reservation.keep();
 
     tracing::info!(%tx_hash, "Submitted proof transactions (finalize_transfer)");
+    state
+        .tx_tracker
+        .record_proof_submitted(&tx_hash_hex, current_timestamp());
 
     Ok(Json(FinalizeTransferResponse { tx_hash }))
 }
 
+/// Checks that both circuits proven for a mint or deposit can take a job.
+fn check_proving_capacity(
+    scheduler: &ProvingScheduler,
+    priority: ProofPriority,
+) -> Result<(), ProverBusy> {
+    scheduler.check_capacity(Circuit::HyliUtxo, priority)?;
+    scheduler.check_capacity(Circuit::SmtIncl, priority)
+}
+
+/// A failed read or write of the note store or address registry.
+fn storage_failed(err: anyhow::Error) -> ApiError {
+    ApiError::internal(format!("storage failed: {err:#}"))
+}
+
+/// Records a failed proof submission for `tx_hash` and builds the API error.
+fn proof_submission_failed(
+    state: &RouterCtx,
+    tx_hash: &str,
+    proof: &str,
+    err: anyhow::Error,
+) -> ApiError {
+    let message = format!("failed to send {proof} proof tx: {err}");
+    state
+        .tx_tracker
+        .record_proof_failed(tx_hash, message.clone(), current_timestamp());
+    ApiError::internal(message)
+}
+
+/// Lifecycle of a transaction submitted by this server, looked up by tx hash
+/// or by the `tracking_id` returned from `/api/faucet` and `/api/deposit`.
+async fn tx_status(
+    State(state): State<RouterCtx>,
+    Path(hash): Path<String>,
+) -> Result<Json<TxRecord>, ApiError> {
+    let hash = hash.trim();
+    let id = hash.strip_prefix("0x").unwrap_or(hash).to_lowercase();
+    state
+        .tx_tracker
+        .get(&id, current_timestamp())
+        .map(Json)
+        .ok_or_else(|| ApiError::not_found(format!("transaction {id} is not tracked")))
+}
+
 /// Compute the 96-byte `smt_blob_data` from input notes and notes root.
 ///
 /// Layout: [nullifier_0 (32B)][nullifier_1 (32B)][notes_root (32B)]
-/// Nullifiers are computed as `hash_merge([psi, secret_key])`.
+/// Nullifiers are computed as `hash_merge([commitment, nullifier_key])`.
 fn compute_smt_blob_data(
     input_notes: &[InputNoteData; 2],
     notes_root_hex: &str,
 ) -> Result<Vec<u8>, ApiError> {
-    use std::str::FromStr;
-
-    let sk0 = element::Element::from_str(&input_notes[0].secret_key)
-        .map_err(|e| ApiError::bad_request(format!("invalid secret_key[0]: {e}")))?;
-    let sk1 = element::Element::from_str(&input_notes[1].secret_key)
-        .map_err(|e| ApiError::bad_request(format!("invalid secret_key[1]: {e}")))?;
-
-    let nullifier_0 = hash::hash_merge([input_notes[0].note.psi, sk0]);
-    let nullifier_1 = hash::hash_merge([input_notes[1].note.psi, sk1]);
+    let [input_0, input_1] = parse_inclusion_notes(input_notes)?;
+    let nullifier_0 = input_0.nullifier();
+    let nullifier_1 = input_1.nullifier();
 
     let root_normalized = notes_root_hex.strip_prefix("0x").unwrap_or(notes_root_hex);
     let root_bytes = hex::decode(root_normalized)
@@ -763,12 +1034,25 @@ fn compute_smt_blob_data(
     Ok(smt_blob)
 }
 
-/// Generate the SMT inclusion proof server-side from input notes and siblings.
-///
-/// Builds a [`HyliSmtIncl`] witness and calls the Barretenberg prover.
-/// Returns the proof bytes (public_inputs ++ raw_proof) ready for `ProofData`.
-async fn generate_smt_proof(
-    state: &RouterCtx,
+/// Parse client-supplied input notes into the witness notes of the inclusion proof.
+fn parse_inclusion_notes(input_notes: &[InputNoteData; 2]) -> Result<[InclusionNote; 2], ApiError> {
+    use std::str::FromStr;
+
+    let parse = |index: usize| -> Result<InclusionNote, ApiError> {
+        let input = &input_notes[index];
+        let nullifier_key = element::Element::from_str(&input.nullifier_key)
+            .map_err(|e| ApiError::bad_request(format!("invalid nullifier_key[{index}]: {e}")))?;
+        Ok(InclusionNote::new(input.note.clone(), nullifier_key))
+    };
+
+    Ok([parse(0)?, parse(1)?])
+}
+
+/// Builds the [`HyliSmtIncl`] witness of a server-generated SMT inclusion
+/// proof from the input notes and siblings of the request.
+#[allow(clippy::too_many_arguments)]
+fn smt_proof_witness(
+    contract_name: &str,
     tx_hash: &TxHash,
     blob_payload: [u8; HYLI_SMT_INCL_BLOB_LENGTH_BYTES],
     identity: Identity,
@@ -777,10 +1061,8 @@ async fn generate_smt_proof(
     input_notes: Option<[InputNoteData; 2]>,
     siblings_0: Option<Vec<String>>,
     siblings_1: Option<Vec<String>>,
-) -> Result<Vec<u8>, ApiError> {
+) -> Result<HyliSmtIncl, ApiError> {
     use acvm::AcirField;
-    use barretenberg::Prove;
-    use std::str::FromStr;
 
     let input_notes = input_notes.ok_or_else(|| {
         ApiError::bad_request("input_notes is required when smt_proof is not provided".to_string())
@@ -805,19 +1087,7 @@ async fn generate_smt_proof(
         )));
     }
 
-    // Convert InputNoteData to zk_primitives::InputNote
-    let zk_input_notes: [InputNote; 2] = [
-        InputNote::new(
-            input_notes[0].note.clone(),
-            element::Element::from_str(&input_notes[0].secret_key)
-                .map_err(|e| ApiError::bad_request(format!("invalid secret_key[0]: {e}")))?,
-        ),
-        InputNote::new(
-            input_notes[1].note.clone(),
-            element::Element::from_str(&input_notes[1].secret_key)
-                .map_err(|e| ApiError::bad_request(format!("invalid secret_key[1]: {e}")))?,
-        ),
-    ];
+    let zk_input_notes = parse_inclusion_notes(&input_notes)?;
 
     // Convert hex sibling strings to element::Base arrays
     let parse_siblings = |hex_strs: &[String]| -> Result<Box<[element::Base; 256]>, ApiError> {
@@ -842,13 +1112,27 @@ async fn generate_smt_proof(
         input_notes: zk_input_notes,
         siblings_0: sib_0,
         siblings_1: sib_1,
+        priority: ProofPriority::User,
     };
 
-    let contract_name = state.smt_incl_proof_contract_name.clone();
-    let hyli_smt_incl = HyliSmtInclNoirProver::build_hyli_smt_incl(&contract_name, &job)
-        .map_err(|e| ApiError::internal(format!("building HyliSmtIncl witness: {e}")))?;
+    HyliSmtInclNoirProver::build_hyli_smt_incl(contract_name, &job)
+        .map_err(|e| ApiError::internal(format!("building HyliSmtIncl witness: {e}")))
+}
+
+/// Proves a witness built by [`smt_proof_witness`] with the Barretenberg prover.
+/// Returns the proof bytes (public_inputs ++ raw_proof) ready for `ProofData`.
+async fn prove_smt_incl(
+    state: &RouterCtx,
+    tx_hash: &TxHash,
+    hyli_smt_incl: HyliSmtIncl,
+) -> Result<Vec<u8>, ApiError> {
+    use barretenberg::Prove;
 
     // Proof generation is CPU-intensive; run in a blocking thread
+    let permit = state
+        .proving_scheduler
+        .acquire(Circuit::SmtIncl, ProofPriority::User)
+        .await;
     let proof = tokio::task::spawn_blocking(move || {
         hyli_smt_incl
             .prove()
@@ -857,12 +1141,28 @@ async fn generate_smt_proof(
     .await
     .map_err(|e| ApiError::internal(format!("proof task panicked: {e}")))?
     .map_err(ApiError::internal)?;
+    drop(permit);
 
     tracing::info!(%tx_hash, "Server-generated hyli_smt_incl_proof");
 
     Ok(proof.to_bytes())
 }
 
+/// Decodes the base64 `field` of a client proof and prepends its public
+/// inputs, as the node verifies it.
+fn client_proof_with_inputs(
+    circuit: Circuit,
+    field: &str,
+    proof: &str,
+    public_inputs: &[String],
+) -> Result<Vec<u8>, ApiError> {
+    let proof_bytes = base64_decode(proof)
+        .map_err(|e| ApiError::bad_request(format!("invalid base64 {field}: {e}")))?;
+    let mut proof_with_inputs = decode_public_inputs(circuit, public_inputs)?;
+    proof_with_inputs.extend_from_slice(&proof_bytes);
+    Ok(proof_with_inputs)
+}
+
 /// Decode base64 string to bytes
 fn base64_decode(input: &str) -> Result<Vec<u8>, String> {
     use base64::prelude::*;
@@ -962,12 +1262,18 @@ async fn upload_note(
         .unwrap_or(&request.ephemeral_pubkey)
         .to_lowercase();
 
-    let (id, stored_at) = state.note_store.insert(
-        recipient_tag,
-        request.encrypted_payload,
-        ephemeral_pubkey,
-        sender_tag,
-    );
+    let note_store = state.note_store.clone();
+    let (id, stored_at) = blocking(move || {
+        note_store.insert(
+            recipient_tag,
+            request.encrypted_payload,
+            ephemeral_pubkey,
+            sender_tag,
+        )
+    })
+    .await
+    .map_err(storage_failed)?
+    .map_err(storage_failed)?;
 
     Ok(Json(UploadNoteResponse { id, stored_at }))
 }
@@ -984,20 +1290,14 @@ async fn get_notes(
         .unwrap_or(&recipient_tag)
         .to_lowercase();
 
-    let (notes, has_more) = state
-        .note_store
-        .get_notes(&normalized_tag, query.since, query.limit);
-
-    let records: Vec<EncryptedNoteRecord> = notes
-        .into_iter()
-        .map(|n| EncryptedNoteRecord {
-            id: n.id,
-            encrypted_payload: n.encrypted_payload,
-            ephemeral_pubkey: n.ephemeral_pubkey,
-            sender_tag: n.sender_tag,
-            stored_at: n.stored_at,
-        })
-        .collect();
+    let note_store = state.note_store.clone();
+    let (notes, has_more) =
+        blocking(move || note_store.get_notes(&normalized_tag, query.since, query.limit))
+            .await
+            .map_err(storage_failed)?
+            .map_err(storage_failed)?;
+
+    let records: Vec<EncryptedNoteRecord> = notes.into_iter().map(Into::into).collect();
 
     Ok(Json(GetNotesResponse {
         notes: records,
@@ -1005,9 +1305,81 @@ async fn get_notes(
     }))
 }
 
+/// Streams notes stored for one or more recipient tags as Server-Sent Events.
+/// See [`NoteStreamQuery`] for the event format and resumption.
+async fn stream_notes(
+    State(state): State<RouterCtx>,
+    headers: HeaderMap,
+    Query(query): Query<NoteStreamQuery>,
+) -> Result<Sse<impl Stream<Item = Result<Event, axum::Error>>>, ApiError> {
+    let mut tags = Vec::new();
+    for tag in query.tags.split(',').map(str::trim) {
+        validate_tag(tag, "tags")?;
+        let normalized = tag.strip_prefix("0x").unwrap_or(tag).to_lowercase();
+        if !tags.contains(&normalized) {
+            tags.push(normalized);
+        }
+    }
+    if tags.len() > MAX_NOTE_STREAM_TAGS {
+        return Err(ApiError::bad_request(format!(
+            "at most {MAX_NOTE_STREAM_TAGS} tags can be streamed at once"
+        )));
+    }
+
+    // EventSource sends the last event id on reconnect
+    let cursor = headers
+        .get("last-event-id")
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+        .or(query.cursor);
+    if let Some(cursor) = &cursor {
+        if cursor.len() != 24 || !cursor.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(ApiError::bad_request(
+                "cursor must be the id of a note stream event",
+            ));
+        }
+    }
+
+    let events = note_events(
+        state.note_store.clone(),
+        tags,
+        cursor.map(|cursor| cursor.to_lowercase()),
+        state.stream_heartbeat,
+    )
+    .await
+    .map_err(storage_failed)?;
+    Ok(Sse::new(events))
+}
+
+/// See [`StateStreamQuery`] for the event format and resumption.
+async fn stream_state(
+    State(state): State<RouterCtx>,
+    headers: HeaderMap,
+    Query(query): Query<StateStreamQuery>,
+) -> Result<Sse<impl Stream<Item = Result<Event, axum::Error>>>, ApiError> {
+    // EventSource sends the last event id on reconnect
+    let cursor = match headers.get("last-event-id") {
+        Some(value) => Some(
+            value
+                .to_str()
+                .ok()
+                .and_then(|value| value.parse::<u64>().ok())
+                .ok_or_else(|| ApiError::bad_request("cursor must be a sequence number"))?,
+        ),
+        None => query.cursor,
+    };
+
+    Ok(Sse::new(transition_events(
+        state.state_feed.clone(),
+        cursor,
+        state.stream_heartbeat,
+    )))
+}
+
 async fn delete_note(
     State(state): State<RouterCtx>,
     Path((recipient_tag, note_id)): Path<(String, String)>,
+    Query(query): Query<DeleteNoteQuery>,
 ) -> Result<StatusCode, ApiError> {
     validate_tag(&recipient_tag, "recipient_tag")?;
 
@@ -1016,7 +1388,26 @@ async fn delete_note(
         .unwrap_or(&recipient_tag)
         .to_lowercase();
 
-    if state.note_store.delete_note(&normalized_tag, &note_id) {
+    let authorizer = state.request_authorizer.clone();
+    let address_registry = state.address_registry.clone();
+    let note_store = state.note_store.clone();
+    let deleted = blocking(move || -> Result<bool, ApiError> {
+        authorizer.authorize_note_deletion(
+            &address_registry,
+            &normalized_tag,
+            &note_id,
+            query.timestamp,
+            query.signature.as_deref(),
+            current_timestamp(),
+        )?;
+        note_store
+            .delete_note(&normalized_tag, &note_id)
+            .map_err(storage_failed)
+    })
+    .await
+    .map_err(storage_failed)??;
+
+    if deleted {
         Ok(StatusCode::NO_CONTENT)
     } else {
         Err(ApiError::not_found("Note not found"))
@@ -1112,17 +1503,35 @@ async fn register_address(
         .unwrap_or(&request.utxo_address)
         .to_lowercase();
 
-    let previous = state.address_registry.register(
-        request.username.clone(),
-        utxo_address.clone(),
-        encryption_pubkey.clone(),
-    );
-
-    // Get the registration we just made
-    let registration = state
-        .address_registry
-        .resolve(&request.username)
-        .ok_or_else(|| ApiError::internal("Failed to retrieve registration after insert"))?;
+    let username = request.username.to_lowercase();
+    let authorizer = state.request_authorizer.clone();
+    let address_registry = state.address_registry.clone();
+    let (previous, registration) = blocking(move || -> Result<_, ApiError> {
+        let proof = authorizer.authorize_registration(
+            address_registry
+                .resolve(&username)
+                .map_err(storage_failed)?
+                .as_ref(),
+            &username,
+            &utxo_address,
+            &encryption_pubkey,
+            request.timestamp,
+            request.signature.as_deref(),
+            current_timestamp(),
+        )?;
+
+        let previous =
+            address_registry.register(username, utxo_address, encryption_pubkey, proof)?;
+
+        // Get the registration we just made
+        let registration = address_registry
+            .resolve(&request.username)
+            .map_err(storage_failed)?
+            .ok_or_else(|| ApiError::internal("Failed to retrieve registration after insert"))?;
+        Ok((previous, registration))
+    })
+    .await
+    .map_err(storage_failed)??;
 
     Ok(Json(RegisterAddressResponse {
         username: registration.username,
@@ -1139,9 +1548,12 @@ async fn resolve_address(
 ) -> Result<Json<ResolveAddressResponse>, ApiError> {
     validate_username(&username)?;
 
-    let registration = state
-        .address_registry
-        .resolve(&username)
+    let address_registry = state.address_registry.clone();
+    let lookup = username.clone();
+    let registration = blocking(move || address_registry.resolve(&lookup))
+        .await
+        .map_err(storage_failed)?
+        .map_err(storage_failed)?
         .ok_or_else(|| ApiError::not_found(format!("Username '{}' not registered", username)))?;
 
     Ok(Json(ResolveAddressResponse {
@@ -1152,56 +1564,209 @@ async fn resolve_address(
     }))
 }
 
+async fn release_address(
+    State(state): State<RouterCtx>,
+    Path(username): Path<String>,
+    Query(query): Query<ReleaseAddressQuery>,
+) -> Result<StatusCode, ApiError> {
+    validate_username(&username)?;
+
+    let authorizer = state.request_authorizer.clone();
+    let address_registry = state.address_registry.clone();
+    blocking(move || -> Result<(), ApiError> {
+        let registration = address_registry
+            .resolve(&username)
+            .map_err(storage_failed)?
+            .ok_or_else(|| {
+                ApiError::not_found(format!("Username '{}' not registered", username))
+            })?;
+        let owner_pubkey = authorizer.authorize_release(
+            &registration,
+            query.timestamp,
+            query.signature.as_deref(),
+            current_timestamp(),
+        )?;
+        address_registry.release(&username, &owner_pubkey)?;
+        Ok(())
+    })
+    .await
+    .map_err(storage_failed)??;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
 #[derive(Debug)]
 struct ApiError {
     status: StatusCode,
+    code: ErrorCode,
     message: String,
+    /// Sent as `Retry-After` when set
+    retry_after_secs: Option<u64>,
 }
 
 impl ApiError {
-    fn bad_request(message: impl Into<String>) -> Self {
+    fn new(status: StatusCode, code: ErrorCode, message: impl Into<String>) -> Self {
         Self {
-            status: StatusCode::BAD_REQUEST,
+            status,
+            code,
             message: message.into(),
+            retry_after_secs: None,
         }
     }
 
+    fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, ErrorCode::BadRequest, message)
+    }
+
     fn internal(message: impl Into<String>) -> Self {
-        Self {
-            status: StatusCode::INTERNAL_SERVER_ERROR,
-            message: message.into(),
-        }
+        Self::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            ErrorCode::Internal,
+            message,
+        )
     }
 
     fn not_found(message: impl Into<String>) -> Self {
-        Self {
-            status: StatusCode::NOT_FOUND,
-            message: message.into(),
-        }
+        Self::new(StatusCode::NOT_FOUND, ErrorCode::NotFound, message)
     }
 
     fn payload_too_large(message: impl Into<String>) -> Self {
+        Self::new(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            ErrorCode::PayloadTooLarge,
+            message,
+        )
+    }
+}
+
+impl From<RequestAuthError> for ApiError {
+    fn from(err: RequestAuthError) -> Self {
+        let (status, code) = match err {
+            RequestAuthError::MissingSignature | RequestAuthError::MalformedSignature(_) => {
+                (StatusCode::UNAUTHORIZED, ErrorCode::Unauthorized)
+            }
+            RequestAuthError::StaleTimestamp { .. }
+            | RequestAuthError::UnknownRecipient
+            | RequestAuthError::ConflictingOwners
+            | RequestAuthError::InvalidSignature
+            | RequestAuthError::NotOwner
+            | RequestAuthError::Replayed => (StatusCode::FORBIDDEN, ErrorCode::Forbidden),
+            RequestAuthError::Registry(_) | RequestAuthError::Storage(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, ErrorCode::Internal)
+            }
+        };
+        Self::new(status, code, err.to_string())
+    }
+}
+
+impl From<PowError> for ApiError {
+    fn from(err: PowError) -> Self {
+        let status = match err {
+            PowError::Missing => StatusCode::BAD_REQUEST,
+            _ => StatusCode::FORBIDDEN,
+        };
+        Self::new(status, ErrorCode::InvalidPow, err.to_string())
+    }
+}
+
+impl From<QuotaError> for ApiError {
+    fn from(err: QuotaError) -> Self {
+        let (status, code) = match err {
+            QuotaError::AmountTooLarge { .. } | QuotaError::MissingClientIp { .. } => {
+                (StatusCode::BAD_REQUEST, ErrorCode::BadRequest)
+            }
+            _ => (StatusCode::TOO_MANY_REQUESTS, ErrorCode::QuotaExceeded),
+        };
+        Self::new(status, code, err.to_string())
+    }
+}
+
+impl From<NullifierError> for ApiError {
+    fn from(err: NullifierError) -> Self {
+        let code = match err {
+            NullifierError::InFlight { .. } => ErrorCode::NullifierInFlight,
+            NullifierError::Spent { .. } => ErrorCode::NullifierSpent,
+            NullifierError::Storage(_) => return Self::internal(err.to_string()),
+        };
+        Self::new(StatusCode::CONFLICT, code, err.to_string())
+    }
+}
+
+impl From<ProverBusy> for ApiError {
+    fn from(err: ProverBusy) -> Self {
         Self {
-            status: StatusCode::PAYLOAD_TOO_LARGE,
-            message: message.into(),
+            retry_after_secs: Some(err.retry_after_secs),
+            ..Self::new(
+                StatusCode::SERVICE_UNAVAILABLE,
+                ErrorCode::ProverBusy,
+                err.to_string(),
+            )
         }
     }
 }
 
+impl From<ProofCheckError> for ApiError {
+    fn from(err: ProofCheckError) -> Self {
+        tracing::warn!(error = %err, "Rejected client proof");
+        let code = match err {
+            ProofCheckError::PublicInputCount { .. }
+            | ProofCheckError::InvalidPublicInput { .. } => ErrorCode::InvalidPublicInput,
+            ProofCheckError::Malformed { .. } | ProofCheckError::Invalid { .. } => {
+                ErrorCode::InvalidProof
+            }
+            ProofCheckError::TxHashMismatch { .. }
+            | ProofCheckError::IdentityMismatch { .. }
+            | ProofCheckError::BlobMismatch { .. }
+            | ProofCheckError::NotSuccessful { .. } => ErrorCode::ProofMismatch,
+        };
+        Self::new(StatusCode::BAD_REQUEST, code, err.to_string())
+    }
+}
+
+impl From<RegistrationError> for ApiError {
+    fn from(err: RegistrationError) -> Self {
+        let (status, code) = match err {
+            RegistrationError::NotRegistered(_) => (StatusCode::NOT_FOUND, ErrorCode::NotFound),
+            RegistrationError::NotOwner(_) | RegistrationError::AddressTaken(_) => {
+                (StatusCode::FORBIDDEN, ErrorCode::Forbidden)
+            }
+            RegistrationError::Storage(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, ErrorCode::Internal)
+            }
+        };
+        Self::new(status, code, err.to_string())
+    }
+}
+
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
-        let body = Json(json!({ "error": self.message }));
-        (self.status, body).into_response()
+        let body = Json(ErrorResponse {
+            error: self.message,
+            code: self.code,
+        });
+        let mut response = (self.status, body).into_response();
+        if let Some(retry_after_secs) = self.retry_after_secs {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(retry_after_secs));
+        }
+        response
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use sdk::BlobIndex;
-
-    use crate::{api::withdraw_topology, types::TokenTransferRequest};
+    use axum::{http::StatusCode, response::IntoResponse};
+    use sdk::{BlobIndex, Identity, TxHash};
+    use zk_primitives::{Note, HYLI_SMT_INCL_BLOB_LENGTH_BYTES, HYLI_UTXO_PUBLIC_INPUTS_COUNT};
+
+    use crate::{
+        api::withdraw_topology,
+        proving_scheduler::Circuit,
+        types::{ErrorCode, ErrorResponse, InputNoteData, TokenTransferRequest},
+    };
 
-    use super::normalize_encryption_pubkey;
+    use super::{client_proof_with_inputs, normalize_encryption_pubkey, smt_proof_witness};
 
     #[test]
     fn normalizes_x_coordinate_pubkey() {
@@ -1246,6 +1811,52 @@ mod tests {
         assert_eq!(err.message, "encryption_pubkey must be valid hexadecimal");
     }
 
+    #[tokio::test]
+    async fn rejects_a_proof_with_too_few_public_inputs() {
+        let public_inputs = vec![format!("{:064x}", 1); HYLI_UTXO_PUBLIC_INPUTS_COUNT - 1];
+        let response = client_proof_with_inputs(Circuit::HyliUtxo, "proof", "AAAA", &public_inputs)
+            .unwrap_err()
+            .into_response();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: ErrorResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body.code, ErrorCode::InvalidPublicInput);
+        assert!(body.error.contains("public inputs"), "{}", body.error);
+    }
+
+    #[test]
+    fn rejects_a_malformed_sibling_before_proving() {
+        let input_note = InputNoteData {
+            note: Note::padding_note(),
+            nullifier_key: format!("{:064x}", 1),
+        };
+        let mut siblings = vec![format!("{:064x}", 0); 256];
+        siblings[17] = "zz".to_string();
+
+        let err = smt_proof_witness(
+            "hyli_smt_incl_proof",
+            &TxHash(vec![7; 32]),
+            [0; HYLI_SMT_INCL_BLOB_LENGTH_BYTES],
+            Identity("transfer@hyli_utxo".to_string()),
+            3,
+            2,
+            Some([input_note.clone(), input_note]),
+            Some(vec![format!("{:064x}", 0); 256]),
+            Some(siblings),
+        )
+        .unwrap_err();
+
+        assert_eq!(err.status, StatusCode::BAD_REQUEST);
+        assert!(
+            err.message.contains("invalid sibling hex [17]"),
+            "{}",
+            err.message
+        );
+    }
+
     #[test]
     fn withdraw_topology_adds_token_callee_and_state_caller() {
         let token_transfer = TokenTransferRequest {
@@ -1,8 +1,10 @@
+use std::sync::Arc;
+
 use acvm::AcirField;
 use anyhow::{bail, Context, Result};
+use cachecash_client::types::TxKind;
 use client_sdk::rest_client::{NodeApiClient, NodeApiHttpClient};
 use element::Element;
-use hash::hash_merge;
 use hex::encode as hex_encode;
 use hyli_modules::{
     bus::{BusClientSender, BusMessage, SharedMessageBus},
@@ -12,18 +14,25 @@ use hyli_modules::{
 use hyli_smt_token::SmtTokenAction;
 use hyli_utxo_state::{state::HYLI_UTXO_STATE_ACTION, zk::BorshableH256};
 use sdk::{
-    Blob, BlobData, BlobIndex, BlobTransaction, ContractAction, ContractName, Identity, ProgramId,
-    ProofData, ProofTransaction, StructuredBlobData, TxHash, Verifier,
+    Blob, BlobData, BlobIndex, BlobTransaction, ContractAction, ContractName, Hashed, Identity,
+    ProgramId, ProofData, ProofTransaction, StructuredBlobData, TxHash, Verifier,
 };
 use tracing::{info, warn};
 use zk_primitives::{
-    InputNote, Note, Utxo, HYLI_BLOB_HASH_BYTE_LENGTH, HYLI_BLOB_LENGTH_BYTES,
+    InclusionNote, InputNote, Note, Utxo, HYLI_BLOB_HASH_BYTE_LENGTH, HYLI_BLOB_LENGTH_BYTES,
     HYLI_SMT_INCL_BLOB_LENGTH_BYTES, HYLI_SMT_INCL_PAYLOAD_LENGTH_BYTES,
 };
 
 use crate::{
-    hyli_utxo_state_client::HyliUtxoStateEvent, init::HYLI_UTXO_NOIR_VK,
-    noir_prover::HyliUtxoProofJob, smt_incl_prover::SmtInclProofJob, tx::FAUCET_IDENTITY_PREFIX,
+    hyli_utxo_state_client::HyliUtxoStateEvent,
+    init::HYLI_UTXO_NOIR_VK,
+    noir_prover::HyliUtxoProofJob,
+    note_store::current_timestamp,
+    proof_verification::decode_public_inputs,
+    proving_scheduler::{Circuit, ProofPriority},
+    smt_incl_prover::SmtInclProofJob,
+    tx::FAUCET_IDENTITY_PREFIX,
+    tx_tracker::TxTracker,
 };
 
 pub const FAUCET_MINT_AMOUNT: u64 = 10;
@@ -33,6 +42,8 @@ pub struct FaucetMintCommand {
     pub recipient_pubkey: Vec<u8>,
     pub amount: u64,
     pub note: Note,
+    /// Id the mint was queued under in the [`TxTracker`]
+    pub tracking_id: String,
 }
 
 impl BusMessage for FaucetMintCommand {}
@@ -46,6 +57,8 @@ pub struct FaucetDepositCommand {
     pub wallet_account: Option<String>,
     pub secp256k1_blob: Option<Vec<u8>>,
     pub wallet_blob: Option<Vec<u8>>,
+    /// Id the deposit was queued under in the [`TxTracker`]
+    pub tracking_id: String,
 }
 
 impl BusMessage for FaucetDepositCommand {}
@@ -63,7 +76,7 @@ impl BusMessage for TransferCommand {}
 pub struct TransferWithProofCommand {
     /// Raw proof bytes (without public inputs)
     pub proof: Vec<u8>,
-    /// Public inputs as hex strings (733 field elements)
+    /// Public inputs as hex strings (713 field elements)
     pub public_inputs: Vec<String>,
     /// 128-byte blob data
     pub blob: [u8; 128],
@@ -96,6 +109,7 @@ pub struct FaucetAppContext {
     pub utxo_contract_name: String,
     pub utxo_state_contract_name: String,
     pub incl_proof_contract_name: String,
+    pub tx_tracker: Arc<TxTracker>,
 }
 
 pub struct FaucetApp {
@@ -107,6 +121,7 @@ pub struct FaucetApp {
     utxo_contract_name: String,
     utxo_state_contract_name: String,
     incl_proof_contract_name: String,
+    tx_tracker: Arc<TxTracker>,
 }
 
 impl Module for FaucetApp {
@@ -124,6 +139,7 @@ impl Module for FaucetApp {
             utxo_contract_name: ctx.utxo_contract_name,
             utxo_state_contract_name: ctx.utxo_state_contract_name,
             incl_proof_contract_name: ctx.incl_proof_contract_name,
+            tx_tracker: ctx.tx_tracker,
         })
     }
 
@@ -158,18 +174,25 @@ impl FaucetApp {
             self.build_transaction(&request.note, None, None, None, None)?;
 
         let tx_hash = self
-            .client
-            .send_tx_blob(blob_transaction.clone())
+            .submit_tracked(
+                blob_transaction.clone(),
+                TxKind::Faucet,
+                Some(&request.tracking_id),
+            )
             .await
             .context("dispatching blob transaction")?;
 
         info!(%tx_hash, "Submitted hyli_utxo faucet transaction");
 
-        if let Err(err) = self.enqueue_proof_job(&blob_transaction, &tx_hash, utxo) {
+        if let Err(err) =
+            self.enqueue_proof_job(&blob_transaction, &tx_hash, utxo, ProofPriority::Faucet)
+        {
             warn!(error = %err, "failed to enqueue Noir proof job");
         }
 
-        if let Err(err) = self.enqueue_smt_incl_proof_job(&blob_transaction, &tx_hash) {
+        if let Err(err) =
+            self.enqueue_smt_incl_proof_job(&blob_transaction, &tx_hash, ProofPriority::Faucet)
+        {
             warn!(error = %err, "failed to enqueue SMT incl proof job");
         }
 
@@ -186,24 +209,62 @@ impl FaucetApp {
         )?;
 
         let tx_hash = self
-            .client
-            .send_tx_blob(blob_transaction.clone())
+            .submit_tracked(
+                blob_transaction.clone(),
+                TxKind::Deposit,
+                Some(&request.tracking_id),
+            )
             .await
             .context("dispatching blob transaction")?;
 
         info!(%tx_hash, "Submitted hyli_utxo deposit transaction");
 
-        if let Err(err) = self.enqueue_proof_job(&blob_transaction, &tx_hash, utxo) {
+        if let Err(err) =
+            self.enqueue_proof_job(&blob_transaction, &tx_hash, utxo, ProofPriority::User)
+        {
             warn!(error = %err, "failed to enqueue Noir proof job");
         }
 
-        if let Err(err) = self.enqueue_smt_incl_proof_job(&blob_transaction, &tx_hash) {
+        if let Err(err) =
+            self.enqueue_smt_incl_proof_job(&blob_transaction, &tx_hash, ProofPriority::User)
+        {
             warn!(error = %err, "failed to enqueue SMT incl proof job");
         }
 
         Ok(())
     }
 
+    /// Submits `blob_tx` to the node and records it in the [`TxTracker`],
+    /// including when the node rejects it.
+    async fn submit_tracked(
+        &self,
+        blob_tx: BlobTransaction,
+        kind: TxKind,
+        tracking_id: Option<&str>,
+    ) -> Result<TxHash> {
+        let expected_hash = hex_encode(&blob_tx.hashed().0);
+        match self.client.send_tx_blob(blob_tx).await {
+            Ok(tx_hash) => {
+                self.tx_tracker.record_submitted(
+                    &hex_encode(&tx_hash.0),
+                    kind,
+                    tracking_id,
+                    current_timestamp(),
+                );
+                Ok(tx_hash)
+            }
+            Err(err) => {
+                self.tx_tracker.record_submission_failed(
+                    tracking_id.unwrap_or(&expected_hash),
+                    kind,
+                    format!("submitting blob transaction: {err:#}"),
+                    current_timestamp(),
+                );
+                Err(err)
+            }
+        }
+    }
+
     fn build_transaction(
         &mut self,
         note: &Note,
@@ -236,7 +297,7 @@ impl FaucetApp {
 
         let mut nullifier_index = 2;
         for input in utxo.input_notes.iter() {
-            let nullifier = hash_merge([input.note.psi, input.secret_key]);
+            let nullifier = input.nullifier();
 
             blob_bytes[offset..offset + HYLI_BLOB_HASH_BYTE_LENGTH]
                 .copy_from_slice(&nullifier.to_be_bytes());
@@ -319,6 +380,7 @@ impl FaucetApp {
         blob_tx: &BlobTransaction,
         tx_hash: &TxHash,
         utxo: Utxo,
+        priority: ProofPriority,
     ) -> Result<()> {
         let Some((blob_index, blob)) = blob_tx
             .blobs
@@ -347,6 +409,7 @@ impl FaucetApp {
             blob: payload,
             tx_blob_count: blob_tx.blobs.len() as u32,
             blob_index: blob_index as u32,
+            priority,
         };
 
         self.bus
@@ -360,6 +423,7 @@ impl FaucetApp {
         &mut self,
         blob_tx: &BlobTransaction,
         tx_hash: &TxHash,
+        priority: ProofPriority,
     ) -> Result<()> {
         let Some((blob_index, blob)) = blob_tx
             .blobs
@@ -393,9 +457,10 @@ impl FaucetApp {
             blob_index: blob_index as u32,
             // Server auto-prover doesn't have access to private note data;
             // padding notes are used as placeholders (proof generation will fail for real transfers).
-            input_notes: [InputNote::padding_note(), InputNote::padding_note()],
+            input_notes: [InclusionNote::default(), InclusionNote::default()],
             siblings_0,
             siblings_1,
+            priority,
         };
 
         self.bus
@@ -410,14 +475,13 @@ impl FaucetApp {
             self.build_transfer_transaction(&cmd.input_notes, &cmd.output_notes)?;
 
         let tx_hash = self
-            .client
-            .send_tx_blob(blob_tx.clone())
+            .submit_tracked(blob_tx.clone(), TxKind::Transfer, None)
             .await
             .context("dispatching transfer blob transaction")?;
 
         info!(%tx_hash, "Submitted transfer transaction");
 
-        if let Err(err) = self.enqueue_proof_job(&blob_tx, &tx_hash, utxo) {
+        if let Err(err) = self.enqueue_proof_job(&blob_tx, &tx_hash, utxo, ProofPriority::User) {
             warn!(error = %err, "failed to enqueue transfer proof job");
         }
 
@@ -426,29 +490,22 @@ impl FaucetApp {
 
     /// Process a transfer with pre-generated proof (client-side proving)
     async fn process_transfer_with_proof(&mut self, cmd: TransferWithProofCommand) -> Result<()> {
+        // Convert public inputs from hex strings to bytes, before anything
+        // is submitted
+        let public_inputs_bytes = decode_public_inputs(Circuit::HyliUtxo, &cmd.public_inputs)
+            .context("decoding client-generated proof public inputs")?;
+
         // Build blob transaction from provided blob data and output notes
         let blob_tx = self.build_proved_transfer_blob(&cmd)?;
 
         // Submit blob transaction
         let tx_hash = self
-            .client
-            .send_tx_blob(blob_tx.clone())
+            .submit_tracked(blob_tx, TxKind::Transfer, None)
             .await
             .context("dispatching proved transfer blob transaction")?;
 
         info!(%tx_hash, "Submitted proved transfer blob transaction");
 
-        // Build and submit proof transaction
-        // Convert public inputs from hex strings to bytes
-        let public_inputs_bytes: Vec<u8> = cmd
-            .public_inputs
-            .iter()
-            .flat_map(|hex_str| {
-                let normalized = hex_str.strip_prefix("0x").unwrap_or(hex_str);
-                hex::decode(normalized).unwrap_or_else(|_| vec![0u8; 32])
-            })
-            .collect();
-
         // Combine public inputs and proof
         let mut proof_with_inputs = public_inputs_bytes;
         proof_with_inputs.extend_from_slice(&cmd.proof);
@@ -461,10 +518,17 @@ impl FaucetApp {
             proof: ProofData(proof_with_inputs),
         };
 
-        self.client
-            .send_tx_proof(proof_tx)
-            .await
-            .context("submitting client-generated proof to node")?;
+        let tx_hash_hex = hex_encode(&tx_hash.0);
+        if let Err(err) = self.client.send_tx_proof(proof_tx).await {
+            self.tx_tracker.record_proof_failed(
+                &tx_hash_hex,
+                format!("submitting proof: {err:#}"),
+                current_timestamp(),
+            );
+            return Err(err).context("submitting client-generated proof to node");
+        }
+        self.tx_tracker
+            .record_proof_submitted(&tx_hash_hex, current_timestamp());
 
         info!(%tx_hash, "Submitted client-generated proof transaction");
 
@@ -525,7 +589,7 @@ impl FaucetApp {
 
         // Nullifiers (next 64 bytes)
         for input in input_notes.iter() {
-            let nullifier = hash_merge([input.note.psi, input.secret_key]);
+            let nullifier = input.nullifier();
             blob_bytes[offset..offset + HYLI_BLOB_HASH_BYTE_LENGTH]
                 .copy_from_slice(&nullifier.to_be_bytes());
             offset += HYLI_BLOB_HASH_BYTE_LENGTH;
@@ -556,7 +620,10 @@ impl FaucetApp {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{hyli_utxo_state_client::HyliUtxoStateExecutor, noir_prover::HyliUtxoNoirProver};
+    use crate::{
+        conf::TxTrackerConf, hyli_utxo_state_client::HyliUtxoStateExecutor,
+        noir_prover::HyliUtxoNoirProver,
+    };
     use barretenberg::{Prove, Verify};
     use client_sdk::{
         helpers::test::MockProver, rest_client::test::NodeApiMockClient,
@@ -685,6 +752,7 @@ mod tests {
             utxo_contract_name: TEST_UTXO_CONTRACT_NAME.to_string(),
             utxo_state_contract_name: TEST_UTXO_STATE_CONTRACT_NAME.to_string(),
             incl_proof_contract_name: "hyli_smt_incl_proof".to_string(),
+            tx_tracker: Arc::new(TxTracker::new(TxTrackerConf::default())),
         };
 
         let mut app = FaucetApp::build(bus, context)
@@ -714,6 +782,7 @@ mod tests {
             blob: blob_bytes,
             tx_blob_count: blob_tx.blobs.len() as u32,
             blob_index: blob_index as u32,
+            priority: ProofPriority::Faucet,
         };
 
         let hyli_utxo = HyliUtxoNoirProver::build_hyli_utxo(TEST_UTXO_CONTRACT_NAME, &job)
@@ -750,6 +819,7 @@ mod tests {
             utxo_contract_name: TEST_UTXO_CONTRACT_NAME.to_string(),
             utxo_state_contract_name: TEST_UTXO_STATE_CONTRACT_NAME.to_string(),
             incl_proof_contract_name: TEST_SMT_INCL_CONTRACT_NAME.to_string(),
+            tx_tracker: Arc::new(TxTracker::new(TxTrackerConf::default())),
         };
 
         let mut app = FaucetApp::build(bus, context)
@@ -822,6 +892,7 @@ mod tests {
             utxo_contract_name: TEST_UTXO_CONTRACT_NAME.to_string(),
             utxo_state_contract_name: TEST_UTXO_STATE_CONTRACT_NAME.to_string(),
             incl_proof_contract_name: TEST_SMT_INCL_CONTRACT_NAME.to_string(),
+            tx_tracker: Arc::new(TxTracker::new(TxTrackerConf::default())),
         };
 
         let mut app = FaucetApp::build(bus, context)
@@ -865,7 +936,7 @@ mod tests {
             .input_notes
             .iter()
             .filter(|input| !input.note.is_padding_note())
-            .map(|input| hash_merge([input.note.psi, input.secret_key]))
+            .map(InputNote::nullifier)
             .map(|value: Element| BorshableH256::from(value.to_be_bytes()))
             .collect();
 
@@ -918,6 +989,7 @@ mod tests {
             utxo_contract_name: TEST_UTXO_CONTRACT_NAME.to_string(),
             utxo_state_contract_name: TEST_UTXO_STATE_CONTRACT_NAME.to_string(),
             incl_proof_contract_name: TEST_SMT_INCL_CONTRACT_NAME.to_string(),
+            tx_tracker: Arc::new(TxTracker::new(TxTrackerConf::default())),
         };
 
         let mut app = FaucetApp::build(bus, context)
@@ -943,6 +1015,7 @@ mod tests {
             blob: blob_bytes,
             tx_blob_count: blob_tx.blobs.len() as u32,
             blob_index: blob_index as u32,
+            priority: ProofPriority::Faucet,
         };
 
         let hyli_utxo = HyliUtxoNoirProver::build_hyli_utxo(TEST_UTXO_CONTRACT_NAME, &job)
@@ -1011,6 +1084,7 @@ mod tests {
             utxo_contract_name: TEST_UTXO_CONTRACT_NAME.to_string(),
             utxo_state_contract_name: TEST_UTXO_STATE_CONTRACT_NAME.to_string(),
             incl_proof_contract_name: TEST_SMT_INCL_CONTRACT_NAME.to_string(),
+            tx_tracker: Arc::new(TxTracker::new(TxTrackerConf::default())),
         };
         let mut faucet = FaucetApp::build(faucet_bus, faucet_context)
             .await
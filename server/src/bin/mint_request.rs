@@ -1,4 +1,8 @@
 use anyhow::{anyhow, bail, Context, Result};
+use cachecash_client::{
+    solve_faucet_pow,
+    types::{FaucetChallenge, FaucetChallengeRequest, FaucetPowSolution},
+};
 use clap::Parser;
 use k256::{
     elliptic_curve::sec1::ToEncodedPoint,
@@ -35,6 +39,7 @@ struct FaucetRequest<'a> {
     pubkey_hex: &'a str,
     #[serde(skip_serializing_if = "Option::is_none")]
     amount: Option<u64>,
+    pow: FaucetPowSolution,
 }
 
 #[tokio::main]
@@ -48,10 +53,31 @@ async fn main() -> Result<()> {
         None => derive_pubkey_hex(&args.name)?,
     };
 
+    let challenge_endpoint = format!("{endpoint}/challenge");
+    let challenge: FaucetChallenge = client
+        .post(&challenge_endpoint)
+        .json(&FaucetChallengeRequest {
+            pubkey_hex: derived_pubkey.clone(),
+        })
+        .send()
+        .await
+        .with_context(|| format!("sending request to {challenge_endpoint}"))?
+        .error_for_status()
+        .context("requesting faucet challenge")?
+        .json()
+        .await
+        .context("reading faucet challenge")?;
+    let nonce = solve_faucet_pow(
+        &challenge.challenge,
+        &challenge.pubkey_hex,
+        challenge.difficulty,
+    );
+
     let payload = FaucetRequest {
         name: &args.name,
         pubkey_hex: &derived_pubkey,
         amount: args.amount,
+        pow: FaucetPowSolution { challenge, nonce },
     };
 
     let response = client
@@ -21,6 +21,9 @@ pub struct Conf {
     pub smt_contract_name: String,
     pub utxo_state_contract_name: String,
 
+    /// Source of the settled hyli-utxo-state transactions (default: postgres).
+    #[serde(default)]
+    pub indexer_backend: IndexerBackend,
     pub indexer_database_url: String,
     pub listener_poll_interval_secs: u64,
 
@@ -33,12 +36,303 @@ pub struct Conf {
     /// Whether to persist encrypted notes to disk (default: false).
     #[serde(default)]
     pub persist_encrypted_notes: bool,
+    /// Storage of encrypted notes and username registrations (default: local).
+    #[serde(default)]
+    pub note_store_backend: NoteStoreBackend,
+    pub note_store_database_url: String,
+    /// Interval in milliseconds between reads of the notes other servers
+    /// inserted into the Postgres note store, for `/api/notes/stream`
+    /// (default: 500).
+    #[serde(default = "default_note_feed_poll_interval_ms")]
+    pub note_feed_poll_interval_ms: u64,
+    /// Durability of the local note store and address registry files.
+    #[serde(default)]
+    pub store_log: StoreLogConf,
+    /// Maximum clock skew in seconds accepted for signed note deletions and
+    /// username registrations (default: 300). Accepted signatures are kept
+    /// until they expire, in `signed_requests.json` under the data directory
+    /// or in the Postgres note store, so they are not accepted twice.
+    #[serde(default = "default_signed_request_max_age_secs")]
+    pub signed_request_max_age_secs: u64,
+    /// Interval between heartbeats on `/api/notes/stream` and
+    /// `/api/state/stream` (default: 15).
+    #[serde(default = "default_stream_heartbeat_secs")]
+    pub stream_heartbeat_secs: u64,
+    /// Limits applied to `/api/faucet` mints.
+    #[serde(default)]
+    pub faucet_quota: FaucetQuotaConf,
+    /// Proof-of-work gate in front of `/api/faucet`.
+    #[serde(default)]
+    pub faucet_pow: FaucetPowConf,
+    /// Lifecycle tracking of submitted blob transactions.
+    #[serde(default)]
+    pub tx_tracker: TxTrackerConf,
+    /// Persistence and retries of the Noir proof jobs.
+    #[serde(default)]
+    pub proof_queue: ProofQueueConf,
+    /// Concurrency and queue limits of the Noir provers.
+    #[serde(default)]
+    pub proving_scheduler: ProvingSchedulerConf,
+    /// Snapshots of the hyli-utxo-state indexer, for fast restarts.
+    #[serde(default)]
+    pub snapshot: SnapshotConf,
+    /// Where the indexer keeps its sparse Merkle trees (default: memory).
+    /// The auto-prover builds its state through the SDK and keeps its trees
+    /// in memory either way.
+    #[serde(default)]
+    pub smt_store: SmtStoreBackend,
+}
+
+/// Where the hyli-utxo-state indexer and auto-prover read transactions from.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum IndexerBackend {
+    /// A node indexer database at `indexer_database_url`.
+    #[default]
+    Postgres,
+    /// Blocks streamed from the node DA at `da_read_from`, without external
+    /// services. The DA position is kept under `data_directory`.
+    Embedded,
+}
+
+/// Where encrypted notes and username registrations are stored.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NoteStoreBackend {
+    /// In memory, and in JSON files under `data_directory` when
+    /// `persist_encrypted_notes` is set.
+    #[default]
+    Local,
+    /// The database at `note_store_database_url`, which several servers can
+    /// share.
+    Postgres,
+}
+
+/// Append-only operation logs of the stores persisted in the data directory.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct StoreLogConf {
+    /// When appended operations are flushed to disk.
+    pub fsync: FsyncPolicy,
+    /// Delay between two flushes with the `interval` policy.
+    pub fsync_interval_ms: u64,
+    /// Operations appended before the log is folded into a checkpoint.
+    pub compact_after_ops: u64,
+}
+
+impl Default for StoreLogConf {
+    fn default() -> Self {
+        Self {
+            fsync: FsyncPolicy::Always,
+            fsync_interval_ms: 1_000,
+            compact_after_ops: 10_000,
+        }
+    }
+}
+
+/// When the operations appended to a log are flushed to disk.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FsyncPolicy {
+    /// After every operation, so none is lost on power failure.
+    #[default]
+    Always,
+    /// Every `fsync_interval_ms`, by a background flush or by an operation
+    /// appended once that delay passed since the last flush. A power failure
+    /// loses at most the operations of the last interval.
+    Interval,
+    /// Left to the OS. Only a process crash is survived.
+    Never,
+}
+
+/// Storage of the sparse Merkle trees of the hyli-utxo-state indexer.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SmtStoreBackend {
+    #[default]
+    Memory,
+    /// An embedded database under `data_directory`, so the trees do not
+    /// have to fit in memory. The trees are sealed with each indexer
+    /// snapshot and reopened when it is restored, instead of being imported
+    /// from its leaves.
+    Disk,
+}
+
+/// Faucet abuse protection. Amount limits set to 0 are disabled.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct FaucetQuotaConf {
+    /// Largest amount a single faucet request may mint.
+    pub max_amount_per_request: u64,
+    /// Minimum delay between two mints for the same pubkey or client IP.
+    pub cooldown_secs: u64,
+    /// Length of the per-pubkey and per-IP quota windows.
+    pub window_secs: u64,
+    /// Amount a pubkey may mint per window.
+    pub per_pubkey_limit: u64,
+    /// Amount a client IP may mint per window.
+    pub per_ip_limit: u64,
+    /// Amount the faucet may mint per UTC day, across all clients.
+    pub daily_budget: u64,
+    /// Header carrying the client IP when running behind a reverse proxy
+    /// (e.g. `x-forwarded-for`). The socket address is used when unset.
+    pub client_ip_header: Option<String>,
+}
+
+impl Default for FaucetQuotaConf {
+    fn default() -> Self {
+        Self {
+            max_amount_per_request: 100,
+            cooldown_secs: 60,
+            window_secs: 86_400,
+            per_pubkey_limit: 100,
+            per_ip_limit: 500,
+            daily_budget: 100_000,
+            client_ip_header: None,
+        }
+    }
 }
 
 fn default_max_note_payload_size() -> usize {
     65536 // 64KB
 }
 
+fn default_signed_request_max_age_secs() -> u64 {
+    300
+}
+
+fn default_stream_heartbeat_secs() -> u64 {
+    15
+}
+
+fn default_note_feed_poll_interval_ms() -> u64 {
+    500
+}
+
+/// Hashcash challenges required by `/api/faucet`.
+///
+/// Difficulty is `base_difficulty` leading zero bits, plus one bit each time
+/// the number of challenges issued in the last `rate_window_secs` doubles
+/// past `target_challenges_per_window`, capped at `max_difficulty`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct FaucetPowConf {
+    /// Whether faucet requests must carry a solved challenge.
+    pub enabled: bool,
+    pub base_difficulty: u8,
+    pub max_difficulty: u8,
+    /// Challenges redeemed per `rate_window_secs` before the difficulty
+    /// rises, by one bit per doubling.
+    pub target_challenges_per_window: u32,
+    pub rate_window_secs: u64,
+    /// How long an issued challenge can be redeemed.
+    pub challenge_ttl_secs: u64,
+}
+
+impl Default for FaucetPowConf {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            base_difficulty: 16,
+            max_difficulty: 24,
+            target_challenges_per_window: 30,
+            rate_window_secs: 60,
+            challenge_ttl_secs: 300,
+        }
+    }
+}
+
+/// Retention of the records served by `/api/tx/{hash}`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct TxTrackerConf {
+    /// Time after submission at which a transaction that has not settled is
+    /// reported as timed out, and the notes it spends may be spent again.
+    pub timeout_secs: u64,
+    /// How long settled, failed and timed-out records are kept.
+    pub retention_secs: u64,
+}
+
+impl Default for TxTrackerConf {
+    fn default() -> Self {
+        Self {
+            timeout_secs: 900,
+            retention_secs: 604_800,
+        }
+    }
+}
+
+/// Retries of the proofs submitted by the Noir provers.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct ProofQueueConf {
+    /// Time after a job is queued at which its proof is no longer retried
+    /// and the job is moved to the dead letters.
+    pub deadline_secs: u64,
+    /// Delay before the first retry, doubled after each failed attempt.
+    pub retry_delay_ms: u64,
+    pub max_retry_delay_ms: u64,
+    /// How long dead letters are kept.
+    pub dead_letter_retention_secs: u64,
+}
+
+impl Default for ProofQueueConf {
+    fn default() -> Self {
+        Self {
+            deadline_secs: 600,
+            retry_delay_ms: 1_000,
+            max_retry_delay_ms: 30_000,
+            dead_letter_retention_secs: 604_800,
+        }
+    }
+}
+
+/// Limits shared by the Noir provers so bursts of proofs do not starve the
+/// machine. Queue limits set to 0 are disabled.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct ProvingSchedulerConf {
+    /// `hyli_utxo` proofs generated at once.
+    pub max_parallel_hyli_utxo: usize,
+    /// `hyli_smt_incl_proof` proofs generated at once.
+    pub max_parallel_smt_incl: usize,
+    /// Proofs waiting for a slot, per circuit, beyond which the API answers
+    /// 503. User jobs only count the user jobs ahead of them.
+    pub max_queued: usize,
+    /// `Retry-After` sent before any proof duration has been measured.
+    pub default_retry_after_secs: u64,
+}
+
+impl Default for ProvingSchedulerConf {
+    fn default() -> Self {
+        Self {
+            max_parallel_hyli_utxo: 2,
+            max_parallel_smt_incl: 2,
+            max_queued: 32,
+            default_retry_after_secs: 10,
+        }
+    }
+}
+
+/// Periodic snapshots of the hyli-utxo-state indexer trees.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct SnapshotConf {
+    /// Settled transactions applied between two snapshots.
+    pub interval_txs: u64,
+    /// Snapshots kept on disk, the latest valid one being restored.
+    pub keep: usize,
+}
+
+impl Default for SnapshotConf {
+    fn default() -> Self {
+        Self {
+            interval_txs: 100,
+            keep: 3,
+        }
+    }
+}
+
 impl Conf {
     pub fn new(config_files: Vec<String>) -> Result<Self, anyhow::Error> {
         let mut builder = Config::builder().add_source(File::from_str(
@@ -0,0 +1,243 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::{Context, Result};
+use client_sdk::rest_client::NodeApiClient;
+use hyli_modules::{
+    bus::SharedMessageBus,
+    module_bus_client, module_handle_messages,
+    modules::{
+        contract_listener::{ContractChangeData, ContractListenerEvent, ContractTx},
+        Module,
+    },
+    node_state::module::NodeStateEvent,
+};
+use sdk::{
+    api::{ContractChangeType, TransactionStatusDb},
+    BlobTransaction, BlockHeight, ContractName, NodeStateBlock, StatefulEvent, TxId,
+};
+use tracing::{debug, info, warn};
+
+module_bus_client! {
+    #[derive(Debug)]
+    pub struct DaRelayBusClient {
+        sender(ContractListenerEvent),
+        receiver(NodeStateEvent),
+    }
+}
+
+pub struct DaRelayCtx {
+    pub contract_name: ContractName,
+    pub node: Arc<dyn NodeApiClient + Send + Sync>,
+}
+
+/// Feeds the `HyliUtxoStateIndexer` and `AutoProver` from the blocks streamed
+/// by a `DAListener`, in place of the Postgres-backed `ContractListener`.
+///
+/// Transactions touching the contract are translated into the events the
+/// `ContractListener` would send. Blocks up to the node height at startup are
+/// a backfill: only their settled transactions are relayed, and
+/// `BackfillComplete` is sent once a later block arrives.
+pub struct DaRelay {
+    bus: DaRelayBusClient,
+    contract_name: ContractName,
+    backfill_until: BlockHeight,
+    backfill_complete: bool,
+}
+
+impl Module for DaRelay {
+    type Context = Arc<DaRelayCtx>;
+
+    async fn build(bus: SharedMessageBus, ctx: Self::Context) -> Result<Self> {
+        let bus = DaRelayBusClient::new_from_bus(bus.new_handle()).await;
+        let backfill_until = ctx
+            .node
+            .get_block_height()
+            .await
+            .context("fetching node block height")?;
+        Ok(Self {
+            bus,
+            contract_name: ctx.contract_name.clone(),
+            backfill_until,
+            backfill_complete: false,
+        })
+    }
+
+    async fn run(&mut self) -> Result<()> {
+        module_handle_messages! {
+            on_self self,
+            listen<NodeStateEvent> event => {
+                let NodeStateEvent::NewBlock(block) = event;
+                self.handle_block(&block)?;
+            }
+        };
+
+        Ok(())
+    }
+}
+
+impl DaRelay {
+    fn handle_block(&mut self, block: &NodeStateBlock) -> Result<()> {
+        let height = block.signed_block.height();
+        if !self.backfill_complete && height > self.backfill_until {
+            info!(contract = %self.contract_name, %height, "DA backfill complete");
+            self.backfill_complete = true;
+            self.bus
+                .send(ContractListenerEvent::BackfillComplete(
+                    self.contract_name.clone(),
+                ))
+                .context("sending backfill complete")?;
+        }
+        for event in contract_events(&self.contract_name, block, self.backfill_complete) {
+            self.bus.send(event).context("relaying contract event")?;
+        }
+        Ok(())
+    }
+}
+
+/// Events of `block` concerning `contract_name`, as the `ContractListener`
+/// reports them. Sequenced transactions are only reported when `live`.
+pub fn contract_events(
+    contract_name: &ContractName,
+    block: &NodeStateBlock,
+    live: bool,
+) -> Vec<ContractListenerEvent> {
+    let mut changes: HashMap<TxId, ContractChangeData> = HashMap::new();
+    for (tx_id, event) in &block.stateful_events.events {
+        if let StatefulEvent::ContractRegistration(name, contract, metadata) = event {
+            if name == contract_name {
+                changes.insert(
+                    tx_id.clone(),
+                    ContractChangeData {
+                        change_types: vec![ContractChangeType::Registered],
+                        metadata: metadata.clone(),
+                        verifier: contract.verifier.0.clone(),
+                        program_id: contract.program_id.0.clone(),
+                        state_commitment: contract.state.0.clone(),
+                        soft_timeout: None,
+                        hard_timeout: None,
+                        deleted_at_height: None,
+                    },
+                );
+            }
+        }
+    }
+
+    let mut events = Vec::new();
+    for (tx_id, event) in &block.stateful_events.events {
+        let (tx, tx_ctx, status) = match event {
+            StatefulEvent::SequencedTx(tx, tx_ctx) if live => {
+                (tx, tx_ctx, TransactionStatusDb::Sequenced)
+            }
+            StatefulEvent::SettledTx(unsettled) => (
+                &unsettled.tx,
+                &unsettled.tx_context,
+                TransactionStatusDb::Success,
+            ),
+            StatefulEvent::FailedTx(unsettled) => (
+                &unsettled.tx,
+                &unsettled.tx_context,
+                TransactionStatusDb::Failure,
+            ),
+            StatefulEvent::TimedOutTx(unsettled) => (
+                &unsettled.tx,
+                &unsettled.tx_context,
+                TransactionStatusDb::TimedOut,
+            ),
+            _ => continue,
+        };
+        let contract_changes: HashMap<_, _> = changes
+            .remove(tx_id)
+            .map(|change| (contract_name.clone(), change))
+            .into_iter()
+            .collect();
+        if contract_changes.is_empty() && !touches_contract(tx, contract_name) {
+            continue;
+        }
+        debug!(tx_hash = %hex::encode(&tx_id.1 .0), ?status, "Relaying DA transaction");
+        let sequenced = matches!(status, TransactionStatusDb::Sequenced);
+        let contract_tx = ContractTx {
+            tx_id: tx_id.clone(),
+            tx: tx.clone(),
+            tx_ctx: tx_ctx.clone(),
+            status,
+            contract_changes,
+        };
+        events.push(if sequenced {
+            ContractListenerEvent::SequencedTx(contract_tx)
+        } else {
+            ContractListenerEvent::SettledTx(contract_tx)
+        });
+    }
+
+    for tx_id in changes.keys() {
+        warn!(tx_hash = %hex::encode(&tx_id.1 .0), contract = %contract_name, "Contract registered without a settled transaction");
+    }
+    events
+}
+
+fn touches_contract(tx: &BlobTransaction, contract_name: &ContractName) -> bool {
+    tx.blobs
+        .iter()
+        .any(|blob| &blob.contract_name == contract_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sdk::{
+        hyli_model_utils::TimestampMs, AggregateSignature, Blob, BlobData, BlockStakingData,
+        ConsensusProposal, ConsensusProposalHash, DataProposalHash, Identity, SignedBlock,
+        StatefulEvents, TxContext, TxHash,
+    };
+
+    fn sequenced_block(contract: &str) -> NodeStateBlock {
+        let tx = BlobTransaction::new(
+            Identity("alice@wallet".to_string()),
+            vec![Blob {
+                contract_name: ContractName(contract.to_string()),
+                data: BlobData(vec![1, 2, 3]),
+            }],
+        );
+        let tx_id = TxId(DataProposalHash(b"dp".to_vec()), TxHash(vec![7u8; 32]));
+        NodeStateBlock {
+            signed_block: Arc::new(SignedBlock {
+                data_proposals: Vec::new(),
+                consensus_proposal: ConsensusProposal {
+                    slot: 1,
+                    parent_hash: ConsensusProposalHash(b"parent".to_vec()),
+                    cut: Vec::new(),
+                    staking_actions: Vec::new(),
+                    timestamp: TimestampMs::ZERO,
+                },
+                certificate: AggregateSignature::default(),
+            }),
+            staking_data: Arc::new(BlockStakingData::default()),
+            stateful_events: Arc::new(StatefulEvents {
+                events: vec![(
+                    tx_id,
+                    StatefulEvent::SequencedTx(tx, Arc::new(TxContext::default())),
+                )],
+            }),
+        }
+    }
+
+    #[test]
+    fn relays_sequenced_transactions_of_the_contract_once_live() {
+        let contract = ContractName("hyli-utxo-state".to_string());
+
+        let block = sequenced_block("hyli-utxo-state");
+        assert!(contract_events(&contract, &block, false).is_empty());
+        let events = contract_events(&contract, &block, true);
+        assert_eq!(events.len(), 1);
+        assert!(matches!(
+            &events[0],
+            ContractListenerEvent::SequencedTx(ContractTx {
+                status: TransactionStatusDb::Sequenced,
+                ..
+            })
+        ));
+
+        let other = sequenced_block("oranj");
+        assert!(contract_events(&contract, &other, true).is_empty());
+    }
+}
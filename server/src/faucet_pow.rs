@@ -0,0 +1,439 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io,
+    sync::Mutex,
+};
+
+use cachecash_client::{
+    faucet_pow_digest, leading_zero_bits,
+    types::{FaucetChallenge, FaucetPowSolution},
+};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use tracing::warn;
+
+use crate::{
+    conf::{FaucetPowConf, StoreLogConf},
+    op_log::{LogState, LogStore},
+};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Why a faucet proof of work was rejected.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum PowError {
+    #[error("faucet requests require a solved challenge from /api/faucet/challenge")]
+    Missing,
+    #[error("challenge was not issued by this server")]
+    InvalidSignature,
+    #[error("challenge is bound to another pubkey")]
+    WrongPubkey,
+    #[error("challenge expired")]
+    Expired,
+    #[error("solution does not have {required} leading zero bits")]
+    InsufficientWork { required: u8 },
+    #[error("challenge has already been used")]
+    AlreadyUsed,
+}
+
+impl PowError {
+    /// Reason reported to `FaucetMetrics::record_failure`.
+    pub fn reason(&self) -> &'static str {
+        match self {
+            PowError::Missing => "pow_missing",
+            PowError::InvalidSignature => "pow_invalid_signature",
+            PowError::WrongPubkey => "pow_wrong_pubkey",
+            PowError::Expired => "pow_expired",
+            PowError::InsufficientWork { .. } => "pow_insufficient_work",
+            PowError::AlreadyUsed => "pow_already_used",
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct PowState {
+    /// HMAC key signing challenges (hex), generated on first start
+    secret: String,
+    /// Redeemed challenge ids and their expiry
+    used: HashMap<String, u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+enum PowOp {
+    SetSecret {
+        secret: String,
+    },
+    /// Marks `challenge` as used, dropping the ids that expired at `now`
+    Redeem {
+        challenge: String,
+        expires_at: u64,
+        now: u64,
+    },
+}
+
+impl LogState for PowState {
+    type Op = PowOp;
+
+    fn apply(&mut self, op: PowOp) {
+        match op {
+            PowOp::SetSecret { secret } => self.secret = secret,
+            PowOp::Redeem {
+                challenge,
+                expires_at,
+                now,
+            } => {
+                self.used.retain(|_, expires_at| *expires_at >= now);
+                self.used.insert(challenge, expires_at);
+            }
+        }
+    }
+}
+
+/// Issues and checks the hashcash challenges gating `/api/faucet`.
+///
+/// Challenges are stateless until redeemed: the server HMACs the challenge
+/// fields, and only redeemed ids are stored so each challenge is single-use.
+pub struct FaucetPow {
+    conf: FaucetPowConf,
+    secret: Vec<u8>,
+    store: LogStore<PowState>,
+    /// Redemption timestamps within the rate window, used to scale
+    /// difficulty. Only the most recent ones that can still raise it are kept.
+    redeemed: Mutex<VecDeque<u64>>,
+}
+
+impl FaucetPow {
+    /// Creates an in-memory challenge issuer with a fresh key.
+    pub fn new(conf: FaucetPowConf) -> Self {
+        let secret = new_secret();
+        let store = LogStore::new(PowState {
+            secret: hex::encode(&secret),
+            used: HashMap::new(),
+        });
+        Self::from_store(conf, secret, store)
+    }
+
+    /// Creates a challenge issuer whose key and redeemed challenges are
+    /// persisted to `persistence_path` and its operation log.
+    pub fn with_persistence(
+        conf: FaucetPowConf,
+        store_log: StoreLogConf,
+        persistence_path: String,
+    ) -> io::Result<Self> {
+        let store = LogStore::<PowState>::open(persistence_path, store_log)?;
+        let stored = hex::decode(&store.read().secret).unwrap_or_default();
+        let secret = if stored.is_empty() {
+            let secret = new_secret();
+            store.write().apply(PowOp::SetSecret {
+                secret: hex::encode(&secret),
+            })?;
+            secret
+        } else {
+            stored
+        };
+        Ok(Self::from_store(conf, secret, store))
+    }
+
+    fn from_store(conf: FaucetPowConf, secret: Vec<u8>, store: LogStore<PowState>) -> Self {
+        Self {
+            conf,
+            secret,
+            store,
+            redeemed: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Issues a challenge for `pubkey_hex` (normalized lowercase hex) at the
+    /// difficulty matching the recent mint rate. Issuing is unauthenticated,
+    /// so only redeemed challenges count towards that rate.
+    pub fn issue(&self, pubkey_hex: &str, now: u64) -> FaucetChallenge {
+        let difficulty = {
+            let mut redeemed = self.redeemed.lock().expect("challenge rate lock poisoned");
+            self.prune_window(&mut redeemed, now);
+            self.difficulty(redeemed.len())
+        };
+
+        let mut id = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut id);
+        let mut challenge = FaucetChallenge {
+            challenge: hex::encode(id),
+            pubkey_hex: pubkey_hex.to_string(),
+            difficulty,
+            expires_at: now.saturating_add(self.conf.challenge_ttl_secs),
+            signature: String::new(),
+        };
+        challenge.signature = hex::encode(self.mac(&challenge).finalize().into_bytes());
+        challenge
+    }
+
+    /// Checks a solution for a mint to `pubkey_hex` and marks its challenge as
+    /// used. Always succeeds when the gate is disabled.
+    pub fn redeem(
+        &self,
+        pubkey_hex: &str,
+        solution: Option<&FaucetPowSolution>,
+        now: u64,
+    ) -> Result<(), PowError> {
+        if !self.conf.enabled {
+            return Ok(());
+        }
+        let FaucetPowSolution { challenge, nonce } = solution.ok_or(PowError::Missing)?;
+
+        let signature =
+            hex::decode(&challenge.signature).map_err(|_| PowError::InvalidSignature)?;
+        self.mac(challenge)
+            .verify_slice(&signature)
+            .map_err(|_| PowError::InvalidSignature)?;
+        if challenge.pubkey_hex != pubkey_hex {
+            return Err(PowError::WrongPubkey);
+        }
+        if now > challenge.expires_at {
+            return Err(PowError::Expired);
+        }
+        let digest = faucet_pow_digest(&challenge.challenge, &challenge.pubkey_hex, *nonce);
+        if leading_zero_bits(&digest) < u32::from(challenge.difficulty) {
+            return Err(PowError::InsufficientWork {
+                required: challenge.difficulty,
+            });
+        }
+
+        {
+            let mut state = self.store.write();
+            if state
+                .used
+                .get(&challenge.challenge)
+                .is_some_and(|expires_at| *expires_at >= now)
+            {
+                return Err(PowError::AlreadyUsed);
+            }
+            let op = PowOp::Redeem {
+                challenge: challenge.challenge.clone(),
+                expires_at: challenge.expires_at,
+                now,
+            };
+            if let Err(err) = state.apply(op) {
+                warn!(error = %err, "Failed to persist redeemed faucet challenges");
+            }
+        }
+
+        let mut redeemed = self.redeemed.lock().expect("challenge rate lock poisoned");
+        self.prune_window(&mut redeemed, now);
+        redeemed.push_back(now);
+        let saturation = self.saturation();
+        while redeemed.len() > saturation {
+            redeemed.pop_front();
+        }
+        Ok(())
+    }
+
+    fn prune_window(&self, redeemed: &mut VecDeque<u64>, now: u64) {
+        let window_start = now.saturating_sub(self.conf.rate_window_secs);
+        while redeemed.front().is_some_and(|at| *at <= window_start) {
+            redeemed.pop_front();
+        }
+    }
+
+    /// `base_difficulty` plus one bit per doubling of `recent` redemptions
+    /// past the target.
+    fn difficulty(&self, recent: usize) -> u8 {
+        let max = self.conf.max_difficulty.max(self.conf.base_difficulty);
+        let mut difficulty = self.conf.base_difficulty;
+        let mut threshold = self.conf.target_challenges_per_window.max(1) as usize;
+        while recent > threshold && difficulty < max {
+            difficulty += 1;
+            threshold = threshold.saturating_mul(2);
+        }
+        difficulty
+    }
+
+    /// Redemptions in the window past which `difficulty` stays at its maximum.
+    fn saturation(&self) -> usize {
+        let steps = self
+            .conf
+            .max_difficulty
+            .saturating_sub(self.conf.base_difficulty);
+        if steps == 0 {
+            return 0;
+        }
+        let target = self.conf.target_challenges_per_window.max(1) as usize;
+        1usize
+            .checked_shl(u32::from(steps - 1))
+            .map_or(usize::MAX, |factor| target.saturating_mul(factor))
+            .saturating_add(1)
+    }
+
+    fn mac(&self, challenge: &FaucetChallenge) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(&self.secret).expect("HMAC accepts any key size");
+        mac.update(
+            format!(
+                "{}:{}:{}:{}",
+                challenge.challenge,
+                challenge.pubkey_hex,
+                challenge.difficulty,
+                challenge.expires_at
+            )
+            .as_bytes(),
+        );
+        mac
+    }
+}
+
+/// A fresh HMAC key for signing challenges.
+fn new_secret() -> Vec<u8> {
+    let mut secret = vec![0u8; 32];
+    rand::thread_rng().fill_bytes(&mut secret);
+    secret
+}
+
+#[cfg(test)]
+mod tests {
+    use cachecash_client::solve_faucet_pow;
+
+    use super::*;
+    use crate::conf::FsyncPolicy;
+
+    const NOW: u64 = 1_760_000_000;
+
+    fn conf() -> FaucetPowConf {
+        FaucetPowConf {
+            enabled: true,
+            base_difficulty: 4,
+            max_difficulty: 6,
+            target_challenges_per_window: 2,
+            rate_window_secs: 60,
+            challenge_ttl_secs: 300,
+        }
+    }
+
+    fn solve(challenge: FaucetChallenge) -> FaucetPowSolution {
+        let nonce = solve_faucet_pow(
+            &challenge.challenge,
+            &challenge.pubkey_hex,
+            challenge.difficulty,
+        );
+        FaucetPowSolution { challenge, nonce }
+    }
+
+    #[test]
+    fn accepts_solution_once() {
+        let pow = FaucetPow::new(conf());
+        let pubkey = "ab".repeat(32);
+        let solution = solve(pow.issue(&pubkey, NOW));
+
+        assert_eq!(pow.redeem(&pubkey, Some(&solution), NOW + 1), Ok(()));
+        assert_eq!(
+            pow.redeem(&pubkey, Some(&solution), NOW + 2),
+            Err(PowError::AlreadyUsed)
+        );
+        assert_eq!(pow.redeem(&pubkey, None, NOW), Err(PowError::Missing));
+    }
+
+    #[test]
+    fn rejects_tampered_foreign_and_expired_challenges() {
+        let pow = FaucetPow::new(conf());
+        let pubkey = "ab".repeat(32);
+
+        let mut easier = pow.issue(&pubkey, NOW);
+        easier.difficulty = 0;
+        assert_eq!(
+            pow.redeem(&pubkey, Some(&solve(easier)), NOW),
+            Err(PowError::InvalidSignature)
+        );
+
+        let solution = solve(pow.issue(&pubkey, NOW));
+        assert_eq!(
+            pow.redeem(&"cd".repeat(32), Some(&solution), NOW),
+            Err(PowError::WrongPubkey)
+        );
+        assert_eq!(
+            pow.redeem(&pubkey, Some(&solution), NOW + 301),
+            Err(PowError::Expired)
+        );
+
+        // Another server instance does not accept our challenges
+        let other = FaucetPow::new(conf());
+        assert_eq!(
+            other.redeem(&pubkey, Some(&solution), NOW),
+            Err(PowError::InvalidSignature)
+        );
+
+        let mut unsolved = solution;
+        unsolved.nonce = (0..)
+            .find(|&nonce| {
+                leading_zero_bits(&faucet_pow_digest(
+                    &unsolved.challenge.challenge,
+                    &pubkey,
+                    nonce,
+                )) < 4
+            })
+            .unwrap();
+        assert_eq!(
+            pow.redeem(&pubkey, Some(&unsolved), NOW),
+            Err(PowError::InsufficientWork { required: 4 })
+        );
+    }
+
+    #[test]
+    fn difficulty_scales_with_redeemed_challenges() {
+        let pow = FaucetPow::new(conf());
+        let pubkey = "ab".repeat(32);
+
+        // Requesting challenges without redeeming them does not raise it
+        for _ in 0..10 {
+            assert_eq!(pow.issue(&pubkey, NOW).difficulty, 4);
+        }
+
+        let mut difficulties = Vec::new();
+        for _ in 0..8 {
+            let challenge = pow.issue(&pubkey, NOW);
+            difficulties.push(challenge.difficulty);
+            pow.redeem(&pubkey, Some(&solve(challenge)), NOW).unwrap();
+        }
+        assert_eq!(difficulties, [4, 4, 4, 5, 5, 6, 6, 6]);
+        // Redemptions past the maximum difficulty are not kept
+        assert_eq!(pow.redeemed.lock().unwrap().len(), 5);
+
+        // Back to base once the window has passed
+        assert_eq!(pow.issue(&pubkey, NOW + 61).difficulty, 4);
+    }
+
+    #[test]
+    fn keeps_key_and_redeemed_challenges_across_restarts() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir
+            .path()
+            .join("faucet_pow.json")
+            .to_string_lossy()
+            .to_string();
+        let store_log = StoreLogConf {
+            fsync: FsyncPolicy::Always,
+            fsync_interval_ms: 0,
+            compact_after_ops: 2,
+        };
+        let pubkey = "ab".repeat(32);
+
+        let pow = FaucetPow::with_persistence(conf(), store_log.clone(), path.clone()).unwrap();
+        let used = solve(pow.issue(&pubkey, NOW));
+        let unused = solve(pow.issue(&pubkey, NOW));
+        pow.redeem(&pubkey, Some(&used), NOW).unwrap();
+        drop(pow);
+
+        let reloaded = FaucetPow::with_persistence(conf(), store_log, path).unwrap();
+        assert_eq!(
+            reloaded.redeem(&pubkey, Some(&used), NOW + 1),
+            Err(PowError::AlreadyUsed)
+        );
+        assert_eq!(reloaded.redeem(&pubkey, Some(&unused), NOW + 1), Ok(()));
+    }
+
+    #[test]
+    fn disabled_gate_accepts_anything() {
+        let pow = FaucetPow::new(FaucetPowConf {
+            enabled: false,
+            ..conf()
+        });
+        assert_eq!(pow.redeem(&"ab".repeat(32), None, NOW), Ok(()));
+    }
+}
@@ -0,0 +1,503 @@
+use std::{
+    collections::HashMap,
+    io,
+    net::{IpAddr, SocketAddr},
+};
+
+use axum::http::HeaderMap;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::{
+    conf::{FaucetQuotaConf, StoreLogConf},
+    op_log::{LogState, LogStore},
+};
+
+const SECONDS_PER_DAY: u64 = 86_400;
+
+/// Why a faucet request was refused by the quotas.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum QuotaError {
+    #[error("amount {amount} exceeds the maximum of {max} per request")]
+    AmountTooLarge { amount: u64, max: u64 },
+    #[error("this pubkey already used the faucet recently, retry in {retry_after_secs}s")]
+    PubkeyCooldown { retry_after_secs: u64 },
+    #[error("this client already used the faucet recently, retry in {retry_after_secs}s")]
+    IpCooldown { retry_after_secs: u64 },
+    #[error("this pubkey reached its faucet quota, retry in {retry_after_secs}s")]
+    PubkeyQuotaExceeded { retry_after_secs: u64 },
+    #[error("this client reached its faucet quota, retry in {retry_after_secs}s")]
+    IpQuotaExceeded { retry_after_secs: u64 },
+    #[error("the faucet daily budget is exhausted, retry in {retry_after_secs}s")]
+    DailyBudgetExhausted { retry_after_secs: u64 },
+    #[error("request has no valid `{header}` header")]
+    MissingClientIp { header: String },
+}
+
+impl QuotaError {
+    /// Reason reported to `FaucetMetrics::record_failure`.
+    pub fn reason(&self) -> &'static str {
+        match self {
+            QuotaError::AmountTooLarge { .. } => "amount_too_large",
+            QuotaError::PubkeyCooldown { .. } => "pubkey_cooldown",
+            QuotaError::IpCooldown { .. } => "ip_cooldown",
+            QuotaError::PubkeyQuotaExceeded { .. } => "pubkey_quota_exceeded",
+            QuotaError::IpQuotaExceeded { .. } => "ip_quota_exceeded",
+            QuotaError::DailyBudgetExhausted { .. } => "daily_budget_exhausted",
+            QuotaError::MissingClientIp { .. } => "missing_client_ip",
+        }
+    }
+}
+
+/// Mints attributed to one pubkey or client IP.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct Usage {
+    window_start: u64,
+    minted: u64,
+    last_mint_at: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct QuotaState {
+    pubkeys: HashMap<String, Usage>,
+    ips: HashMap<String, Usage>,
+    /// UTC day number (`timestamp / 86400`) of `minted_today`
+    day: u64,
+    minted_today: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+enum QuotaOp {
+    /// Records a mint of `amount` at `now`, after dropping the entries whose
+    /// window and cooldown have both elapsed
+    Consume {
+        pubkey: String,
+        ip: Option<String>,
+        amount: u64,
+        now: u64,
+        window_secs: u64,
+        cooldown_secs: u64,
+    },
+    Refund {
+        pubkey: String,
+        ip: Option<String>,
+        amount: u64,
+    },
+}
+
+impl LogState for QuotaState {
+    type Op = QuotaOp;
+
+    fn apply(&mut self, op: QuotaOp) {
+        match op {
+            QuotaOp::Consume {
+                pubkey,
+                ip,
+                amount,
+                now,
+                window_secs,
+                cooldown_secs,
+            } => {
+                let keep = |usage: &mut Usage| {
+                    window_open(usage, now, window_secs)
+                        || cooldown_left(usage, now, cooldown_secs).is_some()
+                };
+                self.pubkeys.retain(|_, usage| keep(usage));
+                self.ips.retain(|_, usage| keep(usage));
+
+                let day = now / SECONDS_PER_DAY;
+                if self.day != day {
+                    self.day = day;
+                    self.minted_today = 0;
+                }
+                self.minted_today += amount;
+                record(
+                    self.pubkeys.entry(pubkey).or_default(),
+                    amount,
+                    now,
+                    window_secs,
+                );
+                if let Some(ip) = ip {
+                    record(self.ips.entry(ip).or_default(), amount, now, window_secs);
+                }
+            }
+            QuotaOp::Refund { pubkey, ip, amount } => {
+                self.minted_today = self.minted_today.saturating_sub(amount);
+                if let Some(usage) = self.pubkeys.get_mut(&pubkey) {
+                    usage.minted = usage.minted.saturating_sub(amount);
+                }
+                if let Some(usage) = ip.and_then(|ip| self.ips.get_mut(&ip)) {
+                    usage.minted = usage.minted.saturating_sub(amount);
+                }
+            }
+        }
+    }
+}
+
+/// Per-pubkey, per-IP and global limits for `/api/faucet`, persisted so that
+/// restarting the server does not reset them.
+pub struct FaucetQuota {
+    conf: FaucetQuotaConf,
+    store: LogStore<QuotaState>,
+}
+
+impl FaucetQuota {
+    /// Creates in-memory quotas.
+    pub fn new(conf: FaucetQuotaConf) -> Self {
+        Self {
+            conf,
+            store: LogStore::new(QuotaState::default()),
+        }
+    }
+
+    /// Creates quotas persisted to `persistence_path` and its operation log.
+    pub fn with_persistence(
+        conf: FaucetQuotaConf,
+        store_log: StoreLogConf,
+        persistence_path: String,
+    ) -> io::Result<Self> {
+        let store = LogStore::<QuotaState>::open(persistence_path, store_log)?;
+        let tracked = {
+            let state = store.read();
+            state.pubkeys.len() + state.ips.len()
+        };
+        if tracked > 0 {
+            info!(tracked, "Loaded faucet quotas from disk");
+        }
+        Ok(Self { conf, store })
+    }
+
+    /// Client IP used for the per-IP quota: the first address of the
+    /// configured proxy header if set, otherwise the socket peer address.
+    /// Requests lacking the configured header are refused, since the proxy
+    /// always sets it.
+    pub fn client_ip(
+        &self,
+        headers: &HeaderMap,
+        peer: Option<SocketAddr>,
+    ) -> Result<Option<IpAddr>, QuotaError> {
+        let Some(header) = &self.conf.client_ip_header else {
+            return Ok(peer.map(|peer| peer.ip()));
+        };
+        headers
+            .get(header.as_str())
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(',').next())
+            .and_then(|value| value.trim().parse().ok())
+            .map(Some)
+            .ok_or_else(|| QuotaError::MissingClientIp {
+                header: header.clone(),
+            })
+    }
+
+    /// Checks every limit for a mint of `amount` to `pubkey` requested from
+    /// `client_ip`, and records it if allowed. `pubkey` must be normalized.
+    pub fn try_consume(
+        &self,
+        pubkey: &str,
+        client_ip: Option<IpAddr>,
+        amount: u64,
+        now: u64,
+    ) -> Result<(), QuotaError> {
+        let conf = &self.conf;
+        if conf.max_amount_per_request > 0 && amount > conf.max_amount_per_request {
+            return Err(QuotaError::AmountTooLarge {
+                amount,
+                max: conf.max_amount_per_request,
+            });
+        }
+
+        let ip = client_ip.map(|ip| ip.to_string());
+        let mut state = self.store.write();
+        let day = now / SECONDS_PER_DAY;
+        let minted_today = if state.day == day {
+            state.minted_today
+        } else {
+            0
+        };
+
+        let pubkey_usage = state.pubkeys.get(pubkey);
+        let ip_usage = ip.as_ref().and_then(|ip| state.ips.get(ip));
+
+        if let Some(retry_after_secs) = pubkey_usage.and_then(|usage| self.cooldown(usage, now)) {
+            return Err(QuotaError::PubkeyCooldown { retry_after_secs });
+        }
+        if let Some(retry_after_secs) = ip_usage.and_then(|usage| self.cooldown(usage, now)) {
+            return Err(QuotaError::IpCooldown { retry_after_secs });
+        }
+        if let Some(retry_after_secs) =
+            self.exceeds(pubkey_usage, conf.per_pubkey_limit, amount, now)
+        {
+            return Err(QuotaError::PubkeyQuotaExceeded { retry_after_secs });
+        }
+        if ip.is_some() {
+            if let Some(retry_after_secs) = self.exceeds(ip_usage, conf.per_ip_limit, amount, now) {
+                return Err(QuotaError::IpQuotaExceeded { retry_after_secs });
+            }
+        }
+        if conf.daily_budget > 0 && minted_today.saturating_add(amount) > conf.daily_budget {
+            return Err(QuotaError::DailyBudgetExhausted {
+                retry_after_secs: (day + 1) * SECONDS_PER_DAY - now,
+            });
+        }
+
+        let op = QuotaOp::Consume {
+            pubkey: pubkey.to_string(),
+            ip,
+            amount,
+            now,
+            window_secs: conf.window_secs,
+            cooldown_secs: conf.cooldown_secs,
+        };
+        if let Err(err) = state.apply(op) {
+            warn!(error = %err, "Failed to persist faucet quotas");
+        }
+        Ok(())
+    }
+
+    /// Gives back a consumed amount when the mint could not be submitted.
+    /// Cooldowns are kept.
+    pub fn refund(&self, pubkey: &str, client_ip: Option<IpAddr>, amount: u64) {
+        let op = QuotaOp::Refund {
+            pubkey: pubkey.to_string(),
+            ip: client_ip.map(|ip| ip.to_string()),
+            amount,
+        };
+        if let Err(err) = self.store.write().apply(op) {
+            warn!(error = %err, "Failed to persist faucet quotas");
+        }
+    }
+
+    /// Seconds left before `usage` may mint again, if still cooling down.
+    fn cooldown(&self, usage: &Usage, now: u64) -> Option<u64> {
+        cooldown_left(usage, now, self.conf.cooldown_secs)
+    }
+
+    /// Seconds left in the current window if minting `amount` would exceed `limit`.
+    fn exceeds(&self, usage: Option<&Usage>, limit: u64, amount: u64, now: u64) -> Option<u64> {
+        if limit == 0 {
+            return None;
+        }
+        let minted = usage
+            .filter(|usage| self.window_open(usage, now))
+            .map_or(0, |usage| usage.minted);
+        if minted.saturating_add(amount) <= limit {
+            return None;
+        }
+        let window_end = usage.map_or(now, |usage| {
+            usage.window_start.saturating_add(self.conf.window_secs)
+        });
+        // An amount above the limit never fits, even in a fresh window
+        Some(window_end.saturating_sub(now).max(1))
+    }
+
+    fn window_open(&self, usage: &Usage, now: u64) -> bool {
+        window_open(usage, now, self.conf.window_secs)
+    }
+}
+
+fn record(usage: &mut Usage, amount: u64, now: u64, window_secs: u64) {
+    if !window_open(usage, now, window_secs) {
+        usage.window_start = now;
+        usage.minted = 0;
+    }
+    usage.minted += amount;
+    usage.last_mint_at = now;
+}
+
+fn window_open(usage: &Usage, now: u64, window_secs: u64) -> bool {
+    now < usage.window_start.saturating_add(window_secs)
+}
+
+fn cooldown_left(usage: &Usage, now: u64, cooldown_secs: u64) -> Option<u64> {
+    let ready_at = usage.last_mint_at.saturating_add(cooldown_secs);
+    (now < ready_at).then(|| ready_at - now)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conf::FsyncPolicy;
+
+    const NOW: u64 = 1_760_000_000;
+    const ALICE: &str = "aa";
+    const BOB: &str = "bb";
+
+    fn conf() -> FaucetQuotaConf {
+        FaucetQuotaConf {
+            max_amount_per_request: 10,
+            cooldown_secs: 60,
+            window_secs: 3_600,
+            per_pubkey_limit: 20,
+            per_ip_limit: 30,
+            daily_budget: 50,
+            client_ip_header: None,
+        }
+    }
+
+    fn ip(last: u8) -> Option<IpAddr> {
+        Some(IpAddr::from([10, 0, 0, last]))
+    }
+
+    #[test]
+    fn reads_client_ip_from_configured_header() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", "203.0.113.7, 10.0.0.1".parse().unwrap());
+        let peer = Some(SocketAddr::from(([10, 0, 0, 1], 4000)));
+
+        let direct = FaucetQuota::new(conf());
+        assert_eq!(direct.client_ip(&headers, peer), Ok(ip(1)));
+
+        let proxied = FaucetQuota::new(FaucetQuotaConf {
+            client_ip_header: Some("x-forwarded-for".to_string()),
+            ..conf()
+        });
+        assert_eq!(
+            proxied.client_ip(&headers, peer),
+            Ok(Some(IpAddr::from([203, 0, 113, 7])))
+        );
+        assert_eq!(
+            proxied.client_ip(&HeaderMap::new(), peer),
+            Err(QuotaError::MissingClientIp {
+                header: "x-forwarded-for".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn limits_each_peer_address() {
+        let quota = FaucetQuota::new(FaucetQuotaConf {
+            cooldown_secs: 0,
+            daily_budget: 0,
+            ..conf()
+        });
+        let headers = HeaderMap::new();
+        let peer = |port| Some(SocketAddr::from(([10, 0, 0, 1], port)));
+
+        // New pubkeys from the same host share its quota, whatever the port
+        for (i, pubkey) in ["01", "02", "03"].iter().enumerate() {
+            let client_ip = quota.client_ip(&headers, peer(4000 + i as u16)).unwrap();
+            quota.try_consume(pubkey, client_ip, 10, NOW).unwrap();
+        }
+        let client_ip = quota.client_ip(&headers, peer(5000)).unwrap();
+        assert!(matches!(
+            quota.try_consume("04", client_ip, 10, NOW),
+            Err(QuotaError::IpQuotaExceeded { .. })
+        ));
+
+        let other_host = Some(SocketAddr::from(([10, 0, 0, 2], 4000)));
+        let client_ip = quota.client_ip(&headers, other_host).unwrap();
+        assert_eq!(quota.try_consume("04", client_ip, 10, NOW), Ok(()));
+    }
+
+    #[test]
+    fn enforces_amount_and_cooldown() {
+        let quota = FaucetQuota::new(conf());
+
+        assert_eq!(
+            quota.try_consume(ALICE, ip(1), 11, NOW),
+            Err(QuotaError::AmountTooLarge {
+                amount: 11,
+                max: 10
+            })
+        );
+        assert_eq!(quota.try_consume(ALICE, ip(1), 10, NOW), Ok(()));
+        assert_eq!(
+            quota.try_consume(ALICE, ip(2), 10, NOW + 10),
+            Err(QuotaError::PubkeyCooldown {
+                retry_after_secs: 50
+            })
+        );
+        assert_eq!(
+            quota.try_consume(BOB, ip(1), 10, NOW + 10),
+            Err(QuotaError::IpCooldown {
+                retry_after_secs: 50
+            })
+        );
+        assert_eq!(quota.try_consume(ALICE, ip(1), 10, NOW + 60), Ok(()));
+    }
+
+    #[test]
+    fn enforces_per_pubkey_and_per_ip_windows() {
+        let quota = FaucetQuota::new(FaucetQuotaConf {
+            daily_budget: 0,
+            ..conf()
+        });
+
+        quota.try_consume(ALICE, ip(1), 10, NOW).unwrap();
+        quota.try_consume(ALICE, ip(2), 10, NOW + 60).unwrap();
+        assert_eq!(
+            quota.try_consume(ALICE, ip(3), 10, NOW + 120),
+            Err(QuotaError::PubkeyQuotaExceeded {
+                retry_after_secs: 3_480
+            })
+        );
+        // A new window opens after `window_secs`
+        assert_eq!(quota.try_consume(ALICE, ip(3), 10, NOW + 3_600), Ok(()));
+
+        quota.try_consume("01", ip(9), 10, NOW).unwrap();
+        quota.try_consume("02", ip(9), 10, NOW + 60).unwrap();
+        quota.try_consume("03", ip(9), 10, NOW + 120).unwrap();
+        assert!(matches!(
+            quota.try_consume("04", ip(9), 10, NOW + 180),
+            Err(QuotaError::IpQuotaExceeded { .. })
+        ));
+        // Requests without a known IP only hit the pubkey limits
+        assert_eq!(quota.try_consume("04", None, 10, NOW + 180), Ok(()));
+    }
+
+    #[test]
+    fn enforces_daily_budget_and_refunds() {
+        let quota = FaucetQuota::new(conf());
+        let day_start = NOW - NOW % SECONDS_PER_DAY;
+
+        for (i, pubkey) in ["01", "02", "03", "04", "05"].iter().enumerate() {
+            quota
+                .try_consume(pubkey, ip(i as u8), 10, day_start)
+                .unwrap();
+        }
+        assert_eq!(
+            quota.try_consume("06", ip(6), 10, day_start + 100),
+            Err(QuotaError::DailyBudgetExhausted {
+                retry_after_secs: SECONDS_PER_DAY - 100
+            })
+        );
+
+        quota.refund("05", ip(4), 10);
+        assert_eq!(quota.try_consume("06", ip(6), 10, day_start + 100), Ok(()));
+
+        // Budget resets on the next UTC day
+        assert_eq!(
+            quota.try_consume("07", ip(7), 10, day_start + SECONDS_PER_DAY),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn quotas_survive_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir
+            .path()
+            .join("faucet_quota.json")
+            .to_string_lossy()
+            .to_string();
+        let store_log = StoreLogConf {
+            fsync: FsyncPolicy::Always,
+            fsync_interval_ms: 0,
+            compact_after_ops: 2,
+        };
+
+        let quota = FaucetQuota::with_persistence(conf(), store_log.clone(), path.clone()).unwrap();
+        quota.try_consume(ALICE, ip(1), 10, NOW).unwrap();
+        quota.try_consume(BOB, ip(2), 10, NOW).unwrap();
+        quota.refund(BOB, ip(2), 10);
+        drop(quota);
+
+        let reloaded = FaucetQuota::with_persistence(conf(), store_log, path).unwrap();
+        assert!(matches!(
+            reloaded.try_consume(ALICE, ip(3), 10, NOW + 1),
+            Err(QuotaError::PubkeyCooldown { .. })
+        ));
+        assert_eq!(reloaded.store.read().minted_today, 10);
+    }
+}
@@ -2,82 +2,219 @@ use acvm::AcirField;
 use anyhow::{anyhow, Context, Result};
 use axum::{
     extract::{Query, State},
+    http::StatusCode,
     Json,
 };
 use borsh::{BorshDeserialize, BorshSerialize};
-use client_sdk::{
-    contract_indexer::{ContractHandler, ContractHandlerStore},
-    transaction_builder::TxExecutorHandler,
-};
+use client_sdk::transaction_builder::TxExecutorHandler;
 use hex::encode as hex_encode;
 use hyli_modules::bus::BusMessage;
 use hyli_utxo_state::{
     state::{
-        parse_hyli_utxo_blob, ContractConfig, HyliUtxoState, HyliUtxoStateAction,
-        SeparatedHyliUtxoBlob,
+        parse_hyli_smt_incl_blob, parse_hyli_utxo_blob, ContractConfig, HyliUtxoState,
+        HyliUtxoStateAction, SeparatedHyliUtxoBlob, MAX_ROOTS,
     },
-    zk::BorshableH256,
+    zk::{smt::build_siblings, BorshableH256, SmtStore, SMT},
     HyliUtxoZkVmBatch, HyliUtxoZkVmState,
 };
 use sdk::{
     caller::ExecutionContext, utils::as_hyli_output, BlobIndex, BlobTransaction, Calldata,
     Contract, ContractName, HyliOutput, RegisterContractAction, RunResult, StateCommitment,
-    StructuredBlobData, TxContext,
+    StructuredBlobData, TxHash,
 };
-use std::sync::Arc;
+use sparse_merkle_tree::default_store::DefaultStore;
+use std::{io, sync::Arc};
+use tokio::sync::RwLock;
 use tracing::info;
 use utoipa::openapi::OpenApi;
 use utoipa_axum::{router::OpenApiRouter, routes};
 
+use crate::{
+    notes_history::{NotesHistory, RewindCache, RewoundStore},
+    smt_store::{TreeBackend, TreeStore},
+    types::{
+        CompactSmtWitness, ErrorCode, ErrorResponse, NoteStatusRequest, NoteStatusResponse,
+        SmtWitnessQuery, SmtWitnessResponse, SmtWitnessesRequest, SmtWitnessesResponse,
+    },
+};
+
+/// Maximum number of commitments a single `POST /smt-witnesses` request can
+/// ask witnesses for.
+const MAX_SMT_WITNESSES: usize = 256;
+
+/// Maximum number of commitments plus nullifiers a single `/note-status`
+/// request can look up.
+const MAX_NOTE_STATUS_KEYS: usize = 1024;
+
 /// Event emitted by [`HyliUtxoStateExecutor`] whenever a transaction is successfully settled.
-/// Broadcast as `CSIBusEvent<HyliUtxoStateEvent>` on the message bus.
+/// Broadcast as `CSIBusEvent<HyliUtxoStateEvent>` on the message bus by the
+/// [`HyliUtxoStateIndexer`](crate::state_indexer::HyliUtxoStateIndexer).
 #[derive(Clone, Debug)]
 pub struct HyliUtxoStateEvent {
+    /// Hash of the settled blob transaction.
+    pub tx_hash: TxHash,
+    /// Height of the block the transaction was sequenced in.
+    pub block_height: u64,
     /// The new SMT notes root (big-endian bytes) after the transaction was applied.
     pub notes_root: [u8; 32],
+    /// Note commitments added to the tree, padding excluded.
+    pub created: Vec<[u8; 32]>,
+    /// Nullifiers recorded, padding excluded.
+    pub nullified: Vec<[u8; 32]>,
 }
 
 impl BusMessage for HyliUtxoStateEvent {}
 
-#[derive(Debug, BorshSerialize, BorshDeserialize)]
+/// The indexed hyli-utxo-state, `None` until the contract registration is
+/// indexed. Shared by the indexer and the handlers reading the trees.
+pub type UtxoStateStore = Arc<RwLock<Option<HyliUtxoStateExecutor>>>;
+
+#[derive(Debug, Clone, BorshSerialize)]
 pub struct HyliUtxoStateExecutor {
-    state: HyliUtxoState,
+    state: HyliUtxoState<TreeStore>,
     config: ContractConfig,
+    /// Lets witnesses be served against any root the contract still accepts
+    history: NotesHistory,
+    #[borsh(skip)]
+    rewinds: RewindCache,
 }
 
-impl Clone for HyliUtxoStateExecutor {
-    fn clone(&self) -> Self {
-        let encoded = borsh::to_vec(&self.state).expect("HyliUtxoState should serialize for clone");
-        let state =
-            borsh::from_slice(&encoded).expect("HyliUtxoState should deserialize for clone");
-        let config = self.config.clone();
-        Self { state, config }
+impl BorshDeserialize for HyliUtxoStateExecutor {
+    fn deserialize_reader<R: io::Read>(reader: &mut R) -> io::Result<Self> {
+        let state: HyliUtxoState = BorshDeserialize::deserialize_reader(reader)?;
+        Ok(Self {
+            // Trees are encoded as their leaves, and rebuilt in memory: see
+            // `with_trees_in` to move them to disk
+            state: state.map_stores(|_, store| io::Result::Ok(TreeStore::Memory(store)))?,
+            config: ContractConfig::deserialize_reader(reader)?,
+            history: NotesHistory::deserialize_reader(reader)?,
+            rewinds: RewindCache::default(),
+        })
     }
 }
 
 impl HyliUtxoStateExecutor {
+    /// An empty state keeping its trees in memory.
     pub fn new(config: ContractConfig) -> Self {
+        Self::with_state(
+            config,
+            HyliUtxoState::with_stores(
+                TreeStore::Memory(DefaultStore::default()),
+                TreeStore::Memory(DefaultStore::default()),
+            ),
+        )
+    }
+
+    /// An empty state keeping its trees in `trees`.
+    pub fn with_backend(config: ContractConfig, trees: &TreeBackend) -> Result<Self> {
+        let state = HyliUtxoState::with_stores(trees.empty_store()?, trees.empty_store()?);
+        Ok(Self::with_state(config, state))
+    }
+
+    fn with_state(config: ContractConfig, state: HyliUtxoState<TreeStore>) -> Self {
         Self {
-            state: HyliUtxoState::default(),
+            state,
             config,
+            history: NotesHistory::default(),
+            rewinds: RewindCache::default(),
         }
     }
 
-    pub fn utxo_state(&self) -> &HyliUtxoState {
+    /// Moves the trees held in memory, e.g. after decoding, to `trees`,
+    /// reopening the ones sealed with [`Self::seal_trees`] when they match.
+    pub fn with_trees_in(self, trees: &TreeBackend) -> Result<Self> {
+        let state = self
+            .state
+            .map_stores(|root, store| trees.tree_store(root, store))?;
+        Ok(Self { state, ..self })
+    }
+
+    /// Flushes the trees kept in `trees` and seals them, so that a snapshot
+    /// of this state taken now reopens them when restored.
+    pub fn seal_trees(&mut self, trees: &TreeBackend) -> Result<()> {
+        trees.seal(self.state.stores_mut())
+    }
+
+    pub fn utxo_state(&self) -> &HyliUtxoState<TreeStore> {
         &self.state
     }
 
+    /// The notes tree as it was at `root`, or `None` when `root` is not one
+    /// of the last [`MAX_ROOTS`] roots. The tree reads the current nodes
+    /// through the ones rewound to `root`, cached until the notes tree moves.
+    pub fn notes_tree_at(
+        &self,
+        root: &BorshableH256,
+    ) -> Result<Option<SMT<BorshableH256, RewoundStore<'_, TreeStore>>>> {
+        let current = self.state.notes_tree();
+        let current_root = current.root();
+        if *root == current_root {
+            return Ok(Some(SMT::from_store(
+                current_root,
+                RewoundStore::new(current.store(), Default::default()),
+            )));
+        }
+        if let Some(nodes) = self.rewinds.get(&current_root, root) {
+            return Ok(Some(SMT::from_store(
+                *root,
+                RewoundStore::new(current.store(), nodes),
+            )));
+        }
+
+        let Some(later_notes) = self.history.notes_added_since(root) else {
+            return Ok(None);
+        };
+        let mut tree = SMT::from_store(
+            current_root,
+            RewoundStore::new(current.store(), Default::default()),
+        );
+        for note in later_notes {
+            tree.update_leaf(note, BorshableH256::from([0u8; 32]))
+                .map_err(|e| anyhow!("failed to remove note from SMT: {e}"))?;
+        }
+        if tree.root() != *root {
+            return Err(anyhow!(
+                "rewound notes tree to {}, expected {}",
+                hex_encode(tree.root().as_ref()),
+                hex_encode(root.as_ref())
+            ));
+        }
+        let store = tree.into_store();
+        self.rewinds
+            .insert(current_root, *root, store.clone().into_nodes());
+        Ok(Some(SMT::from_store(*root, store)))
+    }
+
     pub fn zkvm_witness(
         &self,
         created_note_keys: &[BorshableH256],
         nullified_keys: &[BorshableH256],
+        referenced_root: Option<BorshableH256>,
     ) -> Result<HyliUtxoZkVmState> {
         self.state
-            .to_zkvm_state(self.config.clone(), created_note_keys, nullified_keys)
+            .to_zkvm_state(
+                self.config.clone(),
+                created_note_keys,
+                nullified_keys,
+                referenced_root,
+            )
             .map_err(|e| anyhow!(e))
     }
 
-    fn apply_commitments(
+    /// Reads the notes root the SMT inclusion proof of `calldata` was built
+    /// against, if the transaction carries a well-formed proof blob.
+    fn referenced_notes_root(&self, calldata: &Calldata) -> Option<BorshableH256> {
+        let (_, blob) = calldata
+            .blobs
+            .iter()
+            .find(|(_, blob)| blob.contract_name == self.config.smt_incl_proof_contract_name)?;
+        let structured: StructuredBlobData<Vec<u8>> = blob.data.clone().try_into().ok()?;
+        parse_hyli_smt_incl_blob(&structured.parameters)
+            .ok()
+            .map(|(_, _, notes_root)| notes_root)
+    }
+
+    pub(crate) fn apply_commitments(
         &mut self,
         nullified: &[BorshableH256],
         created: &[BorshableH256],
@@ -101,18 +238,65 @@ impl HyliUtxoStateExecutor {
             self.state.record_created(created).map_err(|e| anyhow!(e))?;
         }
         self.state.update_roots();
+        self.history.record(self.state.notes_root(), created);
         Ok(())
     }
 
-    fn update_from_blob(&mut self, calldata: &Calldata) -> Result<SeparatedHyliUtxoBlob> {
+    /// Applies blob `index` of a settled transaction sequenced at
+    /// `block_height`. Returns `None` when the blob is rejected.
+    pub fn apply_settled(
+        &mut self,
+        tx: &BlobTransaction,
+        index: BlobIndex,
+        block_height: u64,
+    ) -> Option<HyliUtxoStateEvent> {
+        // Build calldata and apply the blob (update_from_blob → apply_commitments → update_roots)
+        let calldata = sdk::Calldata {
+            identity: tx.identity.clone(),
+            index,
+            blobs: tx.blobs.clone().into(),
+            tx_blob_count: tx.blobs.len(),
+            tx_hash: sdk::Hashed::hashed(tx),
+            tx_ctx: None,
+            private_input: vec![],
+        };
+        if let Err(e) = self.handle(&calldata) {
+            tracing::error!("Failed to handle blob {index} for hyli_utxo_state: {e}");
+            return None;
+        }
+        let notes_root: [u8; 32] = self.state.notes_root().into();
+        // Registration placeholders carry no hyli_utxo blob
+        let (created, nullified) = self
+            .utxo_blob_commitments(&calldata)
+            .map(|(created, nullified)| (non_padding(&created), non_padding(&nullified)))
+            .unwrap_or_default();
+        Some(HyliUtxoStateEvent {
+            tx_hash: calldata.tx_hash,
+            block_height,
+            notes_root,
+            created,
+            nullified,
+        })
+    }
+
+    pub fn config(&self) -> &ContractConfig {
+        &self.config
+    }
+
+    /// Reads the created commitments and nullifiers from the hyli_utxo blob.
+    fn utxo_blob_commitments(&self, calldata: &Calldata) -> Result<SeparatedHyliUtxoBlob> {
         let (_, utxo_blob) = calldata
             .blobs
             .iter()
             .find(|(_, blob)| blob.contract_name == self.config.utxo_contract_name)
             .ok_or_else(|| anyhow!("state blob not found in calldata"))?;
 
-        let (created, nullified) = parse_hyli_utxo_blob(&utxo_blob.data.0)
-            .map_err(|e| anyhow!("parsing HyliUtxoBlob into commitments: {e}"))?;
+        parse_hyli_utxo_blob(&utxo_blob.data.0)
+            .map_err(|e| anyhow!("parsing HyliUtxoBlob into commitments: {e}"))
+    }
+
+    fn update_from_blob(&mut self, calldata: &Calldata) -> Result<SeparatedHyliUtxoBlob> {
+        let (created, nullified) = self.utxo_blob_commitments(calldata)?;
 
         info!(
             created_len = created.len(),
@@ -142,10 +326,7 @@ impl TxExecutorHandler for HyliUtxoStateExecutor {
             ));
         };
 
-        Ok(HyliUtxoStateExecutor {
-            state: HyliUtxoState::default(),
-            config,
-        })
+        Ok(HyliUtxoStateExecutor::new(config))
     }
 
     fn build_commitment_metadata(&self, calldata: &Calldata) -> Result<Vec<u8>> {
@@ -170,7 +351,8 @@ impl TxExecutorHandler for HyliUtxoStateExecutor {
             "built hyli_utxo_state commitment metadata"
         );
 
-        let witness = self.zkvm_witness(&created, &nullified)?;
+        let witness =
+            self.zkvm_witness(&created, &nullified, self.referenced_notes_root(calldata))?;
         let batch = HyliUtxoZkVmBatch::from_state(witness);
         borsh::to_vec(&batch).context("serializing HyliUtxoZkVmBatch")
     }
@@ -255,19 +437,37 @@ impl TxExecutorHandler for HyliUtxoStateExecutor {
     }
 }
 
-// ---- ContractHandler (SMT witness API) ----
+fn non_padding(keys: &[BorshableH256]) -> Vec<[u8; 32]> {
+    keys.iter()
+        .map(|key| <[u8; 32]>::from(key.0))
+        .filter(|key| *key != [0u8; 32])
+        .collect()
+}
+
+// ---- Indexer API (SMT witnesses) ----
+
+type HandlerError = (StatusCode, Json<ErrorResponse>);
+
+fn handler_error(status: StatusCode, code: ErrorCode, message: impl Into<String>) -> HandlerError {
+    (
+        status,
+        Json(ErrorResponse {
+            error: message.into(),
+            code,
+        }),
+    )
+}
 
-#[derive(serde::Deserialize, utoipa::IntoParams)]
-struct SmtWitnessQuery {
-    commitment0: String,
-    commitment1: Option<String>,
+fn bad_request(message: String) -> HandlerError {
+    handler_error(StatusCode::BAD_REQUEST, ErrorCode::BadRequest, message)
 }
 
-#[derive(serde::Serialize, utoipa::ToSchema)]
-struct SmtWitnessResponse {
-    notes_root: String,
-    siblings_0: Vec<String>,
-    siblings_1: Vec<String>,
+fn not_initialized() -> HandlerError {
+    handler_error(
+        StatusCode::SERVICE_UNAVAILABLE,
+        ErrorCode::Internal,
+        "State not yet initialized",
+    )
 }
 
 fn parse_hex32(hex_str: &str) -> Result<BorshableH256, String> {
@@ -291,25 +491,24 @@ fn parse_hex32(hex_str: &str) -> Result<BorshableH256, String> {
 )]
 async fn get_smt_witness(
     Query(params): Query<SmtWitnessQuery>,
-    State(store): State<ContractHandlerStore<HyliUtxoStateExecutor>>,
-) -> Result<Json<SmtWitnessResponse>, (axum::http::StatusCode, String)> {
+    State(store): State<UtxoStateStore>,
+) -> Result<Json<SmtWitnessResponse>, HandlerError> {
     let store = store.read().await;
-    let executor = store.state.as_ref().ok_or_else(|| {
-        (
-            axum::http::StatusCode::SERVICE_UNAVAILABLE,
-            "State not yet initialized".to_string(),
-        )
-    })?;
+    let executor = store.as_ref().ok_or_else(not_initialized)?;
 
-    let c0 =
-        parse_hex32(&params.commitment0).map_err(|e| (axum::http::StatusCode::BAD_REQUEST, e))?;
+    let c0 = parse_hex32(&params.commitment0).map_err(bad_request)?;
     let c1 = match params.commitment1.as_deref() {
-        Some(s) => parse_hex32(s).map_err(|e| (axum::http::StatusCode::BAD_REQUEST, e))?,
+        Some(s) => parse_hex32(s).map_err(bad_request)?,
         None => BorshableH256::from([0u8; 32]),
     };
 
-    let notes_root = executor.utxo_state().notes_root();
-    let (s0, s1) = executor.utxo_state().build_smt_witnesses(c0, c1);
+    let (notes_root, s0, s1) = with_notes_tree(executor, params.notes_root.as_deref(), |tree| {
+        (
+            tree.root(),
+            build_siblings(tree, c0),
+            build_siblings(tree, c1),
+        )
+    })?;
 
     Ok(Json(SmtWitnessResponse {
         notes_root: hex::encode(notes_root.as_ref()),
@@ -324,35 +523,260 @@ async fn get_smt_witness(
     }))
 }
 
-impl ContractHandler<HyliUtxoStateEvent> for HyliUtxoStateExecutor {
-    fn handle_transaction_success(
-        &mut self,
-        tx: &BlobTransaction,
-        index: BlobIndex,
-        _tx_context: Arc<TxContext>,
-    ) -> Result<Option<HyliUtxoStateEvent>> {
-        // Build calldata and apply the blob (update_from_blob → apply_commitments → update_roots)
-        let calldata = sdk::Calldata {
-            identity: tx.identity.clone(),
-            index,
-            blobs: tx.blobs.clone().into(),
-            tx_blob_count: tx.blobs.len(),
-            tx_hash: sdk::Hashed::hashed(tx),
-            tx_ctx: None,
-            private_input: vec![],
-        };
-        if let Err(e) = self.handle(&calldata) {
-            tracing::error!("Failed to handle blob {index} for hyli_utxo_state: {e}");
-            return Ok(None);
+#[utoipa::path(
+    post,
+    path = "/smt-witnesses",
+    request_body = SmtWitnessesRequest,
+    responses(
+        (status = 200, description = "Compact SMT witnesses for the given commitments, against one notes root", body = SmtWitnessesResponse),
+    )
+)]
+async fn get_smt_witnesses(
+    State(store): State<UtxoStateStore>,
+    Json(request): Json<SmtWitnessesRequest>,
+) -> Result<Json<SmtWitnessesResponse>, HandlerError> {
+    if request.commitments.len() > MAX_SMT_WITNESSES {
+        return Err(bad_request(format!(
+            "at most {MAX_SMT_WITNESSES} witnesses can be requested at once, got {}",
+            request.commitments.len()
+        )));
+    }
+    let commitments = request
+        .commitments
+        .iter()
+        .map(|commitment| parse_hex32(commitment))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(bad_request)?;
+
+    // Every witness is built under the same read lock, so against one root
+    let store = store.read().await;
+    let executor = store.as_ref().ok_or_else(not_initialized)?;
+
+    let response = with_notes_tree(executor, request.notes_root.as_deref(), |tree| {
+        SmtWitnessesResponse {
+            notes_root: hex::encode(tree.root().as_ref()),
+            witnesses: commitments
+                .into_iter()
+                .map(|commitment| compact_witness(tree, commitment))
+                .collect(),
         }
-        let notes_root: [u8; 32] = self.state.notes_root().into();
-        Ok(Some(HyliUtxoStateEvent { notes_root }))
+    })?;
+    Ok(Json(response))
+}
+
+/// Runs `f` on the notes tree at `notes_root`, or on the current tree when no
+/// root is requested.
+fn with_notes_tree<R>(
+    executor: &HyliUtxoStateExecutor,
+    notes_root: Option<&str>,
+    f: impl FnOnce(&SMT<BorshableH256, RewoundStore<'_, TreeStore>>) -> R,
+) -> Result<R, HandlerError> {
+    let root = match notes_root {
+        Some(notes_root) => {
+            parse_hex32(notes_root).map_err(|e| bad_request(format!("notes_root: {e}")))?
+        }
+        None => executor.utxo_state().notes_root(),
+    };
+    let tree = executor
+        .notes_tree_at(&root)
+        .map_err(|e| {
+            handler_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                ErrorCode::Internal,
+                e.to_string(),
+            )
+        })?
+        .ok_or_else(|| {
+            handler_error(
+                StatusCode::NOT_FOUND,
+                ErrorCode::RootTooOld,
+                format!(
+                    "notes root {} is not one of the last {MAX_ROOTS} roots",
+                    hex_encode(root.as_ref())
+                ),
+            )
+        })?;
+    Ok(f(&tree))
+}
+
+fn compact_witness<S: SmtStore>(
+    tree: &SMT<BorshableH256, S>,
+    commitment: BorshableH256,
+) -> CompactSmtWitness {
+    let siblings = build_siblings(tree, commitment).map(|sibling| {
+        let mut bytes = [0u8; 32];
+        bytes.copy_from_slice(&sibling.to_be_bytes());
+        bytes
+    });
+    CompactSmtWitness::from_siblings(hex::encode(commitment.as_ref()), &siblings)
+}
+
+#[utoipa::path(
+    post,
+    path = "/note-status",
+    request_body = NoteStatusRequest,
+    responses(
+        (status = 200, description = "Whether each commitment is in the notes tree and each nullifier is spent", body = NoteStatusResponse),
+    )
+)]
+async fn get_note_status(
+    State(store): State<UtxoStateStore>,
+    Json(request): Json<NoteStatusRequest>,
+) -> Result<Json<NoteStatusResponse>, HandlerError> {
+    let key_count = request.commitments.len() + request.nullifiers.len();
+    if key_count > MAX_NOTE_STATUS_KEYS {
+        return Err(bad_request(format!(
+            "at most {MAX_NOTE_STATUS_KEYS} keys can be looked up at once, got {key_count}"
+        )));
+    }
+    let parse_all = |keys: &[String], field: &str| {
+        keys.iter()
+            .map(|key| parse_hex32(key).map_err(|e| format!("{field}: {e}")))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(bad_request)
+    };
+    let commitments = parse_all(&request.commitments, "commitments")?;
+    let nullifiers = parse_all(&request.nullifiers, "nullifiers")?;
+
+    let store = store.read().await;
+    let executor = store.as_ref().ok_or_else(not_initialized)?;
+    let state = executor.utxo_state();
+
+    Ok(Json(NoteStatusResponse {
+        notes_root: hex::encode(state.notes_root().as_ref()),
+        nullified_root: hex::encode(state.nullified_root().as_ref()),
+        commitments: commitments
+            .iter()
+            .map(|commitment| state.contains_note(commitment))
+            .collect(),
+        nullifiers: nullifiers
+            .iter()
+            .map(|nullifier| state.is_nullified(nullifier))
+            .collect(),
+    }))
+}
+
+/// Routes of the hyli-utxo-state indexer, served under
+/// `/v1/indexer/contract/{contract_name}`.
+pub fn state_api(store: UtxoStateStore) -> (axum::Router<()>, OpenApi) {
+    let (router, api) = OpenApiRouter::default()
+        .routes(routes!(get_smt_witness))
+        .routes(routes!(get_smt_witnesses))
+        .routes(routes!(get_note_status))
+        .split_for_parts();
+    (router.with_state(store), api)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compact_witness_expands_to_full_siblings() {
+        let mut state = HyliUtxoState::new();
+        let commitments: Vec<BorshableH256> = (1u8..=5)
+            .map(|byte| BorshableH256::from([byte; 32]))
+            .collect();
+        state.record_created(&commitments).unwrap();
+
+        for commitment in commitments {
+            let compact = compact_witness(state.notes_tree(), commitment);
+            let full: Vec<String> = state
+                .build_smt_witness(commitment)
+                .iter()
+                .map(|f| format!("0x{}", hex::encode(f.to_be_bytes())))
+                .collect();
+
+            assert_eq!(compact.expand().unwrap(), full);
+            // A sparse tree has far fewer non-zero siblings than levels
+            assert!(compact.siblings.len() < 16);
+        }
+    }
+
+    #[test]
+    fn serves_notes_tree_at_an_accepted_root() {
+        let mut executor = HyliUtxoStateExecutor::new(ContractConfig {
+            utxo_contract_name: "hyli_utxo".into(),
+            smt_incl_proof_contract_name: "hyli_smt_incl_proof".into(),
+            smt_contract_name: "oranj".into(),
+        });
+        let first = BorshableH256::from([1u8; 32]);
+        let later = BorshableH256::from([2u8; 32]);
+        executor.apply_commitments(&[], &[first]).unwrap();
+        let first_root = executor.utxo_state().notes_root();
+        let expected = build_siblings(executor.utxo_state().notes_tree(), first);
+        executor.apply_commitments(&[], &[later]).unwrap();
+
+        let tree = executor.notes_tree_at(&first_root).unwrap().unwrap();
+        assert_eq!(tree.root(), first_root);
+        assert_eq!(build_siblings(&tree, first), expected);
+        assert_ne!(
+            build_siblings(executor.utxo_state().notes_tree(), first),
+            expected
+        );
+        assert!(executor
+            .notes_tree_at(&BorshableH256::from([9u8; 32]))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn reuses_rewound_nodes_until_the_notes_tree_moves() {
+        let mut executor = HyliUtxoStateExecutor::new(ContractConfig {
+            utxo_contract_name: "hyli_utxo".into(),
+            smt_incl_proof_contract_name: "hyli_smt_incl_proof".into(),
+            smt_contract_name: "oranj".into(),
+        });
+        let first = BorshableH256::from([1u8; 32]);
+        let later = BorshableH256::from([2u8; 32]);
+        executor.apply_commitments(&[], &[first]).unwrap();
+        let first_root = executor.utxo_state().notes_root();
+        executor.apply_commitments(&[], &[later]).unwrap();
+        let current_root = executor.utxo_state().notes_root();
+
+        let rewound = executor.notes_tree_at(&first_root).unwrap().unwrap();
+        let expected = build_siblings(&rewound, first);
+        assert!(!rewound.contains(&later));
+        // The current tree is read in place, not changed
+        assert_eq!(executor.utxo_state().notes_root(), current_root);
+        assert!(executor.utxo_state().contains_note(&later));
+        assert!(executor.rewinds.get(&current_root, &first_root).is_some());
+
+        let cached = executor.notes_tree_at(&first_root).unwrap().unwrap();
+        assert_eq!(cached.root(), first_root);
+        assert_eq!(build_siblings(&cached, first), expected);
+
+        executor
+            .apply_commitments(&[], &[BorshableH256::from([3u8; 32])])
+            .unwrap();
+        let moved_root = executor.utxo_state().notes_root();
+        assert!(executor.rewinds.get(&moved_root, &first_root).is_none());
+        let rewound = executor.notes_tree_at(&first_root).unwrap().unwrap();
+        assert_eq!(build_siblings(&rewound, first), expected);
+    }
+
+    #[test]
+    fn refuses_witnesses_against_a_root_no_longer_accepted() {
+        let mut executor = HyliUtxoStateExecutor::new(ContractConfig {
+            utxo_contract_name: "hyli_utxo".into(),
+            smt_incl_proof_contract_name: "hyli_smt_incl_proof".into(),
+            smt_contract_name: "oranj".into(),
+        });
+        executor
+            .apply_commitments(&[], &[BorshableH256::from([1u8; 32])])
+            .unwrap();
+
+        let unknown = hex::encode([9u8; 32]);
+        let (status, Json(body)) = with_notes_tree(&executor, Some(&unknown), |_| ()).unwrap_err();
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body.code, ErrorCode::RootTooOld);
     }
 
-    async fn api(store: ContractHandlerStore<Self>) -> (axum::Router<()>, OpenApi) {
-        let (router, api) = OpenApiRouter::default()
-            .routes(routes!(get_smt_witness))
-            .split_for_parts();
-        (router.with_state(store), api)
+    #[test]
+    fn compact_witness_rejects_inconsistent_bitmap() {
+        let mut witness = CompactSmtWitness::from_siblings(String::new(), &[[1u8; 32]; 256]);
+        assert_eq!(witness.expand().unwrap().len(), 256);
+        witness.siblings.pop();
+        assert!(witness.expand().is_err());
     }
 }
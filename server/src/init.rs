@@ -67,7 +67,7 @@ pub fn hyli_utxo_state_deployment(
 ) -> ContractDeployment {
     let registration_metadata = borsh::to_vec(contract_config)
         .expect("ContractConfig should serialize for registration metadata");
-    let hyli_utxo_state = HyliUtxoState::default();
+    let hyli_utxo_state = HyliUtxoState::new();
     ContractDeployment {
         contract_name: ContractName(contract_name.to_string()),
         program_id: ProgramId(HYLI_UTXO_STATE_VK.to_vec()),
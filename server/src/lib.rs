@@ -1,13 +1,31 @@
 pub mod api;
 pub mod app;
 pub mod conf;
+pub mod da_relay;
+pub mod faucet_pow;
+pub mod faucet_quota;
 pub mod hyli_utxo_state_client;
 pub mod init;
 pub mod metrics;
 pub mod noir_prover;
 pub mod note_store;
+pub mod note_stream;
+pub mod notes_history;
+pub mod nullifier_guard;
+pub mod op_log;
+pub mod pg_store;
+pub mod proof_queue;
+pub mod proof_verification;
 pub mod prover;
+pub mod proving_scheduler;
+pub mod request_auth;
+pub mod rest_server;
 pub mod smt_incl_prover;
+pub mod smt_store;
+pub mod state_indexer;
+pub mod state_snapshot;
+pub mod state_stream;
 pub mod tx;
+pub mod tx_tracker;
 pub mod types;
 pub mod utils;
@@ -1,4 +1,9 @@
-use std::{collections::HashSet, path::PathBuf, sync::Arc, time::Duration};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 
 use anyhow::{Context, Result};
 use axum::Router;
@@ -9,25 +14,27 @@ use client_sdk::{
 };
 use hyli_modules::modules::{
     contract_listener::{ContractListener, ContractListenerConf},
-    contract_state_indexer::{ContractStateIndexer, ContractStateIndexerCtx},
+    da_listener::{DAListener, DAListenerConf},
     ModulesHandlerOptions,
 };
 use hyli_modules::{
     bus::SharedMessageBus,
     modules::{
         prover::{AutoProver, AutoProverCtx},
-        rest::{RestApi, RestApiRunContext},
         BuildApiContextInner, ModulesHandler,
     },
     utils::logger::setup_otlp,
 };
 use hyli_utxo_state::state::ContractConfig;
-use sdk::{api::NodeInfo, verifiers, ContractName, Verifier};
+use sdk::{verifiers, BlockHeight, ContractName, Verifier};
 use server::{
     api::{ApiModule, ApiModuleCtx},
     app::{FaucetApp, FaucetAppContext},
-    conf::Conf,
-    hyli_utxo_state_client::{HyliUtxoStateEvent, HyliUtxoStateExecutor},
+    conf::{Conf, IndexerBackend, NoteStoreBackend, SmtStoreBackend},
+    da_relay::{DaRelay, DaRelayCtx},
+    faucet_pow::FaucetPow,
+    faucet_quota::FaucetQuota,
+    hyli_utxo_state_client::{HyliUtxoStateExecutor, UtxoStateStore},
     init::{
         hyli_smt_incl_proof_noir_deployment, hyli_utxo_noir_deployment, hyli_utxo_state_deployment,
         init_node, ContractInit,
@@ -35,11 +42,31 @@ use server::{
     metrics::FaucetMetrics,
     noir_prover::{HyliUtxoNoirProver, HyliUtxoNoirProverCtx},
     note_store::{AddressRegistry, NoteStore},
+    note_stream::{NoteFeedCtx, NoteFeedModule},
+    nullifier_guard::{NullifierGuard, NullifierGuardModule},
+    pg_store::{self, PgNotes, PgRegistry, PgReplays},
+    proof_queue::ProofJobQueue,
+    proving_scheduler::ProvingScheduler,
+    request_auth::{LocalReplays, ReplayBackend, RequestAuthorizer},
+    rest_server::{RestServer, RestServerCtx},
     smt_incl_prover::{HyliSmtInclNoirProver, SmtInclProverCtx},
+    smt_store::{SmtDatabase, TreeBackend},
+    state_indexer::{HyliUtxoStateIndexer, HyliUtxoStateIndexerCtx},
+    state_snapshot::SnapshotStore,
+    state_stream::{StateTransitionFeed, StateTransitionFeedModule},
+    tx_tracker::{TxTracker, TxTrackerModule},
     utils::load_utxo_state_proving_key,
 };
 use tracing::{error, info};
 
+/// Timeout of the DA stream client in embedded indexer mode.
+const DA_TIMEOUT_CLIENT_SECS: u64 = 10;
+
+/// Files of the local note store backend, under the data directory.
+const NOTES_FILE: &str = "encrypted_notes.json";
+const ADDRESS_REGISTRY_FILE: &str = "address_registry.json";
+const USED_REQUESTS_FILE: &str = "signed_requests.json";
+
 #[derive(Parser, Debug)]
 #[command(version, about = "Run the zfruit faucet server", long_about = None)]
 struct Args {
@@ -62,6 +89,11 @@ struct Args {
     #[arg(long, default_value = "false")]
     pub clean_data_directory: bool,
 
+    /// Copy the JSON encrypted notes and address registry of the data
+    /// directory into the note store database, then exit
+    #[arg(long, default_value = "false")]
+    pub import_json_note_store: bool,
+
     /// Server port (overrides config)
     /// Argument used by hylix tests commands
     #[arg(long)]
@@ -91,6 +123,22 @@ async fn main() -> Result<()> {
         std::fs::remove_dir_all(&config.data_directory).context("cleaning data directory")?;
     }
 
+    if args.import_json_note_store {
+        let pool = pg_store::connect(&config.note_store_database_url)?;
+        let summary = pg_store::import_json(
+            &pool,
+            &config.data_directory.join(NOTES_FILE),
+            &config.data_directory.join(ADDRESS_REGISTRY_FILE),
+        )
+        .context("importing JSON note store")?;
+        info!(
+            notes = summary.notes,
+            registrations = summary.registrations,
+            "Imported JSON note store"
+        );
+        return Ok(());
+    }
+
     let faucet_metrics = FaucetMetrics::global(config.id.clone());
 
     let node_client = Arc::new(
@@ -154,11 +202,48 @@ async fn main() -> Result<()> {
     .await
     .context("Uploading orderbook ELF to registry")?;
 
+    let tx_tracker_path = data_directory.join("tx_tracker.json");
+    let tx_tracker = Arc::new(
+        TxTracker::with_persistence(
+            config.tx_tracker.clone(),
+            config.store_log.clone(),
+            tx_tracker_path.to_string_lossy().to_string(),
+        )
+        .context("initializing transaction tracker")?,
+    );
+
+    let proving_scheduler = Arc::new(ProvingScheduler::new(
+        config.proving_scheduler.clone(),
+        faucet_metrics.clone(),
+    ));
+
+    let utxo_proof_jobs_path = data_directory.join("hyli_utxo_proof_jobs.json");
+    let utxo_proof_jobs = Arc::new(
+        ProofJobQueue::with_persistence(
+            config.proof_queue.clone(),
+            config.store_log.clone(),
+            utxo_proof_jobs_path.to_string_lossy().to_string(),
+        )
+        .context("loading hyli_utxo proof jobs")?,
+    );
+    let smt_incl_proof_jobs_path = data_directory.join("hyli_smt_incl_proof_jobs.json");
+    let smt_incl_proof_jobs = Arc::new(
+        ProofJobQueue::with_persistence(
+            config.proof_queue.clone(),
+            config.store_log.clone(),
+            smt_incl_proof_jobs_path.to_string_lossy().to_string(),
+        )
+        .context("loading hyli_smt_incl_proof jobs")?,
+    );
+
     handler
         .build_module::<HyliUtxoNoirProver>(Arc::new(HyliUtxoNoirProverCtx {
             node: node_client.clone() as Arc<dyn NodeApiClient + Send + Sync>,
             contract: hyli_utxo_contract.clone(),
             metrics: faucet_metrics.clone(),
+            tx_tracker: tx_tracker.clone(),
+            queue: utxo_proof_jobs,
+            scheduler: proving_scheduler.clone(),
         }))
         .await
         .context("building hyli_utxo Noir prover module")?;
@@ -167,6 +252,9 @@ async fn main() -> Result<()> {
         .build_module::<HyliSmtInclNoirProver>(Arc::new(SmtInclProverCtx {
             node: node_client.clone() as Arc<dyn NodeApiClient + Send + Sync>,
             contract: hyli_smt_incl_proof_contract.clone(),
+            tx_tracker: tx_tracker.clone(),
+            queue: smt_incl_proof_jobs,
+            scheduler: proving_scheduler.clone(),
         }))
         .await
         .context("building hyli_smt_incl_proof Noir prover module")?;
@@ -177,36 +265,135 @@ async fn main() -> Result<()> {
             utxo_contract_name: config.utxo_contract_name.clone(),
             utxo_state_contract_name: config.utxo_state_contract_name.clone(),
             incl_proof_contract_name: config.smt_incl_proof_contract_name.clone(),
+            tx_tracker: tx_tracker.clone(),
         })
         .await
         .context("building faucet module")?;
 
+    handler
+        .build_module::<TxTrackerModule>(tx_tracker.clone())
+        .await
+        .context("building transaction tracker module")?;
+
+    // Filled by the hyli-utxo-state indexer, read by the nullifier guard and
+    // the API handlers
+    let utxo_state = UtxoStateStore::default();
+
+    let nullifier_guard_path = data_directory.join("pending_nullifiers.json");
+    let nullifier_guard = Arc::new(
+        NullifierGuard::with_persistence(
+            config.tx_tracker.timeout_secs,
+            utxo_state.clone(),
+            config.store_log.clone(),
+            nullifier_guard_path.to_string_lossy().to_string(),
+        )
+        .context("initializing nullifier guard")?,
+    );
+
+    handler
+        .build_module::<NullifierGuardModule>(nullifier_guard.clone())
+        .await
+        .context("building nullifier guard module")?;
+
+    let state_feed_path = data_directory.join("state_transitions.json");
+    let state_feed = Arc::new(
+        StateTransitionFeed::with_persistence(
+            config.store_log.clone(),
+            state_feed_path.to_string_lossy().to_string(),
+        )
+        .context("initializing state transition feed")?,
+    );
+
+    handler
+        .build_module::<StateTransitionFeedModule>(state_feed.clone())
+        .await
+        .context("building state transition feed module")?;
+
     let api_builder_ctx = Arc::new(BuildApiContextInner {
         router: std::sync::Mutex::new(Some(Router::new())),
         openapi: Default::default(),
     });
 
-    let note_store = if config.persist_encrypted_notes {
-        let notes_path = data_directory.join("encrypted_notes.json");
-        Arc::new(
-            NoteStore::with_persistence(None, notes_path.to_string_lossy().to_string())
-                .context("initializing note store with persistence")?,
-        )
-    } else {
-        Arc::new(NoteStore::new(None))
-    };
+    // Address registry for username -> UTXO address resolution, stored
+    // alongside the encrypted notes
+    let (note_store, address_registry, used_requests): (_, _, Box<dyn ReplayBackend>) =
+        match config.note_store_backend {
+            NoteStoreBackend::Local if config.persist_encrypted_notes => {
+                let notes_path = data_directory.join(NOTES_FILE);
+                let registry_path = data_directory.join(ADDRESS_REGISTRY_FILE);
+                (
+                    Arc::new(
+                        NoteStore::with_persistence(
+                            None,
+                            config.store_log.clone(),
+                            notes_path.to_string_lossy().to_string(),
+                        )
+                        .context("initializing note store with persistence")?,
+                    ),
+                    Arc::new(
+                        AddressRegistry::with_persistence(
+                            config.store_log.clone(),
+                            registry_path.to_string_lossy().to_string(),
+                        )
+                        .context("initializing address registry with persistence")?,
+                    ),
+                    local_used_requests(&config, &data_directory)?,
+                )
+            }
+            NoteStoreBackend::Local => (
+                Arc::new(NoteStore::new(None)),
+                Arc::new(AddressRegistry::new()),
+                local_used_requests(&config, &data_directory)?,
+            ),
+            NoteStoreBackend::Postgres => {
+                let pool = pg_store::connect(&config.note_store_database_url)?;
+                (
+                    Arc::new(NoteStore::with_backend(
+                        None,
+                        Box::new(PgNotes::new(pool.clone())),
+                    )),
+                    Arc::new(AddressRegistry::with_backend(Box::new(PgRegistry::new(
+                        pool.clone(),
+                    )))),
+                    Box::new(PgReplays::new(pool)),
+                )
+            }
+        };
+    let request_authorizer = Arc::new(RequestAuthorizer::with_backend(
+        config.signed_request_max_age_secs,
+        used_requests,
+    ));
+
+    // Notes inserted by other servers reach this server's streams by polling
+    if note_store.shared() {
+        handler
+            .build_module::<NoteFeedModule>(NoteFeedCtx {
+                store: note_store.clone(),
+                poll_interval: Duration::from_millis(config.note_feed_poll_interval_ms),
+            })
+            .await
+            .context("building note feed module")?;
+    }
 
-    // Address registry for username -> UTXO address resolution
-    // Uses same persistence setting as encrypted notes
-    let address_registry = if config.persist_encrypted_notes {
-        let registry_path = data_directory.join("address_registry.json");
-        Arc::new(
-            AddressRegistry::with_persistence(registry_path.to_string_lossy().to_string())
-                .context("initializing address registry with persistence")?,
+    // Faucet quotas are always persisted so a restart does not reset them
+    let faucet_quota_path = data_directory.join("faucet_quota.json");
+    let faucet_quota = Arc::new(
+        FaucetQuota::with_persistence(
+            config.faucet_quota.clone(),
+            config.store_log.clone(),
+            faucet_quota_path.to_string_lossy().to_string(),
         )
-    } else {
-        Arc::new(AddressRegistry::new())
-    };
+        .context("initializing faucet quotas")?,
+    );
+    let faucet_pow_path = data_directory.join("faucet_pow.json");
+    let faucet_pow = Arc::new(
+        FaucetPow::with_persistence(
+            config.faucet_pow.clone(),
+            config.store_log.clone(),
+            faucet_pow_path.to_string_lossy().to_string(),
+        )
+        .context("initializing faucet proof of work")?,
+    );
 
     handler
         .build_module::<ApiModule>(Arc::new(ApiModuleCtx {
@@ -214,9 +401,18 @@ async fn main() -> Result<()> {
             default_amount: config.default_faucet_amount,
             contract_name: ContractName(config.utxo_contract_name.clone()),
             metrics: faucet_metrics.clone(),
+            faucet_quota,
+            faucet_pow,
+            tx_tracker,
+            nullifier_guard,
+            proving_scheduler,
+            state_feed,
             note_store,
             address_registry,
+            utxo_state: utxo_state.clone(),
             max_note_payload_size: config.max_note_payload_size,
+            request_authorizer,
+            stream_heartbeat_secs: config.stream_heartbeat_secs,
             client: node_client.as_ref().clone(),
             utxo_contract_name: config.utxo_contract_name.clone(),
             utxo_state_contract_name: config.utxo_state_contract_name.clone(),
@@ -225,27 +421,73 @@ async fn main() -> Result<()> {
         .await
         .context("building API module")?;
 
-    let listener_contracts = HashSet::from([config.utxo_state_contract_name.clone().into()]);
-    handler
-        .build_module::<ContractListener>(ContractListenerConf {
-            database_url: config.indexer_database_url.clone(),
-            data_directory: config.data_directory.clone(),
-            contracts: listener_contracts,
-            poll_interval: Duration::from_secs(config.listener_poll_interval_secs),
-            replay_settled_from_start: true,
-        })
-        .await?;
+    let trees = match config.smt_store {
+        SmtStoreBackend::Memory => TreeBackend::Memory,
+        SmtStoreBackend::Disk => TreeBackend::Disk(Arc::new(
+            SmtDatabase::open(&data_directory.join("smt_store.redb"))
+                .context("opening on-disk SMT store")?,
+        )),
+    };
+
+    let snapshots = SnapshotStore::new(
+        data_directory.join("hyli_utxo_state_snapshots"),
+        config.snapshot.clone(),
+    )
+    .context("initializing hyli-utxo-state snapshots")?;
+    let restored = snapshots.load_latest(&contract_config);
+
+    match config.indexer_backend {
+        // The listener has no start height: the indexer skips the settled
+        // transactions the restored snapshot covers
+        IndexerBackend::Postgres => {
+            let listener_contracts =
+                HashSet::from([config.utxo_state_contract_name.clone().into()]);
+            handler
+                .build_module::<ContractListener>(ContractListenerConf {
+                    database_url: config.indexer_database_url.clone(),
+                    data_directory: config.data_directory.clone(),
+                    contracts: listener_contracts,
+                    poll_interval: Duration::from_secs(config.listener_poll_interval_secs),
+                    replay_settled_from_start: true,
+                })
+                .await?;
+        }
+        IndexerBackend::Embedded => {
+            handler
+                .build_module::<DaRelay>(Arc::new(DaRelayCtx {
+                    contract_name: ContractName(config.utxo_state_contract_name.clone()),
+                    node: node_client.clone() as Arc<dyn NodeApiClient + Send + Sync>,
+                }))
+                .await
+                .context("building DA relay module")?;
+            handler
+                .build_module::<DAListener>(DAListenerConf {
+                    data_directory: data_directory.clone(),
+                    da_read_from: config.da_read_from.clone(),
+                    // From the block of the last transaction in the snapshot,
+                    // whose later transactions may not be in it
+                    start_block: restored
+                        .as_ref()
+                        .map(|snapshot| BlockHeight(snapshot.position.block_height)),
+                    timeout_client_secs: DA_TIMEOUT_CLIENT_SECS,
+                })
+                .await
+                .context("building DA listener module")?;
+        }
+    }
 
     handler
-        .build_module::<ContractStateIndexer<HyliUtxoStateExecutor, HyliUtxoStateEvent>>(
-            ContractStateIndexerCtx {
-                data_directory: data_directory.clone(),
-                contract_name: ContractName(config.utxo_state_contract_name.clone()),
-                api: api_builder_ctx.clone(),
-            },
-        )
+        .build_module::<HyliUtxoStateIndexer>(HyliUtxoStateIndexerCtx {
+            contract_name: ContractName(config.utxo_state_contract_name.clone()),
+            store: utxo_state.clone(),
+            trees,
+            snapshots,
+            restored,
+            node: node_client.clone() as Arc<dyn NodeApiClient + Send + Sync>,
+            api: api_builder_ctx.clone(),
+        })
         .await
-        .context("building ContractStateIndexer for hyli-utxo-state")?;
+        .context("building hyli-utxo-state indexer")?;
 
     handler
         .build_module::<AutoProver<HyliUtxoStateExecutor, SP1Prover>>(Arc::new(AutoProverCtx {
@@ -276,20 +518,13 @@ async fn main() -> Result<()> {
         .expect("OpenAPI should be available")
         .clone();
 
-    let rest_context = RestApiRunContext::new(
-        config.rest_server_port,
-        NodeInfo {
-            id: config.id.clone(),
-            da_address: config.da_read_from.clone(),
-            pubkey: None,
-        },
-        router,
-        config.rest_server_max_body_size,
-        openapi,
-    );
-
     handler
-        .build_module::<RestApi>(rest_context)
+        .build_module::<RestServer>(RestServerCtx {
+            port: config.rest_server_port,
+            router,
+            max_body_size: config.rest_server_max_body_size,
+            openapi,
+        })
         .await
         .context("building REST API module")?;
 
@@ -308,3 +543,16 @@ async fn main() -> Result<()> {
 
     Ok(())
 }
+
+/// Signatures of the local backend are always persisted so a restart does not
+/// accept them again before they expire.
+fn local_used_requests(config: &Conf, data_directory: &Path) -> Result<Box<dyn ReplayBackend>> {
+    let path = data_directory.join(USED_REQUESTS_FILE);
+    Ok(Box::new(
+        LocalReplays::with_persistence(
+            config.store_log.clone(),
+            path.to_string_lossy().to_string(),
+        )
+        .context("initializing signed request replay cache")?,
+    ))
+}
@@ -12,6 +12,15 @@ impl FaucetMetrics {
     pub fn record_failure(&self, _reason: &'static str) {}
     pub fn track_noir_job_started(&self) {}
     pub fn track_noir_job_finished(&self) {}
+    pub fn record_proof_queue(&self, _circuit: &'static str, _queued: u64, _running: u64) {}
+    pub fn record_proof_wait(
+        &self,
+        _circuit: &'static str,
+        _priority: &'static str,
+        _wait_ms: u64,
+    ) {
+    }
+    pub fn record_proof_rejected(&self, _circuit: &'static str) {}
 }
 
 #[cfg(feature = "instrumentation")]
@@ -21,6 +30,10 @@ pub struct FaucetMetrics {
     requests_failed: Counter<u64>,
     minted_amount: Histogram<u64>,
     noir_jobs_gauge: Gauge<u64>,
+    proofs_queued: Gauge<u64>,
+    proofs_running: Gauge<u64>,
+    proof_wait_ms: Histogram<u64>,
+    proofs_rejected: Counter<u64>,
     base_labels: Vec<KeyValue>,
     current_noir_jobs: Arc<AtomicU64>,
 }
@@ -37,6 +50,10 @@ impl FaucetMetrics {
             minted_amount: meter.u64_histogram("faucet_minted_amount").build(),
             base_labels,
             noir_jobs_gauge: meter.u64_gauge("faucet_noir_jobs_inflight").build(),
+            proofs_queued: meter.u64_gauge("prover_proofs_queued").build(),
+            proofs_running: meter.u64_gauge("prover_proofs_running").build(),
+            proof_wait_ms: meter.u64_histogram("prover_proof_wait_ms").build(),
+            proofs_rejected: meter.u64_counter("prover_proofs_rejected_total").build(),
             current_noir_jobs: Arc::new(AtomicU64::new(0)),
         }
     }
@@ -68,6 +85,26 @@ impl FaucetMetrics {
         self.noir_jobs_gauge.record(current, &self.base());
     }
 
+    pub fn record_proof_queue(&self, circuit: &'static str, queued: u64, running: u64) {
+        let mut labels = self.base();
+        labels.push(KeyValue::new("circuit", circuit));
+        self.proofs_queued.record(queued, &labels);
+        self.proofs_running.record(running, &labels);
+    }
+
+    pub fn record_proof_wait(&self, circuit: &'static str, priority: &'static str, wait_ms: u64) {
+        let mut labels = self.base();
+        labels.push(KeyValue::new("circuit", circuit));
+        labels.push(KeyValue::new("priority", priority));
+        self.proof_wait_ms.record(wait_ms, &labels);
+    }
+
+    pub fn record_proof_rejected(&self, circuit: &'static str) {
+        let mut labels = self.base();
+        labels.push(KeyValue::new("circuit", circuit));
+        self.proofs_rejected.add(1, &labels);
+    }
+
     pub fn track_noir_job_finished(&self) {
         let mut current = self.current_noir_jobs.load(Ordering::Relaxed);
 
@@ -1,4 +1,4 @@
-use std::{process::Command, sync::Arc, time::Instant};
+use std::{process::Command, sync::Arc};
 
 use anyhow::{anyhow, bail, Context, Result};
 use barretenberg::Prove;
@@ -9,24 +9,35 @@ use hyli_modules::{
     modules::Module,
 };
 use sdk::{Identity, TxHash};
-use tokio::task::{JoinError, JoinSet};
-use tracing::{error, info};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    task::{JoinError, JoinSet},
+    time::Instant,
+};
+use tracing::{debug, error, info};
 use zk_primitives::{HyliUtxo, ToBytes, Utxo, HYLI_BLOB_LENGTH_BYTES};
 
 use crate::{
     init::ContractDeployment,
     metrics::FaucetMetrics,
+    note_store::current_timestamp,
+    proof_queue::ProofJobQueue,
     prover::{NoirProofArtifacts, NoirProver},
+    proving_scheduler::{Circuit, ProofPriority, ProvingScheduler},
+    tx_tracker::TxTracker,
 };
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct HyliUtxoProofJob {
     pub tx_hash: TxHash,
     pub identity: Identity,
     pub utxo: Utxo,
+    #[serde(with = "hex::serde")]
     pub blob: [u8; HYLI_BLOB_LENGTH_BYTES],
     pub tx_blob_count: u32,
     pub blob_index: u32,
+    #[serde(default)]
+    pub priority: ProofPriority,
 }
 
 impl BusMessage for HyliUtxoProofJob {
@@ -44,6 +55,9 @@ pub struct HyliUtxoNoirProverCtx {
     pub node: Arc<dyn NodeApiClient + Send + Sync>,
     pub contract: ContractDeployment,
     pub metrics: FaucetMetrics,
+    pub tx_tracker: Arc<TxTracker>,
+    pub queue: Arc<ProofJobQueue<HyliUtxoProofJob>>,
+    pub scheduler: Arc<ProvingScheduler>,
 }
 
 pub struct HyliUtxoNoirProver {
@@ -74,13 +88,20 @@ impl Module for HyliUtxoNoirProver {
     async fn run(&mut self) -> Result<()> {
         let mut proof_tasks = JoinSet::new();
 
+        for (tx_hash, queued) in self.ctx.queue.pending() {
+            info!(%tx_hash, attempts = queued.attempts, "resuming hyli_utxo proof job");
+            self.spawn_proof_job(&mut proof_tasks, tx_hash, queued.job);
+        }
+
         module_handle_messages! {
             on_self self,
             listen<HyliUtxoProofJob> job => {
-                self.metrics.track_noir_job_started();
-                let ctx = Arc::clone(&self.ctx);
-                let prover = self.prover.clone();
-                proof_tasks.spawn(async move { Self::execute_proof_job(ctx, prover, job).await });
+                let tx_hash = hex::encode(&job.tx_hash.0);
+                if self.ctx.queue.enqueue(&tx_hash, job.clone(), current_timestamp()) {
+                    self.spawn_proof_job(&mut proof_tasks, tx_hash, job);
+                } else {
+                    debug!(%tx_hash, "hyli_utxo proof job already queued");
+                }
             }
             Some(res) = proof_tasks.join_next() => {
                 self.handle_task_completion(res);
@@ -92,6 +113,31 @@ impl Module for HyliUtxoNoirProver {
 }
 
 impl HyliUtxoNoirProver {
+    fn spawn_proof_job(
+        &self,
+        proof_tasks: &mut JoinSet<Result<()>>,
+        tx_hash: String,
+        job: HyliUtxoProofJob,
+    ) {
+        self.metrics.track_noir_job_started();
+        let ctx = Arc::clone(&self.ctx);
+        let prover = self.prover.clone();
+        proof_tasks.spawn(async move {
+            let result = Self::execute_proof_job(Arc::clone(&ctx), prover, &tx_hash, job).await;
+            match &result {
+                Ok(()) => ctx.queue.complete(&tx_hash),
+                Err(err) => {
+                    let reason = format!("proving hyli_utxo: {err:#}");
+                    ctx.queue
+                        .dead_letter(&tx_hash, reason.clone(), current_timestamp());
+                    ctx.tx_tracker
+                        .record_proof_failed(&tx_hash, reason, current_timestamp());
+                }
+            }
+            result
+        });
+    }
+
     fn handle_task_completion(&self, result: Result<Result<()>, JoinError>) {
         self.metrics.track_noir_job_finished();
 
@@ -116,8 +162,17 @@ impl HyliUtxoNoirProver {
     async fn execute_proof_job(
         ctx: Arc<HyliUtxoNoirProverCtx>,
         prover: NoirProver,
+        tx_hash_str: &str,
         job: HyliUtxoProofJob,
     ) -> Result<()> {
+        let deadline = ctx
+            .queue
+            .deadline(tx_hash_str, current_timestamp())
+            .context("hyli_utxo proof job is no longer queued")?;
+        if deadline <= Instant::now() {
+            bail!("deadline passed before the hyli_utxo proof was generated");
+        }
+
         let contract = ctx.contract.clone();
         let contract_name = contract.contract_name.0.clone();
         let hyli_utxo = Self::build_hyli_utxo(&contract_name, &job)?;
@@ -130,13 +185,13 @@ impl HyliUtxoNoirProver {
             );
         }
 
-        let tx_hash_str = hex::encode(&job.tx_hash.0);
-
+        let permit = ctx.scheduler.acquire(Circuit::HyliUtxo, job.priority).await;
         let prove_start = Instant::now();
         let proof = hyli_utxo
             .prove()
             .map_err(|err| anyhow!("generating hyli_utxo Noir proof: {err}"))?;
         let prove_duration = prove_start.elapsed();
+        drop(permit);
 
         info!(
             duration_ms = prove_duration.as_millis(),
@@ -154,12 +209,14 @@ impl HyliUtxoNoirProver {
             },
         )?;
 
-        let node = Arc::clone(&ctx.node);
-        node.send_tx_proof(proof_tx)
+        ctx.queue
+            .submit_proof(tx_hash_str, &ctx.node, proof_tx, deadline)
             .await
             .context("submitting hyli_utxo Noir proof to node")?;
 
         info!(%tx_hash_str, "submitted hyli_utxo Noir proof");
+        ctx.tx_tracker
+            .record_proof_submitted(tx_hash_str, current_timestamp());
 
         Ok(())
     }
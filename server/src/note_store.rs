@@ -1,168 +1,340 @@
 use std::{
     collections::{HashMap, VecDeque},
-    fs,
-    io::{self, BufReader, BufWriter},
-    path::Path,
-    sync::{RwLock, RwLockReadGuard, RwLockWriteGuard},
+    io,
+    sync::Mutex,
     time::{SystemTime, UNIX_EPOCH},
 };
 
-use serde::{de::DeserializeOwned, Deserialize, Serialize};
-use tracing::{debug, info, warn};
+use anyhow::{Context, Result};
+use cachecash_client::types::EncryptedNoteRecord;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tracing::{debug, info};
+use zk_primitives::note_encryption::derive_recipient_tag;
+
+use crate::{
+    conf::StoreLogConf,
+    op_log::{LogState, LogStore},
+};
 
 // ============================================================================
 // Shared Utilities
 // ============================================================================
 
 /// Returns the current Unix timestamp in seconds.
-fn current_timestamp() -> u64 {
+pub(crate) fn current_timestamp() -> u64 {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .unwrap_or_default()
         .as_secs()
 }
 
+fn now_nanos() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos() as u64
+}
+
+/// Runs a call to a store off the async runtime, as the Postgres backends
+/// block on their connection pool.
+pub(crate) async fn blocking<T: Send + 'static>(
+    call: impl FnOnce() -> T + Send + 'static,
+) -> Result<T> {
+    tokio::task::spawn_blocking(call)
+        .await
+        .context("store task failed")
+}
+
 // ============================================================================
-// PersistentStore - Generic in-memory store with optional JSON file persistence
+// Address Registry - Maps usernames to UTXO addresses
 // ============================================================================
 
-/// Generic in-memory store backed by `RwLock<T>` with optional atomic JSON file persistence.
-struct PersistentStore<T> {
-    data: RwLock<T>,
-    persistence_path: Option<String>,
+/// A registered username -> UTXO address mapping.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AddressRegistration {
+    /// The username (e.g., "matteo" from "matteo@wallet")
+    pub username: String,
+    /// The UTXO address (64-char hex, derived from secret key)
+    pub utxo_address: String,
+    /// The secp256k1 public key for ECDH encryption (64-char hex, x-coordinate)
+    #[serde(default)]
+    pub encryption_pubkey: String,
+    /// Unix timestamp when registered
+    pub registered_at: u64,
+    /// Signature proving control of the registering key (absent for legacy registrations)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ownership_proof: Option<OwnershipProof>,
 }
 
-impl<T> PersistentStore<T> {
-    /// Creates a new in-memory store with no persistence.
-    fn new(data: T) -> Self {
-        Self {
-            data: RwLock::new(data),
-            persistence_path: None,
+impl AddressRegistration {
+    /// The key allowed to update or release this username: the key that signed
+    /// the first registration, or the encryption pubkey of a legacy
+    /// registration. `None` if a legacy registration has no key at all.
+    pub fn owner_pubkey(&self) -> Option<&str> {
+        match &self.ownership_proof {
+            Some(proof) => Some(&proof.owner_pubkey),
+            None if !self.encryption_pubkey.is_empty() => Some(&self.encryption_pubkey),
+            None => None,
         }
     }
+}
 
-    fn read(&self) -> RwLockReadGuard<'_, T> {
-        self.data.read().expect("store lock poisoned")
+/// Signed request that created or last updated an [`AddressRegistration`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OwnershipProof {
+    /// secp256k1 x-only pubkey (64-char hex) owning the username
+    pub owner_pubkey: String,
+    /// Hex-encoded recoverable signature over the registration message
+    pub signature: String,
+    /// Unix timestamp included in the signed message
+    pub signed_at: u64,
+}
+
+/// Why the registry refused to change a registration.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum RegistrationError {
+    #[error("username '{0}' is not registered")]
+    NotRegistered(String),
+    #[error("username '{0}' is owned by another key")]
+    NotOwner(String),
+    #[error("UTXO address {0} is registered by another key")]
+    AddressTaken(String),
+    #[error("address registry storage failed: {0}")]
+    Storage(String),
+}
+
+impl From<anyhow::Error> for RegistrationError {
+    fn from(err: anyhow::Error) -> Self {
+        Self::Storage(format!("{err:#}"))
     }
+}
+
+/// Computes the new registration of a username from its current one. `None`
+/// removes the username.
+pub type RegistrationUpdate<'a> =
+    &'a mut dyn FnMut(
+        Option<&AddressRegistration>,
+    ) -> Result<Option<AddressRegistration>, RegistrationError>;
+
+/// Storage of the registrations behind an [`AddressRegistry`]. Usernames are
+/// already normalized.
+pub trait RegistryBackend: Send + Sync {
+    fn get(&self, username: &str) -> Result<Option<AddressRegistration>>;
+
+    /// Replaces the registration of `username` by the result of `update`,
+    /// atomically with respect to other updates. Returns the registration it
+    /// replaced. Fails with [`RegistrationError::AddressTaken`] if another
+    /// username registers the new UTXO address under a different owner (see
+    /// [`check_address_owner`]).
+    fn update(
+        &self,
+        username: &str,
+        update: RegistrationUpdate<'_>,
+    ) -> Result<Option<AddressRegistration>, RegistrationError>;
+
+    /// Registrations whose UTXO address derives `recipient_tag`.
+    fn with_recipient_tag(&self, recipient_tag: &str) -> Result<Vec<AddressRegistration>>;
+
+    fn count(&self) -> Result<usize>;
 
-    fn write(&self) -> RwLockWriteGuard<'_, T> {
-        self.data.write().expect("store lock poisoned")
+    fn list_all(&self) -> Result<Vec<AddressRegistration>>;
+}
+
+/// Refuses `registration` if one of `others` registers the same UTXO address
+/// under another owner key.
+///
+/// Nothing proves that a registrant holds the secret key of the address, so
+/// the first key to register an address stays its only owner and alone can
+/// register it under other usernames.
+pub(crate) fn check_address_owner<'a>(
+    registration: &AddressRegistration,
+    others: impl IntoIterator<Item = &'a AddressRegistration>,
+) -> Result<(), RegistrationError> {
+    let owner = registration.owner_pubkey();
+    let taken = others.into_iter().any(|other| {
+        other.username != registration.username
+            && other.utxo_address == registration.utxo_address
+            && other.owner_pubkey() != owner
+    });
+    if taken {
+        return Err(RegistrationError::AddressTaken(
+            registration.utxo_address.clone(),
+        ));
     }
+    Ok(())
 }
 
-impl<T: Serialize + DeserializeOwned + Default> PersistentStore<T> {
-    /// Creates a store with file persistence. Loads existing data from disk if the file exists.
-    fn with_persistence(path: String) -> io::Result<Self> {
-        let store = Self {
-            data: RwLock::new(T::default()),
-            persistence_path: Some(path.clone()),
-        };
+/// A change to the registrations kept by a [`LocalRegistry`].
+#[derive(Serialize, Deserialize)]
+pub enum RegistryOp {
+    Put {
+        username: String,
+        registration: AddressRegistration,
+    },
+    Remove {
+        username: String,
+    },
+}
 
-        if Path::new(&path).exists() {
-            store.load_from_file()?;
-        }
+impl LogState for HashMap<String, AddressRegistration> {
+    type Op = RegistryOp;
 
-        Ok(store)
+    fn apply(&mut self, op: RegistryOp) {
+        match op {
+            RegistryOp::Put {
+                username,
+                registration,
+            } => {
+                self.insert(username, registration);
+            }
+            RegistryOp::Remove { username } => {
+                self.remove(&username);
+            }
+        }
     }
+}
 
-    /// Persists to disk if persistence is enabled (atomic write via temp file + rename).
-    fn maybe_persist(&self) -> io::Result<()> {
-        let Some(ref path) = self.persistence_path else {
-            return Ok(());
-        };
-        let data = self.data.read().expect("store lock poisoned");
-        let temp_path = format!("{}.tmp", path);
-        let file = fs::File::create(&temp_path)?;
-        let writer = BufWriter::new(file);
-        serde_json::to_writer(writer, &*data).map_err(io::Error::other)?;
-        fs::rename(&temp_path, path)?;
-        debug!(path = %path, "Persisted store to disk");
-        Ok(())
-    }
-
-    /// Loads state from the persistence file, replacing current in-memory data.
-    fn load_from_file(&self) -> io::Result<()> {
-        let Some(ref path) = self.persistence_path else {
-            return Ok(());
-        };
+/// Registrations kept in memory, optionally persisted to a checkpoint and an
+/// operation log.
+pub struct LocalRegistry {
+    store: LogStore<HashMap<String, AddressRegistration>>,
+}
 
-        let file = fs::File::open(path)?;
-        let reader = BufReader::new(file);
-        let loaded: T = serde_json::from_reader(reader)
-            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+impl LocalRegistry {
+    pub fn new() -> Self {
+        Self {
+            store: LogStore::new(HashMap::new()),
+        }
+    }
 
-        let mut data = self.data.write().expect("store lock poisoned");
-        *data = loaded;
+    pub fn with_persistence(conf: StoreLogConf, persistence_path: String) -> io::Result<Self> {
+        let store = LogStore::<HashMap<_, _>>::open(persistence_path, conf)?;
+        let count = store.read().len();
+        if count > 0 {
+            info!(registrations = count, "Loaded address registry from disk");
+        }
+        Ok(Self { store })
+    }
+}
 
-        Ok(())
+impl Default for LocalRegistry {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
-// ============================================================================
-// Address Registry - Maps usernames to UTXO addresses
-// ============================================================================
+impl RegistryBackend for LocalRegistry {
+    fn get(&self, username: &str) -> Result<Option<AddressRegistration>> {
+        Ok(self.store.read().get(username).cloned())
+    }
 
-/// A registered username -> UTXO address mapping.
-#[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct AddressRegistration {
-    /// The username (e.g., "matteo" from "matteo@wallet")
-    pub username: String,
-    /// The UTXO address (64-char hex, derived from secret key)
-    pub utxo_address: String,
-    /// The secp256k1 public key for ECDH encryption (64-char hex, x-coordinate)
-    #[serde(default)]
-    pub encryption_pubkey: String,
-    /// Unix timestamp when registered
-    pub registered_at: u64,
+    fn update(
+        &self,
+        username: &str,
+        update: RegistrationUpdate<'_>,
+    ) -> Result<Option<AddressRegistration>, RegistrationError> {
+        let mut registry = self.store.write();
+        let previous = registry.get(username).cloned();
+        let op = match update(previous.as_ref())? {
+            Some(registration) => {
+                check_address_owner(&registration, registry.values())?;
+                RegistryOp::Put {
+                    username: username.to_string(),
+                    registration,
+                }
+            }
+            None if previous.is_some() => RegistryOp::Remove {
+                username: username.to_string(),
+            },
+            None => return Ok(None),
+        };
+        registry.apply(op).map_err(anyhow::Error::from)?;
+        Ok(previous)
+    }
+
+    fn with_recipient_tag(&self, recipient_tag: &str) -> Result<Vec<AddressRegistration>> {
+        Ok(self
+            .store
+            .read()
+            .values()
+            .filter(|registration| {
+                derive_recipient_tag(&registration.utxo_address)
+                    .is_ok_and(|tag| tag == recipient_tag)
+            })
+            .cloned()
+            .collect())
+    }
+
+    fn count(&self) -> Result<usize> {
+        Ok(self.store.read().len())
+    }
+
+    fn list_all(&self) -> Result<Vec<AddressRegistration>> {
+        Ok(self.store.read().values().cloned().collect())
+    }
 }
 
-/// In-memory registry for username -> UTXO address mappings.
+/// Registry for username -> UTXO address mappings.
 pub struct AddressRegistry {
-    store: PersistentStore<HashMap<String, AddressRegistration>>,
+    backend: Box<dyn RegistryBackend>,
 }
 
 impl AddressRegistry {
     /// Creates a new in-memory address registry.
     pub fn new() -> Self {
-        Self {
-            store: PersistentStore::new(HashMap::new()),
-        }
+        Self::with_backend(Box::new(LocalRegistry::new()))
     }
 
     /// Creates an address registry with file persistence enabled.
-    pub fn with_persistence(persistence_path: String) -> io::Result<Self> {
-        let store = PersistentStore::<HashMap<_, _>>::with_persistence(persistence_path)?;
-        let count = store.read().len();
-        if count > 0 {
-            info!(registrations = count, "Loaded address registry from disk");
-        }
-        Ok(Self { store })
+    pub fn with_persistence(conf: StoreLogConf, persistence_path: String) -> io::Result<Self> {
+        Ok(Self::with_backend(Box::new(
+            LocalRegistry::with_persistence(conf, persistence_path)?,
+        )))
+    }
+
+    /// Creates an address registry stored in `backend`.
+    pub fn with_backend(backend: Box<dyn RegistryBackend>) -> Self {
+        Self { backend }
     }
 
     /// Registers a username -> UTXO address mapping.
-    /// Returns the previous registration if the username was already registered.
+    ///
+    /// `proof.owner_pubkey` must own any existing registration; the signature
+    /// itself is checked by the caller. Returns the previous registration if
+    /// the username was already registered.
     pub fn register(
         &self,
         username: String,
         utxo_address: String,
         encryption_pubkey: String,
-    ) -> Option<AddressRegistration> {
+        proof: OwnershipProof,
+    ) -> Result<Option<AddressRegistration>, RegistrationError> {
         let normalized_username = username.to_lowercase();
-        let registration = AddressRegistration {
-            username: normalized_username.clone(),
-            utxo_address,
-            encryption_pubkey,
-            registered_at: current_timestamp(),
-        };
-
-        let previous = {
-            let mut registry = self.store.write();
-            registry.insert(normalized_username.clone(), registration)
-        };
 
-        if let Err(err) = self.store.maybe_persist() {
-            warn!(error = %err, "Failed to persist address registry");
-        }
+        let previous = self.backend.update(&normalized_username, &mut |existing| {
+            if existing
+                .and_then(AddressRegistration::owner_pubkey)
+                .is_some_and(|owner| owner != proof.owner_pubkey)
+            {
+                return Err(RegistrationError::NotOwner(normalized_username.clone()));
+            }
+            // Keep the original owner across updates
+            let owner_pubkey = existing
+                .and_then(AddressRegistration::owner_pubkey)
+                .map_or_else(|| proof.owner_pubkey.clone(), str::to_string);
+
+            Ok(Some(AddressRegistration {
+                username: normalized_username.clone(),
+                utxo_address: utxo_address.clone(),
+                encryption_pubkey: encryption_pubkey.clone(),
+                registered_at: current_timestamp(),
+                ownership_proof: Some(OwnershipProof {
+                    owner_pubkey,
+                    ..proof.clone()
+                }),
+            }))
+        })?;
 
         if previous.is_some() {
             info!(username = %normalized_username, "Updated existing address registration");
@@ -170,37 +342,76 @@ impl AddressRegistry {
             info!(username = %normalized_username, "New address registration");
         }
 
-        previous
+        Ok(previous)
     }
 
     /// Resolves a username to its UTXO address.
-    pub fn resolve(&self, username: &str) -> Option<AddressRegistration> {
-        let normalized = username.to_lowercase();
-        self.store.read().get(&normalized).cloned()
+    pub fn resolve(&self, username: &str) -> Result<Option<AddressRegistration>> {
+        self.backend.get(&username.to_lowercase())
+    }
+
+    /// Returns the owner key of the UTXO address deriving `recipient_tag`
+    /// (normalized, lowercase hex), `None` if no registration uses it. Fails
+    /// with [`RegistrationError::AddressTaken`] if registrations made before
+    /// addresses had a single owner disagree on it.
+    pub fn address_owner_for_tag(
+        &self,
+        recipient_tag: &str,
+    ) -> Result<Option<String>, RegistrationError> {
+        let registrations = self.backend.with_recipient_tag(recipient_tag)?;
+        let mut owners = registrations
+            .iter()
+            .filter_map(AddressRegistration::owner_pubkey);
+        let Some(owner) = owners.next() else {
+            return Ok(None);
+        };
+        if owners.any(|other| other != owner) {
+            return Err(RegistrationError::AddressTaken(
+                registrations[0].utxo_address.clone(),
+            ));
+        }
+        Ok(Some(owner.to_string()))
     }
 
-    /// Removes a username registration.
-    pub fn unregister(&self, username: &str) -> Option<AddressRegistration> {
+    /// Removes a username registration on behalf of its owner.
+    pub fn release(
+        &self,
+        username: &str,
+        owner_pubkey: &str,
+    ) -> Result<AddressRegistration, RegistrationError> {
         let normalized = username.to_lowercase();
-        let removed = self.store.write().remove(&normalized);
+        let removed = self
+            .backend
+            .update(&normalized, &mut |existing| {
+                let registration =
+                    existing.ok_or_else(|| RegistrationError::NotRegistered(normalized.clone()))?;
+                if registration.owner_pubkey() != Some(owner_pubkey) {
+                    return Err(RegistrationError::NotOwner(normalized.clone()));
+                }
+                Ok(None)
+            })?
+            .expect("registration checked above");
+        info!(username = %normalized, "Released address registration");
 
-        if removed.is_some() {
-            if let Err(err) = self.store.maybe_persist() {
-                warn!(error = %err, "Failed to persist address registry after removal");
-            }
-        }
+        Ok(removed)
+    }
 
-        removed
+    /// Removes a username registration without ownership checks.
+    pub fn unregister(&self, username: &str) -> Result<Option<AddressRegistration>> {
+        let normalized = username.to_lowercase();
+        self.backend
+            .update(&normalized, &mut |_| Ok(None))
+            .map_err(anyhow::Error::from)
     }
 
     /// Returns the total number of registered usernames.
-    pub fn count(&self) -> usize {
-        self.store.read().len()
+    pub fn count(&self) -> Result<usize> {
+        self.backend.count()
     }
 
     /// Lists all registrations (for admin/debug purposes).
-    pub fn list_all(&self) -> Vec<AddressRegistration> {
-        self.store.read().values().cloned().collect()
+    pub fn list_all(&self) -> Result<Vec<AddressRegistration>> {
+        self.backend.list_all()
     }
 }
 
@@ -217,6 +428,9 @@ impl Default for AddressRegistry {
 /// Maximum number of notes stored per recipient tag (FIFO eviction).
 const DEFAULT_MAX_NOTES_PER_RECIPIENT: usize = 1000;
 
+/// Inserted notes buffered for slow subscribers before they lag.
+const NOTE_EVENTS_CAPACITY: usize = 1024;
+
 /// An encrypted note stored on the server.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct StoredEncryptedNote {
@@ -232,29 +446,150 @@ pub struct StoredEncryptedNote {
     pub stored_at: u64,
 }
 
-/// In-memory storage for encrypted notes with optional file persistence.
-pub struct NoteStore {
-    store: PersistentStore<HashMap<String, VecDeque<StoredEncryptedNote>>>,
-    /// Maximum notes per recipient before FIFO eviction.
-    max_notes_per_recipient: usize,
+impl From<StoredEncryptedNote> for EncryptedNoteRecord {
+    fn from(note: StoredEncryptedNote) -> Self {
+        Self {
+            id: note.id,
+            encrypted_payload: note.encrypted_payload,
+            ephemeral_pubkey: note.ephemeral_pubkey,
+            sender_tag: note.sender_tag,
+            stored_at: note.stored_at,
+        }
+    }
 }
 
-impl NoteStore {
-    /// Creates a new in-memory note store.
-    pub fn new(max_notes_per_recipient: Option<usize>) -> Self {
+/// A note broadcast to [`NoteStore::subscribe`]rs as it is inserted.
+#[derive(Clone, Debug)]
+pub struct InsertedNote {
+    pub recipient_tag: String,
+    pub note: StoredEncryptedNote,
+    /// Position of the note in the insertion order of its backend, sent as
+    /// the stream event id
+    pub cursor: String,
+}
+
+/// Storage of the notes behind a [`NoteStore`]. Recipient tags are already
+/// normalized.
+///
+/// Cursors are fixed-width lowercase hex, so comparing them as strings
+/// follows the insertion order.
+pub trait NoteBackend: Send + Sync {
+    /// Stores `note` for `recipient_tag`, then evicts the oldest notes of the
+    /// recipient beyond `max_notes`. Returns the cursor of the note.
+    fn insert(
+        &self,
+        recipient_tag: &str,
+        note: StoredEncryptedNote,
+        max_notes: usize,
+    ) -> Result<String>;
+
+    /// Notes of `recipient_tags` inserted after `cursor`, in insertion order.
+    fn notes_after(&self, recipient_tags: &[String], cursor: &str) -> Result<Vec<InsertedNote>>;
+
+    /// The first `limit` notes of any recipient inserted after `cursor`, in
+    /// insertion order.
+    fn all_notes_after(&self, cursor: &str, limit: usize) -> Result<Vec<InsertedNote>>;
+
+    /// A cursor sorting before every note inserted from now on.
+    fn cursor_now(&self) -> Result<String>;
+
+    /// Whether other servers insert into the same storage. Their notes reach
+    /// subscribers only through a [`NoteFeedModule`](crate::note_stream::NoteFeedModule).
+    fn shared(&self) -> bool {
+        false
+    }
+
+    /// The first `limit` notes of `recipient_tag` stored after `since`, in id
+    /// order, and whether more are left.
+    fn get_notes(
+        &self,
+        recipient_tag: &str,
+        since: Option<u64>,
+        limit: usize,
+    ) -> Result<(Vec<StoredEncryptedNote>, bool)>;
+
+    /// Returns whether the note existed.
+    fn delete_note(&self, recipient_tag: &str, note_id: &str) -> Result<bool>;
+
+    fn total_notes(&self) -> Result<usize>;
+
+    fn recipient_count(&self) -> Result<usize>;
+}
+
+/// A change to the notes kept by a [`LocalNotes`].
+#[derive(Serialize, Deserialize)]
+pub enum NoteOp {
+    /// Adds a note, evicting the oldest ones of the recipient beyond
+    /// `max_notes`
+    Insert {
+        recipient_tag: String,
+        note: StoredEncryptedNote,
+        max_notes: usize,
+    },
+    Delete {
+        recipient_tag: String,
+        note_id: String,
+    },
+}
+
+impl LogState for HashMap<String, VecDeque<StoredEncryptedNote>> {
+    type Op = NoteOp;
+
+    fn apply(&mut self, op: NoteOp) {
+        match op {
+            NoteOp::Insert {
+                recipient_tag,
+                note,
+                max_notes,
+            } => {
+                let queue = self.entry(recipient_tag.clone()).or_default();
+
+                // FIFO eviction if at capacity
+                while queue.len() >= max_notes {
+                    if let Some(evicted) = queue.pop_front() {
+                        debug!(
+                            recipient_tag = %recipient_tag,
+                            evicted_id = %evicted.id,
+                            "Evicted oldest note due to capacity limit"
+                        );
+                    }
+                }
+
+                queue.push_back(note);
+            }
+            NoteOp::Delete {
+                recipient_tag,
+                note_id,
+            } => {
+                let Some(queue) = self.get_mut(&recipient_tag) else {
+                    return;
+                };
+                queue.retain(|note| note.id != note_id);
+
+                // Clean up empty queues
+                if queue.is_empty() {
+                    self.remove(&recipient_tag);
+                }
+            }
+        }
+    }
+}
+
+/// Notes kept in memory, optionally persisted to a checkpoint and an
+/// operation log.
+pub struct LocalNotes {
+    store: LogStore<HashMap<String, VecDeque<StoredEncryptedNote>>>,
+}
+
+impl LocalNotes {
+    pub fn new() -> Self {
         Self {
-            store: PersistentStore::new(HashMap::new()),
-            max_notes_per_recipient: max_notes_per_recipient
-                .unwrap_or(DEFAULT_MAX_NOTES_PER_RECIPIENT),
+            store: LogStore::new(HashMap::new()),
         }
     }
 
-    /// Creates a note store with file persistence enabled.
-    pub fn with_persistence(
-        max_notes_per_recipient: Option<usize>,
-        persistence_path: String,
-    ) -> io::Result<Self> {
-        let store = PersistentStore::<HashMap<_, VecDeque<_>>>::with_persistence(persistence_path)?;
+    pub fn with_persistence(conf: StoreLogConf, persistence_path: String) -> io::Result<Self> {
+        let store = LogStore::<HashMap<_, VecDeque<_>>>::open(persistence_path, conf)?;
         {
             let notes = store.read();
             let total: usize = notes.values().map(|q| q.len()).sum();
@@ -266,25 +601,218 @@ impl NoteStore {
                 );
             }
         }
-        Ok(Self {
-            store,
+        Ok(Self { store })
+    }
+}
+
+impl Default for LocalNotes {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Notes of `queues` whose id is greater than `cursor`, in id order. Ids are
+/// the cursors of the local backend.
+fn local_notes_after<'a>(
+    queues: impl Iterator<Item = (&'a String, &'a VecDeque<StoredEncryptedNote>)>,
+    cursor: &str,
+) -> Vec<InsertedNote> {
+    let mut inserted: Vec<_> = queues
+        .flat_map(|(tag, queue)| {
+            queue
+                .iter()
+                .filter(|note| note.id.as_str() > cursor)
+                .map(|note| InsertedNote {
+                    recipient_tag: tag.clone(),
+                    note: note.clone(),
+                    cursor: note.id.clone(),
+                })
+        })
+        .collect();
+    inserted.sort_by(|a, b| a.cursor.cmp(&b.cursor));
+    inserted
+}
+
+impl NoteBackend for LocalNotes {
+    fn insert(
+        &self,
+        recipient_tag: &str,
+        note: StoredEncryptedNote,
+        max_notes: usize,
+    ) -> Result<String> {
+        let cursor = note.id.clone();
+        self.store.write().apply(NoteOp::Insert {
+            recipient_tag: recipient_tag.to_string(),
+            note,
+            max_notes,
+        })?;
+        Ok(cursor)
+    }
+
+    fn notes_after(&self, recipient_tags: &[String], cursor: &str) -> Result<Vec<InsertedNote>> {
+        let notes = self.store.read();
+        let queues = recipient_tags
+            .iter()
+            .filter_map(|tag| notes.get_key_value(tag));
+        Ok(local_notes_after(queues, cursor))
+    }
+
+    fn all_notes_after(&self, cursor: &str, limit: usize) -> Result<Vec<InsertedNote>> {
+        let mut inserted = local_notes_after(self.store.read().iter(), cursor);
+        inserted.truncate(limit);
+        Ok(inserted)
+    }
+
+    fn cursor_now(&self) -> Result<String> {
+        Ok(format!("{:016x}{:08x}", now_nanos(), 0))
+    }
+
+    fn get_notes(
+        &self,
+        recipient_tag: &str,
+        since: Option<u64>,
+        limit: usize,
+    ) -> Result<(Vec<StoredEncryptedNote>, bool)> {
+        let notes = self.store.read();
+
+        let Some(queue) = notes.get(recipient_tag) else {
+            return Ok((Vec::new(), false));
+        };
+
+        let filtered: Vec<_> = queue
+            .iter()
+            .filter(|note| {
+                if let Some(since_ts) = since {
+                    note.stored_at > since_ts
+                } else {
+                    true
+                }
+            })
+            .cloned()
+            .collect();
+
+        let has_more = filtered.len() > limit;
+        let result = filtered.into_iter().take(limit).collect();
+
+        Ok((result, has_more))
+    }
+
+    fn delete_note(&self, recipient_tag: &str, note_id: &str) -> Result<bool> {
+        let mut notes = self.store.write();
+        let exists = notes
+            .get(recipient_tag)
+            .is_some_and(|queue| queue.iter().any(|note| note.id == note_id));
+        if !exists {
+            return Ok(false);
+        }
+
+        notes.apply(NoteOp::Delete {
+            recipient_tag: recipient_tag.to_string(),
+            note_id: note_id.to_string(),
+        })?;
+        Ok(true)
+    }
+
+    fn total_notes(&self) -> Result<usize> {
+        Ok(self.store.read().values().map(|q| q.len()).sum())
+    }
+
+    fn recipient_count(&self) -> Result<usize> {
+        Ok(self.store.read().len())
+    }
+}
+
+/// Storage for encrypted notes with FIFO eviction per recipient.
+///
+/// Note ids are fixed-width hex of the insertion time and a counter, so they
+/// sort in insertion order and double as the cursors of the local backend.
+pub struct NoteStore {
+    backend: Box<dyn NoteBackend>,
+    /// Maximum notes per recipient before FIFO eviction.
+    max_notes_per_recipient: usize,
+    events: broadcast::Sender<InsertedNote>,
+    /// Held while a note is generated, stored and broadcast
+    insert_lock: Mutex<()>,
+}
+
+impl NoteStore {
+    /// Creates a new in-memory note store.
+    pub fn new(max_notes_per_recipient: Option<usize>) -> Self {
+        Self::with_backend(max_notes_per_recipient, Box::new(LocalNotes::new()))
+    }
+
+    /// Creates a note store with file persistence enabled.
+    pub fn with_persistence(
+        max_notes_per_recipient: Option<usize>,
+        conf: StoreLogConf,
+        persistence_path: String,
+    ) -> io::Result<Self> {
+        Ok(Self::with_backend(
+            max_notes_per_recipient,
+            Box::new(LocalNotes::with_persistence(conf, persistence_path)?),
+        ))
+    }
+
+    /// Creates a note store keeping its notes in `backend`.
+    pub fn with_backend(
+        max_notes_per_recipient: Option<usize>,
+        backend: Box<dyn NoteBackend>,
+    ) -> Self {
+        Self {
+            backend,
             max_notes_per_recipient: max_notes_per_recipient
                 .unwrap_or(DEFAULT_MAX_NOTES_PER_RECIPIENT),
-        })
+            events: broadcast::channel(NOTE_EVENTS_CAPACITY).0,
+            insert_lock: Mutex::new(()),
+        }
     }
 
     /// Generates a unique note ID.
     fn generate_id() -> String {
         use std::sync::atomic::{AtomicU64, Ordering};
         static COUNTER: AtomicU64 = AtomicU64::new(0);
+        static LAST_NANOS: AtomicU64 = AtomicU64::new(0);
+
+        // Never behind the previous id, should the clock step back
+        let mut last = LAST_NANOS.load(Ordering::SeqCst);
+        let nanos = loop {
+            let next = now_nanos().max(last + 1);
+            match LAST_NANOS.compare_exchange(last, next, Ordering::SeqCst, Ordering::SeqCst) {
+                Ok(_) => break next,
+                Err(current) => last = current,
+            }
+        };
+        let counter = COUNTER.fetch_add(1, Ordering::SeqCst);
+        format!("{:016x}{:08x}", nanos, counter as u32)
+    }
 
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_nanos() as u64;
+    /// A cursor sorting before every note inserted from now on.
+    pub fn cursor_now(&self) -> Result<String> {
+        self.backend.cursor_now()
+    }
 
-        let counter = COUNTER.fetch_add(1, Ordering::SeqCst);
-        format!("{:016x}{:08x}", timestamp, counter as u32)
+    /// Whether notes inserted by other servers must be read back by a
+    /// [`NoteFeedModule`](crate::note_stream::NoteFeedModule).
+    pub fn shared(&self) -> bool {
+        self.backend.shared()
+    }
+
+    /// Subscribes to notes as they are inserted through this store, or read
+    /// back by [`Self::publish_after`] for a shared backend.
+    pub fn subscribe(&self) -> broadcast::Receiver<InsertedNote> {
+        self.events.subscribe()
+    }
+
+    /// Broadcasts the first `limit` notes inserted after `cursor` and returns
+    /// the cursor of the last one.
+    pub fn publish_after(&self, cursor: &str, limit: usize) -> Result<Option<String>> {
+        let notes = self.backend.all_notes_after(cursor, limit)?;
+        let last = notes.last().map(|inserted| inserted.cursor.clone());
+        for inserted in notes {
+            // Fails only when nobody is subscribed
+            let _ = self.events.send(inserted);
+        }
+        Ok(last)
     }
 
     /// Inserts a new encrypted note for a recipient.
@@ -296,41 +824,46 @@ impl NoteStore {
         encrypted_payload: String,
         ephemeral_pubkey: String,
         sender_tag: Option<String>,
-    ) -> (String, u64) {
-        let id = Self::generate_id();
+    ) -> Result<(String, u64)> {
         let stored_at = current_timestamp();
 
+        // Ids are generated and broadcast under the lock so subscribers see
+        // them in increasing order
+        let _guard = self.insert_lock.lock().expect("note store lock poisoned");
         let note = StoredEncryptedNote {
-            id: id.clone(),
+            id: Self::generate_id(),
             encrypted_payload,
             ephemeral_pubkey,
             sender_tag,
             stored_at,
         };
-
-        {
-            let mut notes = self.store.write();
-            let queue = notes.entry(recipient_tag.clone()).or_default();
-
-            // FIFO eviction if at capacity
-            while queue.len() >= self.max_notes_per_recipient {
-                if let Some(evicted) = queue.pop_front() {
-                    debug!(
-                        recipient_tag = %recipient_tag,
-                        evicted_id = %evicted.id,
-                        "Evicted oldest note due to capacity limit"
-                    );
-                }
-            }
-
-            queue.push_back(note);
+        let cursor =
+            self.backend
+                .insert(&recipient_tag, note.clone(), self.max_notes_per_recipient)?;
+
+        let id = note.id.clone();
+        // Notes of a shared backend are broadcast once read back, in the
+        // order every server sees them
+        if !self.backend.shared() {
+            // Fails only when nobody is subscribed
+            let _ = self.events.send(InsertedNote {
+                recipient_tag,
+                note,
+                cursor,
+            });
         }
 
-        if let Err(err) = self.store.maybe_persist() {
-            warn!(error = %err, "Failed to persist note store");
-        }
+        Ok((id, stored_at))
+    }
 
-        (id, stored_at)
+    /// Returns the notes of `recipient_tags` inserted after `cursor`, in
+    /// insertion order.
+    pub fn notes_after(
+        &self,
+        recipient_tags: &[String],
+        cursor: &str,
+    ) -> Result<Vec<InsertedNote>> {
+        self.backend.notes_after(recipient_tags, cursor)
     }
 
     /// Retrieves notes for a recipient, optionally filtered by timestamp and limited.
@@ -341,93 +874,148 @@ impl NoteStore {
         recipient_tag: &str,
         since: Option<u64>,
         limit: Option<usize>,
-    ) -> (Vec<StoredEncryptedNote>, bool) {
-        let notes = self.store.read();
-
-        let Some(queue) = notes.get(recipient_tag) else {
-            return (Vec::new(), false);
-        };
-
-        let filtered: Vec<_> = queue
-            .iter()
-            .filter(|note| {
-                if let Some(since_ts) = since {
-                    note.stored_at > since_ts
-                } else {
-                    true
-                }
-            })
-            .cloned()
-            .collect();
-
-        let limit = limit.unwrap_or(100);
-        let has_more = filtered.len() > limit;
-        let result = filtered.into_iter().take(limit).collect();
-
-        (result, has_more)
+    ) -> Result<(Vec<StoredEncryptedNote>, bool)> {
+        self.backend
+            .get_notes(recipient_tag, since, limit.unwrap_or(100))
     }
 
     /// Deletes a specific note by recipient tag and note ID.
     ///
     /// Returns true if the note was found and deleted.
-    pub fn delete_note(&self, recipient_tag: &str, note_id: &str) -> bool {
-        let deleted = {
-            let mut notes = self.store.write();
-
-            let Some(queue) = notes.get_mut(recipient_tag) else {
-                return false;
-            };
-
-            let initial_len = queue.len();
-            queue.retain(|note| note.id != note_id);
-            let deleted = queue.len() < initial_len;
-
-            // Clean up empty queues
-            if queue.is_empty() {
-                notes.remove(recipient_tag);
-            }
-
-            deleted
-        };
-
-        if deleted {
-            if let Err(err) = self.store.maybe_persist() {
-                warn!(error = %err, "Failed to persist note store after deletion");
-            }
-        }
-
-        deleted
+    pub fn delete_note(&self, recipient_tag: &str, note_id: &str) -> Result<bool> {
+        self.backend.delete_note(recipient_tag, note_id)
     }
 
     /// Returns the total number of stored notes across all recipients.
-    pub fn total_notes(&self) -> usize {
-        self.store.read().values().map(|q| q.len()).sum()
+    pub fn total_notes(&self) -> Result<usize> {
+        self.backend.total_notes()
     }
 
     /// Returns the number of unique recipient tags.
-    pub fn recipient_count(&self) -> usize {
-        self.store.read().len()
+    pub fn recipient_count(&self) -> Result<usize> {
+        self.backend.recipient_count()
     }
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
 
+    /// Checks `backend` through a store keeping 3 notes per recipient. The
+    /// tags are new to every call, so the backend may hold other notes.
+    pub(crate) fn check_note_backend(backend: Box<dyn NoteBackend>) {
+        let store = NoteStore::with_backend(Some(3), backend);
+        let run = NoteStore::generate_id();
+        let (alice, bob) = (format!("{run}a"), format!("{run}b"));
+        let tags = [alice.clone(), bob.clone()];
+        let insert = |tag: &str, payload: &str| {
+            store
+                .insert(
+                    tag.to_string(),
+                    payload.to_string(),
+                    "eph".to_string(),
+                    Some("sender".to_string()),
+                )
+                .unwrap()
+                .0
+        };
+        let ids = |notes: &[InsertedNote]| -> Vec<String> {
+            notes
+                .iter()
+                .map(|inserted| inserted.note.id.clone())
+                .collect()
+        };
+
+        let total = store.total_notes().unwrap();
+        let start = store.cursor_now().unwrap();
+        let mut events = store.subscribe();
+        let a1 = insert(&alice, "a1");
+        let b1 = insert(&bob, "b1");
+        let a2 = insert(&alice, "a2");
+        assert_eq!(store.total_notes().unwrap(), total + 3);
+
+        // Catch-up reads follow the insertion order across recipients
+        let after = store.notes_after(&tags, &start).unwrap();
+        assert_eq!(ids(&after), [a1.clone(), b1.clone(), a2.clone()]);
+        assert!(after[0].cursor > start);
+        assert!(after.windows(2).all(|w| w[0].cursor < w[1].cursor));
+        assert_eq!(after[1].recipient_tag, bob);
+        assert_eq!(after[1].note.sender_tag.as_deref(), Some("sender"));
+        assert_eq!(
+            ids(&store.notes_after(&tags[..1], &after[0].cursor).unwrap()),
+            [a2.clone()]
+        );
+        assert!(store
+            .notes_after(&tags, &after[2].cursor)
+            .unwrap()
+            .is_empty());
+
+        // Subscribers get every insert with the same cursor, once read back
+        // for a shared backend
+        if store.shared() {
+            let mut cursor = start.clone();
+            while let Some(last) = store.publish_after(&cursor, 100).unwrap() {
+                cursor = last;
+            }
+        }
+        let received: Vec<_> = std::iter::from_fn(|| events.try_recv().ok())
+            .filter(|inserted| tags.contains(&inserted.recipient_tag))
+            .map(|inserted| inserted.cursor)
+            .collect();
+        let cursors: Vec<_> = after
+            .iter()
+            .map(|inserted| inserted.cursor.clone())
+            .collect();
+        assert_eq!(received, cursors);
+
+        // The oldest notes of a recipient are evicted past capacity
+        insert(&alice, "a3");
+        insert(&alice, "a4");
+        let (notes, has_more) = store.get_notes(&alice, None, None).unwrap();
+        let payloads: Vec<_> = notes.iter().map(|n| n.encrypted_payload.as_str()).collect();
+        assert_eq!(payloads, ["a2", "a3", "a4"]);
+        assert!(!has_more);
+        let (notes, has_more) = store.get_notes(&alice, None, Some(2)).unwrap();
+        assert_eq!(notes.len(), 2);
+        assert!(has_more);
+        assert!(!ids(&store.notes_after(&tags, &start).unwrap()).contains(&a1));
+
+        assert!(!store.delete_note(&alice, &b1).unwrap());
+        assert!(store.delete_note(&bob, &b1).unwrap());
+        assert!(!store.delete_note(&bob, &b1).unwrap());
+        assert_eq!(store.total_notes().unwrap(), total + 3);
+        let (notes, _) = store.get_notes(&alice, None, None).unwrap();
+        for note in notes {
+            assert!(store.delete_note(&alice, &note.id).unwrap());
+        }
+        assert_eq!(store.total_notes().unwrap(), total);
+    }
+
     // ---- Address Registry Tests ----
 
+    fn proof(owner_pubkey: String) -> OwnershipProof {
+        OwnershipProof {
+            owner_pubkey,
+            signature: "00".repeat(65),
+            signed_at: current_timestamp(),
+        }
+    }
+
     #[test]
     fn test_address_registry_register_and_resolve() {
         let registry = AddressRegistry::new();
 
-        let previous = registry.register(
-            "matteo".to_string(),
-            "abcd1234".repeat(8), // 64 chars utxo
-            "ffff0000".repeat(8), // 64 chars encryption pubkey
-        );
+        let previous = registry
+            .register(
+                "matteo".to_string(),
+                "abcd1234".repeat(8), // 64 chars utxo
+                "ffff0000".repeat(8), // 64 chars encryption pubkey
+                proof("ffff0000".repeat(8)),
+            )
+            .unwrap();
         assert!(previous.is_none());
 
-        let resolved = registry.resolve("matteo");
+        let resolved = registry.resolve("matteo").unwrap();
         assert!(resolved.is_some());
         let reg = resolved.unwrap();
         assert_eq!(reg.username, "matteo");
@@ -439,28 +1027,48 @@ mod tests {
     fn test_address_registry_case_insensitive() {
         let registry = AddressRegistry::new();
 
-        registry.register("Matteo".to_string(), "1234".repeat(16), "5678".repeat(16));
+        registry
+            .register(
+                "Matteo".to_string(),
+                "1234".repeat(16),
+                "5678".repeat(16),
+                proof("5678".repeat(16)),
+            )
+            .unwrap();
 
         // Should resolve with different cases
-        assert!(registry.resolve("matteo").is_some());
-        assert!(registry.resolve("MATTEO").is_some());
-        assert!(registry.resolve("MaTtEo").is_some());
+        assert!(registry.resolve("matteo").unwrap().is_some());
+        assert!(registry.resolve("MATTEO").unwrap().is_some());
+        assert!(registry.resolve("MaTtEo").unwrap().is_some());
     }
 
     #[test]
     fn test_address_registry_update() {
         let registry = AddressRegistry::new();
 
-        registry.register("matteo".to_string(), "aaaa".repeat(16), "1111".repeat(16));
-
-        // Update should return previous registration
-        let previous =
-            registry.register("matteo".to_string(), "bbbb".repeat(16), "2222".repeat(16));
+        registry
+            .register(
+                "matteo".to_string(),
+                "aaaa".repeat(16),
+                "1111".repeat(16),
+                proof("1111".repeat(16)),
+            )
+            .unwrap();
+
+        // Update signed by the owner should return previous registration
+        let previous = registry
+            .register(
+                "matteo".to_string(),
+                "bbbb".repeat(16),
+                "2222".repeat(16),
+                proof("1111".repeat(16)),
+            )
+            .unwrap();
         assert!(previous.is_some());
         assert_eq!(previous.unwrap().utxo_address, "aaaa".repeat(16));
 
         // New value should be stored
-        let resolved = registry.resolve("matteo").unwrap();
+        let resolved = registry.resolve("matteo").unwrap().unwrap();
         assert_eq!(resolved.utxo_address, "bbbb".repeat(16));
         assert_eq!(resolved.encryption_pubkey, "2222".repeat(16));
     }
@@ -469,36 +1077,197 @@ mod tests {
     fn test_address_registry_unregister() {
         let registry = AddressRegistry::new();
 
-        registry.register("matteo".to_string(), "1234".repeat(16), "5678".repeat(16));
-        assert!(registry.resolve("matteo").is_some());
-
-        let removed = registry.unregister("matteo");
+        registry
+            .register(
+                "matteo".to_string(),
+                "1234".repeat(16),
+                "5678".repeat(16),
+                proof("5678".repeat(16)),
+            )
+            .unwrap();
+        assert!(registry.resolve("matteo").unwrap().is_some());
+
+        let removed = registry.unregister("matteo").unwrap();
         assert!(removed.is_some());
 
-        assert!(registry.resolve("matteo").is_none());
+        assert!(registry.resolve("matteo").unwrap().is_none());
 
         // Unregister again should return None
-        assert!(registry.unregister("matteo").is_none());
+        assert!(registry.unregister("matteo").unwrap().is_none());
     }
 
     #[test]
     fn test_address_registry_count() {
         let registry = AddressRegistry::new();
 
-        assert_eq!(registry.count(), 0);
+        assert_eq!(registry.count().unwrap(), 0);
+
+        registry
+            .register(
+                "alice".to_string(),
+                "aaaa".repeat(16),
+                "1111".repeat(16),
+                proof("1111".repeat(16)),
+            )
+            .unwrap();
+        assert_eq!(registry.count().unwrap(), 1);
+
+        registry
+            .register(
+                "bob".to_string(),
+                "bbbb".repeat(16),
+                "2222".repeat(16),
+                proof("2222".repeat(16)),
+            )
+            .unwrap();
+        assert_eq!(registry.count().unwrap(), 2);
+
+        // Update shouldn't increase count
+        registry
+            .register(
+                "alice".to_string(),
+                "cccc".repeat(16),
+                "3333".repeat(16),
+                proof("1111".repeat(16)),
+            )
+            .unwrap();
+        assert_eq!(registry.count().unwrap(), 2);
+
+        registry.unregister("alice").unwrap();
+        assert_eq!(registry.count().unwrap(), 1);
+    }
 
-        registry.register("alice".to_string(), "aaaa".repeat(16), "1111".repeat(16));
-        assert_eq!(registry.count(), 1);
+    #[test]
+    fn test_address_registry_rejects_non_owner() {
+        let registry = AddressRegistry::new();
 
-        registry.register("bob".to_string(), "bbbb".repeat(16), "2222".repeat(16));
-        assert_eq!(registry.count(), 2);
+        registry
+            .register(
+                "alice".to_string(),
+                "aaaa".repeat(16),
+                "1111".repeat(16),
+                proof("1111".repeat(16)),
+            )
+            .unwrap();
+
+        let hijack = registry.register(
+            "alice".to_string(),
+            "bbbb".repeat(16),
+            "2222".repeat(16),
+            proof("2222".repeat(16)),
+        );
+        assert_eq!(
+            hijack.unwrap_err(),
+            RegistrationError::NotOwner("alice".to_string())
+        );
+        assert_eq!(
+            registry.release("alice", &"2222".repeat(16)).unwrap_err(),
+            RegistrationError::NotOwner("alice".to_string())
+        );
+        assert_eq!(
+            registry.resolve("alice").unwrap().unwrap().utxo_address,
+            "aaaa".repeat(16)
+        );
+    }
 
-        // Update shouldn't increase count
-        registry.register("alice".to_string(), "cccc".repeat(16), "3333".repeat(16));
-        assert_eq!(registry.count(), 2);
+    #[test]
+    fn test_address_registry_single_owner_per_address() {
+        let registry = AddressRegistry::new();
 
-        registry.unregister("alice");
-        assert_eq!(registry.count(), 1);
+        registry
+            .register(
+                "alice".to_string(),
+                "aaaa".repeat(16),
+                "1111".repeat(16),
+                proof("1111".repeat(16)),
+            )
+            .unwrap();
+
+        // Another key cannot point a new username at alice's address
+        let squat = registry.register(
+            "mallory".to_string(),
+            "aaaa".repeat(16),
+            "2222".repeat(16),
+            proof("2222".repeat(16)),
+        );
+        assert_eq!(
+            squat.unwrap_err(),
+            RegistrationError::AddressTaken("aaaa".repeat(16))
+        );
+        assert!(registry.resolve("mallory").unwrap().is_none());
+
+        // The owner can, and the address frees up once released everywhere
+        registry
+            .register(
+                "alice2".to_string(),
+                "aaaa".repeat(16),
+                "1111".repeat(16),
+                proof("1111".repeat(16)),
+            )
+            .unwrap();
+        registry.release("alice", &"1111".repeat(16)).unwrap();
+        registry.release("alice2", &"1111".repeat(16)).unwrap();
+        registry
+            .register(
+                "mallory".to_string(),
+                "aaaa".repeat(16),
+                "2222".repeat(16),
+                proof("2222".repeat(16)),
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_address_registry_owner_survives_key_rotation() {
+        let registry = AddressRegistry::new();
+
+        registry
+            .register(
+                "alice".to_string(),
+                "aaaa".repeat(16),
+                "1111".repeat(16),
+                proof("1111".repeat(16)),
+            )
+            .unwrap();
+        registry
+            .register(
+                "alice".to_string(),
+                "aaaa".repeat(16),
+                "2222".repeat(16),
+                proof("1111".repeat(16)),
+            )
+            .unwrap();
+
+        let resolved = registry.resolve("alice").unwrap().unwrap();
+        assert_eq!(resolved.encryption_pubkey, "2222".repeat(16));
+        assert_eq!(resolved.owner_pubkey(), Some("1111".repeat(16).as_str()));
+
+        // Only the original owner can release
+        assert!(registry.release("alice", &"2222".repeat(16)).is_err());
+        assert!(registry.release("Alice", &"1111".repeat(16)).is_ok());
+        assert_eq!(
+            registry.release("alice", &"1111".repeat(16)).unwrap_err(),
+            RegistrationError::NotRegistered("alice".to_string())
+        );
+    }
+
+    #[test]
+    fn test_address_registry_legacy_owner() {
+        let legacy: AddressRegistration = serde_json::from_value(serde_json::json!({
+            "username": "bob",
+            "utxo_address": "bbbb".repeat(16),
+            "encryption_pubkey": "2222".repeat(16),
+            "registered_at": 1,
+        }))
+        .unwrap();
+        assert_eq!(legacy.owner_pubkey(), Some("2222".repeat(16).as_str()));
+
+        // A legacy registration without any key can be claimed by anyone
+        let keyless = AddressRegistration {
+            encryption_pubkey: String::new(),
+            ..legacy
+        };
+        assert_eq!(keyless.owner_pubkey(), None);
     }
 
     // ---- NoteStore Tests ----
@@ -507,17 +1276,19 @@ mod tests {
     fn test_insert_and_get() {
         let store = NoteStore::new(None);
 
-        let (id, stored_at) = store.insert(
-            "recipient1".to_string(),
-            "payload1".to_string(),
-            "ephemeral1".to_string(),
-            None,
-        );
+        let (id, stored_at) = store
+            .insert(
+                "recipient1".to_string(),
+                "payload1".to_string(),
+                "ephemeral1".to_string(),
+                None,
+            )
+            .unwrap();
 
         assert!(!id.is_empty());
         assert!(stored_at > 0);
 
-        let (notes, has_more) = store.get_notes("recipient1", None, None);
+        let (notes, has_more) = store.get_notes("recipient1", None, None).unwrap();
         assert_eq!(notes.len(), 1);
         assert!(!has_more);
         assert_eq!(notes[0].id, id);
@@ -529,15 +1300,17 @@ mod tests {
         let store = NoteStore::new(Some(3));
 
         for i in 0..5 {
-            store.insert(
-                "recipient".to_string(),
-                format!("payload{}", i),
-                "ephemeral".to_string(),
-                None,
-            );
+            store
+                .insert(
+                    "recipient".to_string(),
+                    format!("payload{}", i),
+                    "ephemeral".to_string(),
+                    None,
+                )
+                .unwrap();
         }
 
-        let (notes, _) = store.get_notes("recipient", None, None);
+        let (notes, _) = store.get_notes("recipient", None, None).unwrap();
         assert_eq!(notes.len(), 3);
 
         // Should have the last 3 notes (payload2, payload3, payload4)
@@ -549,23 +1322,27 @@ mod tests {
     fn test_delete_note() {
         let store = NoteStore::new(None);
 
-        let (id1, _) = store.insert(
-            "recipient".to_string(),
-            "payload1".to_string(),
-            "ephemeral".to_string(),
-            None,
-        );
-        let (id2, _) = store.insert(
-            "recipient".to_string(),
-            "payload2".to_string(),
-            "ephemeral".to_string(),
-            None,
-        );
+        let (id1, _) = store
+            .insert(
+                "recipient".to_string(),
+                "payload1".to_string(),
+                "ephemeral".to_string(),
+                None,
+            )
+            .unwrap();
+        let (id2, _) = store
+            .insert(
+                "recipient".to_string(),
+                "payload2".to_string(),
+                "ephemeral".to_string(),
+                None,
+            )
+            .unwrap();
 
-        assert!(store.delete_note("recipient", &id1));
-        assert!(!store.delete_note("recipient", &id1)); // Already deleted
+        assert!(store.delete_note("recipient", &id1).unwrap());
+        assert!(!store.delete_note("recipient", &id1).unwrap()); // Already deleted
 
-        let (notes, _) = store.get_notes("recipient", None, None);
+        let (notes, _) = store.get_notes("recipient", None, None).unwrap();
         assert_eq!(notes.len(), 1);
         assert_eq!(notes[0].id, id2);
     }
@@ -574,23 +1351,27 @@ mod tests {
     fn test_get_notes_since() {
         let store = NoteStore::new(None);
 
-        let (_, ts1) = store.insert(
-            "recipient".to_string(),
-            "payload1".to_string(),
-            "ephemeral".to_string(),
-            None,
-        );
+        let (_, ts1) = store
+            .insert(
+                "recipient".to_string(),
+                "payload1".to_string(),
+                "ephemeral".to_string(),
+                None,
+            )
+            .unwrap();
 
         // Insert another note (will have same or later timestamp)
-        store.insert(
-            "recipient".to_string(),
-            "payload2".to_string(),
-            "ephemeral".to_string(),
-            None,
-        );
+        store
+            .insert(
+                "recipient".to_string(),
+                "payload2".to_string(),
+                "ephemeral".to_string(),
+                None,
+            )
+            .unwrap();
 
         // Get notes since ts1 (should only return notes with stored_at > ts1)
-        let (notes, _) = store.get_notes("recipient", Some(ts1), None);
+        let (notes, _) = store.get_notes("recipient", Some(ts1), None).unwrap();
         // In practice, both notes might have the same timestamp since they're inserted quickly
         // So we just verify the filter logic works
         assert!(notes.iter().all(|n| n.stored_at > ts1));
@@ -601,31 +1382,78 @@ mod tests {
         let store = NoteStore::new(None);
 
         for i in 0..10 {
-            store.insert(
-                "recipient".to_string(),
-                format!("payload{}", i),
-                "ephemeral".to_string(),
-                None,
-            );
+            store
+                .insert(
+                    "recipient".to_string(),
+                    format!("payload{}", i),
+                    "ephemeral".to_string(),
+                    None,
+                )
+                .unwrap();
         }
 
-        let (notes, has_more) = store.get_notes("recipient", None, Some(5));
+        let (notes, has_more) = store.get_notes("recipient", None, Some(5)).unwrap();
         assert_eq!(notes.len(), 5);
         assert!(has_more);
 
-        let (notes, has_more) = store.get_notes("recipient", None, Some(10));
+        let (notes, has_more) = store.get_notes("recipient", None, Some(10)).unwrap();
         assert_eq!(notes.len(), 10);
         assert!(!has_more);
     }
 
+    #[test]
+    fn test_notes_after_cursor() {
+        let store = NoteStore::new(None);
+        let mut events = store.subscribe();
+        let cursor = store.cursor_now().unwrap();
+
+        let (a, _) = store
+            .insert("alice".into(), "1".into(), "eph".into(), None)
+            .unwrap();
+        let (b, _) = store
+            .insert("bob".into(), "2".into(), "eph".into(), None)
+            .unwrap();
+        let (c, _) = store
+            .insert("alice".into(), "3".into(), "eph".into(), None)
+            .unwrap();
+        store
+            .insert("carol".into(), "4".into(), "eph".into(), None)
+            .unwrap();
+
+        let tags = ["alice".to_string(), "bob".to_string()];
+        let ids = |notes: Vec<InsertedNote>| -> Vec<String> {
+            notes.into_iter().map(|inserted| inserted.note.id).collect()
+        };
+        assert_eq!(
+            ids(store.notes_after(&tags, &cursor).unwrap()),
+            [a.clone(), b.clone(), c.clone()]
+        );
+        assert_eq!(
+            ids(store.notes_after(&tags, &a).unwrap()),
+            [b.clone(), c.clone()]
+        );
+        assert!(store.notes_after(&tags, &c).unwrap().is_empty());
+
+        // Subscribers receive every insert in id order
+        let received: Vec<_> = (0..4).map(|_| events.try_recv().unwrap()).collect();
+        assert_eq!(received[0].recipient_tag, "alice");
+        assert_eq!(received[1].note.id, b);
+        assert!(received.windows(2).all(|w| w[0].note.id < w[1].note.id));
+    }
+
+    #[test]
+    fn local_notes_pass_the_backend_checks() {
+        check_note_backend(Box::new(LocalNotes::new()));
+    }
+
     #[test]
     fn test_nonexistent_recipient() {
         let store = NoteStore::new(None);
 
-        let (notes, has_more) = store.get_notes("nonexistent", None, None);
+        let (notes, has_more) = store.get_notes("nonexistent", None, None).unwrap();
         assert!(notes.is_empty());
         assert!(!has_more);
 
-        assert!(!store.delete_note("nonexistent", "some-id"));
+        assert!(!store.delete_note("nonexistent", "some-id").unwrap());
     }
 }
@@ -0,0 +1,208 @@
+use std::{collections::VecDeque, sync::Arc, time::Duration};
+
+use anyhow::Result;
+use axum::response::sse::Event;
+use cachecash_client::types::{EncryptedNoteRecord, NoteStreamEvent, StreamHeartbeat};
+use futures::Stream;
+use hyli_modules::{
+    bus::SharedMessageBus, module_bus_client, module_handle_messages, modules::Module,
+};
+use tokio::{
+    sync::broadcast::{error::RecvError, Receiver},
+    time::{Interval, MissedTickBehavior},
+};
+use tracing::{debug, warn};
+
+use crate::note_store::{blocking, current_timestamp, InsertedNote, NoteStore};
+
+/// Notes read per query when polling a shared note store.
+const FEED_BATCH_SIZE: usize = 256;
+
+/// Server-Sent Events for the notes of `tags` stored after `cursor`.
+///
+/// Without a cursor only notes inserted from now on are sent. Notes are
+/// deduplicated by cursor, so a lagging subscriber can safely re-read the
+/// store. The stream ends if the store cannot be read, and the client resumes
+/// from its last event id.
+pub async fn note_events(
+    store: Arc<NoteStore>,
+    tags: Vec<String>,
+    cursor: Option<String>,
+    heartbeat: Duration,
+) -> Result<impl Stream<Item = Result<Event, axum::Error>>> {
+    // Subscribe before reading the cursor or the backlog so no insert falls
+    // in between
+    let receiver = store.subscribe();
+    let catch_up = cursor.is_some();
+    let cursor = match cursor {
+        Some(cursor) => cursor,
+        None => {
+            let store = store.clone();
+            blocking(move || store.cursor_now()).await??
+        }
+    };
+    let mut heartbeat = tokio::time::interval(heartbeat);
+    heartbeat.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    let stream = NoteStream {
+        store,
+        receiver,
+        tags,
+        cursor,
+        backlog: VecDeque::new(),
+        catch_up,
+        heartbeat,
+    };
+    Ok(futures::stream::unfold(stream, |mut stream| async move {
+        let event = stream.next_event().await?;
+        Some((event, stream))
+    }))
+}
+
+struct NoteStream {
+    store: Arc<NoteStore>,
+    receiver: Receiver<InsertedNote>,
+    tags: Vec<String>,
+    /// Cursor of the last note sent
+    cursor: String,
+    /// Stored notes to send before live ones
+    backlog: VecDeque<InsertedNote>,
+    /// Whether the backlog must be read from the store first
+    catch_up: bool,
+    heartbeat: Interval,
+}
+
+impl NoteStream {
+    async fn next_event(&mut self) -> Option<Result<Event, axum::Error>> {
+        loop {
+            if self.catch_up {
+                self.catch_up = false;
+                let store = self.store.clone();
+                let tags = self.tags.clone();
+                let cursor = self.cursor.clone();
+                match blocking(move || store.notes_after(&tags, &cursor)).await {
+                    Ok(Ok(notes)) => self.backlog = notes.into(),
+                    Ok(Err(err)) | Err(err) => {
+                        warn!(error = %err, "Failed to read stored notes, closing note stream");
+                        return None;
+                    }
+                }
+            }
+            if let Some(inserted) = self.backlog.pop_front() {
+                if let Some(event) = self.note_event(inserted) {
+                    return Some(event);
+                }
+                continue;
+            }
+
+            tokio::select! {
+                _ = self.heartbeat.tick() => return Some(self.heartbeat_event()),
+                received = self.receiver.recv() => match received {
+                    Ok(inserted) => {
+                        if let Some(event) = self.note_event(inserted) {
+                            return Some(event);
+                        }
+                    }
+                    Err(RecvError::Lagged(skipped)) => {
+                        debug!(skipped, "Note stream lagged, catching up from the store");
+                        self.catch_up = true;
+                    }
+                    Err(RecvError::Closed) => return None,
+                },
+            }
+        }
+    }
+
+    fn note_event(&mut self, inserted: InsertedNote) -> Option<Result<Event, axum::Error>> {
+        if inserted.cursor <= self.cursor || !self.tags.contains(&inserted.recipient_tag) {
+            return None;
+        }
+        self.cursor = inserted.cursor;
+        Some(
+            Event::default()
+                .event("note")
+                .id(&self.cursor)
+                .json_data(NoteStreamEvent {
+                    recipient_tag: inserted.recipient_tag,
+                    note: EncryptedNoteRecord::from(inserted.note),
+                }),
+        )
+    }
+
+    fn heartbeat_event(&self) -> Result<Event, axum::Error> {
+        Event::default()
+            .event("heartbeat")
+            .id(&self.cursor)
+            .json_data(StreamHeartbeat {
+                cursor: self.cursor.clone(),
+                timestamp: current_timestamp(),
+            })
+    }
+}
+
+/// What a [`NoteFeedModule`] polls.
+pub struct NoteFeedCtx {
+    pub store: Arc<NoteStore>,
+    pub poll_interval: Duration,
+}
+
+module_bus_client! {
+    #[derive(Debug)]
+    struct NoteFeedBusClient {}
+}
+
+/// Polls a shared note store and broadcasts the notes inserted by any server,
+/// so note streams deliver them live.
+pub struct NoteFeedModule {
+    bus: NoteFeedBusClient,
+    store: Arc<NoteStore>,
+    poll_interval: Duration,
+    /// Cursor of the last note broadcast
+    cursor: String,
+}
+
+impl Module for NoteFeedModule {
+    type Context = NoteFeedCtx;
+
+    async fn build(bus: SharedMessageBus, ctx: Self::Context) -> Result<Self> {
+        let bus = NoteFeedBusClient::new_from_bus(bus.new_handle()).await;
+        // Streams read the notes stored before they started themselves
+        let store = ctx.store.clone();
+        let cursor = blocking(move || store.cursor_now()).await??;
+        Ok(Self {
+            bus,
+            store: ctx.store,
+            poll_interval: ctx.poll_interval,
+            cursor,
+        })
+    }
+
+    async fn run(&mut self) -> Result<()> {
+        let mut poll = tokio::time::interval(self.poll_interval);
+        poll.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        module_handle_messages! {
+            on_self self,
+            _ = poll.tick() => {
+                if let Err(err) = self.publish_new_notes().await {
+                    warn!(error = %err, "Failed to read new notes from the note store");
+                }
+            }
+        };
+
+        Ok(())
+    }
+}
+
+impl NoteFeedModule {
+    async fn publish_new_notes(&mut self) -> Result<()> {
+        loop {
+            let store = self.store.clone();
+            let cursor = self.cursor.clone();
+            match blocking(move || store.publish_after(&cursor, FEED_BATCH_SIZE)).await?? {
+                Some(last) => self.cursor = last,
+                None => return Ok(()),
+            }
+        }
+    }
+}
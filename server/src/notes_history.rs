@@ -0,0 +1,254 @@
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use hyli_utxo_state::{
+    state::MAX_ROOTS,
+    zk::{BorshableH256, SmtStore},
+};
+use sparse_merkle_tree::{
+    branch::{BranchKey, BranchNode},
+    error::Error,
+    traits::{StoreReadOps, StoreWriteOps},
+    H256,
+};
+
+/// Earlier roots whose rewound nodes are kept for the current notes tree.
+const REWIND_CACHE_ROOTS: usize = 16;
+
+/// Notes root reached by one state update, and the notes it added.
+#[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
+struct RootUpdate {
+    root: BorshableH256,
+    created: Vec<BorshableH256>,
+}
+
+/// The last [`MAX_ROOTS`] notes roots, with the notes added after each of
+/// them.
+///
+/// The notes tree only grows, so the tree at an earlier root is the current
+/// one minus the notes added since; this is enough to serve witnesses against
+/// every root the contract still accepts.
+#[derive(Debug, Clone, Default, BorshSerialize, BorshDeserialize)]
+pub struct NotesHistory {
+    /// Most recent update first
+    updates: VecDeque<RootUpdate>,
+}
+
+impl NotesHistory {
+    /// Records the root reached after adding `created` to the notes tree.
+    pub fn record(&mut self, root: BorshableH256, created: &[BorshableH256]) {
+        self.updates.push_front(RootUpdate {
+            root,
+            created: created
+                .iter()
+                .filter(|note| **note != BorshableH256::from([0u8; 32]))
+                .copied()
+                .collect(),
+        });
+        self.updates.truncate(MAX_ROOTS);
+    }
+
+    /// Whether no update was recorded yet.
+    pub fn is_empty(&self) -> bool {
+        self.updates.is_empty()
+    }
+
+    /// Notes added to the tree after it reached `root`, or `None` when `root`
+    /// is not among the retained roots.
+    pub fn notes_added_since(&self, root: &BorshableH256) -> Option<Vec<BorshableH256>> {
+        let mut later = Vec::new();
+        for update in &self.updates {
+            if update.root == *root {
+                return Some(later);
+            }
+            later.extend_from_slice(&update.created);
+        }
+        None
+    }
+}
+
+/// Nodes of the notes tree at an earlier root that differ from the current
+/// tree, `None` marking a node the earlier tree does not have.
+#[derive(Debug, Clone, Default)]
+pub struct RewoundNodes {
+    branches: HashMap<BranchKey, Option<BranchNode>>,
+    leaves: HashMap<H256, Option<H256>>,
+}
+
+/// A view of the tree stored in `base` at an earlier root: reads go through
+/// the rewound nodes first, and writes only change those.
+///
+/// Rewinding touches the paths of the notes added since, so the current tree
+/// is read in place instead of being copied for every request.
+#[derive(Debug, Clone)]
+pub struct RewoundStore<'a, S> {
+    base: &'a S,
+    nodes: Arc<RewoundNodes>,
+}
+
+impl<'a, S> RewoundStore<'a, S> {
+    pub fn new(base: &'a S, nodes: Arc<RewoundNodes>) -> Self {
+        Self { base, nodes }
+    }
+
+    /// The rewound nodes, to be reused for the same root.
+    pub fn into_nodes(self) -> Arc<RewoundNodes> {
+        self.nodes
+    }
+}
+
+impl<S: StoreReadOps<H256>> StoreReadOps<H256> for RewoundStore<'_, S> {
+    fn get_branch(&self, branch_key: &BranchKey) -> Result<Option<BranchNode>, Error> {
+        match self.nodes.branches.get(branch_key) {
+            Some(branch) => Ok(branch.clone()),
+            None => self.base.get_branch(branch_key),
+        }
+    }
+
+    fn get_leaf(&self, leaf_key: &H256) -> Result<Option<H256>, Error> {
+        match self.nodes.leaves.get(leaf_key) {
+            Some(leaf) => Ok(*leaf),
+            None => self.base.get_leaf(leaf_key),
+        }
+    }
+}
+
+impl<S> StoreWriteOps<H256> for RewoundStore<'_, S> {
+    fn insert_branch(&mut self, node_key: BranchKey, branch: BranchNode) -> Result<(), Error> {
+        Arc::make_mut(&mut self.nodes)
+            .branches
+            .insert(node_key, Some(branch));
+        Ok(())
+    }
+
+    fn insert_leaf(&mut self, leaf_key: H256, leaf: H256) -> Result<(), Error> {
+        Arc::make_mut(&mut self.nodes)
+            .leaves
+            .insert(leaf_key, Some(leaf));
+        Ok(())
+    }
+
+    fn remove_branch(&mut self, node_key: &BranchKey) -> Result<(), Error> {
+        Arc::make_mut(&mut self.nodes)
+            .branches
+            .insert(node_key.clone(), None);
+        Ok(())
+    }
+
+    fn remove_leaf(&mut self, leaf_key: &H256) -> Result<(), Error> {
+        Arc::make_mut(&mut self.nodes)
+            .leaves
+            .insert(*leaf_key, None);
+        Ok(())
+    }
+}
+
+impl<S: SmtStore> SmtStore for RewoundStore<'_, S> {
+    fn contains_leaf(&self, key: &H256) -> bool {
+        match self.nodes.leaves.get(key) {
+            Some(leaf) => leaf.is_some(),
+            None => self.base.contains_leaf(key),
+        }
+    }
+
+    fn leaves(&self) -> Vec<(H256, H256)> {
+        let mut leaves: BTreeMap<H256, H256> = self.base.leaves().into_iter().collect();
+        for (key, leaf) in &self.nodes.leaves {
+            match leaf {
+                Some(leaf) => leaves.insert(*key, *leaf),
+                None => leaves.remove(key),
+            };
+        }
+        leaves.into_iter().collect()
+    }
+}
+
+/// Rewound nodes of the last requested earlier roots, valid while the notes
+/// tree stays at the root they were rewound from.
+#[derive(Debug, Default)]
+pub struct RewindCache {
+    entries: Mutex<RewindEntries>,
+}
+
+#[derive(Debug, Default)]
+struct RewindEntries {
+    /// Notes root the nodes were rewound from
+    current: BorshableH256,
+    /// Most recently rewound first
+    rewound: VecDeque<(BorshableH256, Arc<RewoundNodes>)>,
+}
+
+impl RewindCache {
+    /// The nodes rewinding the tree at `current` to `root`, if cached.
+    pub fn get(&self, current: &BorshableH256, root: &BorshableH256) -> Option<Arc<RewoundNodes>> {
+        let entries = self.entries.lock().ok()?;
+        if entries.current != *current {
+            return None;
+        }
+        entries
+            .rewound
+            .iter()
+            .find(|(rewound_root, _)| rewound_root == root)
+            .map(|(_, nodes)| nodes.clone())
+    }
+
+    pub fn insert(&self, current: BorshableH256, root: BorshableH256, nodes: Arc<RewoundNodes>) {
+        let Ok(mut entries) = self.entries.lock() else {
+            return;
+        };
+        if entries.current != current {
+            entries.current = current;
+            entries.rewound.clear();
+        }
+        entries
+            .rewound
+            .retain(|(rewound_root, _)| *rewound_root != root);
+        entries.rewound.push_front((root, nodes));
+        entries.rewound.truncate(REWIND_CACHE_ROOTS);
+    }
+}
+
+/// A cache is not part of the state: copies start empty.
+impl Clone for RewindCache {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(byte: u8) -> BorshableH256 {
+        BorshableH256::from([byte; 32])
+    }
+
+    #[test]
+    fn lists_notes_added_since_a_root() {
+        let mut history = NotesHistory::default();
+        history.record(key(10), &[key(1), BorshableH256::from([0u8; 32])]);
+        history.record(key(20), &[key(2), key(3)]);
+        history.record(key(30), &[key(4)]);
+
+        assert_eq!(history.notes_added_since(&key(30)), Some(vec![]));
+        assert_eq!(
+            history.notes_added_since(&key(10)),
+            Some(vec![key(4), key(2), key(3)])
+        );
+        assert_eq!(history.notes_added_since(&key(99)), None);
+    }
+
+    #[test]
+    fn forgets_roots_the_contract_no_longer_accepts() {
+        let mut history = NotesHistory::default();
+        history.record(key(0), &[]);
+        for _ in 0..MAX_ROOTS {
+            history.record(key(1), &[]);
+        }
+        assert_eq!(history.notes_added_since(&key(0)), None);
+        assert!(history.notes_added_since(&key(1)).is_some());
+    }
+}
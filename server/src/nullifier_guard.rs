@@ -0,0 +1,543 @@
+use std::{collections::HashMap, io, sync::Arc};
+
+use anyhow::Result;
+use hyli_modules::{
+    bus::SharedMessageBus,
+    module_bus_client, module_handle_messages,
+    modules::{
+        contract_listener::ContractListenerEvent, contract_state_indexer::CSIBusEvent, Module,
+    },
+};
+use hyli_utxo_state::{state::HyliUtxoState, zk::BorshableH256};
+use sdk::api::TransactionStatusDb;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::{
+    conf::StoreLogConf,
+    hyli_utxo_state_client::{HyliUtxoStateEvent, UtxoStateStore},
+    op_log::{LogState, LogStore},
+};
+
+/// Why a transfer spending some notes was refused.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum NullifierError {
+    #[error("nullifier {nullifier} is already being spent by pending transaction {tx_hash}")]
+    InFlight { nullifier: String, tx_hash: String },
+    #[error("nullifier {nullifier} is already spent")]
+    Spent { nullifier: String },
+    #[error("recording the reservation failed: {0}")]
+    Storage(String),
+}
+
+/// A transfer submitted by this server that has not settled yet.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct PendingSpend {
+    tx_hash: String,
+    reserved_at: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct GuardState {
+    /// Nullifiers of unsettled transfers, keyed by hex nullifier
+    pending: HashMap<String, PendingSpend>,
+}
+
+#[derive(Serialize, Deserialize)]
+enum GuardOp {
+    /// Reserves `nullifiers` for `tx_hash`, dropping the reservations that
+    /// expired at `now`
+    Reserve {
+        tx_hash: String,
+        nullifiers: Vec<String>,
+        now: u64,
+        timeout_secs: u64,
+    },
+    Release {
+        tx_hash: String,
+    },
+    Spent {
+        nullifiers: Vec<String>,
+    },
+}
+
+impl LogState for GuardState {
+    type Op = GuardOp;
+
+    fn apply(&mut self, op: GuardOp) {
+        match op {
+            GuardOp::Reserve {
+                tx_hash,
+                nullifiers,
+                now,
+                timeout_secs,
+            } => {
+                for nullifier in nullifiers {
+                    self.pending.insert(
+                        nullifier,
+                        PendingSpend {
+                            tx_hash: tx_hash.clone(),
+                            reserved_at: now,
+                        },
+                    );
+                }
+                self.pending
+                    .retain(|_, pending| now < pending.reserved_at.saturating_add(timeout_secs));
+            }
+            GuardOp::Release { tx_hash } => {
+                self.pending.retain(|_, pending| pending.tx_hash != tx_hash);
+            }
+            GuardOp::Spent { nullifiers } => {
+                for nullifier in nullifiers {
+                    self.pending.remove(&nullifier);
+                }
+            }
+        }
+    }
+}
+
+/// Refuses transfers whose notes are already spent, or are being spent by a
+/// transaction that has not settled yet.
+///
+/// The node would reject the second of two transactions spending the same
+/// note only when settling it; checking here lets wallets know before they
+/// prove and submit. Spent nullifiers are looked up in the indexed
+/// `nullified_tree`; only the reservations are kept here. They are released
+/// when the transaction settles, fails or is not settled within
+/// `timeout_secs`.
+pub struct NullifierGuard {
+    timeout_secs: u64,
+    indexed: UtxoStateStore,
+    store: LogStore<GuardState>,
+}
+
+impl NullifierGuard {
+    /// Creates a guard keeping its reservations in memory.
+    pub fn new(timeout_secs: u64, indexed: UtxoStateStore) -> Self {
+        Self {
+            timeout_secs,
+            indexed,
+            store: LogStore::new(GuardState::default()),
+        }
+    }
+
+    /// Creates a guard whose reservations are persisted to
+    /// `persistence_path` and its operation log.
+    pub fn with_persistence(
+        timeout_secs: u64,
+        indexed: UtxoStateStore,
+        conf: StoreLogConf,
+        persistence_path: String,
+    ) -> io::Result<Self> {
+        Ok(Self {
+            timeout_secs,
+            indexed,
+            store: LogStore::open(persistence_path, conf)?,
+        })
+    }
+
+    /// Checks that none of `nullifiers` is spent or reserved, without
+    /// reserving them.
+    pub async fn check(&self, nullifiers: &[[u8; 32]], now: u64) -> Result<(), NullifierError> {
+        self.check_unspent(nullifiers).await?;
+        let state = self.store.read();
+        nullifiers
+            .iter()
+            .filter(|nullifier| !is_padding(nullifier))
+            .try_for_each(|nullifier| self.check_unreserved(&state, &hex::encode(nullifier), now))
+    }
+
+    /// Reserves `nullifiers` for `tx_hash`, failing if any of them is spent
+    /// or reserved by another transaction. The reservation is released when
+    /// dropped, unless it is [kept](Reservation::keep) once the transaction
+    /// is submitted.
+    pub async fn reserve(
+        &self,
+        tx_hash: &str,
+        nullifiers: &[[u8; 32]],
+        now: u64,
+    ) -> Result<Reservation<'_>, NullifierError> {
+        self.check_unspent(nullifiers).await?;
+        let mut state = self.store.write();
+        let keys: Vec<String> = nullifiers
+            .iter()
+            .filter(|nullifier| !is_padding(nullifier))
+            .map(hex::encode)
+            .collect();
+        let resubmitted = !keys.is_empty()
+            && keys.iter().all(|key| {
+                state
+                    .pending
+                    .get(key)
+                    .is_some_and(|pending| pending.tx_hash == tx_hash)
+            });
+        for key in &keys {
+            match state.pending.get(key) {
+                // Resubmitting the same transaction is not a double spend
+                Some(pending) if pending.tx_hash == tx_hash => {}
+                _ => self.check_unreserved(&state, key, now)?,
+            }
+        }
+        state
+            .apply(GuardOp::Reserve {
+                tx_hash: tx_hash.to_string(),
+                nullifiers: keys,
+                now,
+                timeout_secs: self.timeout_secs,
+            })
+            .map_err(|err| NullifierError::Storage(err.to_string()))?;
+        drop(state);
+        Ok(Reservation {
+            guard: self,
+            tx_hash: tx_hash.to_string(),
+            // Held by an earlier submission of the transaction, which may be
+            // on chain already
+            kept: resubmitted,
+        })
+    }
+
+    /// Releases the reservations of `tx_hash`, after it failed to be
+    /// submitted or settled as failed.
+    pub fn release(&self, tx_hash: &str) {
+        let mut state = self.store.write();
+        if !state
+            .pending
+            .values()
+            .any(|pending| pending.tx_hash == tx_hash)
+        {
+            return;
+        }
+        let op = GuardOp::Release {
+            tx_hash: tx_hash.to_string(),
+        };
+        if let Err(err) = state.apply(op) {
+            warn!(error = %err, %tx_hash, "Failed to release pending nullifiers");
+        }
+    }
+
+    /// Releases the reservations of nullifiers the indexer added to the
+    /// `nullified_tree`, which refuses them from then on.
+    pub fn record_spent(&self, nullifiers: &[[u8; 32]]) {
+        let mut state = self.store.write();
+        let spent: Vec<String> = nullifiers
+            .iter()
+            .filter(|nullifier| !is_padding(nullifier))
+            .map(hex::encode)
+            .filter(|key| state.pending.contains_key(key))
+            .collect();
+        if spent.is_empty() {
+            return;
+        }
+        if let Err(err) = state.apply(GuardOp::Spent { nullifiers: spent }) {
+            warn!(error = %err, "Failed to release spent nullifiers");
+        }
+    }
+
+    /// Fails on the first nullifier in the indexed `nullified_tree`. Nothing
+    /// is refused as spent until the indexer has the state.
+    async fn check_unspent(&self, nullifiers: &[[u8; 32]]) -> Result<(), NullifierError> {
+        let indexed = self.indexed.read().await;
+        let Some(executor) = indexed.as_ref() else {
+            return Ok(());
+        };
+        let spent = nullifiers.iter().find(|nullifier| {
+            !is_padding(nullifier)
+                && executor
+                    .utxo_state()
+                    .is_nullified(&BorshableH256::from(**nullifier))
+        });
+        match spent {
+            Some(nullifier) => Err(NullifierError::Spent {
+                nullifier: hex::encode(nullifier),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    fn check_unreserved(
+        &self,
+        state: &GuardState,
+        key: &str,
+        now: u64,
+    ) -> Result<(), NullifierError> {
+        match state.pending.get(key) {
+            Some(pending) if now < pending.reserved_at.saturating_add(self.timeout_secs) => {
+                Err(NullifierError::InFlight {
+                    nullifier: key.to_string(),
+                    tx_hash: pending.tx_hash.clone(),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Nullifiers reserved by [`NullifierGuard::reserve`], released on drop so
+/// that a transfer refused before it is submitted does not lock its notes
+/// until the timeout.
+#[must_use = "dropping a reservation releases its nullifiers"]
+pub struct Reservation<'a> {
+    guard: &'a NullifierGuard,
+    tx_hash: String,
+    kept: bool,
+}
+
+impl Reservation<'_> {
+    /// Keeps the nullifiers reserved until the transaction settles, fails or
+    /// times out.
+    pub fn keep(mut self) {
+        self.kept = true;
+    }
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        if !self.kept {
+            debug!(tx_hash = %self.tx_hash, "Releasing nullifiers of an unsubmitted transfer");
+            self.guard.release(&self.tx_hash);
+        }
+    }
+}
+
+/// Nullifiers spent by a 128-byte hyli_utxo blob
+/// (`[out_commit0][out_commit1][nullifier0][nullifier1]`).
+pub fn blob_nullifiers(blob_data: &[u8]) -> [[u8; 32]; 2] {
+    let mut nullifiers = [[0u8; 32]; 2];
+    nullifiers[0].copy_from_slice(&blob_data[64..96]);
+    nullifiers[1].copy_from_slice(&blob_data[96..128]);
+    nullifiers
+}
+
+/// Unused input slots carry a zero or padding nullifier, shared by every
+/// transfer and never recorded.
+fn is_padding(nullifier: &[u8; 32]) -> bool {
+    *nullifier == [0u8; 32] || *nullifier == HyliUtxoState::PADDING_NULLIFIER
+}
+
+module_bus_client! {
+    #[derive(Debug)]
+    pub struct NullifierGuardBusClient {
+        receiver(CSIBusEvent<HyliUtxoStateEvent>),
+        receiver(ContractListenerEvent),
+    }
+}
+
+/// Feeds the nullifiers of settled transactions from the hyli-utxo-state
+/// indexer, and the failures reported by the `ContractListener`, into a
+/// [`NullifierGuard`].
+pub struct NullifierGuardModule {
+    bus: NullifierGuardBusClient,
+    guard: Arc<NullifierGuard>,
+}
+
+impl Module for NullifierGuardModule {
+    type Context = Arc<NullifierGuard>;
+
+    async fn build(bus: SharedMessageBus, ctx: Self::Context) -> Result<Self> {
+        let bus = NullifierGuardBusClient::new_from_bus(bus.new_handle()).await;
+        Ok(Self { bus, guard: ctx })
+    }
+
+    async fn run(&mut self) -> Result<()> {
+        module_handle_messages! {
+            on_self self,
+            listen<CSIBusEvent<HyliUtxoStateEvent>> event => {
+                self.guard.record_spent(&event.event.nullified);
+            }
+            listen<ContractListenerEvent> event => {
+                if let ContractListenerEvent::SettledTx(tx) = event {
+                    self.handle_settled(&hex::encode(&tx.tx_id.1 .0), &tx.status);
+                }
+            }
+        };
+
+        Ok(())
+    }
+}
+
+impl NullifierGuardModule {
+    fn handle_settled(&self, tx_hash: &str, status: &TransactionStatusDb) {
+        match status {
+            TransactionStatusDb::Failure | TransactionStatusDb::TimedOut => {
+                self.guard.release(tx_hash);
+            }
+            // Successful transactions are released once the indexer has
+            // recorded their nullifiers
+            other => {
+                debug!(%tx_hash, status = ?other, "Keeping nullifiers of settled tx reserved");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyli_utxo_state::state::ContractConfig;
+
+    use super::*;
+    use crate::{conf::FsyncPolicy, hyli_utxo_state_client::HyliUtxoStateExecutor};
+
+    const NOW: u64 = 1_760_000_000;
+    const TIMEOUT_SECS: u64 = 600;
+
+    fn nullifier(byte: u8) -> [u8; 32] {
+        [byte; 32]
+    }
+
+    fn guard() -> NullifierGuard {
+        NullifierGuard::new(TIMEOUT_SECS, UtxoStateStore::default())
+    }
+
+    /// Reserves and keeps `nullifiers`, as a submitted transfer does.
+    async fn submit(
+        guard: &NullifierGuard,
+        tx_hash: &str,
+        nullifiers: &[[u8; 32]],
+        now: u64,
+    ) -> Result<(), NullifierError> {
+        guard
+            .reserve(tx_hash, nullifiers, now)
+            .await
+            .map(Reservation::keep)
+    }
+
+    /// An indexed state whose `nullified_tree` holds `nullified`.
+    fn indexed(nullified: &[[u8; 32]]) -> UtxoStateStore {
+        let mut executor = HyliUtxoStateExecutor::new(ContractConfig {
+            utxo_contract_name: "hyli_utxo".into(),
+            smt_incl_proof_contract_name: "hyli_smt_incl_proof".into(),
+            smt_contract_name: "oranj".into(),
+        });
+        let nullified: Vec<_> = nullified.iter().map(|n| BorshableH256::from(*n)).collect();
+        executor.apply_commitments(&nullified, &[]).unwrap();
+        Arc::new(tokio::sync::RwLock::new(Some(executor)))
+    }
+
+    #[tokio::test]
+    async fn refuses_nullifiers_reserved_by_another_transaction() {
+        let guard = guard();
+        submit(&guard, "aa", &[nullifier(1), nullifier(2)], NOW)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            submit(&guard, "bb", &[nullifier(3), nullifier(2)], NOW + 1).await,
+            Err(NullifierError::InFlight {
+                nullifier: hex::encode(nullifier(2)),
+                tx_hash: "aa".to_string(),
+            })
+        );
+        assert!(guard.check(&[nullifier(1)], NOW + 1).await.is_err());
+        // A refused reservation does not reserve its other nullifiers
+        assert_eq!(guard.check(&[nullifier(3)], NOW + 1).await, Ok(()));
+        // The same transaction may be submitted again
+        assert_eq!(submit(&guard, "aa", &[nullifier(1)], NOW + 1).await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn ignores_padding_nullifiers() {
+        let padding = [[0u8; 32], HyliUtxoState::PADDING_NULLIFIER];
+        let guard = NullifierGuard::new(TIMEOUT_SECS, indexed(&padding));
+        submit(&guard, "aa", &padding, NOW).await.unwrap();
+        guard.record_spent(&padding);
+        assert_eq!(submit(&guard, "bb", &padding, NOW).await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn releases_failed_and_expired_reservations() {
+        let guard = guard();
+        submit(&guard, "aa", &[nullifier(1)], NOW).await.unwrap();
+        guard.release("aa");
+        assert_eq!(submit(&guard, "bb", &[nullifier(1)], NOW).await, Ok(()));
+
+        assert!(guard
+            .check(&[nullifier(1)], NOW + TIMEOUT_SECS - 1)
+            .await
+            .is_err());
+        assert_eq!(
+            guard.check(&[nullifier(1)], NOW + TIMEOUT_SECS).await,
+            Ok(())
+        );
+        assert_eq!(
+            submit(&guard, "cc", &[nullifier(1)], NOW + TIMEOUT_SECS).await,
+            Ok(())
+        );
+    }
+
+    #[tokio::test]
+    async fn releases_reservations_of_transfers_refused_before_submission() {
+        let guard = guard();
+        {
+            let _reservation = guard.reserve("aa", &[nullifier(1)], NOW).await.unwrap();
+            assert!(guard.check(&[nullifier(1)], NOW).await.is_err());
+            // The transfer is refused here, e.g. a malformed sibling
+        }
+        assert_eq!(guard.check(&[nullifier(1)], NOW).await, Ok(()));
+        assert_eq!(submit(&guard, "bb", &[nullifier(1)], NOW).await, Ok(()));
+
+        // Dropping a failed resubmission keeps the first submission's
+        // reservation
+        drop(guard.reserve("bb", &[nullifier(1)], NOW).await.unwrap());
+        assert!(guard.check(&[nullifier(1)], NOW).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn refuses_nullifiers_in_the_indexed_state() {
+        let guard = NullifierGuard::new(TIMEOUT_SECS, indexed(&[nullifier(1)]));
+        let spent = Err(NullifierError::Spent {
+            nullifier: hex::encode(nullifier(1)),
+        });
+        assert_eq!(guard.check(&[nullifier(2), nullifier(1)], NOW).await, spent);
+        assert_eq!(submit(&guard, "aa", &[nullifier(1)], NOW).await, spent);
+        assert_eq!(guard.check(&[nullifier(2)], NOW).await, Ok(()));
+    }
+
+    fn store_log() -> StoreLogConf {
+        StoreLogConf {
+            fsync: FsyncPolicy::Always,
+            fsync_interval_ms: 0,
+            compact_after_ops: 2,
+        }
+    }
+
+    #[tokio::test]
+    async fn keeps_reservations_across_restarts() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir
+            .path()
+            .join("pending_nullifiers.json")
+            .to_string_lossy()
+            .to_string();
+
+        let guard = NullifierGuard::with_persistence(
+            TIMEOUT_SECS,
+            UtxoStateStore::default(),
+            store_log(),
+            path.clone(),
+        )
+        .unwrap();
+        submit(&guard, "aa", &[nullifier(1)], NOW).await.unwrap();
+        guard.record_spent(&[nullifier(1)]);
+        submit(&guard, "bb", &[nullifier(2)], NOW).await.unwrap();
+        drop(guard);
+
+        let reloaded = NullifierGuard::with_persistence(
+            TIMEOUT_SECS,
+            UtxoStateStore::default(),
+            store_log(),
+            path,
+        )
+        .unwrap();
+        // Settled spends are left to the indexed state
+        assert_eq!(reloaded.check(&[nullifier(1)], NOW).await, Ok(()));
+        assert!(reloaded.check(&[nullifier(2)], NOW).await.is_err());
+    }
+
+    #[test]
+    fn extracts_nullifiers_from_blob() {
+        let mut blob = vec![0u8; 128];
+        blob[64..96].fill(1);
+        blob[96..128].fill(2);
+        assert_eq!(blob_nullifiers(&blob), [nullifier(1), nullifier(2)]);
+    }
+}
@@ -0,0 +1,523 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufReader, BufWriter, Read, Write},
+    ops::Deref,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard, Weak},
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use crate::conf::{FsyncPolicy, StoreLogConf};
+
+/// Length and CRC32 of the payload, both little-endian.
+const RECORD_HEADER_LEN: usize = 8;
+
+/// State changed only through operations, so it can be persisted as an
+/// append-only log of them.
+pub trait LogState: Default + Serialize + DeserializeOwned {
+    type Op: Serialize + DeserializeOwned;
+
+    /// Must be deterministic: the log is replayed on restart.
+    fn apply(&mut self, op: Self::Op);
+}
+
+/// In-memory state with optional persistence as a checkpoint file plus an
+/// append-only log of the operations applied since.
+///
+/// Each operation is appended to `{path}.log` before it is applied, and the
+/// log is folded into the checkpoint at `path` every
+/// `compact_after_ops` operations. On open, the log is replayed on top of the
+/// checkpoint; a torn record at its end (a crash during an append) is
+/// discarded.
+///
+/// With the `interval` fsync policy, a background thread also flushes the
+/// appends left unsynced every `fsync_interval_ms`, so they do not wait for
+/// the next append.
+pub struct LogStore<T: LogState> {
+    data: RwLock<T>,
+    log: Arc<Mutex<Option<OpLog>>>,
+}
+
+impl<T: LogState> LogStore<T> {
+    /// Creates a store with no persistence.
+    pub fn new(data: T) -> Self {
+        Self {
+            data: RwLock::new(data),
+            log: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Opens the store persisted at `path`, recovering its state from the
+    /// checkpoint and the log.
+    pub fn open(path: impl Into<PathBuf>, conf: StoreLogConf) -> io::Result<Self> {
+        let (data, log) = OpLog::open(path.into(), conf.clone())?;
+        let log = Arc::new(Mutex::new(Some(log)));
+        if conf.fsync == FsyncPolicy::Interval && conf.fsync_interval_ms > 0 {
+            spawn_flusher(
+                Arc::downgrade(&log),
+                Duration::from_millis(conf.fsync_interval_ms),
+            )?;
+        }
+        Ok(Self {
+            data: RwLock::new(data),
+            log,
+        })
+    }
+
+    pub fn read(&self) -> RwLockReadGuard<'_, T> {
+        self.data.read().expect("store lock poisoned")
+    }
+
+    /// Locks the state for operations.
+    pub fn write(&self) -> LogWriteGuard<'_, T> {
+        let data = self.data.write().expect("store lock poisoned");
+        let log = self.log.lock().expect("store log lock poisoned");
+        LogWriteGuard { data, log }
+    }
+}
+
+/// Write access to a [`LogStore`]: the state can be read, and changed only
+/// with [`LogWriteGuard::apply`].
+pub struct LogWriteGuard<'a, T: LogState> {
+    data: RwLockWriteGuard<'a, T>,
+    log: MutexGuard<'a, Option<OpLog>>,
+}
+
+impl<T: LogState> LogWriteGuard<'_, T> {
+    /// Appends `op` to the log, then applies it. Nothing is applied if the
+    /// append fails.
+    pub fn apply(&mut self, op: T::Op) -> io::Result<()> {
+        let Some(log) = self.log.as_mut() else {
+            self.data.apply(op);
+            return Ok(());
+        };
+
+        log.append(&op)?;
+        self.data.apply(op);
+        if log.compaction_due() {
+            // The operation is already durable in the log
+            if let Err(err) = log.checkpoint(&*self.data) {
+                warn!(error = %err, path = %log.path.display(), "Failed to compact store log");
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<T: LogState> Deref for LogWriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.data
+    }
+}
+
+#[derive(Serialize)]
+struct CheckpointRef<'a, T> {
+    /// Sequence number of the last operation folded into `data`
+    seq: u64,
+    data: &'a T,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Checkpoint<T> {
+    Current {
+        seq: u64,
+        data: T,
+    },
+    /// The whole state, as written before stores kept a log
+    Legacy(T),
+}
+
+#[derive(Serialize)]
+struct RecordRef<'a, Op> {
+    seq: u64,
+    op: &'a Op,
+}
+
+#[derive(Deserialize)]
+struct Record<Op> {
+    seq: u64,
+    op: Op,
+}
+
+struct OpLog {
+    path: PathBuf,
+    file: File,
+    conf: StoreLogConf,
+    /// Sequence number of the last appended operation
+    seq: u64,
+    /// Records in the log file
+    records: u64,
+    /// Length of the log file up to its last complete record
+    len: u64,
+    last_sync: Instant,
+    /// Whether records were appended since the last flush
+    unsynced: bool,
+}
+
+impl OpLog {
+    fn open<T: LogState>(path: PathBuf, conf: StoreLogConf) -> io::Result<(T, Self)> {
+        let (mut data, checkpoint_seq) = match File::open(&path) {
+            Ok(file) => {
+                let checkpoint: Checkpoint<T> = serde_json::from_reader(BufReader::new(file))
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                match checkpoint {
+                    Checkpoint::Current { seq, data } => (data, seq),
+                    Checkpoint::Legacy(data) => (data, 0),
+                }
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => (T::default(), 0),
+            Err(err) => return Err(err),
+        };
+
+        let log_path = PathBuf::from(format!("{}.log", path.display()));
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&log_path)?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+
+        let mut seq = checkpoint_seq;
+        let mut records = 0;
+        let mut replayed = 0;
+        let mut valid_len = 0;
+        while let Some((payload, record_len)) = read_record(&bytes[valid_len..]) {
+            let record: Record<T::Op> = serde_json::from_slice(payload)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            // Records already folded into the checkpoint are left by a crash
+            // before the log was truncated
+            if record.seq > checkpoint_seq {
+                data.apply(record.op);
+                replayed += 1;
+            }
+            seq = seq.max(record.seq);
+            records += 1;
+            valid_len += record_len;
+        }
+
+        if valid_len < bytes.len() {
+            warn!(
+                path = %log_path.display(),
+                discarded_bytes = bytes.len() - valid_len,
+                "Discarding torn record at the end of store log"
+            );
+            file.set_len(valid_len as u64)?;
+            file.sync_all()?;
+        }
+        if replayed > 0 {
+            info!(path = %path.display(), replayed, "Replayed store log");
+        }
+
+        let log = Self {
+            path,
+            file,
+            conf,
+            seq,
+            records,
+            len: valid_len as u64,
+            last_sync: Instant::now(),
+            unsynced: false,
+        };
+        Ok((data, log))
+    }
+
+    fn append<Op: Serialize>(&mut self, op: &Op) -> io::Result<()> {
+        let seq = self.seq + 1;
+        let payload = serde_json::to_vec(&RecordRef { seq, op }).map_err(io::Error::other)?;
+        let len = u32::try_from(payload.len()).map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidInput, "store log record too large")
+        })?;
+
+        let mut record = Vec::with_capacity(RECORD_HEADER_LEN + payload.len());
+        record.extend_from_slice(&len.to_le_bytes());
+        record.extend_from_slice(&crc32fast::hash(&payload).to_le_bytes());
+        record.extend_from_slice(&payload);
+        let sync = match self.conf.fsync {
+            FsyncPolicy::Always => true,
+            FsyncPolicy::Interval => {
+                self.last_sync.elapsed() >= Duration::from_millis(self.conf.fsync_interval_ms)
+            }
+            FsyncPolicy::Never => false,
+        };
+        let written = self.file.write_all(&record).and_then(|()| {
+            if sync {
+                self.file.sync_data()
+            } else {
+                Ok(())
+            }
+        });
+        if let Err(err) = written {
+            // The operation is not applied, so it must not be replayed, and
+            // later records must not follow a partial one
+            self.file.set_len(self.len)?;
+            return Err(err);
+        }
+        if sync {
+            self.last_sync = Instant::now();
+        }
+        self.unsynced = !sync;
+
+        self.seq = seq;
+        self.records += 1;
+        self.len += record.len() as u64;
+        Ok(())
+    }
+
+    /// Flushes the records appended since the last flush.
+    fn sync_pending(&mut self) -> io::Result<()> {
+        if !self.unsynced {
+            return Ok(());
+        }
+        self.file.sync_data()?;
+        self.last_sync = Instant::now();
+        self.unsynced = false;
+        Ok(())
+    }
+
+    fn compaction_due(&self) -> bool {
+        self.records >= self.conf.compact_after_ops.max(1)
+    }
+
+    /// Writes `data` as the new checkpoint (atomic write via temp file +
+    /// rename), then empties the log.
+    fn checkpoint<T: Serialize>(&mut self, data: &T) -> io::Result<()> {
+        let temp_path = PathBuf::from(format!("{}.tmp", self.path.display()));
+        let file = File::create(&temp_path)?;
+        let mut writer = BufWriter::new(file);
+        serde_json::to_writer(
+            &mut writer,
+            &CheckpointRef {
+                seq: self.seq,
+                data,
+            },
+        )
+        .map_err(io::Error::other)?;
+        writer
+            .into_inner()
+            .map_err(|e| e.into_error())?
+            .sync_all()?;
+        fs::rename(&temp_path, &self.path)?;
+        sync_parent_dir(&self.path)?;
+
+        self.file.set_len(0)?;
+        self.file.sync_all()?;
+        self.records = 0;
+        self.len = 0;
+        self.unsynced = false;
+        debug!(path = %self.path.display(), seq = self.seq, "Compacted store log");
+        Ok(())
+    }
+}
+
+impl Drop for OpLog {
+    fn drop(&mut self) {
+        if let Err(err) = self.sync_pending() {
+            warn!(error = %err, path = %self.path.display(), "Failed to flush store log");
+        }
+    }
+}
+
+/// Flushes the unsynced appends of `log` every `interval`, until its store is
+/// dropped.
+fn spawn_flusher(log: Weak<Mutex<Option<OpLog>>>, interval: Duration) -> io::Result<()> {
+    thread::Builder::new()
+        .name("store-log-fsync".to_string())
+        .spawn(move || loop {
+            thread::sleep(interval);
+            let Some(log) = log.upgrade() else {
+                return;
+            };
+            let Ok(mut log) = log.lock() else {
+                return;
+            };
+            if let Some(log) = log.as_mut() {
+                if let Err(err) = log.sync_pending() {
+                    warn!(error = %err, path = %log.path.display(), "Failed to flush store log");
+                }
+            }
+        })?;
+    Ok(())
+}
+
+/// The payload and total length of the record at the start of `bytes`, or
+/// `None` if it is incomplete or does not match its checksum.
+fn read_record(bytes: &[u8]) -> Option<(&[u8], usize)> {
+    let header = bytes.get(..RECORD_HEADER_LEN)?;
+    let len = u32::from_le_bytes(header[..4].try_into().ok()?) as usize;
+    let crc = u32::from_le_bytes(header[4..].try_into().ok()?);
+    let payload = bytes.get(RECORD_HEADER_LEN..RECORD_HEADER_LEN + len)?;
+    (crc32fast::hash(payload) == crc).then_some((payload, RECORD_HEADER_LEN + len))
+}
+
+/// Makes a rename in the directory of `path` durable.
+fn sync_parent_dir(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => File::open(dir)?.sync_all(),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default, Serialize, Deserialize)]
+    struct Pushes(Vec<u64>);
+
+    impl LogState for Pushes {
+        type Op = u64;
+
+        fn apply(&mut self, op: u64) {
+            self.0.push(op);
+        }
+    }
+
+    fn conf(compact_after_ops: u64) -> StoreLogConf {
+        StoreLogConf {
+            fsync: FsyncPolicy::Always,
+            fsync_interval_ms: 0,
+            compact_after_ops,
+        }
+    }
+
+    fn push_all(store: &LogStore<Pushes>, values: impl IntoIterator<Item = u64>) {
+        let mut guard = store.write();
+        for value in values {
+            guard.apply(value).unwrap();
+        }
+    }
+
+    #[test]
+    fn replays_the_log_after_a_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("store.json");
+
+        push_all(&LogStore::open(&path, conf(100)).unwrap(), [1, 2, 3]);
+        let store = LogStore::<Pushes>::open(&path, conf(100)).unwrap();
+        assert_eq!(store.read().0, [1, 2, 3]);
+
+        push_all(&store, [4]);
+        drop(store);
+        let store = LogStore::<Pushes>::open(&path, conf(100)).unwrap();
+        assert_eq!(store.read().0, [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn discards_a_torn_write() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("store.json");
+        let log_path = dir.path().join("store.json.log");
+
+        push_all(&LogStore::open(&path, conf(100)).unwrap(), [1, 2, 3]);
+        let intact_len = fs::metadata(&log_path).unwrap().len();
+
+        // A crash in the middle of appending a fourth record
+        let mut torn = Vec::new();
+        torn.extend_from_slice(&40u32.to_le_bytes());
+        torn.extend_from_slice(&0u32.to_le_bytes());
+        torn.extend_from_slice(br#"{"seq":4,"op"#);
+        OpenOptions::new()
+            .append(true)
+            .open(&log_path)
+            .unwrap()
+            .write_all(&torn)
+            .unwrap();
+
+        let store = LogStore::<Pushes>::open(&path, conf(100)).unwrap();
+        assert_eq!(store.read().0, [1, 2, 3]);
+        assert_eq!(fs::metadata(&log_path).unwrap().len(), intact_len);
+
+        // Appends resume after the last intact record
+        push_all(&store, [4]);
+        drop(store);
+        let store = LogStore::<Pushes>::open(&path, conf(100)).unwrap();
+        assert_eq!(store.read().0, [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn discards_a_record_failing_its_checksum() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("store.json");
+        let log_path = dir.path().join("store.json.log");
+
+        push_all(&LogStore::open(&path, conf(100)).unwrap(), [1, 2, 3]);
+        let mut bytes = fs::read(&log_path).unwrap();
+        let last = bytes.len() - 2;
+        bytes[last] ^= 0xff;
+        fs::write(&log_path, bytes).unwrap();
+
+        let store = LogStore::<Pushes>::open(&path, conf(100)).unwrap();
+        assert_eq!(store.read().0, [1, 2]);
+    }
+
+    #[test]
+    fn compacts_into_a_checkpoint() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("store.json");
+        let log_path = dir.path().join("store.json.log");
+
+        push_all(&LogStore::open(&path, conf(2)).unwrap(), [1, 2, 3]);
+        assert!(path.exists());
+        // Only the operation after the checkpoint is left in the log
+        let store = LogStore::<Pushes>::open(&path, conf(2)).unwrap();
+        assert_eq!(store.read().0, [1, 2, 3]);
+        assert_eq!(store.log.lock().unwrap().as_ref().unwrap().records, 1);
+        drop(store);
+
+        // A crash between the checkpoint rename and the log truncation
+        let stale_log = fs::read(&log_path).unwrap();
+        push_all(&LogStore::open(&path, conf(2)).unwrap(), [4]);
+        assert_eq!(fs::metadata(&log_path).unwrap().len(), 0);
+        fs::write(&log_path, stale_log).unwrap();
+
+        let store = LogStore::<Pushes>::open(&path, conf(2)).unwrap();
+        assert_eq!(store.read().0, [1, 2, 3, 4]);
+        push_all(&store, [5]);
+        drop(store);
+        let store = LogStore::<Pushes>::open(&path, conf(2)).unwrap();
+        assert_eq!(store.read().0, [1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn flushes_interval_appends_without_waiting_for_the_next_one() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("store.json");
+        let conf = StoreLogConf {
+            fsync: FsyncPolicy::Interval,
+            fsync_interval_ms: 10,
+            compact_after_ops: 100,
+        };
+
+        let store = LogStore::<Pushes>::open(&path, conf).unwrap();
+        // Appended right after the open, within the first interval
+        push_all(&store, [1]);
+        let unsynced = || store.log.lock().unwrap().as_ref().unwrap().unsynced;
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while unsynced() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert!(!unsynced());
+    }
+
+    #[test]
+    fn loads_a_legacy_json_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("store.json");
+        fs::write(&path, "[7, 8]").unwrap();
+
+        let store = LogStore::<Pushes>::open(&path, conf(100)).unwrap();
+        assert_eq!(store.read().0, [7, 8]);
+        push_all(&store, [9]);
+        drop(store);
+        let store = LogStore::<Pushes>::open(&path, conf(100)).unwrap();
+        assert_eq!(store.read().0, [7, 8, 9]);
+    }
+}
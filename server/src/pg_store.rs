@@ -0,0 +1,643 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fs,
+    io::BufReader,
+    path::Path,
+    str::FromStr,
+};
+
+use anyhow::{anyhow, Context, Result};
+use diesel::{
+    dsl::{count_distinct, max},
+    pg::PgConnection,
+    prelude::*,
+    r2d2::{ConnectionManager, Pool, PooledConnection},
+    sql_types::Text,
+};
+use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+use element::Element;
+use primitives::hash::CryptoHash;
+use serde::de::DeserializeOwned;
+use tracing::{debug, info};
+use zk_primitives::note_encryption::derive_recipient_tag;
+
+use crate::{
+    note_store::{
+        check_address_owner, AddressRegistration, InsertedNote, NoteBackend, RegistrationError,
+        RegistrationUpdate, RegistryBackend, StoredEncryptedNote,
+    },
+    request_auth::ReplayBackend,
+};
+
+const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
+
+/// Rows per INSERT when importing, well below the bind parameter limit.
+const IMPORT_BATCH_SIZE: usize = 1_000;
+
+mod schema {
+    diesel::table! {
+        encrypted_notes (id) {
+            id -> Text,
+            seq -> BigInt,
+            recipient_tag -> Text,
+            encrypted_payload -> Text,
+            ephemeral_pubkey -> Text,
+            sender_tag -> Nullable<Text>,
+            stored_at -> BigInt,
+        }
+    }
+
+    diesel::table! {
+        address_registrations (username) {
+            username -> Text,
+            utxo_address -> Text,
+            recipient_tag -> Nullable<Text>,
+            encryption_pubkey -> Text,
+            registered_at -> BigInt,
+            ownership_proof -> Nullable<Jsonb>,
+        }
+    }
+
+    diesel::table! {
+        used_signed_requests (message) {
+            message -> Text,
+            expires_at -> BigInt,
+        }
+    }
+}
+
+use schema::{address_registrations, encrypted_notes, used_signed_requests};
+
+pub type PgPool = Pool<ConnectionManager<PgConnection>>;
+type PgConn = PooledConnection<ConnectionManager<PgConnection>>;
+
+/// Connects to the note store database and applies pending migrations.
+pub fn connect(database_url: &str) -> Result<PgPool> {
+    let pool = Pool::builder()
+        .build(ConnectionManager::<PgConnection>::new(database_url))
+        .context("connecting to the note store database")?;
+    let applied = connection(&pool)?
+        .run_pending_migrations(MIGRATIONS)
+        .map_err(|e| anyhow!("running note store migrations: {e}"))?
+        .len();
+    if applied > 0 {
+        info!(migrations = applied, "Applied note store migrations");
+    }
+    Ok(pool)
+}
+
+fn connection(pool: &PgPool) -> Result<PgConn> {
+    pool.get()
+        .context("getting a note store database connection")
+}
+
+/// Cursor of the note numbered `seq`, as wide as the ids of the local backend.
+fn seq_cursor(seq: i64) -> String {
+    format!("{seq:024x}")
+}
+
+fn cursor_seq(cursor: &str) -> Result<i64> {
+    u128::from_str_radix(cursor, 16)
+        .ok()
+        .and_then(|seq| i64::try_from(seq).ok())
+        .ok_or_else(|| anyhow!("'{cursor}' is not a note cursor of this store"))
+}
+
+/// Serializes note inserts across servers until the transaction ends, so a
+/// later `seq` never becomes visible before an earlier one.
+fn lock_note_inserts(conn: &mut PgConnection) -> Result<()> {
+    diesel::sql_query("SELECT pg_advisory_xact_lock(hashtext('encrypted_notes'))").execute(conn)?;
+    Ok(())
+}
+
+/// A note to insert; its `seq` is assigned by the database.
+#[derive(Insertable)]
+#[diesel(table_name = encrypted_notes)]
+struct NewNoteRow {
+    id: String,
+    recipient_tag: String,
+    encrypted_payload: String,
+    ephemeral_pubkey: String,
+    sender_tag: Option<String>,
+    stored_at: i64,
+}
+
+impl NewNoteRow {
+    fn new(recipient_tag: &str, note: StoredEncryptedNote) -> Self {
+        Self {
+            id: note.id,
+            recipient_tag: recipient_tag.to_string(),
+            encrypted_payload: note.encrypted_payload,
+            ephemeral_pubkey: note.ephemeral_pubkey,
+            sender_tag: note.sender_tag,
+            stored_at: note.stored_at as i64,
+        }
+    }
+}
+
+#[derive(Queryable, Selectable)]
+#[diesel(table_name = encrypted_notes)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+struct NoteRow {
+    id: String,
+    seq: i64,
+    recipient_tag: String,
+    encrypted_payload: String,
+    ephemeral_pubkey: String,
+    sender_tag: Option<String>,
+    stored_at: i64,
+}
+
+impl NoteRow {
+    fn into_inserted(self) -> InsertedNote {
+        InsertedNote {
+            recipient_tag: self.recipient_tag,
+            note: StoredEncryptedNote {
+                id: self.id,
+                encrypted_payload: self.encrypted_payload,
+                ephemeral_pubkey: self.ephemeral_pubkey,
+                sender_tag: self.sender_tag,
+                stored_at: self.stored_at as u64,
+            },
+            cursor: seq_cursor(self.seq),
+        }
+    }
+}
+
+#[derive(Queryable, Selectable, Insertable, AsChangeset)]
+#[diesel(table_name = address_registrations)]
+#[diesel(primary_key(username))]
+#[diesel(treat_none_as_null = true)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+struct RegistrationRow {
+    username: String,
+    utxo_address: Element,
+    recipient_tag: Option<String>,
+    encryption_pubkey: String,
+    registered_at: i64,
+    ownership_proof: Option<serde_json::Value>,
+}
+
+impl RegistrationRow {
+    fn new(registration: &AddressRegistration) -> Result<Self> {
+        let utxo_address = Element::from_str(&registration.utxo_address).map_err(|e| {
+            anyhow!(
+                "invalid UTXO address registered for '{}': {e}",
+                registration.username
+            )
+        })?;
+        Ok(Self {
+            username: registration.username.clone(),
+            utxo_address,
+            recipient_tag: derive_recipient_tag(&registration.utxo_address).ok(),
+            encryption_pubkey: registration.encryption_pubkey.clone(),
+            registered_at: registration.registered_at as i64,
+            ownership_proof: registration
+                .ownership_proof
+                .as_ref()
+                .map(serde_json::to_value)
+                .transpose()?,
+        })
+    }
+
+    fn into_registration(self) -> Result<AddressRegistration> {
+        Ok(AddressRegistration {
+            username: self.username,
+            utxo_address: self.utxo_address.to_hex(),
+            encryption_pubkey: self.encryption_pubkey,
+            registered_at: self.registered_at as u64,
+            ownership_proof: self
+                .ownership_proof
+                .map(serde_json::from_value)
+                .transpose()?,
+        })
+    }
+}
+
+/// Notes stored in Postgres, shared by every server using the database.
+///
+/// Cursors are the `seq` the database assigns to each note. Inserts are
+/// serialized, so notes commit in `seq` order and a reader never sees a note
+/// before an earlier one is visible.
+pub struct PgNotes {
+    pool: PgPool,
+}
+
+impl PgNotes {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+impl NoteBackend for PgNotes {
+    fn insert(
+        &self,
+        recipient_tag: &str,
+        note: StoredEncryptedNote,
+        max_notes: usize,
+    ) -> Result<String> {
+        let row = NewNoteRow::new(recipient_tag, note);
+        connection(&self.pool)?.transaction::<_, anyhow::Error, _>(|conn| {
+            lock_note_inserts(conn)?;
+            let seq: i64 = diesel::insert_into(encrypted_notes::table)
+                .values(&row)
+                .returning(encrypted_notes::seq)
+                .get_result(conn)?;
+
+            // Newest note past capacity: it and every older note are evicted
+            let cutoff: Option<i64> = encrypted_notes::table
+                .filter(encrypted_notes::recipient_tag.eq(recipient_tag))
+                .order(encrypted_notes::seq.desc())
+                .select(encrypted_notes::seq)
+                .offset(max_notes as i64)
+                .first(conn)
+                .optional()?;
+            if let Some(cutoff) = cutoff {
+                let evicted = diesel::delete(
+                    encrypted_notes::table
+                        .filter(encrypted_notes::recipient_tag.eq(recipient_tag))
+                        .filter(encrypted_notes::seq.le(cutoff)),
+                )
+                .execute(conn)?;
+                debug!(
+                    recipient_tag = %recipient_tag,
+                    evicted,
+                    "Evicted oldest notes due to capacity limit"
+                );
+            }
+            Ok(seq_cursor(seq))
+        })
+    }
+
+    fn notes_after(&self, recipient_tags: &[String], cursor: &str) -> Result<Vec<InsertedNote>> {
+        let rows: Vec<NoteRow> = encrypted_notes::table
+            .filter(encrypted_notes::recipient_tag.eq_any(recipient_tags))
+            .filter(encrypted_notes::seq.gt(cursor_seq(cursor)?))
+            .order(encrypted_notes::seq.asc())
+            .select(NoteRow::as_select())
+            .load(&mut connection(&self.pool)?)?;
+        Ok(rows.into_iter().map(NoteRow::into_inserted).collect())
+    }
+
+    fn all_notes_after(&self, cursor: &str, limit: usize) -> Result<Vec<InsertedNote>> {
+        let rows: Vec<NoteRow> = encrypted_notes::table
+            .filter(encrypted_notes::seq.gt(cursor_seq(cursor)?))
+            .order(encrypted_notes::seq.asc())
+            .limit(limit as i64)
+            .select(NoteRow::as_select())
+            .load(&mut connection(&self.pool)?)?;
+        Ok(rows.into_iter().map(NoteRow::into_inserted).collect())
+    }
+
+    fn cursor_now(&self) -> Result<String> {
+        let last: Option<i64> = encrypted_notes::table
+            .select(max(encrypted_notes::seq))
+            .get_result(&mut connection(&self.pool)?)?;
+        Ok(seq_cursor(last.unwrap_or(0)))
+    }
+
+    fn shared(&self) -> bool {
+        true
+    }
+
+    fn get_notes(
+        &self,
+        recipient_tag: &str,
+        since: Option<u64>,
+        limit: usize,
+    ) -> Result<(Vec<StoredEncryptedNote>, bool)> {
+        let mut query = encrypted_notes::table
+            .filter(encrypted_notes::recipient_tag.eq(recipient_tag))
+            .into_boxed();
+        if let Some(since) = since {
+            query = query.filter(encrypted_notes::stored_at.gt(since as i64));
+        }
+        let mut rows: Vec<NoteRow> = query
+            .order(encrypted_notes::seq.asc())
+            .limit(limit as i64 + 1)
+            .select(NoteRow::as_select())
+            .load(&mut connection(&self.pool)?)?;
+
+        let has_more = rows.len() > limit;
+        rows.truncate(limit);
+        let notes = rows
+            .into_iter()
+            .map(|row| row.into_inserted().note)
+            .collect();
+        Ok((notes, has_more))
+    }
+
+    fn delete_note(&self, recipient_tag: &str, note_id: &str) -> Result<bool> {
+        let deleted = diesel::delete(
+            encrypted_notes::table
+                .filter(encrypted_notes::recipient_tag.eq(recipient_tag))
+                .filter(encrypted_notes::id.eq(note_id)),
+        )
+        .execute(&mut connection(&self.pool)?)?;
+        Ok(deleted > 0)
+    }
+
+    fn total_notes(&self) -> Result<usize> {
+        let total: i64 = encrypted_notes::table
+            .count()
+            .get_result(&mut connection(&self.pool)?)?;
+        Ok(total as usize)
+    }
+
+    fn recipient_count(&self) -> Result<usize> {
+        let count: i64 = encrypted_notes::table
+            .select(count_distinct(encrypted_notes::recipient_tag))
+            .get_result(&mut connection(&self.pool)?)?;
+        Ok(count as usize)
+    }
+}
+
+/// Username registrations stored in Postgres, shared by every server using
+/// the database.
+pub struct PgRegistry {
+    pool: PgPool,
+}
+
+impl PgRegistry {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+impl RegistryBackend for PgRegistry {
+    fn get(&self, username: &str) -> Result<Option<AddressRegistration>> {
+        address_registrations::table
+            .find(username)
+            .select(RegistrationRow::as_select())
+            .first(&mut connection(&self.pool)?)
+            .optional()?
+            .map(RegistrationRow::into_registration)
+            .transpose()
+    }
+
+    fn update(
+        &self,
+        username: &str,
+        update: RegistrationUpdate<'_>,
+    ) -> Result<Option<AddressRegistration>, RegistrationError> {
+        connection(&self.pool)?.transaction::<_, anyhow::Error, _>(|conn| {
+            let current = address_registrations::table
+                .find(username)
+                .select(RegistrationRow::as_select())
+                .for_update()
+                .first(conn)
+                .optional()?
+                .map(RegistrationRow::into_registration)
+                .transpose()?;
+            let next = match update(current.as_ref()) {
+                Ok(next) => next,
+                Err(err) => return Ok(Err(err)),
+            };
+
+            let row = match &next {
+                Some(registration) => {
+                    let row = RegistrationRow::new(registration)?;
+                    // Registrations of an address are serialized across servers
+                    // until the transaction ends, so two owners cannot both
+                    // pass the check below
+                    diesel::sql_query("SELECT pg_advisory_xact_lock(hashtext($1))")
+                        .bind::<Text, _>(row.utxo_address.to_hex())
+                        .execute(conn)?;
+                    let others = address_registrations::table
+                        .filter(address_registrations::utxo_address.eq(row.utxo_address))
+                        .filter(address_registrations::username.ne(username))
+                        .select(RegistrationRow::as_select())
+                        .load(conn)?
+                        .into_iter()
+                        .map(RegistrationRow::into_registration)
+                        .collect::<Result<Vec<_>>>()?;
+                    if let Err(err) = check_address_owner(registration, &others) {
+                        return Ok(Err(err));
+                    }
+                    Some(row)
+                }
+                None => None,
+            };
+
+            match (row, &current) {
+                (Some(row), Some(_)) => {
+                    diesel::update(address_registrations::table.find(username))
+                        .set(&row)
+                        .execute(conn)?;
+                }
+                (Some(row), None) => {
+                    // A missing row is not locked, so another server may have
+                    // registered the username meanwhile
+                    let inserted = diesel::insert_into(address_registrations::table)
+                        .values(&row)
+                        .on_conflict_do_nothing()
+                        .execute(conn)?;
+                    if inserted == 0 {
+                        return Err(anyhow!("username '{username}' was registered concurrently"));
+                    }
+                }
+                (None, Some(_)) => {
+                    diesel::delete(address_registrations::table.find(username)).execute(conn)?;
+                }
+                (None, None) => {}
+            }
+            Ok(Ok(current))
+        })?
+    }
+
+    fn with_recipient_tag(&self, recipient_tag: &str) -> Result<Vec<AddressRegistration>> {
+        address_registrations::table
+            .filter(address_registrations::recipient_tag.eq(recipient_tag))
+            .select(RegistrationRow::as_select())
+            .load(&mut connection(&self.pool)?)?
+            .into_iter()
+            .map(RegistrationRow::into_registration)
+            .collect()
+    }
+
+    fn count(&self) -> Result<usize> {
+        let count: i64 = address_registrations::table
+            .count()
+            .get_result(&mut connection(&self.pool)?)?;
+        Ok(count as usize)
+    }
+
+    fn list_all(&self) -> Result<Vec<AddressRegistration>> {
+        address_registrations::table
+            .select(RegistrationRow::as_select())
+            .load(&mut connection(&self.pool)?)?
+            .into_iter()
+            .map(RegistrationRow::into_registration)
+            .collect()
+    }
+}
+
+/// Used request signatures in the `used_signed_requests` table, shared by all
+/// the servers of the database.
+pub struct PgReplays {
+    pool: PgPool,
+}
+
+impl PgReplays {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+impl ReplayBackend for PgReplays {
+    fn claim(&self, message: &CryptoHash, expires_at: u64, now: u64) -> Result<bool> {
+        let mut conn = connection(&self.pool)?;
+        diesel::delete(
+            used_signed_requests::table.filter(used_signed_requests::expires_at.lt(now as i64)),
+        )
+        .execute(&mut conn)?;
+        // Only one of the servers claiming the same message inserts it
+        let inserted = diesel::insert_into(used_signed_requests::table)
+            .values((
+                used_signed_requests::message.eq(message.to_hex()),
+                used_signed_requests::expires_at.eq(expires_at as i64),
+            ))
+            .on_conflict_do_nothing()
+            .execute(&mut conn)?;
+        Ok(inserted == 1)
+    }
+}
+
+/// What [`import_json`] copied into the database.
+#[derive(Debug, Default)]
+pub struct ImportSummary {
+    pub notes: usize,
+    pub registrations: usize,
+}
+
+/// Copies the notes and registrations persisted by the local backend into the
+/// database. Missing files are skipped and rows already in the database are
+/// left untouched, so the import can be run again.
+pub fn import_json(
+    pool: &PgPool,
+    notes_path: &Path,
+    registry_path: &Path,
+) -> Result<ImportSummary> {
+    let mut conn = connection(pool)?;
+    let mut summary = ImportSummary::default();
+
+    if notes_path.exists() {
+        let notes: HashMap<String, VecDeque<StoredEncryptedNote>> = read_json(notes_path)?;
+        let mut rows: Vec<NewNoteRow> = notes
+            .into_iter()
+            .flat_map(|(tag, queue)| {
+                queue
+                    .into_iter()
+                    .map(move |note| NewNoteRow::new(&tag, note))
+            })
+            .collect();
+        // Numbered in id order, the order the notes were inserted in
+        rows.sort_by(|a, b| a.id.cmp(&b.id));
+        summary.notes = conn.transaction::<_, anyhow::Error, _>(|conn| {
+            lock_note_inserts(conn)?;
+            let mut imported = 0;
+            for batch in rows.chunks(IMPORT_BATCH_SIZE) {
+                imported += diesel::insert_into(encrypted_notes::table)
+                    .values(batch)
+                    .on_conflict_do_nothing()
+                    .execute(conn)?;
+            }
+            Ok(imported)
+        })?;
+    }
+
+    if registry_path.exists() {
+        let registrations: HashMap<String, AddressRegistration> = read_json(registry_path)?;
+        let rows = registrations
+            .values()
+            .map(RegistrationRow::new)
+            .collect::<Result<Vec<_>>>()?;
+        for batch in rows.chunks(IMPORT_BATCH_SIZE) {
+            summary.registrations += diesel::insert_into(address_registrations::table)
+                .values(batch)
+                .on_conflict_do_nothing()
+                .execute(&mut conn)?;
+        }
+    }
+
+    Ok(summary)
+}
+
+fn read_json<T: DeserializeOwned>(path: &Path) -> Result<T> {
+    let file = fs::File::open(path).with_context(|| format!("opening {}", path.display()))?;
+    serde_json::from_reader(BufReader::new(file))
+        .with_context(|| format!("parsing {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        note_store::{tests::check_note_backend, OwnershipProof},
+        request_auth::tests::check_replay_backend,
+    };
+
+    /// Database for the tests that need one, skipped when it is not set.
+    const TEST_DATABASE_URL: &str = "NOTE_STORE_TEST_DATABASE_URL";
+
+    #[test]
+    fn pg_notes_pass_the_backend_checks() {
+        let Ok(database_url) = std::env::var(TEST_DATABASE_URL) else {
+            eprintln!("{TEST_DATABASE_URL} is not set, skipping");
+            return;
+        };
+        check_note_backend(Box::new(PgNotes::new(connect(&database_url).unwrap())));
+    }
+
+    #[test]
+    fn pg_replays_pass_the_backend_checks() {
+        let Ok(database_url) = std::env::var(TEST_DATABASE_URL) else {
+            eprintln!("{TEST_DATABASE_URL} is not set, skipping");
+            return;
+        };
+        check_replay_backend(Box::new(PgReplays::new(connect(&database_url).unwrap())));
+    }
+
+    #[test]
+    fn note_cursors_sort_by_seq() {
+        assert_eq!(cursor_seq(&seq_cursor(0)).unwrap(), 0);
+        assert_eq!(cursor_seq(&seq_cursor(i64::MAX)).unwrap(), i64::MAX);
+        assert!(seq_cursor(9) < seq_cursor(10));
+        assert!(seq_cursor(255) < seq_cursor(256));
+        // Ids of the local backend are not cursors of the database
+        assert!(cursor_seq(&format!("{:016x}{:08x}", u64::MAX, 0)).is_err());
+        assert!(cursor_seq("not hex").is_err());
+    }
+
+    #[test]
+    fn registration_rows_round_trip() {
+        let registration = AddressRegistration {
+            username: "alice".to_string(),
+            utxo_address: format!("00{}", "ab".repeat(31)),
+            encryption_pubkey: "11".repeat(32),
+            registered_at: 1_760_000_000,
+            ownership_proof: Some(OwnershipProof {
+                owner_pubkey: "11".repeat(32),
+                signature: "00".repeat(65),
+                signed_at: 1_760_000_000,
+            }),
+        };
+
+        let row = RegistrationRow::new(&registration).unwrap();
+        assert_eq!(
+            row.recipient_tag,
+            Some(derive_recipient_tag(&registration.utxo_address).unwrap())
+        );
+        let restored = row.into_registration().unwrap();
+        // Leading zeros of the address are kept
+        assert_eq!(restored.utxo_address, registration.utxo_address);
+        assert_eq!(restored.ownership_proof, registration.ownership_proof);
+        assert_eq!(restored.registered_at, registration.registered_at);
+
+        let invalid = AddressRegistration {
+            utxo_address: "not hex".to_string(),
+            ..registration
+        };
+        assert!(RegistrationRow::new(&invalid).is_err());
+    }
+}
@@ -0,0 +1,375 @@
+use std::{collections::BTreeMap, io, sync::Arc, time::Duration};
+
+use anyhow::Result;
+use client_sdk::rest_client::NodeApiClient;
+use primitives::retry::retry;
+use sdk::ProofTransaction;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tokio::time::Instant;
+use tracing::{error, info, warn};
+
+use crate::{
+    conf::{ProofQueueConf, StoreLogConf},
+    op_log::{LogState, LogStore},
+};
+
+/// A proof job waiting for its proof to reach the node.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct QueuedJob<J> {
+    pub job: J,
+    pub enqueued_at: u64,
+    /// Failed node submissions so far
+    pub attempts: u32,
+    pub last_error: Option<String>,
+}
+
+/// A proof job that was given up on, kept for inspection and manual replay.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DeadLetter<J> {
+    /// Tx hash (hex) the proof was for
+    pub key: String,
+    pub job: J,
+    pub enqueued_at: u64,
+    pub failed_at: u64,
+    pub attempts: u32,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(bound(deserialize = "J: DeserializeOwned"))]
+struct QueueState<J> {
+    /// Unfinished jobs keyed by tx hash
+    jobs: BTreeMap<String, QueuedJob<J>>,
+    dead_letters: Vec<DeadLetter<J>>,
+}
+
+impl<J> Default for QueueState<J> {
+    fn default() -> Self {
+        Self {
+            jobs: BTreeMap::new(),
+            dead_letters: Vec::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(bound(deserialize = "J: DeserializeOwned"))]
+enum QueueOp<J> {
+    Enqueue {
+        key: String,
+        job: J,
+        now: u64,
+    },
+    AttemptFailed {
+        key: String,
+        error: String,
+    },
+    Complete {
+        key: String,
+    },
+    /// Moves the job of `key` to the dead letters, dropping the ones older
+    /// than `retention_secs` at `now`
+    DeadLetter {
+        key: String,
+        reason: String,
+        now: u64,
+        retention_secs: u64,
+    },
+}
+
+impl<J: Serialize + DeserializeOwned> LogState for QueueState<J> {
+    type Op = QueueOp<J>;
+
+    fn apply(&mut self, op: QueueOp<J>) {
+        match op {
+            QueueOp::Enqueue { key, job, now } => {
+                self.jobs.entry(key).or_insert(QueuedJob {
+                    job,
+                    enqueued_at: now,
+                    attempts: 0,
+                    last_error: None,
+                });
+            }
+            QueueOp::AttemptFailed { key, error } => {
+                if let Some(job) = self.jobs.get_mut(&key) {
+                    job.attempts += 1;
+                    job.last_error = Some(error);
+                }
+            }
+            QueueOp::Complete { key } => {
+                self.jobs.remove(&key);
+            }
+            QueueOp::DeadLetter {
+                key,
+                reason,
+                now,
+                retention_secs,
+            } => {
+                let Some(job) = self.jobs.remove(&key) else {
+                    return;
+                };
+                self.dead_letters
+                    .retain(|dead| now < dead.failed_at.saturating_add(retention_secs));
+                self.dead_letters.push(DeadLetter {
+                    key,
+                    job: job.job,
+                    enqueued_at: job.enqueued_at,
+                    failed_at: now,
+                    attempts: job.attempts,
+                    reason,
+                });
+            }
+        }
+    }
+}
+
+/// Proof jobs of a Noir prover, persisted until their proof is accepted by
+/// the node so they can be resumed after a restart.
+///
+/// A job whose proof cannot be generated, or still cannot be submitted
+/// `deadline_secs` after it was queued, is moved to the dead letters.
+pub struct ProofJobQueue<J: Serialize + DeserializeOwned> {
+    conf: ProofQueueConf,
+    store: LogStore<QueueState<J>>,
+}
+
+impl<J: Clone + Serialize + DeserializeOwned> ProofJobQueue<J> {
+    /// Creates an in-memory queue.
+    pub fn new(conf: ProofQueueConf) -> Self {
+        Self {
+            conf,
+            store: LogStore::new(QueueState::default()),
+        }
+    }
+
+    /// Creates a queue persisted to `persistence_path` and its operation log.
+    pub fn with_persistence(
+        conf: ProofQueueConf,
+        store_log: StoreLogConf,
+        persistence_path: String,
+    ) -> io::Result<Self> {
+        let store = LogStore::<QueueState<J>>::open(persistence_path, store_log)?;
+        let (pending, dead) = {
+            let state = store.read();
+            (state.jobs.len(), state.dead_letters.len())
+        };
+        if pending > 0 || dead > 0 {
+            info!(pending, dead, "Loaded proof jobs from disk");
+        }
+        Ok(Self { conf, store })
+    }
+
+    /// Queues a job for tx `key`. Returns false if a job for this tx is
+    /// already queued.
+    pub fn enqueue(&self, key: &str, job: J, now: u64) -> bool {
+        let mut state = self.store.write();
+        if state.jobs.contains_key(key) {
+            return false;
+        }
+        let op = QueueOp::Enqueue {
+            key: key.to_string(),
+            job,
+            now,
+        };
+        if let Err(err) = state.apply(op) {
+            warn!(error = %err, tx_hash = %key, "Failed to persist proof job");
+        }
+        true
+    }
+
+    /// Unfinished jobs, to resume at startup.
+    pub fn pending(&self) -> Vec<(String, QueuedJob<J>)> {
+        self.store
+            .read()
+            .jobs
+            .iter()
+            .map(|(key, job)| (key.clone(), job.clone()))
+            .collect()
+    }
+
+    /// Time at which the proof of tx `key` stops being retried.
+    pub fn deadline(&self, key: &str, now: u64) -> Option<Instant> {
+        let enqueued_at = self.store.read().jobs.get(key)?.enqueued_at;
+        let deadline = enqueued_at.saturating_add(self.conf.deadline_secs);
+        Some(Instant::now() + Duration::from_secs(deadline.saturating_sub(now)))
+    }
+
+    /// Records a failed submission of the proof of tx `key`.
+    pub fn record_attempt_failed(&self, key: &str, error: String) {
+        let mut state = self.store.write();
+        if !state.jobs.contains_key(key) {
+            return;
+        }
+        let op = QueueOp::AttemptFailed {
+            key: key.to_string(),
+            error,
+        };
+        if let Err(err) = state.apply(op) {
+            warn!(error = %err, tx_hash = %key, "Failed to persist proof job");
+        }
+    }
+
+    /// Removes the job of tx `key` once its proof was accepted by the node.
+    pub fn complete(&self, key: &str) {
+        let mut state = self.store.write();
+        if !state.jobs.contains_key(key) {
+            return;
+        }
+        let op = QueueOp::Complete {
+            key: key.to_string(),
+        };
+        if let Err(err) = state.apply(op) {
+            warn!(error = %err, tx_hash = %key, "Failed to persist proof job");
+        }
+    }
+
+    /// Gives up on the job of tx `key`.
+    pub fn dead_letter(&self, key: &str, reason: String, now: u64) {
+        let mut state = self.store.write();
+        let Some(job) = state.jobs.get(key) else {
+            return;
+        };
+        error!(
+            tx_hash = %key,
+            attempts = job.attempts,
+            %reason,
+            "Giving up on proof job"
+        );
+        let op = QueueOp::DeadLetter {
+            key: key.to_string(),
+            reason,
+            now,
+            retention_secs: self.conf.dead_letter_retention_secs,
+        };
+        if let Err(err) = state.apply(op) {
+            warn!(error = %err, tx_hash = %key, "Failed to persist proof job");
+        }
+    }
+
+    /// Jobs given up on within `dead_letter_retention_secs`, oldest first.
+    pub fn dead_letters(&self) -> Vec<DeadLetter<J>> {
+        self.store.read().dead_letters.clone()
+    }
+}
+
+impl<J: Clone + Serialize + DeserializeOwned + Send + Sync + 'static> ProofJobQueue<J> {
+    /// Sends the proof of tx `key` to the node, retrying with backoff until
+    /// `deadline`. Failed attempts are recorded on the queued job.
+    pub async fn submit_proof(
+        self: &Arc<Self>,
+        key: &str,
+        node: &Arc<dyn NodeApiClient + Send + Sync>,
+        proof_tx: ProofTransaction,
+        deadline: Instant,
+    ) -> Result<()> {
+        let queue = Arc::clone(self);
+        let failed_key = key.to_string();
+        retry(|| node.send_tx_proof(proof_tx.clone()))
+            .retry_delay(Duration::from_millis(self.conf.retry_delay_ms))
+            .backoff(Duration::from_millis(self.conf.max_retry_delay_ms))
+            .on_error(move |err: &anyhow::Error| {
+                warn!(tx_hash = %failed_key, error = %err, "Proof submission failed, retrying");
+                queue.record_attempt_failed(&failed_key, format!("{err:#}"));
+            })
+            .exec_until(deadline)
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conf::FsyncPolicy;
+
+    const NOW: u64 = 1_760_000_000;
+
+    fn conf() -> ProofQueueConf {
+        ProofQueueConf {
+            deadline_secs: 600,
+            retry_delay_ms: 10,
+            max_retry_delay_ms: 100,
+            dead_letter_retention_secs: 3_600,
+        }
+    }
+
+    fn store_log() -> StoreLogConf {
+        StoreLogConf {
+            fsync: FsyncPolicy::Always,
+            fsync_interval_ms: 0,
+            compact_after_ops: 2,
+        }
+    }
+
+    #[test]
+    fn tracks_jobs_until_completed_or_dead_lettered() {
+        let queue = ProofJobQueue::<u32>::new(conf());
+        assert!(queue.enqueue("aa", 1, NOW));
+        assert!(!queue.enqueue("aa", 2, NOW));
+        assert!(queue.enqueue("bb", 3, NOW));
+
+        queue.record_attempt_failed("aa", "node down".into());
+        let pending = queue.pending();
+        assert_eq!(pending.len(), 2);
+        assert_eq!(pending[0].0, "aa");
+        assert_eq!(pending[0].1.job, 1);
+        assert_eq!(pending[0].1.attempts, 1);
+        assert_eq!(pending[0].1.last_error.as_deref(), Some("node down"));
+
+        queue.complete("bb");
+        queue.dead_letter("aa", "deadline passed".into(), NOW + 600);
+        assert!(queue.pending().is_empty());
+        assert_eq!(queue.deadline("aa", NOW), None);
+
+        let dead = queue.dead_letters();
+        assert_eq!(dead.len(), 1);
+        assert_eq!(dead[0].key, "aa");
+        assert_eq!(dead[0].job, 1);
+        assert_eq!(dead[0].attempts, 1);
+
+        // Old dead letters are pruned when a new one is recorded
+        queue.enqueue("cc", 4, NOW + 4_200);
+        queue.dead_letter("cc", "proving failed".into(), NOW + 4_200);
+        let dead = queue.dead_letters();
+        assert_eq!(dead.len(), 1);
+        assert_eq!(dead[0].key, "cc");
+    }
+
+    #[tokio::test]
+    async fn deadline_counts_from_enqueue_time() {
+        let queue = ProofJobQueue::<u32>::new(conf());
+        queue.enqueue("aa", 1, NOW);
+
+        let start = Instant::now();
+        let deadline = queue.deadline("aa", NOW + 100).unwrap();
+        assert!(deadline >= start + Duration::from_secs(500));
+        assert!(deadline <= Instant::now() + Duration::from_secs(500));
+
+        // A job resumed past its deadline is due immediately
+        assert!(queue.deadline("aa", NOW + 700).unwrap() <= Instant::now());
+    }
+
+    #[test]
+    fn resumes_jobs_after_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir
+            .path()
+            .join("proof_jobs.json")
+            .to_string_lossy()
+            .to_string();
+
+        let queue =
+            ProofJobQueue::<u32>::with_persistence(conf(), store_log(), path.clone()).unwrap();
+        queue.enqueue("aa", 1, NOW);
+        queue.enqueue("bb", 2, NOW);
+        queue.dead_letter("bb", "proving failed".into(), NOW);
+        drop(queue);
+
+        let reloaded = ProofJobQueue::<u32>::with_persistence(conf(), store_log(), path).unwrap();
+        let pending = reloaded.pending();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].0, "aa");
+        assert_eq!(pending[0].1.enqueued_at, NOW);
+        assert_eq!(reloaded.dead_letters()[0].key, "bb");
+    }
+}
@@ -0,0 +1,422 @@
+use barretenberg::Verify;
+use element::Element;
+use hyli_verifiers::noir_utils;
+use sdk::{Blob, HyliOutput, Identity, TxHash};
+use zk_primitives::{
+    bytes_to_elements, HyliSmtInclProof, HyliUtxoProof, UtxoProofBytes,
+    HYLI_SMT_INCL_PUBLIC_INPUTS_COUNT, HYLI_UTXO_PUBLIC_INPUTS_COUNT,
+};
+
+use crate::{
+    init::{HYLI_SMT_INCL_PROOF_VK, HYLI_UTXO_NOIR_VK},
+    proving_scheduler::Circuit,
+};
+
+/// A client proof that must not be forwarded to the node.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ProofCheckError {
+    #[error("{circuit} proof has {got} public inputs, expected {expected}")]
+    PublicInputCount {
+        circuit: Circuit,
+        expected: usize,
+        got: usize,
+    },
+    #[error("{circuit} public input {index} is invalid: {reason}")]
+    InvalidPublicInput {
+        circuit: Circuit,
+        index: usize,
+        reason: String,
+    },
+    #[error("{circuit} proof is malformed: {reason}")]
+    Malformed { circuit: Circuit, reason: String },
+    #[error("{circuit} proof does not verify: {reason}")]
+    Invalid { circuit: Circuit, reason: String },
+    #[error("{circuit} proof is for tx {proven}, expected {expected}")]
+    TxHashMismatch {
+        circuit: Circuit,
+        proven: String,
+        expected: String,
+    },
+    #[error("{circuit} proof is for identity {proven}, expected {expected}")]
+    IdentityMismatch {
+        circuit: Circuit,
+        proven: String,
+        expected: String,
+    },
+    #[error("{circuit} proof does not match blob {index} of the transaction: {reason}")]
+    BlobMismatch {
+        circuit: Circuit,
+        index: usize,
+        reason: String,
+    },
+    #[error("{circuit} proof reports a failed execution")]
+    NotSuccessful { circuit: Circuit },
+}
+
+/// What a client proof must commit to.
+pub struct ExpectedTx<'a> {
+    pub tx_hash: &'a TxHash,
+    pub identity: &'a Identity,
+    /// Contract owning the proven blob
+    pub contract_name: &'a str,
+    /// Blobs of the transaction
+    pub blobs: &'a [Blob],
+}
+
+fn public_inputs_count(circuit: Circuit) -> usize {
+    match circuit {
+        Circuit::HyliUtxo => HYLI_UTXO_PUBLIC_INPUTS_COUNT,
+        Circuit::SmtIncl => HYLI_SMT_INCL_PUBLIC_INPUTS_COUNT,
+    }
+}
+
+/// Verifying key registered for the circuit's contract.
+fn verifying_key(circuit: Circuit) -> &'static [u8] {
+    match circuit {
+        Circuit::HyliUtxo => HYLI_UTXO_NOIR_VK,
+        Circuit::SmtIncl => HYLI_SMT_INCL_PROOF_VK,
+    }
+}
+
+/// Decodes the hex-encoded public inputs of a client proof. There must be
+/// exactly as many as the circuit exposes, each a 32-byte canonical field
+/// element, so that the node verifies the very values the client proved.
+pub fn decode_public_inputs(
+    circuit: Circuit,
+    public_inputs: &[String],
+) -> Result<Vec<u8>, ProofCheckError> {
+    let expected = public_inputs_count(circuit);
+    if public_inputs.len() != expected {
+        return Err(ProofCheckError::PublicInputCount {
+            circuit,
+            expected,
+            got: public_inputs.len(),
+        });
+    }
+
+    let mut bytes = Vec::with_capacity(expected * 32);
+    for (index, input) in public_inputs.iter().enumerate() {
+        let invalid = |reason: String| ProofCheckError::InvalidPublicInput {
+            circuit,
+            index,
+            reason,
+        };
+        let normalized = input.strip_prefix("0x").unwrap_or(input);
+        if normalized.len() != 64 {
+            return Err(invalid(format!(
+                "expected 64 hex characters, got {}",
+                normalized.len()
+            )));
+        }
+        let mut element = [0u8; 32];
+        hex::decode_to_slice(normalized, &mut element)
+            .map_err(|err| invalid(format!("invalid hex: {err}")))?;
+        if Element::from_be_bytes(element) >= Element::MODULUS {
+            return Err(invalid("not a canonical field element".to_string()));
+        }
+        bytes.extend_from_slice(&element);
+    }
+    Ok(bytes)
+}
+
+/// Checks a client proof (public inputs followed by the proof) before it is
+/// sent to the node: its public inputs must match `expected` and the proof
+/// must verify against the circuit's key.
+///
+/// The public inputs are checked first as they are cheap to decode, which
+/// spares a `bb verify` run for proofs made for another transaction.
+pub async fn check_client_proof(
+    circuit: Circuit,
+    proof_with_inputs: &[u8],
+    expected: &ExpectedTx<'_>,
+) -> Result<(), ProofCheckError> {
+    let output = parse_public_inputs(circuit, proof_with_inputs)?;
+    check_output(circuit, &output, expected)?;
+    verify_proof(circuit, proof_with_inputs.to_vec()).await
+}
+
+/// Decodes the [`HyliOutput`] committed to by the public inputs of a proof.
+pub fn parse_public_inputs(
+    circuit: Circuit,
+    proof_with_inputs: &[u8],
+) -> Result<HyliOutput, ProofCheckError> {
+    let malformed = |reason: String| ProofCheckError::Malformed { circuit, reason };
+
+    let public_inputs_len = public_inputs_count(circuit) * 32;
+    if proof_with_inputs.len() <= public_inputs_len {
+        return Err(malformed(format!(
+            "expected {} public inputs followed by the proof, got {} bytes",
+            public_inputs_count(circuit),
+            proof_with_inputs.len()
+        )));
+    }
+    if proof_with_inputs.len() % 32 != 0 {
+        return Err(malformed(
+            "proof data must be a multiple of 32 bytes".to_string(),
+        ));
+    }
+
+    let (public_inputs, _) =
+        noir_utils::split_public_inputs(proof_with_inputs, verifying_key(circuit))
+            .ok_or_else(|| malformed("failed to split public inputs from proof".to_string()))?;
+    noir_utils::parse_noir_output(public_inputs).map_err(|err| malformed(format!("{err:#}")))
+}
+
+/// Checks that a decoded proof output is for the expected transaction and blob.
+pub fn check_output(
+    circuit: Circuit,
+    output: &HyliOutput,
+    expected: &ExpectedTx<'_>,
+) -> Result<(), ProofCheckError> {
+    if output.tx_hash != *expected.tx_hash {
+        return Err(ProofCheckError::TxHashMismatch {
+            circuit,
+            proven: hex::encode(&output.tx_hash.0),
+            expected: hex::encode(&expected.tx_hash.0),
+        });
+    }
+    if output.identity != *expected.identity {
+        return Err(ProofCheckError::IdentityMismatch {
+            circuit,
+            proven: output.identity.0.clone(),
+            expected: expected.identity.0.clone(),
+        });
+    }
+    if !output.success {
+        return Err(ProofCheckError::NotSuccessful { circuit });
+    }
+
+    let index = output.index.0;
+    let blob_mismatch = |reason: String| ProofCheckError::BlobMismatch {
+        circuit,
+        index,
+        reason,
+    };
+    let proven = output
+        .blobs
+        .get(&output.index)
+        .ok_or_else(|| blob_mismatch("the proven blob is missing from the proof".to_string()))?;
+    if proven.contract_name.0 != expected.contract_name {
+        return Err(blob_mismatch(format!(
+            "proven blob belongs to {}, expected {}",
+            proven.contract_name.0, expected.contract_name
+        )));
+    }
+
+    let blobs = expected.blobs;
+    if output.tx_blob_count != blobs.len() {
+        return Err(blob_mismatch(format!(
+            "proof is for a transaction of {} blobs, expected {}",
+            output.tx_blob_count,
+            blobs.len()
+        )));
+    }
+    let blob = blobs
+        .get(index)
+        .ok_or_else(|| blob_mismatch("the transaction has no such blob".to_string()))?;
+    if blob.contract_name != proven.contract_name {
+        return Err(blob_mismatch(format!(
+            "blob belongs to {}, proof is for {}",
+            blob.contract_name.0, proven.contract_name.0
+        )));
+    }
+    if blob.data != proven.data {
+        return Err(blob_mismatch("blob bytes differ".to_string()));
+    }
+
+    Ok(())
+}
+
+/// Runs `bb verify` on a proof, off the async runtime.
+pub async fn verify_proof(
+    circuit: Circuit,
+    proof_with_inputs: Vec<u8>,
+) -> Result<(), ProofCheckError> {
+    let verified = tokio::task::spawn_blocking(move || {
+        let public_inputs_len = public_inputs_count(circuit) * 32;
+        if proof_with_inputs.len() < public_inputs_len {
+            return Err("proof is shorter than its public inputs".to_string());
+        }
+        let (public_inputs, proof) = proof_with_inputs.split_at(public_inputs_len);
+        let public_inputs = bytes_to_elements(public_inputs);
+        let proof = UtxoProofBytes(proof.to_vec());
+        // The circuits embed the same keys as `HYLI_UTXO_NOIR_VK` and
+        // `HYLI_SMT_INCL_PROOF_VK`
+        let result = match circuit {
+            Circuit::HyliUtxo => HyliUtxoProof {
+                proof,
+                public_inputs,
+            }
+            .verify(),
+            Circuit::SmtIncl => HyliSmtInclProof {
+                proof,
+                public_inputs,
+            }
+            .verify(),
+        };
+        result.map_err(|err| err.to_string())
+    })
+    .await;
+
+    match verified {
+        Ok(Ok(())) => Ok(()),
+        Ok(Err(reason)) => Err(ProofCheckError::Invalid { circuit, reason }),
+        Err(err) => Err(ProofCheckError::Invalid {
+            circuit,
+            reason: format!("verifier task failed: {err}"),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sdk::{BlobData, ContractName};
+
+    use super::*;
+
+    fn blob(contract_name: &str, data: &[u8]) -> Blob {
+        Blob {
+            contract_name: ContractName(contract_name.to_string()),
+            data: BlobData(data.to_vec()),
+        }
+    }
+
+    fn tx_blobs() -> Vec<Blob> {
+        vec![
+            blob("hyli-utxo-state", &[0]),
+            blob("hyli_utxo", &[1; 128]),
+            blob("hyli_smt_incl_proof", &[2; 110]),
+        ]
+    }
+
+    fn utxo_output(blobs: &[Blob]) -> HyliOutput {
+        HyliOutput {
+            identity: Identity("transfer@hyli_utxo".to_string()),
+            index: sdk::BlobIndex(1),
+            blobs: vec![blobs[1].clone()].into(),
+            tx_blob_count: blobs.len(),
+            tx_hash: TxHash(vec![7; 32]),
+            success: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn accepts_proof_of_the_submitted_blob() {
+        let blobs = tx_blobs();
+        let output = utxo_output(&blobs);
+        let tx_hash = TxHash(vec![7; 32]);
+        let identity = Identity("transfer@hyli_utxo".to_string());
+        let mut expected = ExpectedTx {
+            tx_hash: &tx_hash,
+            identity: &identity,
+            contract_name: "hyli_utxo",
+            blobs: &blobs,
+        };
+        assert_eq!(check_output(Circuit::HyliUtxo, &output, &expected), Ok(()));
+
+        expected.contract_name = "hyli_smt_incl_proof";
+        assert!(matches!(
+            check_output(Circuit::HyliUtxo, &output, &expected),
+            Err(ProofCheckError::BlobMismatch { index: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_proof_of_another_transaction() {
+        let blobs = tx_blobs();
+        let output = utxo_output(&blobs);
+        let identity = Identity("transfer@hyli_utxo".to_string());
+
+        let other_hash = TxHash(vec![8; 32]);
+        let expected = ExpectedTx {
+            tx_hash: &other_hash,
+            identity: &identity,
+            contract_name: "hyli_utxo",
+            blobs: &blobs,
+        };
+        assert!(matches!(
+            check_output(Circuit::HyliUtxo, &output, &expected),
+            Err(ProofCheckError::TxHashMismatch { .. })
+        ));
+
+        let tx_hash = TxHash(vec![7; 32]);
+        let other_identity = Identity("mallory@hyli_utxo".to_string());
+        let expected = ExpectedTx {
+            tx_hash: &tx_hash,
+            identity: &other_identity,
+            contract_name: "hyli_utxo",
+            blobs: &blobs,
+        };
+        assert!(matches!(
+            check_output(Circuit::HyliUtxo, &output, &expected),
+            Err(ProofCheckError::IdentityMismatch { .. })
+        ));
+
+        let mut tampered = tx_blobs();
+        tampered[1] = blob("hyli_utxo", &[3; 128]);
+        let expected = ExpectedTx {
+            tx_hash: &tx_hash,
+            identity: &identity,
+            contract_name: "hyli_utxo",
+            blobs: &tampered,
+        };
+        assert!(matches!(
+            check_output(Circuit::HyliUtxo, &output, &expected),
+            Err(ProofCheckError::BlobMismatch { index: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn decodes_canonical_public_inputs_only() {
+        let mut inputs = vec![format!("{:064x}", 1); HYLI_SMT_INCL_PUBLIC_INPUTS_COUNT];
+        inputs[0] = format!(
+            "0x{}",
+            hex::encode_upper((Element::MODULUS - Element::ONE).to_be_bytes())
+        );
+        let bytes = decode_public_inputs(Circuit::SmtIncl, &inputs).unwrap();
+        assert_eq!(bytes.len(), HYLI_SMT_INCL_PUBLIC_INPUTS_COUNT * 32);
+        assert_eq!(bytes[32..64], Element::ONE.to_be_bytes());
+
+        assert_eq!(
+            decode_public_inputs(Circuit::HyliUtxo, &inputs),
+            Err(ProofCheckError::PublicInputCount {
+                circuit: Circuit::HyliUtxo,
+                expected: HYLI_UTXO_PUBLIC_INPUTS_COUNT,
+                got: HYLI_SMT_INCL_PUBLIC_INPUTS_COUNT,
+            })
+        );
+
+        let invalid_at = |index: usize, value: String| {
+            let mut inputs = inputs.clone();
+            inputs[index] = value;
+            match decode_public_inputs(Circuit::SmtIncl, &inputs) {
+                Err(ProofCheckError::InvalidPublicInput { index, .. }) => index,
+                other => panic!("unexpected result: {other:?}"),
+            }
+        };
+        // Short elements used to be zero-padded
+        assert_eq!(invalid_at(3, "01".to_string()), 3);
+        assert_eq!(invalid_at(4, format!("{:066x}", 1)), 4);
+        assert_eq!(invalid_at(5, "zz".repeat(32)), 5);
+        assert_eq!(
+            invalid_at(6, hex::encode(Element::MODULUS.to_be_bytes())),
+            6
+        );
+    }
+
+    #[test]
+    fn rejects_truncated_proofs() {
+        let short = vec![0u8; HYLI_UTXO_PUBLIC_INPUTS_COUNT * 32];
+        assert!(matches!(
+            parse_public_inputs(Circuit::HyliUtxo, &short),
+            Err(ProofCheckError::Malformed { .. })
+        ));
+
+        let unaligned = vec![0u8; HYLI_SMT_INCL_PUBLIC_INPUTS_COUNT * 32 + 31];
+        assert!(matches!(
+            parse_public_inputs(Circuit::SmtIncl, &unaligned),
+            Err(ProofCheckError::Malformed { .. })
+        ));
+    }
+}
@@ -0,0 +1,413 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::oneshot;
+
+use crate::{conf::ProvingSchedulerConf, metrics::FaucetMetrics};
+
+/// Noir circuits proven by this server.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Circuit {
+    HyliUtxo,
+    SmtIncl,
+}
+
+impl Circuit {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Circuit::HyliUtxo => "hyli_utxo",
+            Circuit::SmtIncl => "hyli_smt_incl_proof",
+        }
+    }
+}
+
+impl fmt::Display for Circuit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.label())
+    }
+}
+
+/// Scheduling class of a proof job. Waiting jobs of a higher class are
+/// started first.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum ProofPriority {
+    /// Faucet mints
+    Faucet,
+    /// Transfers and deposits started by a wallet
+    #[default]
+    User,
+}
+
+impl ProofPriority {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ProofPriority::Faucet => "faucet",
+            ProofPriority::User => "user",
+        }
+    }
+}
+
+/// A circuit has too many proofs waiting to accept more work.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("the {circuit} prover is busy ({queued} proofs queued), retry in {retry_after_secs}s")]
+pub struct ProverBusy {
+    pub circuit: Circuit,
+    pub queued: usize,
+    pub retry_after_secs: u64,
+}
+
+/// Smoothing factor of the average proof duration.
+const DURATION_EMA_WEIGHT: f64 = 0.2;
+
+#[derive(Default)]
+struct CircuitQueue {
+    running: usize,
+    /// Waiting jobs per priority, oldest first
+    waiting: HashMap<ProofPriority, VecDeque<oneshot::Sender<ProvingPermit>>>,
+    /// Moving average of the time a permit is held
+    avg_proof_secs: Option<f64>,
+}
+
+impl CircuitQueue {
+    /// Jobs that would start before a new job of `priority`.
+    fn queued_before(&self, priority: ProofPriority) -> usize {
+        self.waiting
+            .iter()
+            .filter(|(waiting, _)| **waiting >= priority)
+            .flat_map(|(_, queue)| queue)
+            .filter(|sender| !sender.is_closed())
+            .count()
+    }
+
+    fn queued(&self) -> usize {
+        self.queued_before(ProofPriority::Faucet)
+    }
+
+    fn pop_waiter(&mut self) -> Option<(ProofPriority, oneshot::Sender<ProvingPermit>)> {
+        let mut priorities: Vec<_> = self.waiting.keys().copied().collect();
+        priorities.sort_unstable_by(|a, b| b.cmp(a));
+        priorities
+            .into_iter()
+            .find_map(|priority| Some((priority, self.waiting.get_mut(&priority)?.pop_front()?)))
+    }
+}
+
+/// Limits the number of Noir proofs generated at once, shared by the prover
+/// modules and the API.
+///
+/// Each circuit runs at most its configured number of proofs in parallel;
+/// other jobs wait by priority, then in arrival order. The API checks
+/// [`ProvingScheduler::check_capacity`] before accepting work that needs a
+/// proof, so bursts are turned away instead of piling up.
+pub struct ProvingScheduler {
+    conf: ProvingSchedulerConf,
+    metrics: FaucetMetrics,
+    circuits: Mutex<HashMap<Circuit, CircuitQueue>>,
+}
+
+impl ProvingScheduler {
+    pub fn new(conf: ProvingSchedulerConf, metrics: FaucetMetrics) -> Self {
+        Self {
+            conf,
+            metrics,
+            circuits: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn max_parallel(&self, circuit: Circuit) -> usize {
+        let max = match circuit {
+            Circuit::HyliUtxo => self.conf.max_parallel_hyli_utxo,
+            Circuit::SmtIncl => self.conf.max_parallel_smt_incl,
+        };
+        max.max(1)
+    }
+
+    /// Whether a new job of `priority` may be queued for `circuit`.
+    pub fn check_capacity(
+        &self,
+        circuit: Circuit,
+        priority: ProofPriority,
+    ) -> Result<(), ProverBusy> {
+        let circuits = self.circuits.lock().expect("scheduler lock poisoned");
+        let Some(queue) = circuits.get(&circuit) else {
+            return Ok(());
+        };
+        let queued = queue.queued_before(priority);
+        if self.conf.max_queued == 0 || queued < self.conf.max_queued {
+            return Ok(());
+        }
+
+        // Time for the jobs ahead to drain through the parallel slots
+        let retry_after_secs = match queue.avg_proof_secs {
+            Some(avg) => {
+                let rounds = (queued / self.max_parallel(circuit) + 1) as f64;
+                (avg * rounds).ceil().max(1.0) as u64
+            }
+            None => self.conf.default_retry_after_secs.max(1),
+        };
+        drop(circuits);
+        self.metrics.record_proof_rejected(circuit.label());
+        Err(ProverBusy {
+            circuit,
+            queued,
+            retry_after_secs,
+        })
+    }
+
+    /// Waits for a proving slot on `circuit`. The slot is released when the
+    /// permit is dropped.
+    pub async fn acquire(
+        self: &Arc<Self>,
+        circuit: Circuit,
+        priority: ProofPriority,
+    ) -> ProvingPermit {
+        let queued_at = Instant::now();
+        let receiver = {
+            let mut circuits = self.circuits.lock().expect("scheduler lock poisoned");
+            let queue = circuits.entry(circuit).or_default();
+            if queue.running < self.max_parallel(circuit) && queue.queued() == 0 {
+                queue.running += 1;
+                self.record_queue(circuit, queue);
+                None
+            } else {
+                let (sender, receiver) = oneshot::channel();
+                queue.waiting.entry(priority).or_default().push_back(sender);
+                self.record_queue(circuit, queue);
+                Some(receiver)
+            }
+        };
+
+        let permit = match receiver {
+            None => ProvingPermit::new(Arc::clone(self), circuit),
+            // The sender is only dropped with a permit, so this cannot fail
+            // while the scheduler is alive
+            Some(receiver) => receiver.await.expect("proving scheduler dropped a waiter"),
+        };
+        self.metrics.record_proof_wait(
+            circuit.label(),
+            priority.label(),
+            queued_at.elapsed().as_millis() as u64,
+        );
+        permit
+    }
+
+    /// Hands the slot of a finished proof to the next waiting job.
+    fn release(self: &Arc<Self>, circuit: Circuit, held: Duration) {
+        let mut held = Some(held);
+        loop {
+            let waiter = {
+                let mut circuits = self.circuits.lock().expect("scheduler lock poisoned");
+                let queue = circuits.entry(circuit).or_default();
+                if let Some(held) = held.take() {
+                    let secs = held.as_secs_f64();
+                    queue.avg_proof_secs = Some(match queue.avg_proof_secs {
+                        Some(avg) => avg + DURATION_EMA_WEIGHT * (secs - avg),
+                        None => secs,
+                    });
+                }
+                let waiter = queue.pop_waiter();
+                if waiter.is_none() {
+                    queue.running = queue.running.saturating_sub(1);
+                }
+                self.record_queue(circuit, queue);
+                waiter
+            };
+
+            let Some((_, sender)) = waiter else {
+                return;
+            };
+            match sender.send(ProvingPermit::new(Arc::clone(self), circuit)) {
+                Ok(()) => return,
+                // The waiting job was cancelled, try the next one
+                Err(mut permit) => permit.released = true,
+            }
+        }
+    }
+
+    fn record_queue(&self, circuit: Circuit, queue: &CircuitQueue) {
+        self.metrics.record_proof_queue(
+            circuit.label(),
+            queue.queued() as u64,
+            queue.running as u64,
+        );
+    }
+}
+
+/// A proving slot on a circuit, held while the proof is generated.
+pub struct ProvingPermit {
+    scheduler: Arc<ProvingScheduler>,
+    circuit: Circuit,
+    started_at: Instant,
+    released: bool,
+}
+
+impl ProvingPermit {
+    fn new(scheduler: Arc<ProvingScheduler>, circuit: Circuit) -> Self {
+        Self {
+            scheduler,
+            circuit,
+            started_at: Instant::now(),
+            released: false,
+        }
+    }
+}
+
+impl Drop for ProvingPermit {
+    fn drop(&mut self) {
+        if !self.released {
+            self.released = true;
+            self.scheduler
+                .release(self.circuit, self.started_at.elapsed());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scheduler(max_parallel: usize, max_queued: usize) -> Arc<ProvingScheduler> {
+        Arc::new(ProvingScheduler::new(
+            ProvingSchedulerConf {
+                max_parallel_hyli_utxo: max_parallel,
+                max_parallel_smt_incl: max_parallel,
+                max_queued,
+                default_retry_after_secs: 7,
+            },
+            FaucetMetrics::global("test".to_string()),
+        ))
+    }
+
+    fn running(scheduler: &ProvingScheduler, circuit: Circuit) -> usize {
+        scheduler.circuits.lock().unwrap()[&circuit].running
+    }
+
+    #[tokio::test]
+    async fn limits_parallel_proofs_per_circuit() {
+        let scheduler = scheduler(2, 8);
+        let first = scheduler
+            .acquire(Circuit::HyliUtxo, ProofPriority::User)
+            .await;
+        let _second = scheduler
+            .acquire(Circuit::HyliUtxo, ProofPriority::User)
+            .await;
+        // Other circuits have their own slots
+        let _smt = scheduler
+            .acquire(Circuit::SmtIncl, ProofPriority::User)
+            .await;
+
+        let waiting = tokio::spawn({
+            let scheduler = Arc::clone(&scheduler);
+            async move {
+                scheduler
+                    .acquire(Circuit::HyliUtxo, ProofPriority::User)
+                    .await
+            }
+        });
+        tokio::task::yield_now().await;
+        assert!(!waiting.is_finished());
+        assert_eq!(running(&scheduler, Circuit::HyliUtxo), 2);
+
+        drop(first);
+        let third = waiting.await.unwrap();
+        assert_eq!(running(&scheduler, Circuit::HyliUtxo), 2);
+        drop(third);
+        assert_eq!(running(&scheduler, Circuit::HyliUtxo), 1);
+    }
+
+    #[tokio::test]
+    async fn starts_user_proofs_before_faucet_mints() {
+        let scheduler = scheduler(1, 8);
+        let running_permit = scheduler
+            .acquire(Circuit::HyliUtxo, ProofPriority::User)
+            .await;
+
+        let (order_tx, mut order_rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut tasks = Vec::new();
+        for (name, priority) in [
+            ("faucet-1", ProofPriority::Faucet),
+            ("faucet-2", ProofPriority::Faucet),
+            ("user", ProofPriority::User),
+        ] {
+            let scheduler = Arc::clone(&scheduler);
+            let order_tx = order_tx.clone();
+            tasks.push(tokio::spawn(async move {
+                let _permit = scheduler.acquire(Circuit::HyliUtxo, priority).await;
+                order_tx.send(name).unwrap();
+            }));
+            tokio::task::yield_now().await;
+        }
+
+        drop(running_permit);
+        for task in tasks {
+            task.await.unwrap();
+        }
+        let mut order = Vec::new();
+        while let Ok(name) = order_rx.try_recv() {
+            order.push(name);
+        }
+        assert_eq!(order, ["user", "faucet-1", "faucet-2"]);
+    }
+
+    #[tokio::test]
+    async fn rejects_jobs_beyond_queue_depth() {
+        let scheduler = scheduler(1, 2);
+        let permit = scheduler
+            .acquire(Circuit::HyliUtxo, ProofPriority::User)
+            .await;
+        assert_eq!(
+            scheduler.check_capacity(Circuit::HyliUtxo, ProofPriority::Faucet),
+            Ok(())
+        );
+
+        let mut waiting = Vec::new();
+        for _ in 0..2 {
+            let scheduler = Arc::clone(&scheduler);
+            waiting.push(tokio::spawn(async move {
+                scheduler
+                    .acquire(Circuit::HyliUtxo, ProofPriority::Faucet)
+                    .await
+            }));
+            tokio::task::yield_now().await;
+        }
+
+        assert_eq!(
+            scheduler.check_capacity(Circuit::HyliUtxo, ProofPriority::Faucet),
+            Err(ProverBusy {
+                circuit: Circuit::HyliUtxo,
+                queued: 2,
+                retry_after_secs: 7,
+            })
+        );
+        // Queued faucet mints do not hold back user transfers
+        assert_eq!(
+            scheduler.check_capacity(Circuit::HyliUtxo, ProofPriority::User),
+            Ok(())
+        );
+        assert_eq!(
+            scheduler.check_capacity(Circuit::SmtIncl, ProofPriority::Faucet),
+            Ok(())
+        );
+
+        // Cancelled waiters give their turn to the next job
+        waiting[0].abort();
+        let _ = (&mut waiting[0]).await;
+        assert_eq!(
+            scheduler.check_capacity(Circuit::HyliUtxo, ProofPriority::Faucet),
+            Ok(())
+        );
+        drop(permit);
+        let next = (&mut waiting[1]).await.unwrap();
+        assert_eq!(running(&scheduler, Circuit::HyliUtxo), 1);
+        drop(next);
+        assert_eq!(running(&scheduler, Circuit::HyliUtxo), 0);
+    }
+}
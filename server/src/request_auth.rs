@@ -0,0 +1,686 @@
+use std::{collections::HashMap, io};
+
+use anyhow::Result;
+use cachecash_client::{delete_note_message, register_username_message, release_username_message};
+use primitives::{hash::CryptoHash, peer::Address, sig::Signature};
+use secp256k1::{
+    ecdsa::{RecoverableSignature, RecoveryId},
+    PublicKey,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    conf::StoreLogConf,
+    note_store::{AddressRegistration, AddressRegistry, OwnershipProof, RegistrationError},
+    op_log::{LogState, LogStore},
+};
+
+/// Why a signed request was not authorized.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum RequestAuthError {
+    #[error("request requires `timestamp` and `signature` parameters")]
+    MissingSignature,
+    #[error("malformed signature: {0}")]
+    MalformedSignature(String),
+    #[error("signature timestamp {timestamp} is more than {max_age_secs}s away from server time")]
+    StaleTimestamp { timestamp: u64, max_age_secs: u64 },
+    #[error("no key owns the address of this recipient tag")]
+    UnknownRecipient,
+    #[error("several keys claim the address of this recipient tag")]
+    ConflictingOwners,
+    #[error("signature does not match the expected key")]
+    InvalidSignature,
+    #[error("username is owned by another key")]
+    NotOwner,
+    #[error("signature has already been used")]
+    Replayed,
+    #[error("address registry lookup failed: {0}")]
+    Registry(String),
+    #[error("recording the used signature failed: {0}")]
+    Storage(String),
+}
+
+/// Storage of the signed messages a [`RequestAuthorizer`] accepted, kept
+/// until their signature expires.
+pub trait ReplayBackend: Send + Sync {
+    /// Records `message` as used until `expires_at`, first dropping the
+    /// messages expired at `now`. Returns `false`, recording nothing, if
+    /// `message` is already recorded.
+    fn claim(&self, message: &CryptoHash, expires_at: u64, now: u64) -> Result<bool>;
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct UsedMessages {
+    /// Expiry of each used message, by hex message hash
+    expires_at: HashMap<String, u64>,
+}
+
+/// A change to the messages kept by [`LocalReplays`].
+#[derive(Serialize, Deserialize)]
+struct ClaimOp {
+    message: String,
+    expires_at: u64,
+    now: u64,
+}
+
+impl LogState for UsedMessages {
+    type Op = ClaimOp;
+
+    fn apply(&mut self, op: ClaimOp) {
+        self.expires_at
+            .retain(|_, expires_at| *expires_at >= op.now);
+        self.expires_at.insert(op.message, op.expires_at);
+    }
+}
+
+/// Used messages kept in memory, optionally persisted to a checkpoint and an
+/// operation log.
+pub struct LocalReplays {
+    store: LogStore<UsedMessages>,
+}
+
+impl LocalReplays {
+    pub fn new() -> Self {
+        Self {
+            store: LogStore::new(UsedMessages::default()),
+        }
+    }
+
+    pub fn with_persistence(conf: StoreLogConf, persistence_path: String) -> io::Result<Self> {
+        Ok(Self {
+            store: LogStore::open(persistence_path, conf)?,
+        })
+    }
+}
+
+impl Default for LocalReplays {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ReplayBackend for LocalReplays {
+    fn claim(&self, message: &CryptoHash, expires_at: u64, now: u64) -> Result<bool> {
+        let mut used = self.store.write();
+        let message = message.to_hex();
+        if used
+            .expires_at
+            .get(&message)
+            .is_some_and(|used_until| *used_until >= now)
+        {
+            return Ok(false);
+        }
+        used.apply(ClaimOp {
+            message,
+            expires_at,
+            now,
+        })?;
+        Ok(true)
+    }
+}
+
+/// Checks requests signed by a secp256k1 key from the [`AddressRegistry`]:
+/// note deletions and username registrations/releases.
+///
+/// Each signed message binds the request parameters and a timestamp (see
+/// `cachecash_client::auth`). Timestamps must be within `max_age_secs` of the
+/// server clock, and each signed message is accepted once while it is inside
+/// that window. Accepted messages are kept in a [`ReplayBackend`] until they
+/// leave it, so that a restart, or another server sharing the backend, does
+/// not accept them again.
+pub struct RequestAuthorizer {
+    max_age_secs: u64,
+    used: Box<dyn ReplayBackend>,
+}
+
+impl RequestAuthorizer {
+    /// An authorizer keeping the accepted messages in memory only.
+    pub fn new(max_age_secs: u64) -> Self {
+        Self::with_backend(max_age_secs, Box::new(LocalReplays::new()))
+    }
+
+    /// An authorizer keeping the accepted messages in `used`.
+    pub fn with_backend(max_age_secs: u64, used: Box<dyn ReplayBackend>) -> Self {
+        Self { max_age_secs, used }
+    }
+
+    /// Authorizes `DELETE /api/notes/{recipient_tag}/{note_id}`; the signer must
+    /// be the key owning the UTXO address of the tag, i.e. the key whose signed
+    /// registration first claimed it. `recipient_tag` must already be
+    /// normalized (lowercase hex, no `0x`).
+    pub fn authorize_note_deletion(
+        &self,
+        registry: &AddressRegistry,
+        recipient_tag: &str,
+        note_id: &str,
+        timestamp: Option<u64>,
+        signature: Option<&str>,
+        now: u64,
+    ) -> Result<(), RequestAuthError> {
+        let owner = registry
+            .address_owner_for_tag(recipient_tag)
+            .map_err(|err| match err {
+                RegistrationError::AddressTaken(_) => RequestAuthError::ConflictingOwners,
+                err => RequestAuthError::Registry(err.to_string()),
+            })?
+            .ok_or(RequestAuthError::UnknownRecipient)?;
+
+        self.verify(
+            |timestamp| delete_note_message(recipient_tag, note_id, timestamp),
+            &[owner],
+            timestamp,
+            signature,
+            now,
+        )
+        .map(|_| ())
+    }
+
+    /// Authorizes a username registration and returns the proof to store with
+    /// it. A new username must be signed by `encryption_pubkey`, an existing one
+    /// by its owner. All values must already be normalized.
+    #[allow(clippy::too_many_arguments)]
+    pub fn authorize_registration(
+        &self,
+        existing: Option<&AddressRegistration>,
+        username: &str,
+        utxo_address: &str,
+        encryption_pubkey: &str,
+        timestamp: Option<u64>,
+        signature: Option<&str>,
+        now: u64,
+    ) -> Result<OwnershipProof, RequestAuthError> {
+        let owner = existing.and_then(AddressRegistration::owner_pubkey);
+        let expected = owner.unwrap_or(encryption_pubkey).to_string();
+
+        let (owner_pubkey, signed_at) = self
+            .verify(
+                |timestamp| {
+                    register_username_message(username, utxo_address, encryption_pubkey, timestamp)
+                },
+                std::slice::from_ref(&expected),
+                timestamp,
+                signature,
+                now,
+            )
+            .map_err(|err| match err {
+                RequestAuthError::InvalidSignature if owner.is_some() => RequestAuthError::NotOwner,
+                err => err,
+            })?;
+
+        Ok(OwnershipProof {
+            owner_pubkey,
+            signature: signature.unwrap_or_default().to_lowercase(),
+            signed_at,
+        })
+    }
+
+    /// Authorizes `DELETE /api/address/{username}` and returns the owner key
+    /// that signed it.
+    pub fn authorize_release(
+        &self,
+        registration: &AddressRegistration,
+        timestamp: Option<u64>,
+        signature: Option<&str>,
+        now: u64,
+    ) -> Result<String, RequestAuthError> {
+        let Some(owner) = registration.owner_pubkey() else {
+            return Err(RequestAuthError::NotOwner);
+        };
+
+        self.verify(
+            |timestamp| release_username_message(&registration.username, timestamp),
+            &[owner.to_string()],
+            timestamp,
+            signature,
+            now,
+        )
+        .map(|(signer, _)| signer)
+        .map_err(|err| match err {
+            RequestAuthError::InvalidSignature => RequestAuthError::NotOwner,
+            err => err,
+        })
+    }
+
+    /// Checks freshness, recovers the signer of `message(timestamp)` and
+    /// matches it against `pubkeys` (x-only hex). Returns the matching key and
+    /// the signed timestamp.
+    fn verify(
+        &self,
+        message: impl FnOnce(u64) -> CryptoHash,
+        pubkeys: &[String],
+        timestamp: Option<u64>,
+        signature: Option<&str>,
+        now: u64,
+    ) -> Result<(String, u64), RequestAuthError> {
+        let (Some(timestamp), Some(signature)) = (timestamp, signature) else {
+            return Err(RequestAuthError::MissingSignature);
+        };
+
+        if timestamp.abs_diff(now) > self.max_age_secs {
+            return Err(RequestAuthError::StaleTimestamp {
+                timestamp,
+                max_age_secs: self.max_age_secs,
+            });
+        }
+
+        let signature = parse_signature(signature)?;
+        let message = message(timestamp);
+        let signer = signature
+            .verify(&message)
+            .ok_or(RequestAuthError::InvalidSignature)?;
+        let pubkey = pubkeys
+            .iter()
+            .find(|pubkey| signer_addresses(pubkey).contains(&signer))
+            .ok_or(RequestAuthError::InvalidSignature)?;
+
+        // Accepted again once outside the window, where it is refused as stale
+        let expires_at = timestamp.saturating_add(self.max_age_secs);
+        let claimed = self
+            .used
+            .claim(&message, expires_at, now)
+            .map_err(|err| RequestAuthError::Storage(format!("{err:#}")))?;
+        if !claimed {
+            return Err(RequestAuthError::Replayed);
+        }
+
+        Ok((pubkey.clone(), timestamp))
+    }
+}
+
+/// Decodes a hex `r || s || recovery_id` signature, rejecting values that
+/// cannot be recovered.
+fn parse_signature(signature: &str) -> Result<Signature, RequestAuthError> {
+    let normalized = signature.strip_prefix("0x").unwrap_or(signature);
+    let bytes: [u8; 65] = hex::decode(normalized)
+        .map_err(|err| RequestAuthError::MalformedSignature(err.to_string()))?
+        .try_into()
+        .map_err(|_| RequestAuthError::MalformedSignature("expected 65 bytes".to_string()))?;
+
+    let recovery_id = RecoveryId::from_i32(i32::from(bytes[64]))
+        .map_err(|err| RequestAuthError::MalformedSignature(err.to_string()))?;
+    RecoverableSignature::from_compact(&bytes[..64], recovery_id)
+        .map_err(|err| RequestAuthError::MalformedSignature(err.to_string()))?;
+
+    Ok(Signature(bytes))
+}
+
+/// Addresses of both public keys sharing the registered x-coordinate.
+fn signer_addresses(encryption_pubkey: &str) -> Vec<Address> {
+    let Ok(x) = hex::decode(encryption_pubkey) else {
+        return Vec::new();
+    };
+    [0x02u8, 0x03]
+        .into_iter()
+        .filter_map(|prefix| {
+            let mut compressed = vec![prefix];
+            compressed.extend_from_slice(&x);
+            PublicKey::from_slice(&compressed).ok()
+        })
+        .map(Address::from_public_key)
+        .collect()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use cachecash_client::{sign_delete_note, sign_register_username, sign_release_username};
+    use secp256k1::{SecretKey, SECP256K1};
+    use zk_primitives::note_encryption::{derive_recipient_tag, x_only};
+
+    use super::*;
+    use crate::conf::FsyncPolicy;
+
+    const NOW: u64 = 1_760_000_000;
+    const UTXO_ADDRESS: &str = "1f2e3d4c5b6a79881f2e3d4c5b6a79881f2e3d4c5b6a79881f2e3d4c5b6a7988";
+
+    fn pubkey(secret_key: &SecretKey) -> String {
+        hex::encode(x_only(&PublicKey::from_secret_key(SECP256K1, secret_key)))
+    }
+
+    fn setup() -> (AddressRegistry, SecretKey, String) {
+        let secret_key = SecretKey::from_slice(&[0x11; 32]).unwrap();
+        let pubkey = pubkey(&secret_key);
+        let registry = AddressRegistry::new();
+        let proof = OwnershipProof {
+            owner_pubkey: pubkey.clone(),
+            signature: String::new(),
+            signed_at: NOW,
+        };
+        registry
+            .register("alice".to_string(), UTXO_ADDRESS.to_string(), pubkey, proof)
+            .unwrap();
+        (
+            registry,
+            secret_key,
+            derive_recipient_tag(UTXO_ADDRESS).unwrap(),
+        )
+    }
+
+    fn sign(secret_key: &SecretKey, tag: &str, note_id: &str, timestamp: u64) -> String {
+        hex::encode(sign_delete_note(secret_key, tag, note_id, timestamp).inner())
+    }
+
+    fn sign_registration(
+        secret_key: &SecretKey,
+        username: &str,
+        encryption_pubkey: &str,
+    ) -> String {
+        hex::encode(
+            sign_register_username(secret_key, username, UTXO_ADDRESS, encryption_pubkey, NOW)
+                .inner(),
+        )
+    }
+
+    #[test]
+    fn accepts_signature_from_registered_key_once() {
+        let (registry, secret_key, tag) = setup();
+        let authorizer = RequestAuthorizer::new(300);
+        let signature = sign(&secret_key, &tag, "note-1", NOW);
+
+        assert_eq!(
+            authorizer.authorize_note_deletion(
+                &registry,
+                &tag,
+                "note-1",
+                Some(NOW),
+                Some(&signature),
+                NOW
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            authorizer.authorize_note_deletion(
+                &registry,
+                &tag,
+                "note-1",
+                Some(NOW),
+                Some(&signature),
+                NOW + 1
+            ),
+            Err(RequestAuthError::Replayed)
+        );
+    }
+
+    /// Checks `backend` with messages new to every call, so the backend may
+    /// hold other ones.
+    pub(crate) fn check_replay_backend(backend: Box<dyn ReplayBackend>) {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let run = CryptoHash::from_vec_hash(nanos.to_le_bytes().to_vec());
+        let other = CryptoHash::from_vec_hash(run.to_vec());
+
+        assert!(backend.claim(&run, NOW + 300, NOW).unwrap());
+        assert!(!backend.claim(&run, NOW + 301, NOW + 1).unwrap());
+        assert!(backend.claim(&other, NOW + 300, NOW).unwrap());
+        // Claimable again once expired
+        assert!(backend.claim(&run, NOW + 601, NOW + 301).unwrap());
+    }
+
+    #[test]
+    fn local_replays_pass_the_backend_checks() {
+        check_replay_backend(Box::new(LocalReplays::new()));
+    }
+
+    #[test]
+    fn used_signatures_stay_used_across_restarts() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("signed_requests.json");
+        let conf = StoreLogConf {
+            fsync: FsyncPolicy::Always,
+            fsync_interval_ms: 0,
+            compact_after_ops: 2,
+        };
+        let authorizer = || {
+            let replays =
+                LocalReplays::with_persistence(conf.clone(), path.to_string_lossy().to_string())
+                    .unwrap();
+            RequestAuthorizer::with_backend(300, Box::new(replays))
+        };
+        let (registry, secret_key, tag) = setup();
+        let delete = |authorizer: &RequestAuthorizer, note_id: &str| {
+            let signature = sign(&secret_key, &tag, note_id, NOW);
+            authorizer.authorize_note_deletion(
+                &registry,
+                &tag,
+                note_id,
+                Some(NOW),
+                Some(&signature),
+                NOW,
+            )
+        };
+
+        let first = authorizer();
+        for note_id in ["note-1", "note-2", "note-3"] {
+            assert_eq!(delete(&first, note_id), Ok(()));
+        }
+        drop(first);
+
+        let restarted = authorizer();
+        for note_id in ["note-1", "note-2", "note-3"] {
+            assert_eq!(delete(&restarted, note_id), Err(RequestAuthError::Replayed));
+        }
+        assert_eq!(delete(&restarted, "note-4"), Ok(()));
+    }
+
+    #[test]
+    fn rejects_missing_stale_and_foreign_signatures() {
+        let (registry, secret_key, tag) = setup();
+        let authorizer = RequestAuthorizer::new(300);
+
+        assert_eq!(
+            authorizer.authorize_note_deletion(&registry, &tag, "note-1", Some(NOW), None, NOW),
+            Err(RequestAuthError::MissingSignature)
+        );
+
+        let old = sign(&secret_key, &tag, "note-1", NOW - 301);
+        assert!(matches!(
+            authorizer.authorize_note_deletion(
+                &registry,
+                &tag,
+                "note-1",
+                Some(NOW - 301),
+                Some(&old),
+                NOW
+            ),
+            Err(RequestAuthError::StaleTimestamp { .. })
+        ));
+
+        let other_key = SecretKey::from_slice(&[0x22; 32]).unwrap();
+        let foreign = sign(&other_key, &tag, "note-1", NOW);
+        assert_eq!(
+            authorizer.authorize_note_deletion(
+                &registry,
+                &tag,
+                "note-1",
+                Some(NOW),
+                Some(&foreign),
+                NOW
+            ),
+            Err(RequestAuthError::InvalidSignature)
+        );
+
+        // Signature for another note id does not carry over
+        let other_note = sign(&secret_key, &tag, "note-2", NOW);
+        assert_eq!(
+            authorizer.authorize_note_deletion(
+                &registry,
+                &tag,
+                "note-1",
+                Some(NOW),
+                Some(&other_note),
+                NOW
+            ),
+            Err(RequestAuthError::InvalidSignature)
+        );
+
+        assert!(matches!(
+            authorizer.authorize_note_deletion(
+                &registry,
+                &tag,
+                "note-1",
+                Some(NOW),
+                Some("zz"),
+                NOW
+            ),
+            Err(RequestAuthError::MalformedSignature(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_unregistered_tag() {
+        let (registry, secret_key, _) = setup();
+        let authorizer = RequestAuthorizer::new(300);
+        let tag = "ab".repeat(32);
+        let signature = sign(&secret_key, &tag, "note-1", NOW);
+
+        assert_eq!(
+            authorizer.authorize_note_deletion(
+                &registry,
+                &tag,
+                "note-1",
+                Some(NOW),
+                Some(&signature),
+                NOW
+            ),
+            Err(RequestAuthError::UnknownRecipient)
+        );
+    }
+
+    #[test]
+    fn only_the_address_owner_deletes_notes() {
+        let (registry, secret_key, tag) = setup();
+        let authorizer = RequestAuthorizer::new(300);
+
+        // The owner rotates the encryption key of the username
+        let rotated_key = SecretKey::from_slice(&[0x77; 32]).unwrap();
+        let proof = OwnershipProof {
+            owner_pubkey: pubkey(&secret_key),
+            signature: String::new(),
+            signed_at: NOW,
+        };
+        registry
+            .register(
+                "alice".to_string(),
+                UTXO_ADDRESS.to_string(),
+                pubkey(&rotated_key),
+                proof,
+            )
+            .unwrap();
+
+        let rotated = sign(&rotated_key, &tag, "note-1", NOW);
+        assert_eq!(
+            authorizer.authorize_note_deletion(
+                &registry,
+                &tag,
+                "note-1",
+                Some(NOW),
+                Some(&rotated),
+                NOW
+            ),
+            Err(RequestAuthError::InvalidSignature)
+        );
+        let owner = sign(&secret_key, &tag, "note-1", NOW);
+        assert_eq!(
+            authorizer.authorize_note_deletion(
+                &registry,
+                &tag,
+                "note-1",
+                Some(NOW),
+                Some(&owner),
+                NOW
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn new_username_must_be_signed_by_registered_key() {
+        let authorizer = RequestAuthorizer::new(300);
+        let secret_key = SecretKey::from_slice(&[0x33; 32]).unwrap();
+        let pubkey = pubkey(&secret_key);
+
+        let signature = sign_registration(&secret_key, "bob", &pubkey);
+        let proof = authorizer
+            .authorize_registration(
+                None,
+                "bob",
+                UTXO_ADDRESS,
+                &pubkey,
+                Some(NOW),
+                Some(&signature),
+                NOW,
+            )
+            .unwrap();
+        assert_eq!(proof.owner_pubkey, pubkey);
+        assert_eq!(proof.signed_at, NOW);
+
+        // Claiming a key without holding it
+        let other_key = SecretKey::from_slice(&[0x44; 32]).unwrap();
+        let forged = sign_registration(&other_key, "carol", &pubkey);
+        assert_eq!(
+            authorizer.authorize_registration(
+                None,
+                "carol",
+                UTXO_ADDRESS,
+                &pubkey,
+                Some(NOW),
+                Some(&forged),
+                NOW,
+            ),
+            Err(RequestAuthError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn only_owner_updates_or_releases_username() {
+        let (registry, secret_key, _) = setup();
+        let authorizer = RequestAuthorizer::new(300);
+        let existing = registry.resolve("alice").unwrap().unwrap();
+
+        // Attacker signs with the key they want to register
+        let attacker = SecretKey::from_slice(&[0x55; 32]).unwrap();
+        let attacker_pubkey = pubkey(&attacker);
+        let hijack = sign_registration(&attacker, "alice", &attacker_pubkey);
+        assert_eq!(
+            authorizer.authorize_registration(
+                Some(&existing),
+                "alice",
+                UTXO_ADDRESS,
+                &attacker_pubkey,
+                Some(NOW),
+                Some(&hijack),
+                NOW,
+            ),
+            Err(RequestAuthError::NotOwner)
+        );
+
+        let release = hex::encode(sign_release_username(&attacker, "alice", NOW).inner());
+        assert_eq!(
+            authorizer.authorize_release(&existing, Some(NOW), Some(&release), NOW),
+            Err(RequestAuthError::NotOwner)
+        );
+
+        // The owner may rotate the encryption key
+        let rotated = pubkey(&SecretKey::from_slice(&[0x66; 32]).unwrap());
+        let update = sign_registration(&secret_key, "alice", &rotated);
+        let proof = authorizer
+            .authorize_registration(
+                Some(&existing),
+                "alice",
+                UTXO_ADDRESS,
+                &rotated,
+                Some(NOW),
+                Some(&update),
+                NOW,
+            )
+            .unwrap();
+        assert_eq!(proof.owner_pubkey, pubkey(&secret_key));
+
+        let release = hex::encode(sign_release_username(&secret_key, "alice", NOW).inner());
+        assert_eq!(
+            authorizer.authorize_release(&existing, Some(NOW), Some(&release), NOW),
+            Ok(pubkey(&secret_key))
+        );
+    }
+}
@@ -0,0 +1,82 @@
+use std::net::SocketAddr;
+
+use anyhow::{Context, Result};
+use axum::{extract::DefaultBodyLimit, routing::get, Json, Router};
+use hyli_modules::{
+    bus::SharedMessageBus, module_bus_client, module_handle_messages, modules::Module,
+};
+use tokio::{net::TcpListener, sync::oneshot};
+use tracing::info;
+use utoipa::openapi::OpenApi;
+
+module_bus_client! {
+    #[derive(Debug)]
+    pub struct RestServerBusClient {}
+}
+
+pub struct RestServerCtx {
+    pub port: u16,
+    pub router: Router,
+    pub max_body_size: usize,
+    pub openapi: OpenApi,
+}
+
+/// Serves the REST API, recording the peer address of every connection as
+/// `ConnectInfo<SocketAddr>` so handlers can tell clients apart (the faucet
+/// per-IP quota) when no proxy header is configured.
+pub struct RestServer {
+    bus: RestServerBusClient,
+    port: u16,
+    app: Option<Router>,
+}
+
+impl Module for RestServer {
+    type Context = RestServerCtx;
+
+    async fn build(bus: SharedMessageBus, ctx: Self::Context) -> Result<Self> {
+        let bus = RestServerBusClient::new_from_bus(bus.new_handle()).await;
+        let openapi = ctx.openapi;
+        let app = ctx
+            .router
+            .route(
+                "/api-docs/openapi.json",
+                get(move || async move { Json(openapi) }),
+            )
+            .layer(DefaultBodyLimit::max(ctx.max_body_size));
+        Ok(Self {
+            bus,
+            port: ctx.port,
+            app: Some(app),
+        })
+    }
+
+    async fn run(&mut self) -> Result<()> {
+        let listener = TcpListener::bind(("0.0.0.0", self.port))
+            .await
+            .with_context(|| format!("binding REST server to port {}", self.port))?;
+        let app = self.app.take().context("REST server already started")?;
+        info!(port = self.port, "Serving REST API");
+
+        let (stop, stopped) = oneshot::channel::<()>();
+        let server = tokio::spawn(async move {
+            axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .with_graceful_shutdown(async {
+                let _ = stopped.await;
+            })
+            .await
+        });
+
+        module_handle_messages! {
+            on_self self,
+        };
+
+        let _ = stop.send(());
+        server
+            .await
+            .context("joining REST server task")?
+            .context("serving REST API")
+    }
+}
@@ -1,41 +1,82 @@
-use std::{sync::Arc, time::Instant};
+use std::sync::Arc;
 
 use anyhow::{anyhow, bail, Context, Result};
 use barretenberg::Prove;
 use client_sdk::rest_client::NodeApiClient;
+use element::Base;
 use hyli_modules::{
     bus::{BusMessage, SharedMessageBus, LOW_CAPACITY},
     module_bus_client, module_handle_messages,
     modules::Module,
 };
 use sdk::{Identity, TxHash};
-use tokio::task::{JoinError, JoinSet};
-use tracing::{error, info};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    task::{JoinError, JoinSet},
+    time::Instant,
+};
+use tracing::{debug, error, info};
 use zk_primitives::{
-    HyliSmtIncl, HyliSmtInclProof, InputNote, ToBytes, HYLI_SMT_INCL_BLOB_LENGTH_BYTES,
+    HyliSmtIncl, HyliSmtInclProof, InclusionNote, ToBytes, HYLI_SMT_INCL_BLOB_LENGTH_BYTES,
 };
 
 use crate::{
     init::ContractDeployment,
+    note_store::current_timestamp,
+    proof_queue::ProofJobQueue,
     prover::{NoirProofArtifacts, NoirProver},
+    proving_scheduler::{Circuit, ProofPriority, ProvingScheduler},
+    tx_tracker::TxTracker,
 };
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SmtInclProofJob {
     pub tx_hash: TxHash,
     pub identity: Identity,
+    #[serde(with = "hex::serde")]
     pub blob: [u8; HYLI_SMT_INCL_BLOB_LENGTH_BYTES],
     pub tx_blob_count: u32,
     pub blob_index: u32,
-    pub input_notes: [InputNote; 2],
-    pub siblings_0: Box<[element::Base; 256]>,
-    pub siblings_1: Box<[element::Base; 256]>,
+    pub input_notes: [InclusionNote; 2],
+    #[serde(with = "siblings_serde")]
+    pub siblings_0: Box<[Base; 256]>,
+    #[serde(with = "siblings_serde")]
+    pub siblings_1: Box<[Base; 256]>,
+    #[serde(default)]
+    pub priority: ProofPriority,
 }
 
 impl BusMessage for SmtInclProofJob {
     const CAPACITY: usize = LOW_CAPACITY;
 }
 
+/// Serde only derives arrays of up to 32 elements, and [`Base`] is stored as
+/// an [`Element`](element::Element).
+mod siblings_serde {
+    use element::{Base, Element};
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        siblings: &[Base; 256],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(siblings.iter().map(|sibling| Element::from_base(*sibling)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Box<[Base; 256]>, D::Error> {
+        let siblings = Vec::<Element>::deserialize(deserializer)?;
+        let len = siblings.len();
+        siblings
+            .iter()
+            .map(Element::to_base)
+            .collect::<Box<[Base]>>()
+            .try_into()
+            .map_err(|_| D::Error::invalid_length(len, &"256 siblings"))
+    }
+}
+
 module_bus_client! {
     #[derive(Debug)]
     pub struct SmtInclProverBusClient {
@@ -46,6 +87,9 @@ module_bus_client! {
 pub struct SmtInclProverCtx {
     pub node: Arc<dyn NodeApiClient + Send + Sync>,
     pub contract: ContractDeployment,
+    pub tx_tracker: Arc<TxTracker>,
+    pub queue: Arc<ProofJobQueue<SmtInclProofJob>>,
+    pub scheduler: Arc<ProvingScheduler>,
 }
 
 pub struct HyliSmtInclNoirProver {
@@ -67,12 +111,20 @@ impl Module for HyliSmtInclNoirProver {
     async fn run(&mut self) -> Result<()> {
         let mut proof_tasks = JoinSet::new();
 
+        for (tx_hash, queued) in self.ctx.queue.pending() {
+            info!(%tx_hash, attempts = queued.attempts, "resuming hyli_smt_incl_proof job");
+            self.spawn_proof_job(&mut proof_tasks, tx_hash, queued.job);
+        }
+
         module_handle_messages! {
             on_self self,
             listen<SmtInclProofJob> job => {
-                let ctx = Arc::clone(&self.ctx);
-                let prover = self.prover.clone();
-                proof_tasks.spawn(async move { Self::execute_proof_job(ctx, prover, job).await });
+                let tx_hash = hex::encode(&job.tx_hash.0);
+                if self.ctx.queue.enqueue(&tx_hash, job.clone(), current_timestamp()) {
+                    self.spawn_proof_job(&mut proof_tasks, tx_hash, job);
+                } else {
+                    debug!(%tx_hash, "hyli_smt_incl_proof job already queued");
+                }
             }
             Some(res) = proof_tasks.join_next() => {
                 Self::handle_task_completion(res);
@@ -84,6 +136,30 @@ impl Module for HyliSmtInclNoirProver {
 }
 
 impl HyliSmtInclNoirProver {
+    fn spawn_proof_job(
+        &self,
+        proof_tasks: &mut JoinSet<Result<()>>,
+        tx_hash: String,
+        job: SmtInclProofJob,
+    ) {
+        let ctx = Arc::clone(&self.ctx);
+        let prover = self.prover.clone();
+        proof_tasks.spawn(async move {
+            let result = Self::execute_proof_job(Arc::clone(&ctx), prover, &tx_hash, job).await;
+            match &result {
+                Ok(()) => ctx.queue.complete(&tx_hash),
+                Err(err) => {
+                    let reason = format!("proving hyli_smt_incl_proof: {err:#}");
+                    ctx.queue
+                        .dead_letter(&tx_hash, reason.clone(), current_timestamp());
+                    ctx.tx_tracker
+                        .record_proof_failed(&tx_hash, reason, current_timestamp());
+                }
+            }
+            result
+        });
+    }
+
     fn handle_task_completion(result: Result<Result<()>, JoinError>) {
         match result {
             Ok(Ok(())) => {}
@@ -106,8 +182,17 @@ impl HyliSmtInclNoirProver {
     async fn execute_proof_job(
         ctx: Arc<SmtInclProverCtx>,
         prover: NoirProver,
+        tx_hash_str: &str,
         job: SmtInclProofJob,
     ) -> Result<()> {
+        let deadline = ctx
+            .queue
+            .deadline(tx_hash_str, current_timestamp())
+            .context("hyli_smt_incl_proof job is no longer queued")?;
+        if deadline <= Instant::now() {
+            bail!("deadline passed before the hyli_smt_incl_proof proof was generated");
+        }
+
         let contract = ctx.contract.clone();
         let contract_name = contract.contract_name.0.clone();
         let hyli_smt_incl = Self::build_hyli_smt_incl(&contract_name, &job)?;
@@ -120,13 +205,13 @@ impl HyliSmtInclNoirProver {
             );
         }
 
-        let tx_hash_str = hex::encode(&job.tx_hash.0);
-
+        let permit = ctx.scheduler.acquire(Circuit::SmtIncl, job.priority).await;
         let prove_start = Instant::now();
         let proof: HyliSmtInclProof = hyli_smt_incl
             .prove()
             .map_err(|err| anyhow!("generating hyli_smt_incl_proof Noir proof: {err}"))?;
         let prove_duration = prove_start.elapsed();
+        drop(permit);
 
         info!(
             duration_ms = prove_duration.as_millis(),
@@ -144,8 +229,8 @@ impl HyliSmtInclNoirProver {
             },
         )?;
 
-        let node = Arc::clone(&ctx.node);
-        node.send_tx_proof(proof_tx)
+        ctx.queue
+            .submit_proof(tx_hash_str, &ctx.node, proof_tx, deadline)
             .await
             .context("submitting hyli_smt_incl_proof Noir proof to node")?;
 
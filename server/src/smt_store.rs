@@ -0,0 +1,859 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex, Weak,
+    },
+};
+
+use anyhow::{anyhow, Result};
+use hyli_utxo_state::zk::{BorshableH256, SmtStore};
+use redb::{
+    Database, ReadOnlyTable, ReadableTable, TableDefinition, TableHandle, WriteTransaction,
+};
+use sparse_merkle_tree::{
+    branch::{BranchKey, BranchNode},
+    default_store::DefaultStore,
+    error::Error,
+    merge::MergeValue,
+    traits::{StoreReadOps, StoreWriteOps},
+    H256,
+};
+use tracing::warn;
+
+/// Pending changes a [`DiskStore`] buffers in memory before writing them.
+const OVERLAY_LIMIT: usize = 16_384;
+
+/// The generation of each sealed tree, by tree root.
+const SEALED: TableDefinition<&[u8], u64> = TableDefinition::new("sealed");
+
+type Table = ReadOnlyTable<&'static [u8], &'static [u8]>;
+type TableDef<'a> = TableDefinition<'a, &'static [u8], &'static [u8]>;
+
+/// The embedded database holding the on-disk trees, one set of tables per
+/// tree version, or generation.
+///
+/// The trees recorded with the latest indexer snapshot are sealed: their
+/// generations are kept across restarts, with the nodes they held when sealed
+/// saved before being overwritten, so that restoring the snapshot reopens
+/// them instead of importing every leaf again.
+pub struct SmtDatabase {
+    db: Database,
+    next_generation: AtomicU64,
+    /// Generations in use, so that a reopened one is shared by its readers
+    generations: Mutex<HashMap<u64, Weak<Generation>>>,
+}
+
+impl SmtDatabase {
+    /// Opens the database at `path`, creating it if needed. Sealed trees are
+    /// rolled back to the version they were sealed at, and the generations
+    /// of the previous run that were not sealed are dropped.
+    pub fn open(path: &Path) -> Result<Self> {
+        let db = Database::create(path)?;
+        let txn = db.begin_write()?;
+        let sealed = sealed_generations(&txn)?;
+        let mut last = None;
+        for table in txn.list_tables()?.collect::<Vec<_>>() {
+            let Some(id) = generation_id(table.name()) else {
+                continue;
+            };
+            last = last.max(Some(id));
+            if !sealed.contains(&id) {
+                txn.delete_table(table)?;
+            }
+        }
+        for id in &sealed {
+            roll_back(&txn, *id)?;
+        }
+        txn.commit()?;
+        Ok(Self {
+            db,
+            next_generation: AtomicU64::new(last.map_or(0, |id| id + 1)),
+            generations: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Records `trees` as the sealed ones in place of those sealed before,
+    /// which are deleted once no store reads them anymore.
+    fn seal(&self, trees: &[(H256, Arc<Generation>)]) -> Result<()> {
+        // Dropped after the transaction, as dropping a generation writes
+        let mut unsealed = Vec::new();
+        let txn = self.db.begin_write()?;
+        let previous = sealed_generations(&txn)?;
+        txn.delete_table(SEALED)?;
+        {
+            let mut sealed = txn.open_table(SEALED)?;
+            for (root, generation) in trees {
+                sealed.insert(root.as_slice(), generation.id)?;
+            }
+        }
+        for (_, generation) in trees {
+            // Their current version is the one to restore
+            txn.delete_table(generation.undo_branches())?;
+            txn.delete_table(generation.undo_leaves())?;
+        }
+        for id in previous {
+            if trees.iter().any(|(_, generation)| generation.id == id) {
+                continue;
+            }
+            match self.live_generation(id) {
+                Some(generation) => unsealed.push(generation),
+                None => {
+                    for table in table_names(id) {
+                        txn.delete_table(TableDef::new(&table))?;
+                    }
+                }
+            }
+        }
+        txn.commit()?;
+
+        for (_, generation) in trees {
+            generation.sealed.store(true, Ordering::Release);
+        }
+        for generation in &unsealed {
+            generation.sealed.store(false, Ordering::Release);
+        }
+        Ok(())
+    }
+
+    fn live_generation(&self, id: u64) -> Option<Arc<Generation>> {
+        self.generations
+            .lock()
+            .expect("SMT generations lock poisoned")
+            .get(&id)
+            .and_then(Weak::upgrade)
+    }
+}
+
+/// The generations of the sealed trees.
+fn sealed_generations(txn: &WriteTransaction) -> Result<Vec<u64>> {
+    let sealed = txn.open_table(SEALED)?;
+    let ids = sealed
+        .iter()?
+        .map(|entry| entry.map(|(_, id)| id.value()))
+        .collect::<Result<_, _>>()?;
+    Ok(ids)
+}
+
+/// Writes back the nodes generation `id` held when it was sealed.
+fn roll_back(txn: &WriteTransaction, id: u64) -> Result<()> {
+    let [branches, leaves, undo_branches, undo_leaves] = table_names(id);
+    for (table, undo) in [(branches, undo_branches), (leaves, undo_leaves)] {
+        {
+            let mut table = txn.open_table(TableDef::new(&table))?;
+            let saved = txn.open_table(TableDef::new(&undo))?;
+            for entry in saved.iter()? {
+                let (key, value) = entry?;
+                // An empty value stands for a node that was absent
+                if value.value().is_empty() {
+                    table.remove(key.value())?;
+                } else {
+                    table.insert(key.value(), value.value())?;
+                }
+            }
+        }
+        txn.delete_table(TableDef::new(&undo))?;
+    }
+    Ok(())
+}
+
+/// The branches and leaves tables of generation `id`, then the undo tables
+/// holding the nodes they had when sealed.
+fn table_names(id: u64) -> [String; 4] {
+    [
+        format!("branches_{id}"),
+        format!("leaves_{id}"),
+        format!("undo_branches_{id}"),
+        format!("undo_leaves_{id}"),
+    ]
+}
+
+fn generation_id(table: &str) -> Option<u64> {
+    ["branches_", "leaves_", "undo_branches_", "undo_leaves_"]
+        .iter()
+        .find_map(|prefix| table.strip_prefix(prefix)?.parse().ok())
+}
+
+/// Where new trees keep their nodes.
+#[derive(Clone, Default)]
+pub enum TreeBackend {
+    #[default]
+    Memory,
+    Disk(Arc<SmtDatabase>),
+}
+
+impl TreeBackend {
+    /// An empty tree store.
+    pub fn empty_store(&self) -> Result<TreeStore> {
+        Ok(match self {
+            TreeBackend::Memory => TreeStore::Memory(DefaultStore::default()),
+            TreeBackend::Disk(db) => TreeStore::Disk(DiskStore::empty(db.clone())?),
+        })
+    }
+
+    /// Moves the in-memory store of the tree at `root` to this backend,
+    /// leaving on-disk ones as is. The tree sealed at `root`, if any, is
+    /// reopened instead of importing the nodes.
+    pub fn tree_store(&self, root: BorshableH256, store: TreeStore) -> Result<TreeStore> {
+        let TreeBackend::Disk(db) = self else {
+            return Ok(store);
+        };
+        let TreeStore::Memory(memory) = &store else {
+            return Ok(store);
+        };
+        if let Some(sealed) = DiskStore::sealed(db, &root.as_h256())? {
+            return Ok(TreeStore::Disk(sealed));
+        }
+        Ok(TreeStore::Disk(DiskStore::import(db.clone(), memory)?))
+    }
+
+    /// Flushes the on-disk trees and seals them at their root, replacing the
+    /// trees sealed before. Called as a snapshot of the trees is taken, so
+    /// that restoring it reopens them.
+    pub fn seal<'a>(
+        &self,
+        trees: impl IntoIterator<Item = (BorshableH256, &'a mut TreeStore)>,
+    ) -> Result<()> {
+        let TreeBackend::Disk(db) = self else {
+            return Ok(());
+        };
+        let mut sealed = Vec::new();
+        for (root, store) in trees {
+            let TreeStore::Disk(store) = store else {
+                continue;
+            };
+            store.flush()?;
+            // Empty trees are created again rather than reopened
+            if root.as_h256() != H256::zero() {
+                sealed.push((root.as_h256(), store.snapshot.generation.clone()));
+            }
+        }
+        db.seal(&sealed)
+    }
+}
+
+/// Node store of the indexer and auto-prover trees.
+#[derive(Debug, Clone)]
+pub enum TreeStore {
+    Memory(DefaultStore<H256>),
+    Disk(DiskStore),
+}
+
+impl StoreReadOps<H256> for TreeStore {
+    fn get_branch(&self, branch_key: &BranchKey) -> Result<Option<BranchNode>, Error> {
+        match self {
+            TreeStore::Memory(store) => store.get_branch(branch_key),
+            TreeStore::Disk(store) => store.get_branch(branch_key),
+        }
+    }
+
+    fn get_leaf(&self, leaf_key: &H256) -> Result<Option<H256>, Error> {
+        match self {
+            TreeStore::Memory(store) => store.get_leaf(leaf_key),
+            TreeStore::Disk(store) => store.get_leaf(leaf_key),
+        }
+    }
+}
+
+impl StoreWriteOps<H256> for TreeStore {
+    fn insert_branch(&mut self, node_key: BranchKey, branch: BranchNode) -> Result<(), Error> {
+        match self {
+            TreeStore::Memory(store) => store.insert_branch(node_key, branch),
+            TreeStore::Disk(store) => store.insert_branch(node_key, branch),
+        }
+    }
+
+    fn insert_leaf(&mut self, leaf_key: H256, leaf: H256) -> Result<(), Error> {
+        match self {
+            TreeStore::Memory(store) => store.insert_leaf(leaf_key, leaf),
+            TreeStore::Disk(store) => store.insert_leaf(leaf_key, leaf),
+        }
+    }
+
+    fn remove_branch(&mut self, node_key: &BranchKey) -> Result<(), Error> {
+        match self {
+            TreeStore::Memory(store) => store.remove_branch(node_key),
+            TreeStore::Disk(store) => store.remove_branch(node_key),
+        }
+    }
+
+    fn remove_leaf(&mut self, leaf_key: &H256) -> Result<(), Error> {
+        match self {
+            TreeStore::Memory(store) => store.remove_leaf(leaf_key),
+            TreeStore::Disk(store) => store.remove_leaf(leaf_key),
+        }
+    }
+}
+
+impl SmtStore for TreeStore {
+    fn contains_leaf(&self, key: &H256) -> bool {
+        match self {
+            TreeStore::Memory(store) => store.contains_leaf(key),
+            TreeStore::Disk(store) => store.contains_leaf(key),
+        }
+    }
+
+    fn leaves(&self) -> Vec<(H256, H256)> {
+        match self {
+            TreeStore::Memory(store) => store.leaves(),
+            TreeStore::Disk(store) => store.leaves(),
+        }
+    }
+}
+
+/// Tables holding one version of a tree, deleted once no store reads them
+/// unless the version is sealed.
+struct Generation {
+    db: Arc<SmtDatabase>,
+    id: u64,
+    tables: [String; 4],
+    sealed: AtomicBool,
+}
+
+impl Generation {
+    fn create(db: Arc<SmtDatabase>) -> Result<Arc<Self>> {
+        let id = db.next_generation.fetch_add(1, Ordering::Relaxed);
+        let generation = Self::get_or_register(&db, id, false);
+        let txn = generation.db.db.begin_write()?;
+        txn.open_table(generation.branches())?;
+        txn.open_table(generation.leaves())?;
+        txn.commit()?;
+        Ok(generation)
+    }
+
+    /// Sealed generation `id`, shared with the stores already reading it.
+    fn reopen(db: &Arc<SmtDatabase>, id: u64) -> Arc<Self> {
+        Self::get_or_register(db, id, true)
+    }
+
+    fn get_or_register(db: &Arc<SmtDatabase>, id: u64, sealed: bool) -> Arc<Self> {
+        let mut generations = db
+            .generations
+            .lock()
+            .expect("SMT generations lock poisoned");
+        if let Some(generation) = generations.get(&id).and_then(Weak::upgrade) {
+            return generation;
+        }
+        let generation = Arc::new(Self {
+            db: db.clone(),
+            id,
+            tables: table_names(id),
+            sealed: AtomicBool::new(sealed),
+        });
+        generations.insert(id, Arc::downgrade(&generation));
+        generation
+    }
+
+    fn branches(&self) -> TableDef<'_> {
+        TableDefinition::new(&self.tables[0])
+    }
+
+    fn leaves(&self) -> TableDef<'_> {
+        TableDefinition::new(&self.tables[1])
+    }
+
+    fn undo_branches(&self) -> TableDef<'_> {
+        TableDefinition::new(&self.tables[2])
+    }
+
+    fn undo_leaves(&self) -> TableDef<'_> {
+        TableDefinition::new(&self.tables[3])
+    }
+
+    fn delete(&self) -> Result<()> {
+        let txn = self.db.db.begin_write()?;
+        for table in &self.tables {
+            txn.delete_table(TableDef::new(table))?;
+        }
+        txn.commit()?;
+        Ok(())
+    }
+}
+
+impl Drop for Generation {
+    fn drop(&mut self) {
+        if let Ok(mut generations) = self.db.generations.lock() {
+            if generations
+                .get(&self.id)
+                .is_some_and(|generation| generation.strong_count() == 0)
+            {
+                generations.remove(&self.id);
+            }
+        }
+        if self.sealed.load(Ordering::Acquire) {
+            return;
+        }
+        if let Err(err) = self.delete() {
+            warn!(error = %err, generation = self.id, "Failed to delete SMT tables");
+        }
+    }
+}
+
+/// A consistent read view of a [`Generation`].
+struct Snapshot {
+    branches: Table,
+    leaves: Table,
+    generation: Arc<Generation>,
+}
+
+impl Snapshot {
+    fn open(generation: Arc<Generation>) -> Result<Self> {
+        let txn = generation.db.db.begin_read()?;
+        Ok(Self {
+            branches: txn.open_table(generation.branches())?,
+            leaves: txn.open_table(generation.leaves())?,
+            generation,
+        })
+    }
+}
+
+/// A `sparse_merkle_tree` store keeping its nodes in an embedded database,
+/// with the latest changes buffered in memory.
+///
+/// Clones share the on-disk nodes and only copy the buffered changes. Writes
+/// are copy-on-write: a store writes its changes to its tables in place only
+/// when no clone still reads them, and otherwise to a copy of them, which
+/// costs a pass over the whole tree. Clones are meant to be short-lived or
+/// read-only, such as the trees served to witness requests.
+#[derive(Clone)]
+pub struct DiskStore {
+    snapshot: Arc<Snapshot>,
+    branches: HashMap<BranchKey, Option<BranchNode>>,
+    leaves: HashMap<H256, Option<H256>>,
+}
+
+impl DiskStore {
+    /// Creates an empty store in `db`.
+    pub fn empty(db: Arc<SmtDatabase>) -> Result<Self> {
+        Self::reading(Generation::create(db)?)
+    }
+
+    /// Reopens the tree sealed at `root`, if any.
+    pub fn sealed(db: &Arc<SmtDatabase>, root: &H256) -> Result<Option<Self>> {
+        let txn = db.db.begin_read()?;
+        let id = txn
+            .open_table(SEALED)?
+            .get(root.as_slice())?
+            .map(|id| id.value());
+        id.map(|id| Self::reading(Generation::reopen(db, id)))
+            .transpose()
+    }
+
+    /// Creates a store in `db` holding the nodes of `memory`.
+    pub fn import(db: Arc<SmtDatabase>, memory: &DefaultStore<H256>) -> Result<Self> {
+        let mut store = Self::empty(db)?;
+        for (key, branch) in memory.branches_map().iter() {
+            store.branches.insert(key.clone(), Some(branch.clone()));
+        }
+        for (key, leaf) in memory.leaves_map().iter() {
+            store.leaves.insert(*key, Some(*leaf));
+        }
+        store.flush()?;
+        Ok(store)
+    }
+
+    fn reading(generation: Arc<Generation>) -> Result<Self> {
+        Ok(Self {
+            snapshot: Arc::new(Snapshot::open(generation)?),
+            branches: HashMap::new(),
+            leaves: HashMap::new(),
+        })
+    }
+
+    /// Writes the buffered changes to disk. Writing to a sealed generation
+    /// first saves the nodes it overwrites, to roll it back on open.
+    pub fn flush(&mut self) -> Result<()> {
+        let shared = Arc::strong_count(&self.snapshot) > 1
+            || Arc::strong_count(&self.snapshot.generation) > 1;
+        let generation = if shared {
+            Generation::create(self.snapshot.generation.db.clone())?
+        } else {
+            self.snapshot.generation.clone()
+        };
+        let undo = !shared && generation.sealed.load(Ordering::Acquire);
+
+        let txn = generation.db.db.begin_write()?;
+        {
+            let mut branches = txn.open_table(generation.branches())?;
+            let mut leaves = txn.open_table(generation.leaves())?;
+            if shared {
+                for entry in self.snapshot.branches.iter()? {
+                    let (key, value) = entry?;
+                    branches.insert(key.value(), value.value())?;
+                }
+                for entry in self.snapshot.leaves.iter()? {
+                    let (key, value) = entry?;
+                    leaves.insert(key.value(), value.value())?;
+                }
+            }
+            if undo {
+                let mut undo_branches = txn.open_table(generation.undo_branches())?;
+                let mut undo_leaves = txn.open_table(generation.undo_leaves())?;
+                for key in self.branches.keys() {
+                    save_sealed(&branches, &mut undo_branches, &encode_branch_key(key))?;
+                }
+                for key in self.leaves.keys() {
+                    save_sealed(&leaves, &mut undo_leaves, key.as_slice())?;
+                }
+            }
+            for (key, branch) in self.branches.drain() {
+                let key = encode_branch_key(&key);
+                match branch {
+                    Some(branch) => {
+                        let branch = encode_branch(&branch).map_err(|e| anyhow!("{e:?}"))?;
+                        branches.insert(key.as_slice(), branch.as_slice())?;
+                    }
+                    None => {
+                        branches.remove(key.as_slice())?;
+                    }
+                }
+            }
+            for (key, leaf) in self.leaves.drain() {
+                match leaf {
+                    Some(leaf) => {
+                        leaves.insert(key.as_slice(), leaf.as_slice())?;
+                    }
+                    None => {
+                        leaves.remove(key.as_slice())?;
+                    }
+                }
+            }
+        }
+        txn.commit()?;
+
+        self.snapshot = Arc::new(Snapshot::open(generation)?);
+        Ok(())
+    }
+
+    fn maybe_flush(&mut self) -> Result<(), Error> {
+        if self.branches.len() + self.leaves.len() < OVERLAY_LIMIT {
+            return Ok(());
+        }
+        self.flush()
+            .map_err(|err| Error::Store(format!("flushing SMT store: {err:#}")))
+    }
+}
+
+impl fmt::Debug for DiskStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DiskStore")
+            .field("generation", &self.snapshot.generation.id)
+            .field("pending_branches", &self.branches.len())
+            .field("pending_leaves", &self.leaves.len())
+            .finish()
+    }
+}
+
+impl StoreReadOps<H256> for DiskStore {
+    fn get_branch(&self, branch_key: &BranchKey) -> Result<Option<BranchNode>, Error> {
+        if let Some(branch) = self.branches.get(branch_key) {
+            return Ok(branch.clone());
+        }
+        self.snapshot
+            .branches
+            .get(encode_branch_key(branch_key).as_slice())
+            .map_err(store_error)?
+            .map(|value| decode_branch(value.value()))
+            .transpose()
+    }
+
+    fn get_leaf(&self, leaf_key: &H256) -> Result<Option<H256>, Error> {
+        if let Some(leaf) = self.leaves.get(leaf_key) {
+            return Ok(*leaf);
+        }
+        self.snapshot
+            .leaves
+            .get(leaf_key.as_slice())
+            .map_err(store_error)?
+            .map(|value| decode_h256(value.value()))
+            .transpose()
+    }
+}
+
+impl StoreWriteOps<H256> for DiskStore {
+    fn insert_branch(&mut self, node_key: BranchKey, branch: BranchNode) -> Result<(), Error> {
+        self.branches.insert(node_key, Some(branch));
+        self.maybe_flush()
+    }
+
+    fn insert_leaf(&mut self, leaf_key: H256, leaf: H256) -> Result<(), Error> {
+        self.leaves.insert(leaf_key, Some(leaf));
+        self.maybe_flush()
+    }
+
+    fn remove_branch(&mut self, node_key: &BranchKey) -> Result<(), Error> {
+        self.branches.insert(node_key.clone(), None);
+        self.maybe_flush()
+    }
+
+    fn remove_leaf(&mut self, leaf_key: &H256) -> Result<(), Error> {
+        self.leaves.insert(*leaf_key, None);
+        self.maybe_flush()
+    }
+}
+
+impl SmtStore for DiskStore {
+    fn contains_leaf(&self, key: &H256) -> bool {
+        matches!(self.get_leaf(key), Ok(Some(_)))
+    }
+
+    fn leaves(&self) -> Vec<(H256, H256)> {
+        let mut leaves = BTreeMap::new();
+        let stored = match self.snapshot.leaves.iter() {
+            Ok(stored) => stored,
+            Err(err) => {
+                warn!(error = %err, "Failed to read SMT leaves");
+                return Vec::new();
+            }
+        };
+        for entry in stored {
+            let decoded = entry.map_err(store_error).and_then(|(key, value)| {
+                Ok((decode_h256(key.value())?, decode_h256(value.value())?))
+            });
+            match decoded {
+                Ok((key, value)) => {
+                    leaves.insert(key, value);
+                }
+                Err(err) => warn!(error = %err, "Skipping unreadable SMT leaf"),
+            }
+        }
+        for (key, leaf) in &self.leaves {
+            match leaf {
+                Some(leaf) => leaves.insert(*key, *leaf),
+                None => leaves.remove(key),
+            };
+        }
+        leaves.into_iter().collect()
+    }
+}
+
+/// Saves the node `key` held in the sealed version of `table`, empty if it
+/// had none, unless it was saved already.
+fn save_sealed(
+    table: &redb::Table<'_, &'static [u8], &'static [u8]>,
+    undo: &mut redb::Table<'_, &'static [u8], &'static [u8]>,
+    key: &[u8],
+) -> Result<()> {
+    if undo.get(key)?.is_some() {
+        return Ok(());
+    }
+    let sealed = table
+        .get(key)?
+        .map(|value| value.value().to_vec())
+        .unwrap_or_default();
+    undo.insert(key, sealed.as_slice())?;
+    Ok(())
+}
+
+fn store_error(err: impl fmt::Display) -> Error {
+    Error::Store(err.to_string())
+}
+
+fn decode_h256(bytes: &[u8]) -> Result<H256, Error> {
+    <[u8; 32]>::try_from(bytes)
+        .map(H256::from)
+        .map_err(|_| Error::Store(format!("expected 32 bytes, got {}", bytes.len())))
+}
+
+/// `[height][node_key]`
+fn encode_branch_key(key: &BranchKey) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(33);
+    bytes.push(key.height);
+    bytes.extend_from_slice(key.node_key.as_slice());
+    bytes
+}
+
+/// `[left][right]`, each merge value being a tag followed by its hashes.
+fn encode_branch(branch: &BranchNode) -> Result<Vec<u8>, Error> {
+    let mut bytes = Vec::with_capacity(2 * 66);
+    encode_merge_value(&branch.left, &mut bytes)?;
+    encode_merge_value(&branch.right, &mut bytes)?;
+    Ok(bytes)
+}
+
+fn encode_merge_value(value: &MergeValue, bytes: &mut Vec<u8>) -> Result<(), Error> {
+    #[allow(unreachable_patterns)]
+    match value {
+        MergeValue::Value(hash) => {
+            bytes.push(0);
+            bytes.extend_from_slice(hash.as_slice());
+        }
+        MergeValue::MergeWithZero {
+            base_node,
+            zero_bits,
+            zero_count,
+        } => {
+            bytes.push(1);
+            bytes.extend_from_slice(base_node.as_slice());
+            bytes.extend_from_slice(zero_bits.as_slice());
+            bytes.push(*zero_count);
+        }
+        other => return Err(Error::Store(format!("unsupported merge value {other:?}"))),
+    }
+    Ok(())
+}
+
+fn decode_branch(bytes: &[u8]) -> Result<BranchNode, Error> {
+    let (left, rest) = decode_merge_value(bytes)?;
+    let (right, rest) = decode_merge_value(rest)?;
+    if !rest.is_empty() {
+        return Err(Error::Store("trailing bytes after SMT branch".to_string()));
+    }
+    Ok(BranchNode { left, right })
+}
+
+fn decode_merge_value(bytes: &[u8]) -> Result<(MergeValue, &[u8]), Error> {
+    let truncated = || Error::Store("truncated SMT branch".to_string());
+    match bytes.split_first() {
+        Some((0, rest)) if rest.len() >= 32 => {
+            Ok((MergeValue::Value(decode_h256(&rest[..32])?), &rest[32..]))
+        }
+        Some((1, rest)) if rest.len() >= 65 => Ok((
+            MergeValue::MergeWithZero {
+                base_node: decode_h256(&rest[..32])?,
+                zero_bits: decode_h256(&rest[32..64])?,
+                zero_count: rest[64],
+            },
+            &rest[65..],
+        )),
+        Some((0 | 1, _)) | None => Err(truncated()),
+        Some((tag, _)) => Err(Error::Store(format!("unknown SMT merge value tag {tag}"))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyli_utxo_state::zk::{smt::build_siblings, BorshableH256, SMT};
+
+    fn key(byte: u8) -> BorshableH256 {
+        BorshableH256::from([byte; 32])
+    }
+
+    fn open_db(dir: &tempfile::TempDir) -> Arc<SmtDatabase> {
+        Arc::new(SmtDatabase::open(&dir.path().join("smt.redb")).unwrap())
+    }
+
+    fn disk_tree(db: &Arc<SmtDatabase>) -> SMT<BorshableH256, DiskStore> {
+        SMT::from_store(
+            BorshableH256::from(H256::zero()),
+            DiskStore::empty(db.clone()).unwrap(),
+        )
+    }
+
+    fn flushed(tree: SMT<BorshableH256, DiskStore>) -> SMT<BorshableH256, DiskStore> {
+        let root = tree.root();
+        let mut store = tree.into_store();
+        store.flush().unwrap();
+        SMT::from_store(root, store)
+    }
+
+    #[test]
+    fn disk_tree_matches_memory_tree() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = open_db(&dir);
+        let mut disk = disk_tree(&db);
+        let mut memory = SMT::<BorshableH256>::zero();
+
+        for byte in 1u8..=20 {
+            disk.update_leaf(key(byte), key(byte)).unwrap();
+            memory.update_leaf(key(byte), key(byte)).unwrap();
+            if byte % 7 == 0 {
+                disk = flushed(disk);
+            }
+        }
+
+        assert_eq!(disk.root(), memory.root());
+        assert!(disk.contains(&key(3)));
+        assert!(!disk.contains(&key(42)));
+        assert_eq!(disk.store().leaves().len(), 20);
+        assert_eq!(
+            build_siblings(&disk, key(5)),
+            build_siblings(&memory, key(5))
+        );
+
+        let imported: SMT<BorshableH256, DiskStore> = SMT::from_store(
+            memory.root(),
+            DiskStore::import(db, memory.store()).unwrap(),
+        );
+        assert_eq!(
+            build_siblings(&imported, key(9)),
+            build_siblings(&memory, key(9))
+        );
+    }
+
+    #[test]
+    fn clones_keep_their_view_after_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut tree = disk_tree(&open_db(&dir));
+        tree.update_leaf(key(1), key(1)).unwrap();
+        let tree = flushed(tree);
+
+        let before = tree.clone();
+        let mut after = tree;
+        after.update_leaf(key(2), key(2)).unwrap();
+        after
+            .update_leaf(key(1), BorshableH256::from(H256::zero()))
+            .unwrap();
+        // `before` still reads the same tables, so they are copied
+        let after = flushed(after);
+
+        assert!(before.contains(&key(1)));
+        assert!(!before.contains(&key(2)));
+        assert!(!after.contains(&key(1)));
+        assert!(after.contains(&key(2)));
+        assert_eq!(before.store().leaves().len(), 1);
+    }
+
+    #[test]
+    fn reopens_sealed_trees_as_they_were_sealed() {
+        let dir = tempfile::tempdir().unwrap();
+        let trees = TreeBackend::Disk(open_db(&dir));
+        let mut tree = SMT::<BorshableH256, TreeStore>::from_store(
+            BorshableH256::from(H256::zero()),
+            trees.empty_store().unwrap(),
+        );
+        for byte in 1u8..=5 {
+            tree.update_leaf(key(byte), key(byte)).unwrap();
+        }
+        let sealed_root = tree.root();
+        trees.seal([(sealed_root, tree.store_mut())]).unwrap();
+        // Written past the seal, then lost with the process
+        tree.update_leaf(key(6), key(6)).unwrap();
+        tree.update_leaf(key(1), BorshableH256::from(H256::zero()))
+            .unwrap();
+        let TreeStore::Disk(store) = tree.store_mut() else {
+            panic!("expected an on-disk store");
+        };
+        store.flush().unwrap();
+        drop(tree);
+        drop(trees);
+
+        let trees = TreeBackend::Disk(open_db(&dir));
+        let reopened = trees
+            .tree_store(sealed_root, TreeStore::Memory(DefaultStore::default()))
+            .unwrap();
+        assert!(matches!(reopened, TreeStore::Disk(_)));
+        let tree = SMT::<BorshableH256, TreeStore>::from_store(sealed_root, reopened);
+        assert!(tree.contains(&key(1)));
+        assert!(!tree.contains(&key(6)));
+        assert_eq!(tree.store().leaves().len(), 5);
+
+        let mut memory = SMT::<BorshableH256>::zero();
+        for byte in 1u8..=5 {
+            memory.update_leaf(key(byte), key(byte)).unwrap();
+        }
+        assert_eq!(
+            build_siblings(&tree, key(2)),
+            build_siblings(&memory, key(2))
+        );
+
+        // Any other root is imported from the snapshot leaves
+        let imported = trees
+            .tree_store(key(9), TreeStore::Memory(memory.store().clone()))
+            .unwrap();
+        assert_eq!(imported.leaves().len(), 5);
+    }
+}
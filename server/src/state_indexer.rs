@@ -0,0 +1,280 @@
+use std::sync::Arc;
+
+use anyhow::{bail, Context, Result};
+use axum::Router;
+use client_sdk::rest_client::NodeApiClient;
+use hex::encode as hex_encode;
+use hyli_modules::{
+    bus::{BusClientSender, SharedMessageBus},
+    module_bus_client, module_handle_messages,
+    modules::{
+        contract_listener::{ContractListenerEvent, ContractTx},
+        contract_state_indexer::CSIBusEvent,
+        BuildApiContextInner, Module,
+    },
+};
+use hyli_utxo_state::state::ContractConfig;
+use sdk::{
+    api::{ContractChangeType, TransactionStatusDb},
+    BlobIndex, ContractName, StateCommitment,
+};
+use tracing::{debug, info, warn};
+
+use crate::{
+    hyli_utxo_state_client::{
+        state_api, HyliUtxoStateEvent, HyliUtxoStateExecutor, UtxoStateStore,
+    },
+    smt_store::TreeBackend,
+    state_snapshot::{AppliedPosition, ExecutorSnapshot, SnapshotStore},
+};
+
+/// Settled transactions applied after the on-chain commitment is read before
+/// a restored snapshot that never reached it is considered diverged.
+const RESTORE_CHECK_TXS: usize = 16;
+
+module_bus_client! {
+    #[derive(Debug)]
+    pub struct HyliUtxoStateIndexerBusClient {
+        sender(CSIBusEvent<HyliUtxoStateEvent>),
+        receiver(ContractListenerEvent),
+    }
+}
+
+pub struct HyliUtxoStateIndexerCtx {
+    pub contract_name: ContractName,
+    pub store: UtxoStateStore,
+    /// Where the indexed trees keep their nodes
+    pub trees: TreeBackend,
+    pub snapshots: SnapshotStore,
+    /// Latest snapshot, loaded beforehand to pick where the listeners resume
+    pub restored: Option<ExecutorSnapshot>,
+    pub node: Arc<dyn NodeApiClient + Send + Sync>,
+    pub api: Arc<BuildApiContextInner>,
+}
+
+/// A restored snapshot not yet confirmed by the on-chain state commitment.
+struct PendingCheck {
+    /// Commitment read from the node once the backfill completed
+    onchain: Option<StateCommitment>,
+    /// Transactions left to reach `onchain`
+    remaining: usize,
+}
+
+/// Indexes the settled transactions of the hyli-utxo-state contract into
+/// [`HyliUtxoStateExecutor`] trees, serving witnesses from them and emitting
+/// a `CSIBusEvent<HyliUtxoStateEvent>` per applied transaction.
+///
+/// The state is restored from the latest snapshot when the module is built,
+/// and the transactions it covers are skipped when the listeners replay them.
+pub struct HyliUtxoStateIndexer {
+    bus: HyliUtxoStateIndexerBusClient,
+    contract_name: ContractName,
+    store: UtxoStateStore,
+    trees: TreeBackend,
+    snapshots: SnapshotStore,
+    node: Arc<dyn NodeApiClient + Send + Sync>,
+    position: AppliedPosition,
+    pending_check: Option<PendingCheck>,
+}
+
+impl Module for HyliUtxoStateIndexer {
+    type Context = HyliUtxoStateIndexerCtx;
+
+    async fn build(bus: SharedMessageBus, ctx: Self::Context) -> Result<Self> {
+        let bus = HyliUtxoStateIndexerBusClient::new_from_bus(bus.new_handle()).await;
+
+        let mut position = AppliedPosition::default();
+        let mut pending_check = None;
+        if let Some(snapshot) = ctx.restored {
+            info!(
+                block_height = snapshot.position.block_height,
+                commitment = %hex_encode(&snapshot.commitment),
+                "Restored hyli-utxo-state from snapshot"
+            );
+            position = snapshot.position;
+            let executor = snapshot
+                .executor
+                .with_trees_in(&ctx.trees)
+                .context("moving the restored hyli-utxo-state trees to their store")?;
+            *ctx.store.write().await = Some(executor);
+            pending_check = Some(PendingCheck {
+                onchain: None,
+                remaining: RESTORE_CHECK_TXS,
+            });
+        }
+
+        let (router, api) = state_api(ctx.store.clone());
+        let path = format!("/v1/indexer/contract/{}", ctx.contract_name.0);
+        if let Ok(mut guard) = ctx.api.router.lock() {
+            let nested = guard.take().unwrap_or_else(Router::new).nest(&path, router);
+            guard.replace(nested);
+        }
+        if let Ok(mut openapi) = ctx.api.openapi.lock() {
+            *openapi = openapi.clone().nest(path, api);
+        }
+
+        Ok(Self {
+            bus,
+            contract_name: ctx.contract_name,
+            store: ctx.store,
+            trees: ctx.trees,
+            snapshots: ctx.snapshots,
+            node: ctx.node,
+            position,
+            pending_check,
+        })
+    }
+
+    async fn run(&mut self) -> Result<()> {
+        module_handle_messages! {
+            on_self self,
+            listen<ContractListenerEvent> event => {
+                if let ContractListenerEvent::SettledTx(tx) = event {
+                    self.handle_settled(tx).await?;
+                } else if let ContractListenerEvent::BackfillComplete(contract_name) = event {
+                    if contract_name == self.contract_name {
+                        self.read_onchain_commitment().await?;
+                    }
+                }
+            }
+        };
+
+        self.save_snapshot().await;
+        Ok(())
+    }
+}
+
+impl HyliUtxoStateIndexer {
+    async fn handle_settled(&mut self, tx: ContractTx) -> Result<()> {
+        let tx_hash = tx.tx_id.1.clone();
+        let block_height = tx.tx_ctx.block_height.0;
+        if self.position.covers(block_height, &tx_hash) {
+            debug!(tx_hash = %tx_hash, block_height, "Skipping transaction already in the restored state");
+            return Ok(());
+        }
+        if !matches!(tx.status, TransactionStatusDb::Success) {
+            return Ok(());
+        }
+
+        let mut events = Vec::new();
+        {
+            let mut store = self.store.write().await;
+            if let Some(change) = tx.contract_changes.get(&self.contract_name) {
+                if change
+                    .change_types
+                    .iter()
+                    .any(|change| matches!(change, ContractChangeType::Registered))
+                {
+                    let config = change
+                        .metadata
+                        .as_deref()
+                        .context("hyli-utxo-state registration without metadata")?;
+                    let config: ContractConfig = borsh::from_slice(config)
+                        .context("decoding ContractConfig from registration metadata")?;
+                    info!(contract = %self.contract_name, block_height, "Indexing hyli-utxo-state registration");
+                    *store = Some(
+                        HyliUtxoStateExecutor::with_backend(config, &self.trees)
+                            .context("creating the hyli-utxo-state trees")?,
+                    );
+                }
+            }
+            let Some(executor) = store.as_mut() else {
+                warn!(tx_hash = %tx_hash, "Settled hyli-utxo-state transaction before its registration");
+                return Ok(());
+            };
+            for (index, blob) in tx.tx.blobs.iter().enumerate() {
+                if blob.contract_name != self.contract_name {
+                    continue;
+                }
+                events.extend(executor.apply_settled(&tx.tx, BlobIndex(index), block_height));
+            }
+            self.position.record(block_height, tx_hash);
+            // An unconfirmed restored state is not snapshotted again
+            if self.pending_check.is_none() && self.snapshots.record_applied() {
+                self.save_snapshot_of(executor);
+            }
+        }
+
+        for event in events {
+            self.bus
+                .send(CSIBusEvent {
+                    contract_name: self.contract_name.clone(),
+                    event,
+                })
+                .context("sending hyli-utxo-state event")?;
+        }
+        self.check_restored_state().await
+    }
+
+    /// Reads the commitment the restored state should reach once the
+    /// transactions settled so far are applied.
+    async fn read_onchain_commitment(&mut self) -> Result<()> {
+        let Some(check) = self.pending_check.as_mut() else {
+            return Ok(());
+        };
+        let contract = self
+            .node
+            .get_contract(self.contract_name.clone())
+            .await
+            .context("fetching the hyli-utxo-state contract")?;
+        check.onchain = Some(contract.state);
+        self.check_restored_state().await
+    }
+
+    /// Settlement updates the on-chain commitment as the indexer applies the
+    /// same transactions, so a restored state matching its own commitment but
+    /// not the chain is dropped with every snapshot, for a full rebuild.
+    async fn check_restored_state(&mut self) -> Result<()> {
+        let Some(PendingCheck {
+            onchain: Some(onchain),
+            remaining,
+        }) = self.pending_check.as_mut()
+        else {
+            return Ok(());
+        };
+        let commitment = self
+            .store
+            .read()
+            .await
+            .as_ref()
+            .map(|executor| executor.utxo_state().commitment());
+        if commitment.as_ref() == Some(&*onchain) {
+            info!("Restored hyli-utxo-state matches the on-chain state commitment");
+            self.pending_check = None;
+            return Ok(());
+        }
+        if *remaining > 0 {
+            *remaining -= 1;
+            return Ok(());
+        }
+        if let Err(err) = self.snapshots.discard_all() {
+            warn!(error = %err, "Failed to remove hyli-utxo-state snapshots");
+        }
+        bail!(
+            "restored hyli-utxo-state never reached the on-chain state commitment {}, snapshots were removed",
+            hex_encode(&onchain.0)
+        );
+    }
+
+    async fn save_snapshot(&self) {
+        let mut store = self.store.write().await;
+        let Some(executor) = store.as_mut() else {
+            return;
+        };
+        if self.pending_check.is_some() {
+            return;
+        }
+        self.save_snapshot_of(executor);
+    }
+
+    /// Seals the on-disk trees along with the snapshot, so that restoring it
+    /// reopens them. Unsealed trees are imported from the snapshot leaves.
+    fn save_snapshot_of(&self, executor: &mut HyliUtxoStateExecutor) {
+        if let Err(err) = executor.seal_trees(&self.trees) {
+            warn!(error = %err, "Failed to seal the on-disk hyli-utxo-state trees");
+        }
+        if let Err(err) = self.snapshots.save(&self.position, executor) {
+            warn!(error = %err, "Failed to save hyli-utxo-state snapshot");
+        }
+    }
+}
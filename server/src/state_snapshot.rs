@@ -0,0 +1,276 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use sdk::TxHash;
+use tracing::{info, warn};
+
+use hyli_utxo_state::state::ContractConfig;
+
+use crate::{conf::SnapshotConf, hyli_utxo_state_client::HyliUtxoStateExecutor};
+
+/// The last settled transactions applied by the indexer.
+///
+/// The contract settles its transactions in the order they were sequenced,
+/// so every transaction of an earlier block was applied before these.
+#[derive(Debug, Clone, Default, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct AppliedPosition {
+    /// Block the last applied transaction was sequenced in
+    pub block_height: u64,
+    /// Transactions of that block applied so far
+    pub txs: Vec<TxHash>,
+}
+
+impl AppliedPosition {
+    /// Whether the transaction sequenced at `block_height` was applied.
+    pub fn covers(&self, block_height: u64, tx_hash: &TxHash) -> bool {
+        block_height < self.block_height
+            || (block_height == self.block_height && self.txs.contains(tx_hash))
+    }
+
+    pub fn record(&mut self, block_height: u64, tx_hash: TxHash) {
+        if block_height != self.block_height {
+            self.block_height = block_height;
+            self.txs.clear();
+        }
+        self.txs.push(tx_hash);
+    }
+}
+
+/// The indexer state after the settled transactions up to `position`.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct ExecutorSnapshot {
+    pub position: AppliedPosition,
+    /// `HyliUtxoState::commitment()` of `executor`, checked when loading
+    pub commitment: Vec<u8>,
+    pub executor: HyliUtxoStateExecutor,
+}
+
+/// Periodic snapshots of the hyli-utxo-state indexer, so a restart resumes
+/// from the latest one instead of rebuilding both trees from the whole
+/// history.
+pub struct SnapshotStore {
+    dir: PathBuf,
+    conf: SnapshotConf,
+    applied_since_snapshot: AtomicU64,
+    /// Highest block height tagged so far, so file names keep increasing
+    /// when transactions settle out of order
+    last_block_height: AtomicU64,
+}
+
+impl SnapshotStore {
+    /// Creates a store keeping its snapshots in `dir`.
+    pub fn new(dir: PathBuf, conf: SnapshotConf) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir,
+            conf,
+            applied_since_snapshot: AtomicU64::new(0),
+            last_block_height: AtomicU64::new(0),
+        })
+    }
+
+    /// Counts one applied transaction, returning whether a snapshot is due.
+    pub fn record_applied(&self) -> bool {
+        let applied = self.applied_since_snapshot.fetch_add(1, Ordering::Relaxed) + 1;
+        if applied < self.conf.interval_txs {
+            return false;
+        }
+        self.applied_since_snapshot.store(0, Ordering::Relaxed);
+        true
+    }
+
+    /// Writes a snapshot of `executor` (atomic write via temp file + rename)
+    /// and removes the oldest ones beyond `keep`.
+    pub fn save(
+        &self,
+        position: &AppliedPosition,
+        executor: &HyliUtxoStateExecutor,
+    ) -> io::Result<()> {
+        let block_height = self
+            .last_block_height
+            .fetch_max(position.block_height, Ordering::Relaxed)
+            .max(position.block_height);
+        // Same layout as `ExecutorSnapshot`, without cloning the trees
+        let commitment = executor.utxo_state().commitment().0;
+        let bytes = borsh::to_vec(&(position, &commitment, executor))?;
+        let path = self.dir.join(format!("{block_height:020}.bin"));
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, bytes)?;
+        fs::rename(&temp_path, &path)?;
+        info!(block_height, path = %path.display(), "Saved hyli-utxo-state snapshot");
+
+        for stale in self
+            .snapshot_paths()?
+            .into_iter()
+            .skip(self.conf.keep.max(1))
+        {
+            fs::remove_file(stale)?;
+        }
+        Ok(())
+    }
+
+    /// The most recent snapshot of a contract deployed with `config` that
+    /// decodes and matches its recorded commitment, if any.
+    pub fn load_latest(&self, config: &ContractConfig) -> Option<ExecutorSnapshot> {
+        let paths = match self.snapshot_paths() {
+            Ok(paths) => paths,
+            Err(err) => {
+                warn!(error = %err, "Failed to list hyli-utxo-state snapshots");
+                return None;
+            }
+        };
+        paths.iter().find_map(|path| match load(path, config) {
+            Ok(snapshot) => Some(snapshot),
+            Err(err) => {
+                warn!(error = %err, path = %path.display(), "Skipping invalid hyli-utxo-state snapshot");
+                None
+            }
+        })
+    }
+
+    /// Removes every snapshot, so the next start rebuilds the state from the
+    /// whole history.
+    pub fn discard_all(&self) -> io::Result<()> {
+        for path in self.snapshot_paths()? {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    /// Snapshot files, most recent first.
+    fn snapshot_paths(&self) -> io::Result<Vec<PathBuf>> {
+        let mut paths = fs::read_dir(&self.dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<io::Result<Vec<_>>>()?;
+        paths.retain(|path| path.extension().is_some_and(|ext| ext == "bin"));
+        // Zero-padded heights sort chronologically
+        paths.sort_unstable_by(|a, b| b.cmp(a));
+        Ok(paths)
+    }
+}
+
+fn load(path: &Path, config: &ContractConfig) -> io::Result<ExecutorSnapshot> {
+    let snapshot: ExecutorSnapshot = borsh::from_slice(&fs::read(path)?)?;
+    if borsh::to_vec(snapshot.executor.config())? != borsh::to_vec(config)? {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "snapshot taken with another contract config",
+        ));
+    }
+    if snapshot.executor.utxo_state().commitment().0 != snapshot.commitment {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "state does not match its recorded commitment",
+        ));
+    }
+    Ok(snapshot)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyli_utxo_state::zk::BorshableH256;
+
+    fn config() -> ContractConfig {
+        ContractConfig {
+            utxo_contract_name: "hyli_utxo".into(),
+            smt_incl_proof_contract_name: "hyli_smt_incl_proof".into(),
+            smt_contract_name: "oranj".into(),
+        }
+    }
+
+    fn executor_with_notes(notes: &[u8]) -> HyliUtxoStateExecutor {
+        let mut executor = HyliUtxoStateExecutor::new(config());
+        for byte in notes {
+            executor
+                .apply_commitments(&[], &[BorshableH256::from([*byte; 32])])
+                .unwrap();
+        }
+        executor
+    }
+
+    fn at(block_height: u64) -> AppliedPosition {
+        AppliedPosition {
+            block_height,
+            txs: vec![TxHash(vec![block_height as u8])],
+        }
+    }
+
+    fn conf(keep: usize) -> SnapshotConf {
+        SnapshotConf {
+            interval_txs: 2,
+            keep,
+        }
+    }
+
+    #[test]
+    fn loads_the_latest_valid_snapshot() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SnapshotStore::new(dir.path().to_path_buf(), conf(3)).unwrap();
+        assert!(store.load_latest(&config()).is_none());
+
+        let older = executor_with_notes(&[1]);
+        let newer = executor_with_notes(&[1, 2]);
+        store.save(&at(5), &older).unwrap();
+        store.save(&at(9), &newer).unwrap();
+
+        let snapshot = store.load_latest(&config()).unwrap();
+        assert_eq!(snapshot.position, at(9));
+        assert_eq!(
+            snapshot.executor.utxo_state().notes_root(),
+            newer.utxo_state().notes_root()
+        );
+
+        // A corrupted snapshot falls back to the previous one
+        fs::write(dir.path().join(format!("{:020}.bin", 9)), b"garbage").unwrap();
+        let snapshot = store.load_latest(&config()).unwrap();
+        assert_eq!(snapshot.position, at(5));
+        assert_eq!(
+            snapshot.executor.utxo_state().notes_root(),
+            older.utxo_state().notes_root()
+        );
+    }
+
+    #[test]
+    fn keeps_only_the_most_recent_snapshots() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SnapshotStore::new(dir.path().to_path_buf(), conf(2)).unwrap();
+        let executor = executor_with_notes(&[1]);
+        for height in [1, 2, 3] {
+            store.save(&at(height), &executor).unwrap();
+        }
+        assert_eq!(store.snapshot_paths().unwrap().len(), 2);
+        assert_eq!(
+            store.load_latest(&config()).unwrap().position.block_height,
+            3
+        );
+
+        assert!(!store.record_applied());
+        assert!(store.record_applied());
+        assert!(!store.record_applied());
+
+        store.discard_all().unwrap();
+        assert!(store.load_latest(&config()).is_none());
+    }
+
+    #[test]
+    fn position_covers_earlier_blocks_and_applied_transactions() {
+        let mut position = AppliedPosition::default();
+        position.record(7, TxHash(vec![1]));
+        position.record(7, TxHash(vec![2]));
+
+        assert!(position.covers(6, &TxHash(vec![9])));
+        assert!(position.covers(7, &TxHash(vec![2])));
+        // Later transactions of the same block, e.g. padding-only ones
+        assert!(!position.covers(7, &TxHash(vec![3])));
+        assert!(!position.covers(8, &TxHash(vec![1])));
+
+        position.record(8, TxHash(vec![3]));
+        assert_eq!(position.txs, [TxHash(vec![3])]);
+        assert!(position.covers(7, &TxHash(vec![3])));
+    }
+}
@@ -0,0 +1,481 @@
+use std::{collections::VecDeque, io, sync::Arc, time::Duration};
+
+use anyhow::Result;
+use axum::response::sse::Event;
+use cachecash_client::types::{StateTransitionEvent, StreamHeartbeat};
+use futures::Stream;
+use hyli_modules::{
+    bus::SharedMessageBus,
+    module_bus_client, module_handle_messages,
+    modules::{contract_state_indexer::CSIBusEvent, Module},
+};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::broadcast::{self, error::RecvError, Receiver},
+    time::{Interval, MissedTickBehavior},
+};
+use tracing::{debug, warn};
+
+use crate::{
+    conf::StoreLogConf,
+    hyli_utxo_state_client::HyliUtxoStateEvent,
+    note_store::current_timestamp,
+    op_log::{LogState, LogStore},
+};
+
+/// Settled transitions kept for clients resuming from a cursor.
+const RETAINED_TRANSITIONS: usize = 1024;
+
+/// Transitions buffered for slow subscribers before they lag.
+const TRANSITION_EVENTS_CAPACITY: usize = 256;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct FeedState {
+    /// Sequence number of the last published transition
+    last_sequence: u64,
+    /// Most recent transitions, oldest first
+    recent: VecDeque<StateTransitionEvent>,
+    /// Block height of the last published transition
+    #[serde(default)]
+    last_block_height: Option<u64>,
+    /// Transactions published at `last_block_height`
+    #[serde(default)]
+    last_block_txs: Vec<String>,
+}
+
+impl FeedState {
+    /// Whether the transaction was published already. Settlement follows the
+    /// block order, so everything below the last published height was.
+    fn published(&self, block_height: u64, tx_hash: &str) -> bool {
+        match self.last_block_height {
+            Some(last) if block_height < last => true,
+            Some(last) if block_height == last => {
+                self.last_block_txs.iter().any(|tx| tx == tx_hash)
+            }
+            _ => false,
+        }
+    }
+
+    fn record_published(&mut self, block_height: u64, tx_hash: &str) {
+        if self.last_block_height != Some(block_height) {
+            self.last_block_height = Some(block_height);
+            self.last_block_txs.clear();
+        }
+        self.last_block_txs.push(tx_hash.to_string());
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+enum FeedOp {
+    Publish {
+        block_height: u64,
+        event: StateTransitionEvent,
+    },
+}
+
+impl LogState for FeedState {
+    type Op = FeedOp;
+
+    fn apply(&mut self, op: FeedOp) {
+        match op {
+            FeedOp::Publish {
+                block_height,
+                event,
+            } => {
+                self.record_published(block_height, &event.tx_hash);
+                self.last_sequence = event.sequence;
+                self.recent.push_back(event);
+                while self.recent.len() > RETAINED_TRANSITIONS {
+                    self.recent.pop_front();
+                }
+            }
+        }
+    }
+}
+
+/// Numbered history of the settled hyli-utxo-state transitions, served by
+/// `/api/state/stream`.
+pub struct StateTransitionFeed {
+    store: LogStore<FeedState>,
+    events: broadcast::Sender<StateTransitionEvent>,
+}
+
+impl StateTransitionFeed {
+    /// Creates an in-memory feed.
+    pub fn new() -> Self {
+        Self::from_store(LogStore::new(FeedState::default()))
+    }
+
+    /// Creates a feed whose recent transitions are persisted to
+    /// `persistence_path` and its operation log, so sequence numbers survive
+    /// restarts.
+    pub fn with_persistence(conf: StoreLogConf, persistence_path: String) -> io::Result<Self> {
+        Ok(Self::from_store(LogStore::open(persistence_path, conf)?))
+    }
+
+    fn from_store(store: LogStore<FeedState>) -> Self {
+        Self {
+            store,
+            events: broadcast::channel(TRANSITION_EVENTS_CAPACITY).0,
+        }
+    }
+
+    /// Numbers and broadcasts a settled transition. Transitions that touch no
+    /// note, or that were already published (the listener replays settled
+    /// transactions on start), are skipped.
+    pub fn publish(
+        &self,
+        tx_hash: String,
+        block_height: u64,
+        notes_root: [u8; 32],
+        created: &[[u8; 32]],
+        nullified: &[[u8; 32]],
+        now: u64,
+    ) -> Option<StateTransitionEvent> {
+        if created.is_empty() && nullified.is_empty() {
+            return None;
+        }
+        let mut state = self.store.write();
+        if state.published(block_height, &tx_hash) {
+            return None;
+        }
+        let event = StateTransitionEvent {
+            sequence: state.last_sequence + 1,
+            tx_hash,
+            notes_root: hex::encode(notes_root),
+            created_commitments: created.iter().map(hex::encode).collect(),
+            nullifiers: nullified.iter().map(hex::encode).collect(),
+            settled_at: now,
+        };
+        let op = FeedOp::Publish {
+            block_height,
+            event: event.clone(),
+        };
+        if let Err(err) = state.apply(op) {
+            // Not numbered, so the sequence stays gapless
+            warn!(error = %err, tx_hash = %event.tx_hash, "Failed to persist state transition");
+            return None;
+        }
+        // Broadcast under the lock so subscribers see increasing sequences;
+        // fails only when nobody is subscribed
+        let _ = self.events.send(event.clone());
+        Some(event)
+    }
+
+    /// Subscribes to transitions as they are published.
+    pub fn subscribe(&self) -> Receiver<StateTransitionEvent> {
+        self.events.subscribe()
+    }
+
+    /// Sequence number of the last published transition (0 if none).
+    pub fn last_sequence(&self) -> u64 {
+        self.store.read().last_sequence
+    }
+
+    /// Transitions published after `cursor`, or `None` when some of them are
+    /// no longer retained or the cursor is from another history.
+    pub fn after(&self, cursor: u64) -> Option<Vec<StateTransitionEvent>> {
+        let state = self.store.read();
+        if cursor > state.last_sequence {
+            return None;
+        }
+        let first_retained = state
+            .recent
+            .front()
+            .map_or(state.last_sequence + 1, |event| event.sequence);
+        if cursor + 1 < first_retained {
+            return None;
+        }
+        Some(
+            state
+                .recent
+                .iter()
+                .filter(|event| event.sequence > cursor)
+                .cloned()
+                .collect(),
+        )
+    }
+}
+
+impl Default for StateTransitionFeed {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+module_bus_client! {
+    #[derive(Debug)]
+    pub struct StateTransitionFeedBusClient {
+        receiver(CSIBusEvent<HyliUtxoStateEvent>),
+    }
+}
+
+/// Publishes the events of the hyli-utxo-state indexer to a
+/// [`StateTransitionFeed`].
+pub struct StateTransitionFeedModule {
+    bus: StateTransitionFeedBusClient,
+    feed: Arc<StateTransitionFeed>,
+}
+
+impl Module for StateTransitionFeedModule {
+    type Context = Arc<StateTransitionFeed>;
+
+    async fn build(bus: SharedMessageBus, ctx: Self::Context) -> Result<Self> {
+        let bus = StateTransitionFeedBusClient::new_from_bus(bus.new_handle()).await;
+        Ok(Self { bus, feed: ctx })
+    }
+
+    async fn run(&mut self) -> Result<()> {
+        module_handle_messages! {
+            on_self self,
+            listen<CSIBusEvent<HyliUtxoStateEvent>> event => {
+                let event = event.event;
+                self.feed.publish(
+                    hex::encode(&event.tx_hash.0),
+                    event.block_height,
+                    event.notes_root,
+                    &event.created,
+                    &event.nullified,
+                    current_timestamp(),
+                );
+            }
+        };
+
+        Ok(())
+    }
+}
+
+/// Server-Sent Events for the transitions published after `cursor`.
+///
+/// Without a cursor only new transitions are sent. When the cursor cannot be
+/// resumed from, a `reset` event tells the client to refresh its witnesses.
+pub fn transition_events(
+    feed: Arc<StateTransitionFeed>,
+    cursor: Option<u64>,
+    heartbeat: Duration,
+) -> impl Stream<Item = Result<Event, axum::Error>> {
+    // Subscribe before reading the backlog so no transition falls in between
+    let receiver = feed.subscribe();
+    let mut stream = TransitionStream {
+        cursor: feed.last_sequence(),
+        feed,
+        receiver,
+        backlog: VecDeque::new(),
+        reset: false,
+        heartbeat: tokio::time::interval(heartbeat),
+    };
+    stream
+        .heartbeat
+        .set_missed_tick_behavior(MissedTickBehavior::Delay);
+    if let Some(cursor) = cursor {
+        stream.resume_from(cursor);
+    }
+
+    futures::stream::unfold(stream, |mut stream| async move {
+        let event = stream.next_event().await?;
+        Some((event, stream))
+    })
+}
+
+struct TransitionStream {
+    feed: Arc<StateTransitionFeed>,
+    receiver: Receiver<StateTransitionEvent>,
+    /// Sequence of the last transition sent
+    cursor: u64,
+    /// Retained transitions to send before live ones
+    backlog: VecDeque<StateTransitionEvent>,
+    /// Whether a `reset` event is due
+    reset: bool,
+    heartbeat: Interval,
+}
+
+impl TransitionStream {
+    fn resume_from(&mut self, cursor: u64) {
+        match self.feed.after(cursor) {
+            Some(backlog) => {
+                self.cursor = cursor;
+                self.backlog = backlog.into();
+            }
+            None => {
+                self.cursor = self.feed.last_sequence();
+                self.backlog.clear();
+                self.reset = true;
+            }
+        }
+    }
+
+    async fn next_event(&mut self) -> Option<Result<Event, axum::Error>> {
+        loop {
+            if self.reset {
+                self.reset = false;
+                return Some(Ok(Event::default()
+                    .event("reset")
+                    .id(self.cursor.to_string())
+                    .data(self.cursor.to_string())));
+            }
+            if let Some(transition) = self.backlog.pop_front() {
+                if let Some(event) = self.transition_event(transition) {
+                    return Some(event);
+                }
+                continue;
+            }
+
+            tokio::select! {
+                _ = self.heartbeat.tick() => return Some(self.heartbeat_event()),
+                received = self.receiver.recv() => match received {
+                    Ok(transition) => {
+                        if let Some(event) = self.transition_event(transition) {
+                            return Some(event);
+                        }
+                    }
+                    Err(RecvError::Lagged(skipped)) => {
+                        debug!(skipped, "State stream lagged, catching up from the feed");
+                        self.resume_from(self.cursor);
+                    }
+                    Err(RecvError::Closed) => return None,
+                },
+            }
+        }
+    }
+
+    fn transition_event(
+        &mut self,
+        transition: StateTransitionEvent,
+    ) -> Option<Result<Event, axum::Error>> {
+        if transition.sequence <= self.cursor {
+            return None;
+        }
+        self.cursor = transition.sequence;
+        Some(
+            Event::default()
+                .event("transition")
+                .id(self.cursor.to_string())
+                .json_data(transition),
+        )
+    }
+
+    fn heartbeat_event(&self) -> Result<Event, axum::Error> {
+        Event::default()
+            .event("heartbeat")
+            .id(self.cursor.to_string())
+            .json_data(StreamHeartbeat {
+                cursor: self.cursor.to_string(),
+                timestamp: current_timestamp(),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conf::FsyncPolicy;
+
+    const NOW: u64 = 1_760_000_000;
+
+    fn store_log() -> StoreLogConf {
+        StoreLogConf {
+            fsync: FsyncPolicy::Always,
+            fsync_interval_ms: 0,
+            compact_after_ops: 2,
+        }
+    }
+
+    fn publish_at(
+        feed: &StateTransitionFeed,
+        block_height: u64,
+        tx: &str,
+        commitment: u8,
+    ) -> Option<u64> {
+        feed.publish(
+            tx.to_string(),
+            block_height,
+            [9; 32],
+            &[[commitment; 32]],
+            &[],
+            NOW,
+        )
+        .map(|event| event.sequence)
+    }
+
+    fn publish(feed: &StateTransitionFeed, tx: &str, commitment: u8) -> Option<u64> {
+        let block_height = feed.store.read().last_block_height.unwrap_or(0) + 1;
+        publish_at(feed, block_height, tx, commitment)
+    }
+
+    #[test]
+    fn numbers_transitions_and_skips_replays() {
+        let feed = StateTransitionFeed::new();
+        let mut events = feed.subscribe();
+
+        assert_eq!(publish_at(&feed, 1, "aa", 1), Some(1));
+        assert_eq!(publish_at(&feed, 2, "bb", 2), Some(2));
+        // Replayed by the listener after a restart
+        assert_eq!(publish_at(&feed, 1, "aa", 1), None);
+        // Registration and other no-op transitions
+        assert_eq!(feed.publish("cc".into(), 3, [9; 32], &[], &[], NOW), None);
+
+        let first = events.try_recv().unwrap();
+        assert_eq!(first.tx_hash, "aa");
+        assert_eq!(first.notes_root, hex::encode([9; 32]));
+        assert_eq!(first.created_commitments, [hex::encode([1; 32])]);
+        assert_eq!(events.try_recv().unwrap().sequence, 2);
+        assert!(events.try_recv().is_err());
+    }
+
+    #[test]
+    fn resumes_only_from_retained_cursors() {
+        let feed = StateTransitionFeed::new();
+        assert_eq!(feed.after(0), Some(vec![]));
+        assert_eq!(feed.after(1), None);
+
+        for i in 0..RETAINED_TRANSITIONS + 2 {
+            publish(&feed, &format!("{i:x}"), 1);
+        }
+        let last = feed.last_sequence();
+        assert_eq!(last, RETAINED_TRANSITIONS as u64 + 2);
+
+        let tail = feed.after(last - 2).unwrap();
+        assert_eq!(
+            tail.iter().map(|event| event.sequence).collect::<Vec<_>>(),
+            [last - 1, last]
+        );
+        // The two oldest transitions were dropped
+        assert!(feed.after(2).is_some());
+        assert_eq!(feed.after(1), None);
+        assert_eq!(feed.after(last + 1), None);
+    }
+
+    #[test]
+    fn persists_sequence_across_restarts() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir
+            .path()
+            .join("state_transitions.json")
+            .to_string_lossy()
+            .to_string();
+
+        let feed = StateTransitionFeed::with_persistence(store_log(), path.clone()).unwrap();
+        publish(&feed, "aa", 1);
+        drop(feed);
+
+        let reloaded = StateTransitionFeed::with_persistence(store_log(), path).unwrap();
+        assert_eq!(publish_at(&reloaded, 1, "aa", 1), None);
+        assert_eq!(publish_at(&reloaded, 2, "bb", 2), Some(2));
+    }
+
+    #[test]
+    fn skips_replays_older_than_the_retained_transitions() {
+        let feed = StateTransitionFeed::new();
+        for height in 1..=RETAINED_TRANSITIONS as u64 + 1 {
+            publish_at(&feed, height, &format!("{height:x}"), 1);
+        }
+        let last = feed.last_sequence();
+        // No longer retained, but below the last published height
+        assert_eq!(publish_at(&feed, 1, "1", 1), None);
+        // Several transactions of the same block
+        assert_eq!(publish_at(&feed, 2000, "aa", 1), Some(last + 1));
+        assert_eq!(publish_at(&feed, 2000, "bb", 2), Some(last + 2));
+        assert_eq!(publish_at(&feed, 2000, "aa", 1), None);
+        assert_eq!(feed.last_sequence(), last + 2);
+    }
+}
@@ -0,0 +1,550 @@
+use std::{collections::HashMap, io, sync::Arc};
+
+use anyhow::Result;
+use cachecash_client::types::{TxKind, TxRecord, TxStatus};
+use hyli_modules::{
+    bus::SharedMessageBus,
+    module_bus_client, module_handle_messages,
+    modules::{contract_listener::ContractListenerEvent, Module},
+};
+use sdk::{api::TransactionStatusDb, Blob};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::{
+    conf::{StoreLogConf, TxTrackerConf},
+    note_store::current_timestamp,
+    op_log::{LogState, LogStore, LogWriteGuard},
+};
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct TrackerState {
+    /// Records keyed by tx hash, or by tracking id until the hash is known
+    records: HashMap<String, TxRecord>,
+    /// Tracking ids handed out before submission, mapped to their tx hash
+    aliases: HashMap<String, String>,
+    /// Blobs of the pending transfers whose proofs are submitted by a later
+    /// request, keyed by tx hash
+    #[serde(default)]
+    blobs: HashMap<String, Vec<Blob>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TrackerOp {
+    now: u64,
+    /// Final records not updated for this long are pruned after the change
+    retention_secs: u64,
+    change: TrackerChange,
+}
+
+#[derive(Serialize, Deserialize)]
+enum TrackerChange {
+    Queued {
+        tracking_id: String,
+        kind: TxKind,
+    },
+    Submitted {
+        tx_hash: String,
+        kind: TxKind,
+        tracking_id: Option<String>,
+    },
+    Blobs {
+        tx_hash: String,
+        blobs: Vec<Blob>,
+    },
+    SubmissionFailed {
+        id: String,
+        kind: TxKind,
+        reason: String,
+    },
+    ProofSubmitted {
+        tx_hash: String,
+    },
+    ProofFailed {
+        tx_hash: String,
+        reason: String,
+    },
+    Settled {
+        tx_hash: String,
+        status: TxStatus,
+        reason: Option<String>,
+    },
+    TimedOut {
+        key: String,
+        reason: String,
+    },
+}
+
+impl LogState for TrackerState {
+    type Op = TrackerOp;
+
+    fn apply(&mut self, op: TrackerOp) {
+        let TrackerOp {
+            now,
+            retention_secs,
+            change,
+        } = op;
+        match change {
+            TrackerChange::Queued { tracking_id, kind } => {
+                self.records.insert(tracking_id, new_record(kind, now));
+            }
+            TrackerChange::Submitted {
+                tx_hash,
+                kind,
+                tracking_id,
+            } => {
+                let queued = tracking_id.as_ref().and_then(|id| self.records.remove(id));
+                let mut record = queued.unwrap_or_else(|| new_record(kind, now));
+                record.tx_hash = Some(tx_hash.clone());
+                record.updated_at = now;
+                self.records.insert(tx_hash.clone(), record);
+                if let Some(id) = tracking_id {
+                    self.aliases.insert(id, tx_hash);
+                }
+            }
+            TrackerChange::Blobs { tx_hash, blobs } => {
+                self.blobs.insert(tx_hash, blobs);
+            }
+            TrackerChange::SubmissionFailed { id, kind, reason } => {
+                let record = self
+                    .records
+                    .entry(id)
+                    .or_insert_with(|| new_record(kind, now));
+                set_status(record, TxStatus::Failed, Some(reason), now);
+            }
+            TrackerChange::ProofSubmitted { tx_hash } => {
+                if let Some(record) = self.records.get_mut(&tx_hash) {
+                    if record.status == TxStatus::Pending {
+                        set_status(record, TxStatus::ProofSubmitted, None, now);
+                    }
+                }
+            }
+            TrackerChange::ProofFailed { tx_hash, reason } => {
+                if let Some(record) = self.records.get_mut(&tx_hash) {
+                    if !record.status.is_final() {
+                        set_status(record, TxStatus::Failed, Some(reason), now);
+                    }
+                }
+            }
+            TrackerChange::Settled {
+                tx_hash,
+                status,
+                reason,
+            } => {
+                if let Some(record) = self.records.get_mut(&tx_hash) {
+                    set_status(record, status, reason, now);
+                }
+            }
+            TrackerChange::TimedOut { key, reason } => {
+                if let Some(record) = self.records.get_mut(&key) {
+                    set_status(record, TxStatus::TimedOut, Some(reason), now);
+                }
+            }
+        }
+
+        self.records.retain(|_, record| {
+            !record.status.is_final() || now < record.updated_at.saturating_add(retention_secs)
+        });
+        let records = &self.records;
+        self.aliases
+            .retain(|_, tx_hash| records.contains_key(tx_hash));
+        self.blobs.retain(|tx_hash, _| {
+            records
+                .get(tx_hash)
+                .is_some_and(|record| !record.status.is_final())
+        });
+    }
+}
+
+/// Follows the blob transactions submitted by the server until they settle,
+/// fail or time out.
+///
+/// Faucet and deposit mints are submitted asynchronously, so the API hands out
+/// a tracking id first and the record is re-keyed by tx hash on submission;
+/// both ids resolve to the same record.
+pub struct TxTracker {
+    conf: TxTrackerConf,
+    store: LogStore<TrackerState>,
+}
+
+impl TxTracker {
+    /// Creates an in-memory tracker.
+    pub fn new(conf: TxTrackerConf) -> Self {
+        Self {
+            conf,
+            store: LogStore::new(TrackerState::default()),
+        }
+    }
+
+    /// Creates a tracker whose records are persisted to `persistence_path`
+    /// and its operation log.
+    pub fn with_persistence(
+        conf: TxTrackerConf,
+        store_log: StoreLogConf,
+        persistence_path: String,
+    ) -> io::Result<Self> {
+        Ok(Self {
+            conf,
+            store: LogStore::open(persistence_path, store_log)?,
+        })
+    }
+
+    /// Records a transaction queued for submission under `tracking_id`.
+    pub fn record_queued(&self, tracking_id: &str, kind: TxKind, now: u64) {
+        self.mutate(
+            now,
+            TrackerChange::Queued {
+                tracking_id: tracking_id.to_string(),
+                kind,
+            },
+        );
+    }
+
+    /// Records a blob transaction accepted by the node, re-keying the record
+    /// queued under `tracking_id` if any.
+    pub fn record_submitted(
+        &self,
+        tx_hash: &str,
+        kind: TxKind,
+        tracking_id: Option<&str>,
+        now: u64,
+    ) {
+        self.mutate(
+            now,
+            TrackerChange::Submitted {
+                tx_hash: tx_hash.to_string(),
+                kind,
+                tracking_id: tracking_id.map(str::to_string),
+            },
+        );
+    }
+
+    /// Keeps the blobs of the submitted transfer `tx_hash` until it settles,
+    /// so that its proofs can be checked against them.
+    pub fn record_blobs(&self, tx_hash: &str, blobs: Vec<Blob>, now: u64) {
+        self.update(
+            tx_hash,
+            now,
+            TrackerChange::Blobs {
+                tx_hash: tx_hash.to_string(),
+                blobs,
+            },
+        );
+    }
+
+    /// Blobs recorded for `tx_hash`, if it has not settled yet.
+    pub fn blobs(&self, tx_hash: &str) -> Option<Vec<Blob>> {
+        self.store.read().blobs.get(tx_hash).cloned()
+    }
+
+    /// Records a transaction that could not be built or submitted. `id` is
+    /// the tracking id, or the tx hash when it is already known.
+    pub fn record_submission_failed(&self, id: &str, kind: TxKind, reason: String, now: u64) {
+        self.mutate(
+            now,
+            TrackerChange::SubmissionFailed {
+                id: id.to_string(),
+                kind,
+                reason,
+            },
+        );
+    }
+
+    /// Records that the hyli_utxo proof for `tx_hash` reached the node.
+    pub fn record_proof_submitted(&self, tx_hash: &str, now: u64) {
+        self.update(
+            tx_hash,
+            now,
+            TrackerChange::ProofSubmitted {
+                tx_hash: tx_hash.to_string(),
+            },
+        );
+    }
+
+    /// Records that the proof for `tx_hash` could not be generated or
+    /// submitted.
+    pub fn record_proof_failed(&self, tx_hash: &str, reason: String, now: u64) {
+        self.update(
+            tx_hash,
+            now,
+            TrackerChange::ProofFailed {
+                tx_hash: tx_hash.to_string(),
+                reason,
+            },
+        );
+    }
+
+    /// Records the settlement outcome reported by the node. The chain is
+    /// authoritative, so this overrides local failures and timeouts.
+    pub fn record_settled(
+        &self,
+        tx_hash: &str,
+        status: TxStatus,
+        reason: Option<String>,
+        now: u64,
+    ) {
+        self.update(
+            tx_hash,
+            now,
+            TrackerChange::Settled {
+                tx_hash: tx_hash.to_string(),
+                status,
+                reason,
+            },
+        );
+    }
+
+    /// Looks up a record by tx hash or tracking id. Records that have not
+    /// settled within `timeout_secs` are reported as timed out.
+    pub fn get(&self, id: &str, now: u64) -> Option<TxRecord> {
+        let mut state = self.store.write();
+        let key = state
+            .aliases
+            .get(id)
+            .cloned()
+            .unwrap_or_else(|| id.to_string());
+        let record = state.records.get(&key)?;
+        if !record.status.is_final()
+            && now >= record.submitted_at.saturating_add(self.conf.timeout_secs)
+        {
+            let reason = format!("not settled within {}s", self.conf.timeout_secs);
+            self.apply(
+                &mut state,
+                now,
+                TrackerChange::TimedOut {
+                    key: key.clone(),
+                    reason,
+                },
+            );
+        }
+        state.records.get(&key).cloned()
+    }
+
+    fn update(&self, tx_hash: &str, now: u64, change: TrackerChange) {
+        let mut state = self.store.write();
+        // Settled events are replayed for every tx touching the contract;
+        // only the ones this server submitted are tracked
+        if state.records.contains_key(tx_hash) {
+            self.apply(&mut state, now, change);
+        }
+    }
+
+    fn mutate(&self, now: u64, change: TrackerChange) {
+        self.apply(&mut self.store.write(), now, change);
+    }
+
+    fn apply(&self, state: &mut LogWriteGuard<'_, TrackerState>, now: u64, change: TrackerChange) {
+        let op = TrackerOp {
+            now,
+            retention_secs: self.conf.retention_secs,
+            change,
+        };
+        if let Err(err) = state.apply(op) {
+            warn!(error = %err, "Failed to record tracked transaction");
+        }
+    }
+}
+
+fn new_record(kind: TxKind, now: u64) -> TxRecord {
+    TxRecord {
+        tx_hash: None,
+        kind,
+        status: TxStatus::Pending,
+        failure_reason: None,
+        submitted_at: now,
+        updated_at: now,
+    }
+}
+
+fn set_status(record: &mut TxRecord, status: TxStatus, reason: Option<String>, now: u64) {
+    record.status = status;
+    record.failure_reason = reason;
+    record.updated_at = now;
+}
+
+module_bus_client! {
+    #[derive(Debug)]
+    pub struct TxTrackerBusClient {
+        receiver(ContractListenerEvent),
+    }
+}
+
+/// Feeds settlement outcomes from the `ContractListener` into a [`TxTracker`].
+pub struct TxTrackerModule {
+    bus: TxTrackerBusClient,
+    tracker: Arc<TxTracker>,
+}
+
+impl Module for TxTrackerModule {
+    type Context = Arc<TxTracker>;
+
+    async fn build(bus: SharedMessageBus, ctx: Self::Context) -> Result<Self> {
+        let bus = TxTrackerBusClient::new_from_bus(bus.new_handle()).await;
+        Ok(Self { bus, tracker: ctx })
+    }
+
+    async fn run(&mut self) -> Result<()> {
+        module_handle_messages! {
+            on_self self,
+            listen<ContractListenerEvent> event => {
+                if let ContractListenerEvent::SettledTx(tx) = event {
+                    self.handle_settled(&hex::encode(&tx.tx_id.1 .0), &tx.status);
+                }
+            }
+        };
+
+        Ok(())
+    }
+}
+
+impl TxTrackerModule {
+    fn handle_settled(&self, tx_hash: &str, status: &TransactionStatusDb) {
+        let (status, reason) = match status {
+            TransactionStatusDb::Success => (TxStatus::Settled, None),
+            TransactionStatusDb::Failure => (
+                TxStatus::Failed,
+                Some("transaction settled as failed by the node".to_string()),
+            ),
+            TransactionStatusDb::TimedOut => (
+                TxStatus::TimedOut,
+                Some("timed out on chain before its proofs settled".to_string()),
+            ),
+            other => {
+                debug!(%tx_hash, status = ?other, "Ignoring non-final settled tx status");
+                return;
+            }
+        };
+        self.tracker
+            .record_settled(tx_hash, status, reason, current_timestamp());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conf::FsyncPolicy;
+
+    const NOW: u64 = 1_760_000_000;
+
+    fn conf() -> TxTrackerConf {
+        TxTrackerConf {
+            timeout_secs: 600,
+            retention_secs: 3_600,
+        }
+    }
+
+    fn store_log() -> StoreLogConf {
+        StoreLogConf {
+            fsync: FsyncPolicy::Always,
+            fsync_interval_ms: 0,
+            compact_after_ops: 2,
+        }
+    }
+
+    #[test]
+    fn follows_faucet_mint_from_tracking_id_to_settlement() {
+        let tracker = TxTracker::new(conf());
+        tracker.record_queued("commitment", TxKind::Faucet, NOW);
+        let queued = tracker.get("commitment", NOW).unwrap();
+        assert_eq!(queued.status, TxStatus::Pending);
+        assert_eq!(queued.tx_hash, None);
+
+        tracker.record_submitted("aa", TxKind::Faucet, Some("commitment"), NOW + 1);
+        tracker.record_proof_submitted("aa", NOW + 2);
+        assert_eq!(
+            tracker.get("commitment", NOW + 2).unwrap().status,
+            TxStatus::ProofSubmitted
+        );
+
+        tracker.record_settled("aa", TxStatus::Settled, None, NOW + 3);
+        let settled = tracker.get("aa", NOW + 3).unwrap();
+        assert_eq!(settled.status, TxStatus::Settled);
+        assert_eq!(settled.tx_hash.as_deref(), Some("aa"));
+        assert_eq!(settled.submitted_at, NOW);
+        assert_eq!(tracker.get("commitment", NOW + 3), Some(settled));
+    }
+
+    #[test]
+    fn records_failures_with_reasons() {
+        let tracker = TxTracker::new(conf());
+        tracker.record_queued("commitment", TxKind::Deposit, NOW);
+        tracker.record_submission_failed("commitment", TxKind::Deposit, "node down".into(), NOW);
+        let failed = tracker.get("commitment", NOW).unwrap();
+        assert_eq!(failed.status, TxStatus::Failed);
+        assert_eq!(failed.failure_reason.as_deref(), Some("node down"));
+
+        tracker.record_submitted("bb", TxKind::Transfer, None, NOW);
+        tracker.record_proof_failed("bb", "prover crashed".into(), NOW + 1);
+        // A late proof submission does not revive a failed transaction
+        tracker.record_proof_submitted("bb", NOW + 2);
+        assert_eq!(tracker.get("bb", NOW + 2).unwrap().status, TxStatus::Failed);
+
+        // Settlement reported by the node wins over local failures
+        tracker.record_settled("bb", TxStatus::Settled, None, NOW + 3);
+        let settled = tracker.get("bb", NOW + 3).unwrap();
+        assert_eq!(settled.status, TxStatus::Settled);
+        assert_eq!(settled.failure_reason, None);
+
+        // Transactions this server did not submit are ignored
+        tracker.record_settled("cc", TxStatus::Settled, None, NOW);
+        assert_eq!(tracker.get("cc", NOW), None);
+    }
+
+    #[test]
+    fn keeps_transfer_blobs_until_settlement() {
+        let tracker = TxTracker::new(conf());
+        let blobs = vec![Blob {
+            contract_name: "hyli_utxo".into(),
+            data: sdk::BlobData(vec![1, 2, 3]),
+        }];
+        tracker.record_submitted("aa", TxKind::Transfer, None, NOW);
+        tracker.record_blobs("aa", blobs.clone(), NOW);
+        tracker.record_proof_submitted("aa", NOW + 1);
+        assert_eq!(tracker.blobs("aa"), Some(blobs.clone()));
+
+        // Blobs of transactions this server did not submit are not kept
+        tracker.record_blobs("bb", blobs, NOW);
+        assert_eq!(tracker.blobs("bb"), None);
+
+        tracker.record_settled("aa", TxStatus::Settled, None, NOW + 2);
+        assert_eq!(tracker.blobs("aa"), None);
+    }
+
+    #[test]
+    fn times_out_and_prunes_old_records() {
+        let tracker = TxTracker::new(conf());
+        tracker.record_submitted("aa", TxKind::Transfer, None, NOW);
+        assert_eq!(
+            tracker.get("aa", NOW + 599).unwrap().status,
+            TxStatus::Pending
+        );
+
+        let timed_out = tracker.get("aa", NOW + 600).unwrap();
+        assert_eq!(timed_out.status, TxStatus::TimedOut);
+        assert!(timed_out.failure_reason.is_some());
+
+        tracker.record_submitted("bb", TxKind::Transfer, None, NOW + 600 + 3_600);
+        assert_eq!(tracker.get("aa", NOW + 600 + 3_600), None);
+        assert!(tracker.get("bb", NOW + 600 + 3_600).is_some());
+    }
+
+    #[test]
+    fn persists_records_across_restarts() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir
+            .path()
+            .join("tx_tracker.json")
+            .to_string_lossy()
+            .to_string();
+
+        let tracker = TxTracker::with_persistence(conf(), store_log(), path.clone()).unwrap();
+        tracker.record_queued("commitment", TxKind::Faucet, NOW);
+        tracker.record_submitted("aa", TxKind::Faucet, Some("commitment"), NOW);
+        drop(tracker);
+
+        let reloaded = TxTracker::with_persistence(conf(), store_log(), path).unwrap();
+        let record = reloaded.get("commitment", NOW).unwrap();
+        assert_eq!(record.tx_hash.as_deref(), Some("aa"));
+        assert_eq!(record.status, TxStatus::Pending);
+    }
+}
@@ -1,312 +1,8 @@
 use borsh::{BorshDeserialize, BorshSerialize};
-use sdk::{Blob, BlobData, BlobIndex, ContractAction, ContractName, StructuredBlobData, TxHash};
+use sdk::{Blob, BlobData, BlobIndex, ContractAction, ContractName, StructuredBlobData};
 use serde::{Deserialize, Serialize};
-use zk_primitives::Note;
 
-// ---- Server Config API Types ----
-
-/// Response for the /api/config endpoint.
-#[derive(Debug, Serialize)]
-pub struct ServerConfigResponse {
-    /// The UTXO contract name (e.g. "hyli_utxo")
-    pub contract_name: String,
-    /// The UTXO state contract name (e.g. "hyli-utxo-state")
-    pub utxo_state_contract_name: String,
-    /// The SMT inclusion proof contract name (e.g. "hyli_smt_incl_proof")
-    pub smt_incl_proof_contract_name: String,
-}
-
-// ---- Address Registry API Types ----
-
-/// Request to register a username -> UTXO address mapping.
-#[derive(Debug, Deserialize)]
-pub struct RegisterAddressRequest {
-    /// The username (e.g., "matteo" - without @wallet suffix)
-    pub username: String,
-    /// The UTXO address (64-char hex, derived from poseidon2([secret_key, 0]))
-    pub utxo_address: String,
-    /// The secp256k1 public key for ECDH encryption (64-char hex, x-coordinate)
-    pub encryption_pubkey: String,
-}
-
-/// Response after successfully registering an address.
-#[derive(Debug, Serialize)]
-pub struct RegisterAddressResponse {
-    /// The normalized username (lowercase)
-    pub username: String,
-    /// The registered UTXO address
-    pub utxo_address: String,
-    /// The secp256k1 public key for ECDH encryption
-    #[serde(skip_serializing_if = "String::is_empty")]
-    pub encryption_pubkey: String,
-    /// Unix timestamp when registered
-    pub registered_at: u64,
-    /// Whether this was an update to an existing registration
-    pub was_update: bool,
-}
-
-/// Response when resolving a username to an address.
-#[derive(Debug, Serialize)]
-pub struct ResolveAddressResponse {
-    /// The username
-    pub username: String,
-    /// The UTXO address
-    pub utxo_address: String,
-    /// The secp256k1 public key for ECDH encryption (may be empty for legacy registrations)
-    #[serde(skip_serializing_if = "String::is_empty")]
-    pub encryption_pubkey: String,
-    /// Unix timestamp when registered
-    pub registered_at: u64,
-}
-
-// ---- Encrypted Notes API Types ----
-
-/// Request to upload an encrypted note.
-#[derive(Debug, Deserialize)]
-pub struct UploadNoteRequest {
-    /// Recipient tag (hex-encoded, derived from recipient's public key).
-    pub recipient_tag: String,
-    /// Base64-encoded encrypted payload.
-    pub encrypted_payload: String,
-    /// Hex-encoded ephemeral public key for ECDH decryption.
-    pub ephemeral_pubkey: String,
-    /// Optional sender tag for grouping/filtering.
-    #[serde(default)]
-    pub sender_tag: Option<String>,
-}
-
-/// Response after successfully uploading a note.
-#[derive(Debug, Serialize)]
-pub struct UploadNoteResponse {
-    /// Unique identifier for the stored note.
-    pub id: String,
-    /// Unix timestamp when the note was stored.
-    pub stored_at: u64,
-}
-
-/// Query parameters for fetching notes.
-#[derive(Debug, Deserialize, Default)]
-pub struct GetNotesQuery {
-    /// Only return notes stored after this Unix timestamp.
-    #[serde(default)]
-    pub since: Option<u64>,
-    /// Maximum number of notes to return.
-    #[serde(default)]
-    pub limit: Option<usize>,
-}
-
-/// A single encrypted note record in API responses.
-#[derive(Debug, Serialize)]
-pub struct EncryptedNoteRecord {
-    /// Unique identifier.
-    pub id: String,
-    /// Base64-encoded encrypted payload.
-    pub encrypted_payload: String,
-    /// Hex-encoded ephemeral public key.
-    pub ephemeral_pubkey: String,
-    /// Optional sender tag.
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub sender_tag: Option<String>,
-    /// Unix timestamp when stored.
-    pub stored_at: u64,
-}
-
-/// Response containing fetched notes.
-#[derive(Debug, Serialize)]
-pub struct GetNotesResponse {
-    /// The encrypted notes.
-    pub notes: Vec<EncryptedNoteRecord>,
-    /// Whether there are more notes available beyond the limit.
-    pub has_more: bool,
-}
-
-// ---- Transfer API Types ----
-
-/// Input note data for transfer requests (includes full note + secret key)
-#[derive(Debug, Clone, Deserialize)]
-pub struct InputNoteData {
-    /// The note being spent
-    pub note: Note,
-    /// Secret key for spending this note (64-char hex)
-    pub secret_key: String,
-}
-
-/// Response after successful transfer
-#[derive(Debug, Serialize)]
-pub struct TransferResponse {
-    /// Transaction hash
-    pub tx_hash: TxHash,
-    /// Change note if any
-    pub change_note: Option<Note>,
-}
-
-/// Request to transfer with a pre-generated proof (client-side proving)
-#[derive(Debug, Deserialize)]
-pub struct ProvedTransferRequest {
-    /// Base64-encoded proof bytes (raw proof without public inputs)
-    pub proof: String,
-    /// Public inputs as hex strings (733 field elements)
-    pub public_inputs: Vec<String>,
-    /// 128-byte blob data
-    pub blob_data: Vec<u8>,
-    /// Output notes: [recipient_note, change_note]
-    pub output_notes: [Note; 2],
-}
-
-// ---- Two-Step Transfer API Types ----
-
-/// Request to create a blob transaction (step 1 of two-step transfer)
-///
-/// `smt_blob_data` can be provided directly, or the server computes it from
-/// `input_notes` + `notes_root`.
-#[derive(Debug, Deserialize)]
-pub struct CreateBlobRequest {
-    /// 128-byte blob data: [input_commit_0, input_commit_1, nullifier_0, nullifier_1]
-    pub blob_data: Vec<u8>,
-    /// 96-byte SMT blob data: [nullifier_0 (32B)][nullifier_1 (32B)][notes_root (32B)].
-    /// Optional when `input_notes` + `notes_root` are provided.
-    #[serde(default)]
-    pub smt_blob_data: Option<Vec<u8>>,
-    /// Output notes: [recipient_note, change_note]
-    pub output_notes: [Note; 2],
-    /// Optional token transfer blob to include in the same transaction.
-    #[serde(default)]
-    pub token_transfer: Option<TokenTransferRequest>,
-    /// Input notes for computing smt_blob_data server-side (required when smt_blob_data is absent)
-    #[serde(default)]
-    pub input_notes: Option<[InputNoteData; 2]>,
-    /// Hex-encoded notes root (required when smt_blob_data is absent; returned by /smt-witness)
-    #[serde(default)]
-    pub notes_root: Option<String>,
-}
-
-/// Response after creating a blob transaction
-#[derive(Debug, Serialize)]
-pub struct CreateBlobResponse {
-    /// Transaction hash from the blockchain
-    pub tx_hash: String,
-    /// The blobs that were included in the transaction (for client to use in proof)
-    pub blobs: Vec<BlobInfo>,
-}
-
-/// Information about a blob in the transaction
-#[derive(Debug, Serialize, Clone)]
-pub struct BlobInfo {
-    /// Contract name this blob targets
-    pub contract_name: String,
-    /// Blob data as hex string
-    pub data: String,
-}
-
-/// Response for /api/blob/hash — returns the tx_hash without submitting to the chain.
-#[derive(Debug, Serialize)]
-pub struct BlobHashResponse {
-    /// Deterministic transaction hash (SHA3-256 of identity + blob hashes)
-    pub tx_hash: TxHash,
-}
-
-/// Request to finalize a transfer atomically: submit blob tx + both proofs in one call.
-///
-/// Two modes:
-/// 1. **Client-provided proofs**: supply `smt_blob_data`, `smt_proof`, and `smt_public_inputs`.
-/// 2. **Server-side SMT proof generation**: omit `smt_proof` and instead provide `input_notes`,
-///    `siblings_0`, `siblings_1`, and `notes_root`. The server computes nullifiers, builds
-///    `smt_blob_data`, and generates the proof.
-#[derive(Debug, Deserialize)]
-pub struct FinalizeTransferRequest {
-    /// 128-byte blob data: [input_commit_0, input_commit_1, nullifier_0, nullifier_1]
-    pub blob_data: Vec<u8>,
-    /// 96-byte SMT blob data: [nullifier_0 (32B)][nullifier_1 (32B)][notes_root (32B)].
-    /// Required when `smt_proof` is provided; computed by the server otherwise.
-    #[serde(default)]
-    pub smt_blob_data: Option<Vec<u8>>,
-    /// Output notes: [recipient_note, change_note]
-    pub output_notes: [Note; 2],
-    /// Optional token transfer blob to include in the same transaction.
-    #[serde(default)]
-    pub token_transfer: Option<TokenTransferRequest>,
-    /// Base64-encoded proof bytes for hyli_utxo
-    pub proof: String,
-    /// Public inputs as hex strings for hyli_utxo
-    pub public_inputs: Vec<String>,
-    /// Base64-encoded proof bytes for hyli_smt_incl_proof (optional — server generates if absent)
-    #[serde(default)]
-    pub smt_proof: Option<String>,
-    /// Public inputs as hex strings for hyli_smt_incl_proof (required when smt_proof is provided)
-    #[serde(default)]
-    pub smt_public_inputs: Option<Vec<String>>,
-    /// Input notes for server-side SMT proof generation (required when smt_proof is absent)
-    #[serde(default)]
-    pub input_notes: Option<[InputNoteData; 2]>,
-    /// SMT siblings for input_notes[0] — hex-encoded field elements (required when smt_proof is absent)
-    #[serde(default)]
-    pub siblings_0: Option<Vec<String>>,
-    /// SMT siblings for input_notes[1] — hex-encoded field elements (required when smt_proof is absent)
-    #[serde(default)]
-    pub siblings_1: Option<Vec<String>>,
-    /// Hex-encoded notes root from the SMT (required when smt_proof is absent; returned by /smt-witness)
-    #[serde(default)]
-    pub notes_root: Option<String>,
-}
-
-/// Response after finalizing a transfer
-#[derive(Debug, Serialize)]
-pub struct FinalizeTransferResponse {
-    /// Transaction hash from the blockchain
-    pub tx_hash: TxHash,
-}
-
-/// Request to submit a proof for an existing blob transaction (step 2 of two-step transfer)
-#[derive(Debug, Deserialize)]
-pub struct SubmitProofRequest {
-    /// Transaction hash from CreateBlobResponse
-    pub tx_hash: TxHash,
-    /// Base64-encoded proof bytes for hyli_utxo
-    pub proof: String,
-    /// Public inputs as hex strings for hyli_utxo
-    pub public_inputs: Vec<String>,
-    /// Base64-encoded proof bytes for hyli_smt_incl_proof
-    pub smt_proof: String,
-    /// Public inputs as hex strings for hyli_smt_incl_proof
-    pub smt_public_inputs: Vec<String>,
-}
-
-// ---- Existing Types ----
-
-#[derive(Debug, Deserialize)]
-pub struct FaucetRequest {
-    pub pubkey_hex: String,
-    #[serde(default)]
-    pub amount: Option<u64>,
-}
-
-#[derive(Debug, Deserialize)]
-pub struct DepositRequest {
-    pub pubkey_hex: String,
-    #[serde(default)]
-    pub amount: Option<u64>,
-    #[serde(default)]
-    pub token_contract: Option<String>,
-    #[serde(default)]
-    pub wallet_account: Option<String>,
-    #[serde(default)]
-    pub secp256k1_blob: Option<Vec<u8>>,
-    #[serde(default)]
-    pub wallet_blob: Option<Vec<u8>>,
-}
-
-#[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct TokenTransferRequest {
-    pub token_contract: String,
-    pub sender: String,
-    pub recipient: String,
-    pub amount: u64,
-}
-
-#[derive(Debug, Serialize)]
-pub struct FaucetResponse {
-    pub note: Note,
-}
+pub use cachecash_client::types::*;
 
 #[derive(Clone, Debug, BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
 pub enum ZfruitAction {